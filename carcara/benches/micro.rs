@@ -0,0 +1,472 @@
+//! Micro-benchmarks for individual components of the checker. Run them with `cargo bench -p
+//! carcara`, optionally passing a filter to only run the benchmarks whose name contains it, as in
+//! `cargo bench -p carcara -- forall_inst`.
+
+use carcara::{
+    ast::{Operator, PrimitivePool, ProofNode, Rc, Sort, StepNode, Term, TermPool},
+    checker, elaborator, parser,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// A global allocator that counts the number of allocations made, so benchmarks can report them.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// The number of times each measured function is run. The reported time is the median of these.
+const RUNS: usize = 10;
+
+/// Runs `f` `RUNS` times, and returns the median of the measured times.
+fn measure<T>(mut f: impl FnMut() -> T) -> Duration {
+    let mut times: Vec<_> = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            std::hint::black_box(f());
+            start.elapsed()
+        })
+        .collect();
+    times.sort_unstable();
+    times[RUNS / 2]
+}
+
+/// Runs `setup` and then `f` on its result `RUNS` times, and returns the median of the times
+/// measured for `f`, and the number of allocations made by its last run.
+fn measure_with_setup<S, T>(
+    mut setup: impl FnMut() -> S,
+    mut f: impl FnMut(S) -> T,
+) -> (Duration, usize) {
+    let mut allocations = 0;
+    let mut times: Vec<_> = (0..RUNS)
+        .map(|_| {
+            let input = setup();
+            let before = ALLOCATIONS.load(Ordering::Relaxed);
+            let start = Instant::now();
+            let output = std::hint::black_box(f(input));
+            let time = start.elapsed();
+            allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+
+            // The output, and anything it owns, is only dropped after the measurement
+            drop(output);
+            time
+        })
+        .collect();
+    times.sort_unstable();
+    (times[RUNS / 2], allocations)
+}
+
+fn report(name: &str, time: Duration) {
+    println!("    {name:<40} {time:>12.3?}");
+}
+
+fn report_with_allocations(name: &str, (time, allocations): (Duration, usize)) {
+    println!("    {name:<40} {time:>12.3?} {allocations:>10} allocations");
+}
+
+/// Parses a problem and proof, and measures how long checking the proof takes.
+fn measure_checking(problem: &str, proof: &str) -> Duration {
+    let (prelude, proof, mut pool) =
+        parser::parse_instance(problem.as_bytes(), proof.as_bytes(), parser::Config::new())
+            .unwrap();
+    measure(|| {
+        let mut checker = checker::ProofChecker::new(&mut pool, checker::Config::new(), &prelude);
+        checker.check(&proof).unwrap()
+    })
+}
+
+/// Builds a problem and a proof with `num_steps` `forall_inst` steps, each instantiating a
+/// quantifier over a conjunction of 50 atoms. If `renamed` is true, the steps only differ by the
+/// names of their bound variables. Otherwise, every step instantiates a different term.
+fn forall_inst_instance(num_steps: usize, renamed: bool) -> (String, String) {
+    const BODY_SIZE: usize = 50;
+
+    let mut problem = String::from("(declare-fun p (Int Int) Bool)\n");
+    for i in 0..num_steps {
+        problem += &format!("(declare-fun a{i} () Int)\n");
+    }
+    problem += "(assert false)\n";
+
+    let mut proof = String::from("(assume h false)\n");
+    for i in 0..num_steps {
+        let (x, y) = (format!("x{i}"), format!("y{i}"));
+        let body: String = (0..BODY_SIZE)
+            .map(|j| format!(" (p (+ {x} {j}) (* {y} {j}))"))
+            .collect();
+        let a = if renamed {
+            "a0".to_owned()
+        } else {
+            format!("a{i}")
+        };
+        let inst: String = (0..BODY_SIZE)
+            .map(|j| format!(" (p (+ {a} {j}) (* {a} {j}))"))
+            .collect();
+        proof += &format!(
+            "(step t{i} (cl (or (not (forall (({x} Int) ({y} Int)) (and{body}))) \
+            (and{inst}))) :rule forall_inst :args ((:= {x} {a}) (:= {y} {a})))\n"
+        );
+    }
+    proof += "(step f (cl (not false)) :rule false)\n";
+    proof += "(step end (cl) :rule resolution :premises (h f))\n";
+    (problem, proof)
+}
+
+/// Checks a proof with many `forall_inst` steps that only differ by the names of their bound
+/// variables, which all hit the alpha-equivalence step cache after the first one. For comparison,
+/// it also checks a proof of the same size, where every step instantiates a different term, so
+/// none of them can be cached.
+fn forall_inst_cache() {
+    const NUM_STEPS: usize = 2_000;
+
+    let (problem, proof) = forall_inst_instance(NUM_STEPS, true);
+    report(
+        "renamed copies of one step",
+        measure_checking(&problem, &proof),
+    );
+    let (problem, proof) = forall_inst_instance(NUM_STEPS, false);
+    report("distinct steps", measure_checking(&problem, &proof));
+}
+
+/// Checks a proof with many independent `forall_inst` steps, none of which can be cached, with the
+/// sequential checker and with the parallel checker using different numbers of threads.
+fn parallel_checking() {
+    const NUM_STEPS: usize = 2_000;
+    const STACK_SIZE: usize = 128 * 1024 * 1024;
+
+    let (problem, proof) = forall_inst_instance(NUM_STEPS, false);
+    report("sequential", measure_checking(&problem, &proof));
+
+    let (prelude, proof, pool) =
+        parser::parse_instance(problem.as_bytes(), proof.as_bytes(), parser::Config::new())
+            .unwrap();
+    let pool = Arc::new(pool);
+    for num_threads in [1, 2, 4, 8] {
+        let (scheduler, context_usage) = checker::Scheduler::new(num_threads, &proof);
+        let time = measure(|| {
+            let mut checker = checker::ParallelProofChecker::new(
+                pool.clone(),
+                checker::Config::new(),
+                &prelude,
+                &context_usage,
+                STACK_SIZE,
+            );
+            checker.check(&proof, &scheduler).unwrap()
+        });
+        report(&format!("parallel, {num_threads} threads"), time);
+    }
+}
+
+/// Runs two mutation passes over a proof whose graph is a balanced binary tree, after a single leaf
+/// of it was changed. With fresh caches, both passes visit and rebuild the whole graph. With caches
+/// kept from a previous run over the original proof, they only visit the nodes that depend on the
+/// changed leaf.
+fn mutation_cache() {
+    const DEPTH: usize = 14;
+
+    let mut proof = String::new();
+    let mut level: Vec<String> = (0..1 << DEPTH)
+        .map(|i| {
+            proof += &format!("(step l{i} (cl p) :rule hole)\n");
+            format!("l{i}")
+        })
+        .collect();
+    let mut next = 0;
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| {
+                next += 1;
+                proof += &format!(
+                    "(step n{next} (cl p) :rule hole :premises ({} {}))\n",
+                    pair[0], pair[1]
+                );
+                format!("n{next}")
+            })
+            .collect();
+    }
+    let (_, proof, mut pool) = parser::parse_instance(
+        "(declare-fun p () Bool)".as_bytes(),
+        proof.as_bytes(),
+        parser::Config::new(),
+    )
+    .unwrap();
+    let root = ProofNode::from_commands(&proof.commands);
+    let new_clause = vec![pool.bool_false()];
+    let edited = elaborator::mutate(&mut pool, &root, |_, node| match node.as_ref() {
+        ProofNode::Step(s) if s.id == "l0" => Rc::new(ProofNode::Step(StepNode {
+            clause: new_clause.clone(),
+            ..s.clone()
+        })),
+        _ => node.clone(),
+    });
+
+    // The first pass renames one in ten steps, and the second leaves every node unchanged
+    let rename = |_: &mut dyn TermPool, node: &Rc<ProofNode>| match node.as_ref() {
+        ProofNode::Step(s) if s.id.ends_with('0') => {
+            let id = format!("{}'", s.id);
+            Rc::new(ProofNode::Step(StepNode { id, ..s.clone() }))
+        }
+        _ => node.clone(),
+    };
+    let identity = |_: &mut dyn TermPool, node: &Rc<ProofNode>| node.clone();
+
+    let fresh = measure_with_setup(
+        || (),
+        |()| {
+            let first = elaborator::mutate(&mut pool, &edited, rename);
+            elaborator::mutate(&mut pool, &first, identity)
+        },
+    );
+    report_with_allocations("fresh caches", fresh);
+
+    // The mutation functions don't use the pool, so the setup can use a separate one
+    let mut setup_pool = PrimitivePool::new();
+    let persistent = measure_with_setup(
+        || {
+            let mut first_pass = elaborator::ElaborationCache::new(rename);
+            let mut second_pass = elaborator::ElaborationCache::new(identity);
+            let first = elaborator::mutate_with_cache(&mut setup_pool, &root, &mut first_pass);
+            elaborator::mutate_with_cache(&mut setup_pool, &first, &mut second_pass);
+            (first_pass, second_pass)
+        },
+        |(mut first_pass, mut second_pass)| {
+            let first = elaborator::mutate_with_cache(&mut pool, &edited, &mut first_pass);
+            let second = elaborator::mutate_with_cache(&mut pool, &first, &mut second_pass);
+            (second, first_pass, second_pass)
+        },
+    );
+    report_with_allocations("caches kept from a previous run", persistent);
+}
+
+/// Builds the graph representation of a proof with 100,000 steps, allocating each node separately
+/// or building the graph in an arena first. This is done once with a last step that uses every
+/// other step, and once with a last step that only uses one in ten steps.
+fn proof_node_construction() {
+    const NUM_ASSUMES: usize = 1_000;
+    const NUM_STEPS: usize = 100_000;
+
+    let problem = "(declare-fun p () Bool) (declare-fun q () Bool)";
+    let build_proof = |used_every: usize| {
+        let mut proof = String::new();
+        for i in 0..NUM_ASSUMES {
+            proof += &format!("(assume h{i} (or p q))\n");
+        }
+        for i in 0..NUM_STEPS {
+            let (a, b) = (i % NUM_ASSUMES, (i * 7) % NUM_ASSUMES);
+            proof += &format!("(step t{i} (cl p q) :rule hole :premises (h{a} h{b}))\n");
+        }
+        let used: Vec<_> = (0..NUM_STEPS)
+            .step_by(used_every)
+            .map(|i| format!("t{i}"))
+            .collect();
+        proof += &format!("(step t (cl) :rule hole :premises ({}))\n", used.join(" "));
+        let (_, proof, _) =
+            parser::parse_instance(problem.as_bytes(), proof.as_bytes(), parser::Config::new())
+                .unwrap();
+        proof
+    };
+
+    for (name, used_every) in [("all used", 1), ("one in ten used", 10)] {
+        let proof = build_proof(used_every);
+        let separate = measure_with_setup(|| (), |()| ProofNode::from_commands(&proof.commands));
+        report_with_allocations(&format!("{name}, separate allocations"), separate);
+        let arena = measure_with_setup(
+            || (),
+            |()| ProofNode::from_commands_in_arena(&proof.commands),
+        );
+        report_with_allocations(&format!("{name}, arena"), arena);
+    }
+}
+
+/// Interns 10,000 variables and an application of `+` to all of them, either one term at a time
+/// or as a batch, in a new pool. Then, interns the same terms again, which only finds the existing
+/// terms.
+fn term_interning() {
+    const NUM_ARGS: usize = 10_000;
+
+    let add_sequential = |pool: &mut PrimitivePool, vars: Vec<Term>| {
+        let args: Vec<_> = vars.into_iter().map(|t| pool.add(t)).collect();
+        pool.add(Term::Op(Operator::Add, args))
+    };
+    let add_batch = |pool: &mut PrimitivePool, vars: Vec<Term>| {
+        let args = pool.add_terms_batch(vars);
+        pool.add_terms_batch(vec![Term::Op(Operator::Add, args)])
+    };
+
+    // Returns a pool, that may already contain the terms, and the variables to be added to it
+    let setup = |existing: bool| {
+        let mut pool = PrimitivePool::new();
+        let int_sort = pool.add(Term::Sort(Sort::Int));
+        let vars = || -> Vec<_> {
+            (0..NUM_ARGS)
+                .map(|i| Term::new_var(format!("a{i}"), int_sort.clone()))
+                .collect()
+        };
+        if existing {
+            add_sequential(&mut pool, vars());
+        }
+        (pool, vars())
+    };
+    for (name, existing) in [("new", false), ("existing", true)] {
+        let (sequential, _) = measure_with_setup(
+            || setup(existing),
+            |(mut pool, vars)| (add_sequential(&mut pool, vars), pool),
+        );
+        report(&format!("{name} terms, one at a time"), sequential);
+        let (batch, _) = measure_with_setup(
+            || setup(existing),
+            |(mut pool, vars)| (add_batch(&mut pool, vars), pool),
+        );
+        report(&format!("{name} terms, as a batch"), batch);
+    }
+}
+
+/// Builds two pools with the same 2,000 terms, each with about 150 nodes, added in different
+/// orders. Then, computes the structural information of every term in the first pool, both on the
+/// first request and once it is cached, and compares every term to its copy in the second pool.
+fn term_info() {
+    const NUM_TERMS: usize = 2_000;
+    const TERM_SIZE: usize = 50;
+
+    // Each term is the sum of `TERM_SIZE` products, some of which are shared with other terms
+    let build = |pool: &mut PrimitivePool, reversed: bool| -> Vec<Rc<Term>> {
+        let int_sort = pool.add(Term::Sort(Sort::Int));
+        let mut indices: Vec<_> = (0..NUM_TERMS).collect();
+        if reversed {
+            indices.reverse();
+        }
+        let mut terms: Vec<_> = indices
+            .into_iter()
+            .map(|i| {
+                let args = (0..TERM_SIZE)
+                    .map(|j| {
+                        let var = pool.add(Term::new_var(format!("x{}", i + j), int_sort.clone()));
+                        let constant = pool.add(Term::new_int(j));
+                        pool.add(Term::Op(Operator::Mult, vec![constant, var]))
+                    })
+                    .collect();
+                pool.add(Term::Op(Operator::Add, args))
+            })
+            .collect();
+        if reversed {
+            terms.reverse();
+        }
+        terms
+    };
+
+    let (first, _) = measure_with_setup(
+        || {
+            let mut pool = PrimitivePool::new();
+            let terms = build(&mut pool, false);
+            (pool, terms)
+        },
+        |(mut pool, terms)| {
+            let sizes: usize = terms.iter().map(|t| pool.term_size(t)).sum();
+            (sizes, pool)
+        },
+    );
+    report("first request", first);
+
+    let mut pool = PrimitivePool::new();
+    let terms = build(&mut pool, false);
+    let cached = measure(|| terms.iter().map(|t| pool.term_size(t)).sum::<usize>());
+    report("cached", cached);
+
+    let mut other = PrimitivePool::new();
+    let other_terms = build(&mut other, true);
+    let compare = measure(|| {
+        terms
+            .iter()
+            .zip(&other_terms)
+            .all(|(a, b)| pool.structurally_equal(a, &mut other, b))
+    });
+    report("structural comparison", compare);
+}
+
+/// Lexes and parses a proof with many steps, each with a fresh term, reading the input either from
+/// a `BufRead`, which allocates a buffer for each line, or directly from a string slice.
+fn lexer_input() {
+    const NUM_STEPS: usize = 20_000;
+
+    let problem = "(declare-fun f (Int Int) Int)\n(declare-fun x () Int)\n";
+    let mut proof = String::new();
+    for i in 0..NUM_STEPS {
+        proof += &format!(
+            "(step t{i} (cl (= (f (+ x {i}) (* x {i})) (f (+ x {i}) (* x {i})))) :rule refl)\n"
+        );
+    }
+
+    // Reads all tokens from the lexer, and returns how many were read
+    fn lex<R: parser::LexerInput>(mut lexer: parser::Lexer<R>) -> usize {
+        let mut count = 0;
+        while lexer.next_token().unwrap().0 != parser::Token::Eof {
+            count += 1;
+        }
+        count
+    }
+    let reader = measure(|| lex(parser::Lexer::new(proof.as_bytes()).unwrap()));
+    report("lexing, BufRead", reader);
+    let slice = measure(|| lex(parser::Lexer::from_str(&proof)));
+    report("lexing, string slice", slice);
+
+    let reader = measure(|| {
+        let mut pool = PrimitivePool::new();
+        let config = parser::Config::new();
+        let mut parser = parser::Parser::new(&mut pool, config, problem.as_bytes()).unwrap();
+        parser.parse_problem().unwrap();
+        parser.reset(proof.as_bytes()).unwrap();
+        parser.parse_proof().unwrap()
+    });
+    report("parsing, BufRead", reader);
+
+    let slice = measure(|| {
+        let mut pool = PrimitivePool::new();
+        let config = parser::Config::new();
+        let lexer = parser::Lexer::from_str(problem);
+        let mut parser = parser::Parser::from_lexer(&mut pool, config, lexer).unwrap();
+        parser.parse_problem().unwrap();
+        parser.reset_lexer(parser::Lexer::from_str(&proof)).unwrap();
+        parser.parse_proof().unwrap()
+    });
+    report("parsing, string slice", slice);
+}
+
+fn main() {
+    let benchmarks: &[(&str, fn())] = &[
+        ("forall_inst_cache", forall_inst_cache),
+        ("parallel_checking", parallel_checking),
+        ("mutation_cache", mutation_cache),
+        ("proof_node_construction", proof_node_construction),
+        ("term_interning", term_interning),
+        ("term_info", term_info),
+        ("lexer_input", lexer_input),
+    ];
+
+    // When run through `cargo bench`, the binary receives a `--bench` flag, which we ignore
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    for (name, benchmark) in benchmarks {
+        if filter.as_ref().map_or(true, |f| name.contains(f.as_str())) {
+            println!("{name}:");
+            benchmark();
+        }
+    }
+}
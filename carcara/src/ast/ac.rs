@@ -0,0 +1,84 @@
+//! Canonicalization of applications of associative-commutative operators.
+
+use super::{BindingList, Operator, Rc, Term, TermPool};
+use indexmap::IndexMap;
+
+/// Canonicalizes the applications of associative-commutative operators in `term`. This is used to
+/// implement [`TermPool::canonicalize_ac`].
+pub(super) fn canonicalize_ac<P: TermPool + ?Sized>(pool: &mut P, term: &Rc<Term>) -> Rc<Term> {
+    AcCanonicalizer { pool, cache: IndexMap::new() }.canonicalize(term)
+}
+
+/// Returns `true` if `op` is both associative and commutative. Note that `-` and `/` are neither,
+/// and `=` is commutative but chainable, not associative.
+fn is_ac(op: Operator) -> bool {
+    matches!(
+        op,
+        Operator::And | Operator::Or | Operator::Add | Operator::Mult
+    )
+}
+
+struct AcCanonicalizer<'a, P: ?Sized> {
+    pool: &'a mut P,
+    cache: IndexMap<Rc<Term>, Rc<Term>>,
+}
+
+impl<P: TermPool + ?Sized> AcCanonicalizer<'_, P> {
+    fn canonicalize(&mut self, term: &Rc<Term>) -> Rc<Term> {
+        if let Some(t) = self.cache.get(term) {
+            return t.clone();
+        }
+        let result = self.canonicalize_uncached(term);
+        self.cache.insert(term.clone(), result.clone());
+        result
+    }
+
+    fn canonicalize_all(&mut self, terms: &[Rc<Term>]) -> Vec<Rc<Term>> {
+        terms.iter().map(|t| self.canonicalize(t)).collect()
+    }
+
+    fn canonicalize_uncached(&mut self, term: &Rc<Term>) -> Rc<Term> {
+        let result = match term.as_ref() {
+            Term::Const(_) | Term::Var(..) | Term::Sort(_) => return term.clone(),
+            Term::App(func, args) => {
+                Term::App(self.canonicalize(func), self.canonicalize_all(args))
+            }
+            Term::Op(op, args) if is_ac(*op) => {
+                // Since the arguments are already canonical, any nested application of the same
+                // operator is already flattened, so we only need to flatten one level
+                let mut flattened = Vec::with_capacity(args.len());
+                for arg in self.canonicalize_all(args) {
+                    match arg.as_ref() {
+                        Term::Op(inner, inner_args) if inner == op => {
+                            flattened.extend(inner_args.iter().cloned());
+                        }
+                        _ => flattened.push(arg),
+                    }
+                }
+
+                // The arguments are ordered by their structural hashes, which don't depend on the
+                // pool. Terms are hash consed, so arguments whose hashes collide are ordered by
+                // address, which is still canonical for all terms in the same pool
+                flattened.sort_by_key(|t| (self.pool.term_info(t).hash, t.as_ref() as *const Term));
+                Term::Op(*op, flattened)
+            }
+            Term::Op(op, args) => Term::Op(*op, self.canonicalize_all(args)),
+            Term::ParamOp { op, op_args, args } => Term::ParamOp {
+                op: *op,
+                op_args: op_args.clone(),
+                args: self.canonicalize_all(args),
+            },
+            Term::Binder(binder, bindings, inner) => {
+                Term::Binder(*binder, bindings.clone(), self.canonicalize(inner))
+            }
+            Term::Let(bindings, inner) => {
+                let bindings = bindings
+                    .iter()
+                    .map(|(name, value)| (name.clone(), self.canonicalize(value)))
+                    .collect();
+                Term::Let(BindingList(bindings), self.canonicalize(inner))
+            }
+        };
+        self.pool.add(result)
+    }
+}
@@ -0,0 +1,153 @@
+//! An algorithm for renaming bound variables into a canonical form.
+
+use super::{BindingList, Rc, Term, TermPool};
+use crate::utils::HashMapStack;
+use indexmap::{IndexMap, IndexSet};
+
+/// Renames all bound variables in a term to canonical names, such that any two terms that are
+/// alpha-equivalent are mapped to the same canonical term.
+///
+/// Each bound variable is renamed to `@c{n}`, where `n` is the number of binder variables that
+/// are in scope at the point where it is bound. For example, both `(forall ((x Int)) (= x y))` and
+/// `(forall ((z Int)) (= z y))` are renamed to `(forall ((@c0 Int)) (= @c0 y))`. Free variables
+/// are never renamed. If a canonical name is already used by a free variable of the term, primes
+/// are appended to it until it is fresh, so a bound variable can never be confused with a free one.
+///
+/// Note that the resulting term is not necessarily a valid Alethe term, and should only be used
+/// as a key when comparing or hashing terms modulo alpha-equivalence.
+pub fn canonicalize_binders(pool: &mut dyn TermPool, term: &Rc<Term>) -> Rc<Term> {
+    let free_names = pool
+        .free_vars(term)
+        .iter()
+        .filter_map(|v| v.as_var().map(str::to_owned))
+        .collect();
+    BinderCanonicalizer::new(free_names).canonicalize(pool, term)
+}
+
+struct BinderCanonicalizer {
+    /// The canonical variable associated with each bound variable currently in scope.
+    renaming: HashMapStack<Rc<Term>, Rc<Term>>,
+
+    /// A cache for each binder scope. Results computed in an outer scope can't be reused in an
+    /// inner scope, since the inner binder might shadow some of the variables in the term.
+    cache: Vec<IndexMap<Rc<Term>, Rc<Term>>>,
+
+    /// The number of bound variables currently in scope.
+    level: usize,
+
+    /// The names of the free variables in the term being canonicalized. Canonical names must never
+    /// be equal to any of these.
+    free_names: IndexSet<String>,
+}
+
+impl BinderCanonicalizer {
+    fn new(free_names: IndexSet<String>) -> Self {
+        Self {
+            renaming: HashMapStack::new(),
+            cache: vec![IndexMap::new()],
+            level: 0,
+            free_names,
+        }
+    }
+
+    /// Returns the canonical name for a variable bound at the current level.
+    fn canonical_name(&self) -> String {
+        let mut name = format!("@c{}", self.level);
+        while self.free_names.contains(&name) {
+            name.push('\'');
+        }
+        name
+    }
+
+    fn canonicalize(&mut self, pool: &mut dyn TermPool, term: &Rc<Term>) -> Rc<Term> {
+        if let Some(t) = self.cache.last().unwrap().get(term) {
+            return t.clone();
+        }
+
+        let result = match term.as_ref() {
+            Term::Const(_) | Term::Sort(_) => term.clone(),
+            Term::Var(..) => self.renaming.get(term).unwrap_or(term).clone(),
+            Term::App(func, args) => {
+                let func = self.canonicalize(pool, func);
+                let args = self.canonicalize_all(pool, args);
+                pool.add(Term::App(func, args))
+            }
+            Term::Op(op, args) => {
+                let args = self.canonicalize_all(pool, args);
+                pool.add(Term::Op(*op, args))
+            }
+            Term::ParamOp { op, op_args, args } => {
+                let args = self.canonicalize_all(pool, args);
+                pool.add(Term::ParamOp {
+                    op: *op,
+                    op_args: op_args.clone(),
+                    args,
+                })
+            }
+            Term::Binder(binder, bindings, inner) => {
+                let sorts: Vec<_> = bindings.iter().map(|(_, sort)| sort.clone()).collect();
+                let (bindings, inner) = self.canonicalize_scope(pool, bindings, sorts, inner);
+                pool.add(Term::Binder(*binder, bindings, inner))
+            }
+            Term::Let(bindings, inner) => {
+                // The values in a `let` term are not in the scope of its bindings, so they must be
+                // canonicalized before the new scope is opened
+                let sorts: Vec<_> = bindings.iter().map(|(_, value)| pool.sort(value)).collect();
+                let values: Vec<_> = bindings
+                    .iter()
+                    .map(|(_, value)| self.canonicalize(pool, value))
+                    .collect();
+                let (bindings, inner) = self.canonicalize_scope(pool, bindings, sorts, inner);
+                let bindings = bindings
+                    .0
+                    .into_iter()
+                    .zip(values)
+                    .map(|((name, _), value)| (name, value))
+                    .collect();
+                pool.add(Term::Let(BindingList(bindings), inner))
+            }
+        };
+        self.cache
+            .last_mut()
+            .unwrap()
+            .insert(term.clone(), result.clone());
+        result
+    }
+
+    fn canonicalize_all(&mut self, pool: &mut dyn TermPool, terms: &[Rc<Term>]) -> Vec<Rc<Term>> {
+        terms.iter().map(|t| self.canonicalize(pool, t)).collect()
+    }
+
+    /// Opens a new scope binding the variables in `bindings`, with the given sorts, and
+    /// canonicalizes `inner` in it. Returns the canonical bindings, associated with their sorts,
+    /// and the canonicalized inner term.
+    fn canonicalize_scope(
+        &mut self,
+        pool: &mut dyn TermPool,
+        bindings: &BindingList,
+        sorts: Vec<Rc<Term>>,
+        inner: &Rc<Term>,
+    ) -> (BindingList, Rc<Term>) {
+        self.renaming.push_scope();
+        self.cache.push(IndexMap::new());
+
+        let new_bindings = bindings
+            .iter()
+            .zip(sorts)
+            .map(|((name, _), sort)| {
+                let new_name = self.canonical_name();
+                self.level += 1;
+                let old_var = pool.add(Term::new_var(name, sort.clone()));
+                let new_var = pool.add(Term::new_var(&new_name, sort.clone()));
+                self.renaming.insert(old_var, new_var);
+                (new_name, sort)
+            })
+            .collect();
+        let inner = self.canonicalize(pool, inner);
+
+        self.level -= bindings.len();
+        self.cache.pop();
+        self.renaming.pop_scope();
+        (BindingList(new_bindings), inner)
+    }
+}
@@ -0,0 +1,135 @@
+//! Constant folding of arithmetic terms.
+
+use super::{BindingList, Constant, Operator, Rc, Term, TermPool};
+use indexmap::IndexMap;
+use rug::Rational;
+
+/// Folds the constant arithmetic subterms of `term`. This is used to implement
+/// [`TermPool::fold_constants`].
+pub(super) fn fold_constants<P: TermPool + ?Sized>(pool: &mut P, term: &Rc<Term>) -> Rc<Term> {
+    ConstantFolder { pool, cache: IndexMap::new() }.fold(term)
+}
+
+struct ConstantFolder<'a, P: ?Sized> {
+    pool: &'a mut P,
+    cache: IndexMap<Rc<Term>, Rc<Term>>,
+}
+
+impl<P: TermPool + ?Sized> ConstantFolder<'_, P> {
+    fn fold(&mut self, term: &Rc<Term>) -> Rc<Term> {
+        if let Some(t) = self.cache.get(term) {
+            return t.clone();
+        }
+        let result = self.fold_uncached(term);
+        self.cache.insert(term.clone(), result.clone());
+        result
+    }
+
+    fn fold_all(&mut self, terms: &[Rc<Term>]) -> Vec<Rc<Term>> {
+        terms.iter().map(|t| self.fold(t)).collect()
+    }
+
+    fn fold_uncached(&mut self, term: &Rc<Term>) -> Rc<Term> {
+        let folded = match term.as_ref() {
+            Term::Const(_) | Term::Var(..) | Term::Sort(_) => return term.clone(),
+            Term::App(func, args) => Term::App(self.fold(func), self.fold_all(args)),
+            Term::Op(op, args) => {
+                let args = self.fold_all(args);
+                if let Some(result) = self.evaluate(*op, &args) {
+                    return result;
+                }
+                Term::Op(*op, args)
+            }
+            Term::ParamOp { op, op_args, args } => Term::ParamOp {
+                op: *op,
+                op_args: op_args.clone(),
+                args: self.fold_all(args),
+            },
+            Term::Binder(binder, bindings, inner) => {
+                Term::Binder(*binder, bindings.clone(), self.fold(inner))
+            }
+            Term::Let(bindings, inner) => {
+                let bindings = bindings
+                    .iter()
+                    .map(|(name, value)| (name.clone(), self.fold(value)))
+                    .collect();
+                Term::Let(BindingList(bindings), self.fold(inner))
+            }
+        };
+        self.pool.add(folded)
+    }
+
+    /// Evaluates the arithmetic operation `op` over `args`. Returns `None` if the operation is not
+    /// an arithmetic operation, if any argument is not a constant, or if it divides by zero.
+    fn evaluate(&mut self, op: Operator, args: &[Rc<Term>]) -> Option<Rc<Term>> {
+        if !matches!(
+            op,
+            Operator::Add | Operator::Sub | Operator::Mult | Operator::IntDiv | Operator::RealDiv
+        ) {
+            return None;
+        }
+        let is_negation = op == Operator::Sub && args.len() == 1;
+        let args: Vec<_> = args.iter().map(as_constant).collect::<Option<_>>()?;
+        let is_integer = op != Operator::RealDiv && args.iter().all(|(_, is_int)| *is_int);
+        let mut values = args.into_iter().map(|(value, _)| value);
+        let first = values.next()?;
+
+        let result = match op {
+            _ if is_negation => -first,
+            Operator::Add => values.fold(first, |acc, v| acc + v),
+            Operator::Sub => values.fold(first, |acc, v| acc - v),
+            Operator::Mult => values.fold(first, |acc, v| acc * v),
+            Operator::IntDiv if is_integer => {
+                let mut acc = first.into_numer_denom().0;
+                for v in values {
+                    if v == 0 {
+                        return None;
+                    }
+                    acc = acc.div_rem_euc(v.into_numer_denom().0).0;
+                }
+                Rational::from(acc)
+            }
+            Operator::RealDiv => {
+                let mut acc = first;
+                for v in values {
+                    if v == 0 {
+                        return None;
+                    }
+                    acc /= v;
+                }
+                acc
+            }
+            _ => return None,
+        };
+        Some(self.make_constant(result, is_integer))
+    }
+
+    /// Builds the constant term for `value`. Negative values are represented by applying the unary
+    /// `-` operator to their absolute value, like in the Alethe format.
+    fn make_constant(&mut self, value: Rational, is_integer: bool) -> Rc<Term> {
+        let is_negative = value < 0;
+        let value = value.abs();
+        let term = if is_integer {
+            Term::new_int(value.into_numer_denom().0)
+        } else {
+            Term::new_real(value)
+        };
+        let term = self.pool.add(term);
+        if is_negative {
+            self.pool.add(Term::Op(Operator::Sub, vec![term]))
+        } else {
+            term
+        }
+    }
+}
+
+/// Returns the value of `term` if it is an integer or real constant, possibly negated with the
+/// unary `-` operator, together with whether it is an integer.
+fn as_constant(term: &Rc<Term>) -> Option<(Rational, bool)> {
+    let (value, unsigned) = match term.as_ref() {
+        Term::Op(Operator::Sub, args) if args.len() == 1 => (-args[0].as_number()?, &args[0]),
+        _ => (term.as_number()?, term),
+    };
+    let is_integer = matches!(unsigned.as_ref(), Term::Const(Constant::Integer(_)));
+    Some((value, is_integer))
+}
@@ -9,7 +9,9 @@ use super::*;
 /// encountering a subproof, the iterator first yields the `ProofCommand::Subproof`, and then
 /// iterates through the subproofs commands.
 ///
-/// This struct is created by the [`iter`](Proof::iter) method on proofs.
+/// This struct is created by the [`iter`](Proof::iter) method on proofs, or by [`ProofIter::new`]
+/// from a slice of commands. Besides iterating, it keeps track of the open subproofs, which allows
+/// resolving premise indices with [`ProofIter::get_premise`].
 ///
 /// # Examples
 ///
@@ -34,13 +36,45 @@ use super::*;
 /// # Ok(())
 /// # }
 /// ```
+///
+/// Premises are given as `(depth, index)` pairs, which can be resolved while iterating:
+///
+/// ```
+/// # use carcara::{ast::*, *};
+/// # fn main() -> CarcaraResult<()> {
+/// let proof = "
+///     (assume h1 (not p))
+///     (anchor :step t2 :args ((x Int)))
+///     (step t2.t1 (cl p (not p)) :rule hole)
+///     (step t2.t2 (cl p) :rule resolution :premises (t2.t1 h1))
+///     (step t2 (cl p) :rule hole)
+/// "
+/// .as_bytes();
+/// let problem = "(declare-fun p () Bool)".as_bytes();
+/// let (_, proof, _) = parser::parse_instance(problem, proof, parser::Config::new())?;
+///
+/// let mut iter = proof.iter();
+/// while let Some(command) = iter.next() {
+///     let ProofCommand::Step(step) = command else { continue };
+///     if step.id == "t2.t2" {
+///         assert_eq!(iter.depth(), 1);
+///         assert_eq!(iter.current_subproof_args().map(<[_]>::len), Some(1));
+///
+///         let premise = iter.get_premise(step.premises[1]);
+///         assert_eq!(premise.id(), "h1");
+///         assert_eq!(premise.clause()[0].to_string(), "(not p)");
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
 pub struct ProofIter<'a> {
     stack: Vec<(usize, &'a [ProofCommand])>,
 }
 
 impl<'a> ProofIter<'a> {
     /// Constructs a new `ProofIter`, given a slice of proof commands.
-    pub(super) fn new(commands: &'a [ProofCommand]) -> Self {
+    pub fn new(commands: &'a [ProofCommand]) -> Self {
         Self { stack: vec![(0, commands)] }
     }
 
@@ -62,6 +96,15 @@ impl<'a> ProofIter<'a> {
         self.is_in_subproof().then(|| self.stack.last().unwrap().1)
     }
 
+    /// Returns the arguments of the `anchor` command that opened the inner-most open subproof.
+    pub fn current_subproof_args(&self) -> Option<&'a [AnchorArg]> {
+        let &(i, commands) = self.stack.iter().rev().nth(1)?;
+        match &commands[i - 1] {
+            ProofCommand::Subproof(s) => Some(&s.args),
+            _ => unreachable!(),
+        }
+    }
+
     /// Returns `true` if the last command that was returned was the end step of the current
     /// subproof.
     pub fn is_end_step(&self) -> bool {
@@ -71,9 +114,26 @@ impl<'a> ProofIter<'a> {
         }
     }
 
+    /// Returns the position of the last command that was returned, that is, its index in each of
+    /// the subproofs that enclose it, starting from the root proof. The position of a subproof is
+    /// the position of its `anchor` command.
+    pub fn position(&self) -> Vec<usize> {
+        let mut position: Vec<_> = self
+            .stack
+            .iter()
+            .map(|&(i, _)| i.saturating_sub(1))
+            .collect();
+
+        // If the last command was a subproof, its commands were pushed, but none was returned yet
+        if self.stack.len() > 1 && self.stack.last().unwrap().0 == 0 {
+            position.pop();
+        }
+        position
+    }
+
     /// Returns the command referenced by a premise index of the form (depth, index in subproof).
     /// This method may panic if the premise index does not refer to a valid command.
-    pub fn get_premise(&self, (depth, index): (usize, usize)) -> &ProofCommand {
+    pub fn get_premise(&self, (depth, index): (usize, usize)) -> &'a ProofCommand {
         &self.stack[depth].1[index]
     }
 }
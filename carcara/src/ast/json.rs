@@ -0,0 +1,645 @@
+//! Serialization of proofs to a stable JSON form, for use by external tools, and deserialization
+//! from that form. This module is only available with the `serde` feature.
+//!
+//! Terms are written as objects tagged by a `"type"` field, and operators are written by their
+//! SMT-LIB names. Constants and sorts are also tagged by a `"kind"` field, so, for example, the
+//! integer `1` and the string `"1"` are written differently. Numbers that may not fit in a machine
+//! integer, like the values of constants, are written as strings. Shared subterms are always
+//! written by value, so the output does not preserve sharing.
+//!
+//! The types in the AST implement [`serde::Serialize`] using this form. Since terms must be added
+//! to a term pool, they can't implement [`serde::Deserialize`], so [`from_json`] and
+//! [`node_from_json`] should be used instead.
+
+use super::*;
+use crate::parser::{self, ParserError};
+use serde::{Deserialize, Serialize, Serializer};
+use std::borrow::Cow;
+use thiserror::Error;
+
+/// The error type for the JSON reader.
+#[derive(Debug, Error)]
+pub enum JsonError {
+    /// The input is not valid JSON, or does not have the expected form. This includes inputs that
+    /// are nested too deeply, since the reader is recursive.
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// A field has a value that can't be converted, like a constant that is not a valid number, or
+    /// a sort that is not a sort term.
+    #[error("invalid value for field '{0}'")]
+    InvalidValue(&'static str),
+
+    /// An operator name is not recognized.
+    #[error("unknown operator '{0}'")]
+    UnknownOperator(String),
+
+    /// A term is not well sorted.
+    #[error(transparent)]
+    Sort(#[from] ParserError),
+
+    /// The commands read are not a structurally valid proof.
+    #[error(transparent)]
+    Structure(#[from] StructureError),
+}
+
+/// Serializes a proof, including its premises and named terms, to JSON. The original clauses
+/// recorded by the parser are only included if there are any.
+///
+/// # Examples
+///
+/// ```
+/// # use carcara::{ast::to_json, parser};
+/// let proof = "(assume h1 (not p)) (step t2 (cl) :rule false :premises (h1))";
+/// let (_, proof, _) = parser::parse_instance(
+///     "(declare-fun p () Bool)".as_bytes(),
+///     proof.as_bytes(),
+///     parser::Config::new(),
+/// )
+/// .unwrap();
+/// let json = to_json(&proof);
+/// assert!(json.starts_with(r#"{"premises":[],"commands":[{"type":"assume","id":"h1""#));
+/// ```
+pub fn to_json(proof: &Proof) -> String {
+    serde_json::to_string(proof).unwrap()
+}
+
+/// Reads a proof from the JSON form written by [`to_json`], adding its terms to `pool`.
+///
+/// The terms are sort checked, and the structure of the proof is validated as in
+/// [`Proof::validate_structure`]. The context ids of the subproofs are assigned in order, as the
+/// parser does. If the terms are added to the same pool as the original proof, the result is equal
+/// to it.
+///
+/// The reader is recursive, so, to avoid stack overflows, it rejects inputs that are nested more
+/// than 128 levels deep. Each level of a term takes two levels in JSON, so this limits the depth
+/// of the terms that can be read.
+pub fn from_json(pool: &mut dyn TermPool, json: &str) -> Result<Proof, JsonError> {
+    let data: ProofRepr<TermData, Vec<CommandData>> = serde_json::from_str(json)?;
+    let mut reader = Reader::new(pool);
+    let premises = reader.terms(data.premises)?.into_iter().collect();
+    let commands = reader.commands(data.commands)?;
+    let named_terms = data
+        .named_terms
+        .into_iter()
+        .map(|n| Ok((reader.term(n.term)?, n.name.into_owned())))
+        .collect::<Result<_, JsonError>>()?;
+    let original_clauses = data
+        .original_clauses
+        .into_iter()
+        .map(|c| Ok((c.position, reader.terms(c.clause)?)))
+        .collect::<Result<_, JsonError>>()?;
+    structure::validate_commands(&commands)?;
+    Ok(Proof {
+        premises,
+        commands,
+        named_terms,
+        original_clauses,
+    })
+}
+
+/// Serializes the graph rooted at a proof node to JSON. The output is an object with a single
+/// `"commands"` field, holding the commands given by [`ProofNode::into_commands`].
+pub fn node_to_json(node: &ProofNode) -> String {
+    serde_json::to_string(node).unwrap()
+}
+
+/// Reads a proof node from the JSON form written by [`node_to_json`], adding its terms to `pool`.
+/// This has the same checks and limitations as [`from_json`].
+pub fn node_from_json(pool: &mut dyn TermPool, json: &str) -> Result<Rc<ProofNode>, JsonError> {
+    let data: NodeRepr<Vec<CommandData>> = serde_json::from_str(json)?;
+    let commands = Reader::new(pool).commands(data.commands)?;
+    if commands.is_empty() {
+        return Err(JsonError::InvalidValue("commands"));
+    }
+    structure::validate_commands(&commands)?;
+    Ok(ProofNode::from_commands(&commands))
+}
+
+// The types below describe the JSON form. Each one is generic over the types used for terms, lists
+// of terms and lists of commands, so the same type is used to serialize references to the AST and
+// to deserialize owned data, which is then converted into terms by the `Reader`.
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TermRepr<'a, T, Ts> {
+    Const(ConstRepr<'a>),
+    Var {
+        name: Cow<'a, str>,
+        sort: T,
+    },
+    App {
+        func: T,
+        args: Ts,
+    },
+    Op {
+        op: Cow<'a, str>,
+        args: Ts,
+    },
+    ParamOp {
+        op: Cow<'a, str>,
+        op_args: Ts,
+        args: Ts,
+    },
+    Sort {
+        sort: SortRepr<'a, T, Ts>,
+    },
+    Binder {
+        binder: BinderRepr,
+        bindings: Vec<BindingRepr<'a, T>>,
+        term: T,
+    },
+    Let {
+        bindings: Vec<LetBindingRepr<'a, T>>,
+        term: T,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ConstRepr<'a> {
+    Int {
+        value: Cow<'a, str>,
+    },
+    Real {
+        value: Cow<'a, str>,
+    },
+    String {
+        value: Cow<'a, str>,
+    },
+    Bitvec {
+        value: Cow<'a, str>,
+        width: Cow<'a, str>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum SortRepr<'a, T, Ts> {
+    Function {
+        sorts: Ts,
+    },
+    Atom {
+        name: Cow<'a, str>,
+        args: Ts,
+    },
+    Array {
+        index: T,
+        element: T,
+    },
+    Bitvec {
+        width: Cow<'a, str>,
+    },
+    Bool,
+    Int,
+    Real,
+    String,
+    #[serde(rename = "reglan")]
+    RegLan,
+    RareList,
+    Type,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum BinderRepr {
+    Forall,
+    Exists,
+    Choice,
+    Lambda,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BindingRepr<'a, T> {
+    name: Cow<'a, str>,
+    sort: T,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LetBindingRepr<'a, T> {
+    name: Cow<'a, str>,
+    value: T,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum CommandRepr<'a, T, Ts, Cs> {
+    Assume {
+        id: Cow<'a, str>,
+        term: T,
+    },
+    Step {
+        id: Cow<'a, str>,
+        clause: Ts,
+        rule: Cow<'a, str>,
+        premises: Cow<'a, [(usize, usize)]>,
+        args: Vec<ProofArgRepr<'a, T>>,
+        discharge: Cow<'a, [(usize, usize)]>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        weight: Option<u64>,
+    },
+    Subproof {
+        args: Vec<AnchorArgRepr<'a, T>>,
+        commands: Cs,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ProofArgRepr<'a, T> {
+    Term { term: T },
+    Assign { name: Cow<'a, str>, term: T },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnchorArgRepr<'a, T> {
+    Variable {
+        name: Cow<'a, str>,
+        sort: T,
+    },
+    Assign {
+        name: Cow<'a, str>,
+        sort: T,
+        term: T,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct NamedTermRepr<'a, T> {
+    name: Cow<'a, str>,
+    term: T,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OriginalClauseRepr<T> {
+    position: Vec<usize>,
+    clause: Vec<T>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProofRepr<'a, T, Cs> {
+    premises: Vec<T>,
+    commands: Cs,
+    named_terms: Vec<NamedTermRepr<'a, T>>,
+
+    // Most proofs are parsed without normalizing the clause order, so this is omitted when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    original_clauses: Vec<OriginalClauseRepr<T>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct NodeRepr<Cs> {
+    commands: Cs,
+}
+
+#[derive(Deserialize)]
+#[serde(transparent)]
+struct TermData(Box<TermRepr<'static, TermData, Vec<TermData>>>);
+
+#[derive(Deserialize)]
+#[serde(transparent)]
+struct CommandData(CommandRepr<'static, TermData, Vec<TermData>, Vec<CommandData>>);
+
+type TermRef<'a> = TermRepr<'a, &'a Rc<Term>, &'a [Rc<Term>]>;
+
+impl<T: Serialize + ?Sized> Serialize for Rc<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.as_ref().serialize(serializer)
+    }
+}
+
+impl Serialize for Term {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr: TermRef = match self {
+            Term::Const(c) => TermRepr::Const(match c {
+                Constant::Integer(i) => ConstRepr::Int { value: i.to_string().into() },
+                Constant::Real(r) => ConstRepr::Real { value: r.to_string().into() },
+                Constant::String(s) => ConstRepr::String { value: s.into() },
+                Constant::BitVec(value, width) => ConstRepr::Bitvec {
+                    value: value.to_string().into(),
+                    width: width.to_string().into(),
+                },
+            }),
+            Term::Var(name, sort) => TermRepr::Var { name: name.into(), sort },
+            Term::App(func, args) => TermRepr::App { func, args },
+            Term::Op(op, args) => TermRepr::Op { op: op.to_symbol().into(), args },
+            Term::ParamOp { op, op_args, args } => TermRepr::ParamOp {
+                op: op.to_symbol().into(),
+                op_args,
+                args,
+            },
+            Term::Sort(sort) => TermRepr::Sort { sort: sort_repr(sort) },
+            Term::Binder(binder, bindings, term) => TermRepr::Binder {
+                binder: match binder {
+                    Binder::Forall => BinderRepr::Forall,
+                    Binder::Exists => BinderRepr::Exists,
+                    Binder::Choice => BinderRepr::Choice,
+                    Binder::Lambda => BinderRepr::Lambda,
+                },
+                bindings: (bindings.iter())
+                    .map(|(name, sort)| BindingRepr { name: name.into(), sort })
+                    .collect(),
+                term,
+            },
+            Term::Let(bindings, term) => TermRepr::Let {
+                bindings: (bindings.iter())
+                    .map(|(name, value)| LetBindingRepr { name: name.into(), value })
+                    .collect(),
+                term,
+            },
+        };
+        repr.serialize(serializer)
+    }
+}
+
+fn sort_repr(sort: &Sort) -> SortRepr<&Rc<Term>, &[Rc<Term>]> {
+    match sort {
+        Sort::Function(sorts) => SortRepr::Function { sorts },
+        Sort::Atom(name, args) => SortRepr::Atom { name: name.into(), args },
+        Sort::Array(index, element) => SortRepr::Array { index, element },
+        Sort::BitVec(width) => SortRepr::Bitvec { width: width.to_string().into() },
+        Sort::Bool => SortRepr::Bool,
+        Sort::Int => SortRepr::Int,
+        Sort::Real => SortRepr::Real,
+        Sort::String => SortRepr::String,
+        Sort::RegLan => SortRepr::RegLan,
+        Sort::RareList => SortRepr::RareList,
+        Sort::Type => SortRepr::Type,
+    }
+}
+
+fn step_repr(s: &ProofStep) -> CommandRepr<&Rc<Term>, &[Rc<Term>], &[ProofCommand]> {
+    CommandRepr::Step {
+        id: s.id.as_str().into(),
+        clause: &s.clause,
+        rule: s.rule.as_str().into(),
+        premises: s.premises.as_slice().into(),
+        args: (s.args.iter())
+            .map(|arg| match arg {
+                ProofArg::Term(term) => ProofArgRepr::Term { term },
+                ProofArg::Assign(name, term) => ProofArgRepr::Assign { name: name.into(), term },
+            })
+            .collect(),
+        discharge: s.discharge.as_slice().into(),
+        weight: s.weight,
+    }
+}
+
+impl Serialize for ProofStep {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        step_repr(self).serialize(serializer)
+    }
+}
+
+impl Serialize for ProofCommand {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = match self {
+            ProofCommand::Assume { id, term } => CommandRepr::Assume { id: id.into(), term },
+            ProofCommand::Step(s) => step_repr(s),
+            ProofCommand::Subproof(s) => CommandRepr::Subproof {
+                args: (s.args.iter())
+                    .map(|arg| match arg {
+                        AnchorArg::Variable((name, sort)) => {
+                            AnchorArgRepr::Variable { name: name.into(), sort }
+                        }
+                        AnchorArg::Assign((name, sort), term) => {
+                            AnchorArgRepr::Assign { name: name.into(), sort, term }
+                        }
+                    })
+                    .collect(),
+                commands: s.commands.as_slice(),
+            },
+        };
+        repr.serialize(serializer)
+    }
+}
+
+impl Serialize for Proof {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ProofRepr::<_, &[ProofCommand]> {
+            premises: self.premises.iter().collect(),
+            commands: &self.commands,
+            named_terms: (self.named_terms.iter())
+                .map(|(term, name)| NamedTermRepr { name: name.into(), term })
+                .collect(),
+            original_clauses: (self.original_clauses.iter())
+                .map(|(position, clause)| OriginalClauseRepr {
+                    position: position.clone(),
+                    clause: clause.iter().collect(),
+                })
+                .collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl Serialize for ProofNode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        NodeRepr { commands: self.into_commands() }.serialize(serializer)
+    }
+}
+
+/// Converts deserialized data into terms and commands, checking that they are valid.
+struct Reader<'p> {
+    pool: &'p mut dyn TermPool,
+
+    /// The configuration used to sort check operation terms. This is as permissive as the parser
+    /// allows, so any proof that was parsed can be read back.
+    config: parser::Config,
+
+    next_context_id: usize,
+}
+
+impl<'p> Reader<'p> {
+    fn new(pool: &'p mut dyn TermPool) -> Self {
+        let config = parser::Config {
+            allow_int_real_subtyping: true,
+            allow_unary_logical_ops: true,
+            ..parser::Config::new()
+        };
+        Self { pool, config, next_context_id: 0 }
+    }
+
+    fn terms(&mut self, data: Vec<TermData>) -> Result<Vec<Rc<Term>>, JsonError> {
+        data.into_iter().map(|t| self.term(t)).collect()
+    }
+
+    /// Reads a term that must be a sort, like the sort of a variable.
+    fn sort(&mut self, data: TermData) -> Result<Rc<Term>, JsonError> {
+        let sort = self.term(data)?;
+        if sort.is_sort() {
+            Ok(sort)
+        } else {
+            Err(JsonError::InvalidValue("sort"))
+        }
+    }
+
+    fn sorts(&mut self, data: Vec<TermData>) -> Result<Vec<Rc<Term>>, JsonError> {
+        data.into_iter().map(|t| self.sort(t)).collect()
+    }
+
+    fn term(&mut self, data: TermData) -> Result<Rc<Term>, JsonError> {
+        fn parse<T: std::str::FromStr>(s: &str, field: &'static str) -> Result<T, JsonError> {
+            s.parse().map_err(|_| JsonError::InvalidValue(field))
+        }
+
+        let term = match *data.0 {
+            TermRepr::Const(c) => Term::Const(match c {
+                ConstRepr::Int { value } => Constant::Integer(parse(&value, "value")?),
+                ConstRepr::Real { value } => Constant::Real(parse(&value, "value")?),
+                ConstRepr::String { value } => Constant::String(value.into_owned()),
+                ConstRepr::Bitvec { value, width } => {
+                    Constant::BitVec(parse(&value, "value")?, parse(&width, "width")?)
+                }
+            }),
+            TermRepr::Var { name, sort } => Term::Var(name.into_owned(), self.sort(sort)?),
+            TermRepr::App { func, args } => {
+                let func = self.term(func)?;
+                let args = self.terms(args)?;
+                parser::check_app_args(self.pool, &func, &args)?;
+                Term::App(func, args)
+            }
+            TermRepr::Op { op, args } => {
+                let op = Operator::from_symbol(&op)
+                    .ok_or_else(|| JsonError::UnknownOperator(op.into_owned()))?;
+                let args = self.terms(args)?;
+                parser::check_op_args(self.pool, op, &args, &self.config)?;
+                Term::Op(op, args)
+            }
+            TermRepr::ParamOp { op, op_args, args } => {
+                let op = ParamOperator::from_symbol(&op)
+                    .ok_or_else(|| JsonError::UnknownOperator(op.into_owned()))?;
+                let op_args = self.terms(op_args)?;
+                let args = self.terms(args)?;
+                if op == ParamOperator::ArrayConst {
+                    let [sort] = op_args.as_slice() else {
+                        return Err(JsonError::InvalidValue("op_args"));
+                    };
+                    if !sort.is_sort() {
+                        return Err(JsonError::InvalidValue("op_args"));
+                    }
+                    parser::check_qualified_op_args(self.pool, op, sort, &args)?;
+                } else {
+                    let constants = (op_args.iter())
+                        .map(|t| match t.as_ref() {
+                            Term::Const(c) => Ok(c.clone()),
+                            _ => Err(JsonError::InvalidValue("op_args")),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    parser::check_indexed_op_args(self.pool, op, &constants, &args)?;
+                }
+                Term::ParamOp { op, op_args, args }
+            }
+            TermRepr::Sort { sort } => Term::Sort(match sort {
+                SortRepr::Function { sorts } => {
+                    // A function sort has at least one argument sort and a result sort
+                    if sorts.len() < 2 {
+                        return Err(JsonError::InvalidValue("sorts"));
+                    }
+                    Sort::Function(self.sorts(sorts)?)
+                }
+                SortRepr::Atom { name, args } => Sort::Atom(name.into_owned(), self.sorts(args)?),
+                SortRepr::Array { index, element } => {
+                    Sort::Array(self.sort(index)?, self.sort(element)?)
+                }
+                SortRepr::Bitvec { width } => Sort::BitVec(parse(&width, "width")?),
+                SortRepr::Bool => Sort::Bool,
+                SortRepr::Int => Sort::Int,
+                SortRepr::Real => Sort::Real,
+                SortRepr::String => Sort::String,
+                SortRepr::RegLan => Sort::RegLan,
+                SortRepr::RareList => Sort::RareList,
+                SortRepr::Type => Sort::Type,
+            }),
+            TermRepr::Binder { binder, bindings, term } => {
+                if bindings.is_empty() {
+                    return Err(JsonError::InvalidValue("bindings"));
+                }
+                let binder = match binder {
+                    BinderRepr::Forall => Binder::Forall,
+                    BinderRepr::Exists => Binder::Exists,
+                    BinderRepr::Choice => Binder::Choice,
+                    BinderRepr::Lambda => Binder::Lambda,
+                };
+                let bindings = (bindings.into_iter())
+                    .map(|b| Ok((b.name.into_owned(), self.sort(b.sort)?)))
+                    .collect::<Result<_, JsonError>>()?;
+                Term::Binder(binder, BindingList(bindings), self.term(term)?)
+            }
+            TermRepr::Let { bindings, term } => {
+                let bindings = (bindings.into_iter())
+                    .map(|b| Ok((b.name.into_owned(), self.term(b.value)?)))
+                    .collect::<Result<_, JsonError>>()?;
+                Term::Let(BindingList(bindings), self.term(term)?)
+            }
+        };
+        Ok(self.pool.add(term))
+    }
+
+    fn commands(&mut self, data: Vec<CommandData>) -> Result<Vec<ProofCommand>, JsonError> {
+        data.into_iter().map(|c| self.command(c)).collect()
+    }
+
+    fn command(&mut self, data: CommandData) -> Result<ProofCommand, JsonError> {
+        Ok(match data.0 {
+            CommandRepr::Assume { id, term } => ProofCommand::Assume {
+                id: id.into_owned(),
+                term: self.term(term)?,
+            },
+            CommandRepr::Step {
+                id,
+                clause,
+                rule,
+                premises,
+                args,
+                discharge,
+                weight,
+            } => {
+                let args = (args.into_iter())
+                    .map(|arg| {
+                        Ok(match arg {
+                            ProofArgRepr::Term { term } => ProofArg::Term(self.term(term)?),
+                            ProofArgRepr::Assign { name, term } => {
+                                ProofArg::Assign(name.into_owned(), self.term(term)?)
+                            }
+                        })
+                    })
+                    .collect::<Result<_, JsonError>>()?;
+                ProofCommand::Step(ProofStep {
+                    id: id.into_owned(),
+                    clause: self.terms(clause)?,
+                    rule: rule.into_owned(),
+                    premises: premises.into_owned(),
+                    args,
+                    discharge: discharge.into_owned(),
+                    weight,
+                })
+            }
+            CommandRepr::Subproof { args, commands } => {
+                // Like in the parser, context ids are assigned in the order the subproofs are
+                // opened
+                let context_id = self.next_context_id;
+                self.next_context_id += 1;
+                let args = (args.into_iter())
+                    .map(|arg| {
+                        Ok(match arg {
+                            AnchorArgRepr::Variable { name, sort } => {
+                                AnchorArg::Variable((name.into_owned(), self.sort(sort)?))
+                            }
+                            AnchorArgRepr::Assign { name, sort, term } => AnchorArg::Assign(
+                                (name.into_owned(), self.sort(sort)?),
+                                self.term(term)?,
+                            ),
+                        })
+                    })
+                    .collect::<Result<_, JsonError>>()?;
+                ProofCommand::Subproof(Subproof {
+                    commands: self.commands(commands)?,
+                    args,
+                    context_id,
+                })
+            }
+        })
+    }
+}
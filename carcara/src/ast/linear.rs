@@ -0,0 +1,144 @@
+//! A normal form for linear arithmetic terms.
+
+use super::{Operator, Rc, Sort, Term, TermPool};
+use indexmap::{map::Entry, IndexMap};
+use rug::Rational;
+
+/// A linear combination of integer or real terms, of the form `c_1 * x_1 + ... + c_n * x_n + k`,
+/// where each `c_i` and `k` are constants.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinearCombination {
+    /// The coefficient of each non-constant term, in the order they first appear. Terms whose
+    /// coefficients add up to zero are removed.
+    pub coefficients: IndexMap<Rc<Term>, Rational>,
+
+    /// The constant term.
+    pub constant: Rational,
+
+    /// Whether the terms in the linear combination are integers, instead of reals.
+    pub is_integer: bool,
+}
+
+impl LinearCombination {
+    /// Builds a linear combination from a term of sort `Int` or `Real`. Nested additions,
+    /// subtractions and multiplications by constants are flattened, and the coefficients of each
+    /// non-constant term are collected. Any term that is not one of these operations or a
+    /// constant is considered an atom.
+    ///
+    /// Returns `None` if the term is not of an arithmetic sort, or if it multiplies two
+    /// non-constant terms.
+    pub fn from_term(pool: &mut dyn TermPool, term: &Rc<Term>) -> Option<Self> {
+        let is_integer = match pool.sort(term).as_sort()? {
+            Sort::Int => true,
+            Sort::Real => false,
+            _ => return None,
+        };
+        let mut result = Self {
+            coefficients: IndexMap::new(),
+            constant: Rational::new(),
+            is_integer,
+        };
+        result.add_term(term, &Rational::from(1))?;
+        Some(result)
+    }
+
+    /// Flattens a term and adds it to the linear combination, multiplying it by `coeff`.
+    fn add_term(&mut self, term: &Rc<Term>, coeff: &Rational) -> Option<()> {
+        match term.as_ref() {
+            Term::Op(Operator::Add, args) => {
+                for a in args {
+                    self.add_term(a, coeff)?;
+                }
+            }
+            Term::Op(Operator::Sub, args) if args.len() == 1 => {
+                self.add_term(&args[0], &coeff.as_neg())?;
+            }
+            Term::Op(Operator::Sub, args) => {
+                self.add_term(&args[0], coeff)?;
+                for a in &args[1..] {
+                    self.add_term(a, &coeff.as_neg())?;
+                }
+            }
+            Term::Op(Operator::Mult, args) => {
+                let mut coeff = coeff.clone();
+                let mut non_constant = None;
+                for a in args {
+                    match a.as_fraction() {
+                        Some(r) => coeff *= r,
+                        None if non_constant.is_none() => non_constant = Some(a),
+                        None => return None,
+                    }
+                }
+                match non_constant {
+                    Some(a) => self.add_term(a, &coeff)?,
+                    None => self.constant += coeff,
+                }
+            }
+            _ => match term.as_fraction() {
+                Some(r) => self.constant += r * coeff,
+                None => self.insert(term.clone(), coeff.clone()),
+            },
+        }
+        Some(())
+    }
+
+    fn insert(&mut self, key: Rc<Term>, value: Rational) {
+        match self.coefficients.entry(key) {
+            Entry::Occupied(mut e) => {
+                *e.get_mut() += value;
+                if *e.get() == 0 {
+                    e.remove();
+                }
+            }
+            Entry::Vacant(e) => {
+                if value != 0 {
+                    e.insert(value);
+                }
+            }
+        }
+    }
+
+    /// Builds a term from the linear combination, of the form `(+ (* c_1 x_1) ... (* c_n x_n) k)`.
+    /// Coefficients of one are omitted, coefficients of minus one are represented with the unary
+    /// `-` operator, and the constant is omitted if it is zero. If only one summand is left, it is
+    /// returned without the `+` operator.
+    pub fn to_term(&self, pool: &mut dyn TermPool) -> Rc<Term> {
+        let mut summands = Vec::with_capacity(self.coefficients.len() + 1);
+        for (term, coeff) in &self.coefficients {
+            let summand = if *coeff == 1 {
+                term.clone()
+            } else if *coeff == -1 {
+                pool.add(Term::Op(Operator::Sub, vec![term.clone()]))
+            } else {
+                let coeff = self.make_constant(pool, coeff);
+                pool.add(Term::Op(Operator::Mult, vec![coeff, term.clone()]))
+            };
+            summands.push(summand);
+        }
+        if self.constant != 0 || summands.is_empty() {
+            summands.push(self.make_constant(pool, &self.constant));
+        }
+
+        if summands.len() == 1 {
+            summands.pop().unwrap()
+        } else {
+            pool.add(Term::Op(Operator::Add, summands))
+        }
+    }
+
+    /// Builds the constant term for `value`, with the sort of the linear combination. Negative
+    /// values are represented by applying the unary `-` operator to their absolute value.
+    fn make_constant(&self, pool: &mut dyn TermPool, value: &Rational) -> Rc<Term> {
+        let abs = value.clone().abs();
+        let term = if self.is_integer && abs.is_integer() {
+            pool.add(Term::new_int(abs.into_numer_denom().0))
+        } else {
+            pool.add(Term::new_real(abs))
+        };
+        if *value < 0 {
+            pool.add(Term::Op(Operator::Sub, vec![term]))
+        } else {
+            term
+        }
+    }
+}
@@ -162,6 +162,7 @@ macro_rules! match_term {
     (@GET_VARIANT div)      => { $crate::ast::Operator::IntDiv };
     (@GET_VARIANT /)        => { $crate::ast::Operator::RealDiv };
     (@GET_VARIANT mod)      => { $crate::ast::Operator::Mod };
+    (@GET_VARIANT abs)      => { $crate::ast::Operator::Abs };
     (@GET_VARIANT <)        => { $crate::ast::Operator::LessThan };
     (@GET_VARIANT >)        => { $crate::ast::Operator::GreaterThan };
     (@GET_VARIANT <=)       => { $crate::ast::Operator::LessEq };
@@ -206,6 +207,10 @@ macro_rules! match_term_err {
 /// representing the term to be built. Subterms in that s-expression that are surrounded by `{}` are
 /// evaluated as expressions, and they should have type `Rc<Term>`.
 ///
+/// Quantifiers and `let` terms can also be built. The sorts of the bound variables and the values
+/// of the `let` bindings must be given as subterms, and to refer to a bound variable in the inner
+/// term, the corresponding variable term must also be passed as a subterm.
+///
 /// # Examples
 ///
 /// Building the term `(and true (not false))`:
@@ -215,12 +220,53 @@ macro_rules! match_term_err {
 /// let t = build_term!(pool, (and {pool.bool_true()} (not {pool.bool_false()})));
 /// assert!(match_term!((and true (not false)) = t).is_some());
 /// ```
+///
+/// Building the term `(abs (mod x 2))`, and matching it back:
+/// ```
+/// # use carcara::{ast::*, build_term, match_term};
+/// let mut pool = PrimitivePool::new();
+/// let int_sort = pool.add(Term::Sort(Sort::Int));
+/// let x = pool.add(Term::new_var("x", int_sort));
+/// let t = build_term!(pool, (abs (mod {x.clone()} 2)));
+/// let (a, b) = match_term!((abs (mod a b)) = t).unwrap();
+/// assert_eq!(a, &x);
+/// assert_eq!(b.as_integer(), Some(2.into()));
+/// ```
+///
+/// Building the terms `(forall ((x Int)) (> x 0))` and `(let ((y 1)) (> y 0))`:
+/// ```
+/// # use carcara::{ast::*, build_term, match_term};
+/// let mut pool = PrimitivePool::new();
+/// let int_sort = pool.add(Term::Sort(Sort::Int));
+/// let x = pool.add(Term::new_var("x", int_sort.clone()));
+/// let t = build_term!(pool, (forall ((x {int_sort.clone()})) (> {x.clone()} 0)));
+/// let (bindings, (a, _)) = match_term!((forall ... (> a b)) = t).unwrap();
+/// assert_eq!(bindings.as_slice(), &[("x".to_owned(), int_sort.clone())]);
+/// assert_eq!(a, &x);
+///
+/// let y = pool.add(Term::new_var("y", int_sort));
+/// let t = build_term!(pool, (let ((y 1)) (> {y.clone()} 0)));
+/// let (bindings, inner) = t.as_let().unwrap();
+/// assert_eq!(bindings.as_slice(), &[("y".to_owned(), pool.add(Term::new_int(1)))]);
+/// assert_eq!(match_term!((> a b) = inner).unwrap().0, &y);
+/// ```
 #[macro_export]
 macro_rules! build_term {
     ($pool:expr, true) => { $pool.bool_true() };
     ($pool:expr, false) => { $pool.bool_false() };
     ($pool:expr, $int:literal) => { $pool.add(Term::Const(Constant::Integer($int.into()))) };
     ($pool:expr, {$terminal:expr}) => { $terminal };
+    ($pool:expr, (forall ($(($var:ident $sort:tt))+) $inner:tt)) => {
+        build_term!(@BINDER $pool, Forall, ($(($var $sort))+) $inner)
+    };
+    ($pool:expr, (exists ($(($var:ident $sort:tt))+) $inner:tt)) => {
+        build_term!(@BINDER $pool, Exists, ($(($var $sort))+) $inner)
+    };
+    ($pool:expr, (let ($(($var:ident $value:tt))+) $inner:tt)) => {{
+        let bindings = vec![ $((stringify!($var).to_owned(), build_term!($pool, $value))),+ ];
+        let inner = build_term!($pool, $inner);
+        $pool.add($crate::ast::Term::Let($crate::ast::BindingList(bindings), inner))
+    }};
     ($pool:expr, ((_ $indexed_op:tt $($op_args:tt)+) $($args:tt)+)) => {{
         let term = $crate::ast::Term::ParamOp {
             op: match_term!(@GET_VARIANT $indexed_op),
@@ -236,13 +282,24 @@ macro_rules! build_term {
         );
         $pool.add(term)
     }};
+    (@BINDER $pool:expr, $binder:ident, ($(($var:ident $sort:tt))+) $inner:tt) => {{
+        let bindings = vec![ $((stringify!($var).to_owned(), build_term!($pool, $sort))),+ ];
+        let inner = build_term!($pool, $inner);
+        $pool.add($crate::ast::Term::Binder(
+            $crate::ast::Binder::$binder,
+            $crate::ast::BindingList(bindings),
+            inner,
+        ))
+    }};
 }
 
 /// Implements `FromStr` and `Display` for an enum, given a mapping from each variant to a string
 /// literal.
 ///
 /// This macros only supports enums that don't hold any data in any of their variants. The error
-/// type for the implementation of `FromStr` will be `()`.
+/// type for the implementation of `FromStr` will be `()`. This also implements the inherent methods
+/// `from_symbol` and `to_symbol`, which do the same conversions without allocating or using a unit
+/// error type, and the `ALL` constant, which lists every variant.
 ///
 /// # Examples
 ///
@@ -268,27 +325,44 @@ macro_rules! build_term {
 ///     assert_eq!(Foo::from_str("a"), Ok(Foo::A));
 ///     assert_eq!(format!("{}", Foo::B), "b");
 ///     assert_eq!(Foo::from_str("d"), Err(()));
+///     assert_eq!(Foo::from_symbol("c"), Some(Foo::C));
+///     assert_eq!(Foo::A.to_symbol(), "a");
 /// }
 /// ```
 macro_rules! impl_str_conversion_traits {
     ($enum_name:ident { $($variant:ident: $str:literal),* $(,)? }) => {
+        #[allow(dead_code)]
+        impl $enum_name {
+            /// All variants of this enum that have a string form.
+            pub const ALL: &'static [Self] = &[$($enum_name::$variant),*];
+
+            /// Returns the variant whose string form is `s`, if there is one.
+            pub fn from_symbol(s: &str) -> Option<Self> {
+                match s {
+                    $($str => Some($enum_name::$variant),)*
+                    _ => None,
+                }
+            }
+
+            /// Returns the string form of this variant.
+            pub fn to_symbol(&self) -> &'static str {
+                match self {
+                    $($enum_name::$variant => $str,)*
+                }
+            }
+        }
+
         impl std::str::FromStr for $enum_name {
             type Err = ();
 
             fn from_str(s: &str) -> Result<Self, Self::Err> {
-                match s {
-                    $($str => Ok($enum_name::$variant),)*
-                    _ => Err(()),
-                }
+                Self::from_symbol(s).ok_or(())
             }
         }
 
         impl std::fmt::Display for $enum_name {
             fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-                let s = match self {
-                    $($enum_name::$variant => $str,)*
-                };
-                write!(f, "{}", s)
+                write!(f, "{}", self.to_symbol())
             }
         }
     }
@@ -378,7 +452,7 @@ mod tests {
         let zeros = pool.add(Term::new_bv(0, 6));
 
         let cases = [
-            ("(= a b)", build_term!(pool, (= {a} {b}))),
+            ("(= a b)", build_term!(pool, (= {a.clone()} {b.clone()}))),
             (
                 "(= 1 2)",
                 build_term!(pool, (= {one.clone()} {two.clone()})),
@@ -397,7 +471,7 @@ mod tests {
             ),
             (
                 "(distinct p q true)",
-                build_term!(pool, (distinct {p} {q} {pool.bool_true()})),
+                build_term!(pool, (distinct {p.clone()} {q} {pool.bool_true()})),
             ),
             (
                 "(or (not (= 2 3)) (= 1 1))",
@@ -413,6 +487,20 @@ mod tests {
                 ),
             ),
             ("(and true false)", build_term!(pool, (and true false))),
+            (
+                "(forall ((a Int) (b Int)) (= a b))",
+                build_term!(pool, (forall ((a {int_sort.clone()}) (b {int_sort.clone()}))
+                    (= {a.clone()} {b.clone()})
+                )),
+            ),
+            (
+                "(exists ((p Bool)) (not p))",
+                build_term!(pool, (exists ((p {bool_sort})) (not {p.clone()}))),
+            ),
+            (
+                "(let ((a 1) (b 2)) (= a b))",
+                build_term!(pool, (let ((a 1) (b 2)) (= {a} {b}))),
+            ),
         ];
 
         for (s, got) in &cases {
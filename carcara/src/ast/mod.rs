@@ -4,31 +4,52 @@
 
 #[macro_use]
 mod macros;
+mod ac;
+mod canonical;
+mod constant_folding;
 mod context;
 mod iter;
+#[cfg(feature = "serde")]
+mod json;
+pub mod linear;
+mod nnf;
+mod node;
 mod polyeq;
 pub mod pool;
 pub(crate) mod printer;
 mod rc;
+mod signature;
+mod structure;
 mod substitution;
 #[cfg(test)]
 mod tests;
 
+pub use canonical::canonicalize_binders;
 pub use context::{Context, ContextStack};
 pub use iter::ProofIter;
+#[cfg(feature = "serde")]
+pub use json::{from_json, node_from_json, node_to_json, to_json, JsonError};
+pub use node::{
+    CyclicProofError, DepthError, DischargeError, ProofNode, ProofVisitor, StepNode, SubproofNode,
+};
 pub use polyeq::{alpha_equiv, polyeq, polyeq_mod_nary, tracing_polyeq_mod_nary};
 pub use pool::{PrimitivePool, TermPool};
-pub use printer::{print_proof, USE_SHARING_IN_TERM_DISPLAY};
+pub use printer::{
+    print_proof, write_proof, write_proof_with_real_format, Dialect, RealFormat,
+    USE_SHARING_IN_TERM_DISPLAY,
+};
 pub use rc::Rc;
+pub use signature::{ArgSorts, Arity, OperatorSignature, SortKind};
+pub use structure::StructureError;
 pub use substitution::{Substitution, SubstitutionError};
 
 pub(crate) use polyeq::{Polyeq, PolyeqComparator};
 
 use crate::checker::error::CheckerError;
-use indexmap::IndexSet;
+use indexmap::{IndexMap, IndexSet};
 use rug::Integer;
 use rug::Rational;
-use std::{hash::Hash, ops::Deref};
+use std::{collections::HashMap, hash::Hash, ops::Deref};
 
 /// The prelude of an SMT-LIB problem instance.
 ///
@@ -55,6 +76,44 @@ pub struct Proof {
 
     /// The proof commands.
     pub commands: Vec<ProofCommand>,
+
+    /// The names given to terms using the `:named` attribute, in the problem or in the proof.
+    ///
+    /// Since the parser replaces every reference to these names by the term itself, they are only
+    /// needed to reproduce the original names when printing the proof. If a term was given
+    /// multiple names, only the first one is kept.
+    pub named_terms: IndexMap<Rc<Term>, String>,
+
+    /// The original conclusions of the steps whose literals were reordered by the parser, because
+    /// of [`Config::normalize_clause_order`](crate::parser::Config::normalize_clause_order).
+    /// Each step is identified by its position in the proof, as given by [`ProofIter::position`].
+    ///
+    /// These are only needed to reproduce the original order when printing the proof. If commands
+    /// are added or removed directly, the positions are no longer valid, and this should be
+    /// cleared.
+    pub original_clauses: IndexMap<Vec<usize>, Vec<Rc<Term>>>,
+}
+
+/// Size metrics of a proof, returned by [`Proof::metrics`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProofMetrics {
+    /// The number of `step` commands, including steps inside subproofs.
+    pub num_steps: usize,
+
+    /// The number of `assume` commands, including assumptions inside subproofs.
+    pub num_assumptions: usize,
+
+    /// The number of subproofs, including nested subproofs.
+    pub num_subproofs: usize,
+
+    /// The maximum nesting depth of subproofs. This is zero if the proof has no subproofs.
+    pub max_depth: usize,
+
+    /// The number of commands in the longest chain of commands where each one is a premise or a
+    /// discharged assumption of the next. A subproof used as a premise counts as its last step.
+    /// The last step of a subproof also depends on the command that precedes it, since it may be
+    /// implicitly referenced by the step (see [`StepNode::previous_step`]).
+    pub critical_path: usize,
 }
 
 impl Proof {
@@ -62,6 +121,160 @@ impl Proof {
     pub fn iter(&self) -> ProofIter {
         ProofIter::new(&self.commands)
     }
+
+    /// Checks that the proof is structurally well-formed, independently of the rules used in each
+    /// step.
+    ///
+    /// This verifies that every premise and discharge index refers to a command that precedes the
+    /// step, in the current subproof or in one of the subproofs that enclose it, that discharged
+    /// commands are `assume` commands, and that every subproof is non-empty and ends in a `step`.
+    /// Proofs that fail this validation may cause the checker to panic.
+    pub fn validate_structure(&self) -> Result<(), crate::Error> {
+        structure::validate_commands(&self.commands).map_err(crate::Error::Structure)
+    }
+
+    /// Returns the names of all rules used by steps in the proof, including steps inside
+    /// subproofs, in the order they first appear.
+    pub fn rules_used(&self) -> IndexSet<String> {
+        self.iter()
+            .filter_map(|c| match c {
+                ProofCommand::Step(s) => Some(s.rule.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the sum of the weights of all steps in the proof, including steps inside subproofs.
+    /// Steps without a `:weight` attribute are not counted. If the sum doesn't fit in a `u64`, this
+    /// returns `u64::MAX`.
+    pub fn total_weight(&self) -> u64 {
+        self.iter()
+            .filter_map(|c| match c {
+                ProofCommand::Step(s) => s.weight,
+                _ => None,
+            })
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Computes size metrics of the proof. See [`ProofMetrics`].
+    pub fn metrics(&self) -> ProofMetrics {
+        // Returns the length of the longest premise chain ending in the last command of `commands`.
+        // `lengths` holds the same for every command in the enclosing subproofs
+        fn visit(
+            commands: &[ProofCommand],
+            lengths: &mut Vec<Vec<usize>>,
+            metrics: &mut ProofMetrics,
+        ) -> usize {
+            let depth = lengths.len();
+            metrics.max_depth = std::cmp::max(metrics.max_depth, depth);
+            lengths.push(Vec::with_capacity(commands.len()));
+            for (i, command) in commands.iter().enumerate() {
+                let length = match command {
+                    ProofCommand::Assume { .. } => {
+                        metrics.num_assumptions += 1;
+                        1
+                    }
+                    ProofCommand::Step(s) => {
+                        metrics.num_steps += 1;
+                        let is_end_step = depth > 0 && i == commands.len() - 1;
+                        let previous = (is_end_step && i > 0).then(|| (depth, i - 1));
+                        let premises = s.premises.iter().chain(&s.discharge).chain(&previous);
+                        1 + premises.map(|&(d, j)| lengths[d][j]).max().unwrap_or(0)
+                    }
+                    ProofCommand::Subproof(s) => {
+                        metrics.num_subproofs += 1;
+                        visit(&s.commands, lengths, metrics)
+                    }
+                };
+                metrics.critical_path = std::cmp::max(metrics.critical_path, length);
+                lengths[depth].push(length);
+            }
+            lengths.pop().unwrap().last().copied().unwrap_or(0)
+        }
+
+        let mut metrics = ProofMetrics::default();
+        visit(&self.commands, &mut Vec::new(), &mut metrics);
+        metrics
+    }
+
+    /// Returns a copy of the proof containing only the commands that are transitively used by its
+    /// last command, with the premises of the remaining steps renumbered. This is done by
+    /// converting the proof into the graph representation, where unused commands are not
+    /// reachable from the root, and back. See [`ProofNode`].
+    ///
+    /// The proof structure is validated first, so this returns an error instead of panicking if a
+    /// premise doesn't refer to a previous command. See [`Proof::validate_structure`].
+    ///
+    /// Since the positions of the remaining steps change, the original clauses recorded by the
+    /// parser are not kept in the result.
+    pub fn prune_unused(&self) -> Result<Proof, crate::Error> {
+        self.validate_structure()?;
+        let commands = if self.commands.is_empty() {
+            Vec::new()
+        } else {
+            ProofNode::from_commands(&self.commands).into_commands()
+        };
+        Ok(Proof {
+            premises: self.premises.clone(),
+            commands,
+            named_terms: self.named_terms.clone(),
+            original_clauses: IndexMap::new(),
+        })
+    }
+
+    /// Removes every top-level `assume` command whose term is syntactically equal to the term of
+    /// a previous top-level `assume` command, rewriting the premises that referenced it to refer
+    /// to the first one instead. Returns the number of removed commands.
+    ///
+    /// Assumptions inside subproofs are local hypotheses that may be discharged, so they are never
+    /// removed.
+    pub fn dedup_assumptions(&mut self) -> usize {
+        fn remap(commands: &mut [ProofCommand], new_indices: &[usize]) {
+            for command in commands {
+                match command {
+                    ProofCommand::Assume { .. } => (),
+                    ProofCommand::Step(s) => {
+                        for (depth, i) in s.premises.iter_mut().chain(&mut s.discharge) {
+                            if *depth == 0 {
+                                *i = new_indices[*i];
+                            }
+                        }
+                    }
+                    ProofCommand::Subproof(s) => remap(&mut s.commands, new_indices),
+                }
+            }
+        }
+
+        let mut seen: HashMap<Rc<Term>, usize> = HashMap::new();
+        let mut new_indices = Vec::with_capacity(self.commands.len());
+        let original = std::mem::take(&mut self.commands);
+        for command in original {
+            if let ProofCommand::Assume { term, .. } = &command {
+                if let Some(&i) = seen.get(term) {
+                    new_indices.push(i);
+                    continue;
+                }
+                seen.insert(term.clone(), self.commands.len());
+            }
+            new_indices.push(self.commands.len());
+            self.commands.push(command);
+        }
+
+        let removed = new_indices.len() - self.commands.len();
+        if removed > 0 {
+            remap(&mut self.commands, &new_indices);
+
+            // Only assumptions are removed, so the steps with original clauses are all kept
+            self.original_clauses = std::mem::take(&mut self.original_clauses)
+                .into_iter()
+                .map(|(mut position, clause)| {
+                    position[0] = new_indices[position[0]];
+                    (position, clause)
+                })
+                .collect();
+        }
+        removed
+    }
 }
 
 /// A proof command.
@@ -143,6 +356,10 @@ pub struct ProofStep {
     /// The local premises that this step discharges, given via the `:discharge` attribute, and
     /// indexed similarly to premises.
     pub discharge: Vec<(usize, usize)>,
+
+    /// The weight of the step, given via the `:weight` attribute, if any. This is not used by the
+    /// checker, but is kept for tools that analyse the cost of proofs.
+    pub weight: Option<u64>,
 }
 
 /// A subproof.
@@ -196,7 +413,7 @@ impl ProofArg {
 }
 
 /// An argument for an `anchor` command.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AnchorArg {
     /// A "variable declaration" style argument, of the form `(<symbol> <sort>)`.
     Variable(SortedVar),
@@ -792,6 +1009,43 @@ impl Term {
         pool.sort(&added).as_sort().unwrap().clone()
     }
 
+    /// Applies `f` to each immediate child of this term, and rebuilds the term with the results,
+    /// adding it to the pool.
+    ///
+    /// The children of a term are: the function and arguments of an application; the arguments of
+    /// an operation; the inner term of a binder; and the values and inner term of a `let`. The
+    /// parameters of a parameterized operation are not considered children, and neither are the
+    /// sorts of variables and bindings. Constants, variables and sorts have no children.
+    ///
+    /// Note that `f` is not applied recursively, so to transform all subterms of a term, `f` must
+    /// itself call this method.
+    pub fn map_children<F>(&self, pool: &mut dyn TermPool, mut f: F) -> Rc<Term>
+    where
+        F: FnMut(&Rc<Term>) -> Rc<Term>,
+    {
+        let term = match self {
+            Term::Const(_) | Term::Var(..) | Term::Sort(_) => self.clone(),
+            Term::App(func, args) => Term::App(f(func), args.iter().map(&mut f).collect()),
+            Term::Op(op, args) => Term::Op(*op, args.iter().map(f).collect()),
+            Term::ParamOp { op, op_args, args } => Term::ParamOp {
+                op: *op,
+                op_args: op_args.clone(),
+                args: args.iter().map(f).collect(),
+            },
+            Term::Binder(binder, bindings, inner) => {
+                Term::Binder(*binder, bindings.clone(), f(inner))
+            }
+            Term::Let(bindings, inner) => {
+                let bindings = bindings
+                    .iter()
+                    .map(|(name, value)| (name.clone(), f(value)))
+                    .collect();
+                Term::Let(BindingList(bindings), f(inner))
+            }
+        };
+        pool.add(term)
+    }
+
     /// Returns `true` if the term is the empty String.
     pub fn is_empty_string(&self) -> bool {
         match self {
@@ -972,6 +1226,16 @@ impl Term {
             false => self.is_bool_false(),
         }
     }
+
+    /// Returns the set of variables that occur free in this term, that is, that are not bound by
+    /// any enclosing `forall`, `exists`, `choice`, `lambda` or `let` in the term.
+    ///
+    /// This adds the term to the pool and uses [`TermPool::free_vars`], so the result is cached.
+    /// If you already have an `Rc<Term>` for this term, you can call that method directly.
+    pub fn free_vars(&self, pool: &mut dyn TermPool) -> IndexSet<Rc<Term>> {
+        let term = pool.add(self.clone());
+        pool.free_vars(&term)
+    }
 }
 
 impl Rc<Term> {
@@ -0,0 +1,89 @@
+//! A transformation of boolean terms into negation normal form.
+
+use super::{Binder, Operator, Rc, Sort, Term, TermPool};
+use indexmap::IndexMap;
+
+/// Converts `term` to negation normal form. This is used to implement [`TermPool::to_nnf`].
+pub(super) fn to_nnf<P: TermPool + ?Sized>(pool: &mut P, term: &Rc<Term>) -> Rc<Term> {
+    NnfConverter { pool, cache: IndexMap::new() }.convert(term, true)
+}
+
+struct NnfConverter<'a, P: ?Sized> {
+    pool: &'a mut P,
+
+    /// The result of converting each term, indexed by the term and its polarity.
+    cache: IndexMap<(Rc<Term>, bool), Rc<Term>>,
+}
+
+impl<P: TermPool + ?Sized> NnfConverter<'_, P> {
+    /// Converts `term` to negation normal form. If `polarity` is `false`, this instead converts the
+    /// negation of `term`.
+    fn convert(&mut self, term: &Rc<Term>, polarity: bool) -> Rc<Term> {
+        let key = (term.clone(), polarity);
+        if let Some(t) = self.cache.get(&key) {
+            return t.clone();
+        }
+        let result = self.convert_uncached(term, polarity);
+        self.cache.insert(key, result.clone());
+        result
+    }
+
+    fn convert_uncached(&mut self, term: &Rc<Term>, polarity: bool) -> Rc<Term> {
+        // With a negative polarity, conjunctions become disjunctions and vice versa
+        let (and, or) = if polarity {
+            (Operator::And, Operator::Or)
+        } else {
+            (Operator::Or, Operator::And)
+        };
+
+        match term.as_ref() {
+            Term::Op(Operator::Not, args) if args.len() == 1 => self.convert(&args[0], !polarity),
+            Term::Op(Operator::True | Operator::False, args) if args.is_empty() => {
+                let value = term.is_bool_true();
+                self.pool.bool_constant(value == polarity)
+            }
+            Term::Op(Operator::And, args) => {
+                let args = args.iter().map(|a| self.convert(a, polarity)).collect();
+                self.pool.add(Term::Op(and, args))
+            }
+            Term::Op(Operator::Or, args) => {
+                let args = args.iter().map(|a| self.convert(a, polarity)).collect();
+                self.pool.add(Term::Op(or, args))
+            }
+            Term::Op(Operator::Implies, args) if !args.is_empty() => {
+                // `(=> a_1 ... a_n b)` is equivalent to `(or (not a_1) ... (not a_n) b)`
+                let (conclusion, hypotheses) = args.split_last().unwrap();
+                let mut args: Vec<_> = hypotheses
+                    .iter()
+                    .map(|a| self.convert(a, !polarity))
+                    .collect();
+                args.push(self.convert(conclusion, polarity));
+                self.pool.add(Term::Op(or, args))
+            }
+            Term::Op(Operator::Ite, args)
+                if args.len() == 3 && *self.pool.sort(term) == Term::Sort(Sort::Bool) =>
+            {
+                // `(ite c a b)` is equivalent to `(and (or (not c) a) (or c b))`, and its negation
+                // is equivalent to `(and (or (not c) (not a)) (or c (not b)))`
+                let [pos_cond, neg_cond] = [true, false].map(|p| self.convert(&args[0], p));
+                let [a, b] = [&args[1], &args[2]].map(|t| self.convert(t, polarity));
+                let first = self.pool.add(Term::Op(Operator::Or, vec![neg_cond, a]));
+                let second = self.pool.add(Term::Op(Operator::Or, vec![pos_cond, b]));
+                self.pool.add(Term::Op(Operator::And, vec![first, second]))
+            }
+            Term::Binder(binder @ (Binder::Forall | Binder::Exists), bindings, inner) => {
+                let binder = match (binder, polarity) {
+                    (_, true) => *binder,
+                    (Binder::Forall, false) => Binder::Exists,
+                    (_, false) => Binder::Forall,
+                };
+                let inner = self.convert(inner, polarity);
+                self.pool.add(Term::Binder(binder, bindings.clone(), inner))
+            }
+
+            // Any other term is an atom
+            _ if polarity => term.clone(),
+            _ => self.pool.add(Term::Op(Operator::Not, vec![term.clone()])),
+        }
+    }
+}
@@ -0,0 +1,1048 @@
+//! A graph representation of Alethe proofs.
+//!
+//! While the vector representation in [`Proof`](super::Proof) is convenient for checking, as
+//! commands can be visited in order, it makes it awkward to insert, remove or replace commands,
+//! since every premise is addressed by its index. In this representation, each command is a node
+//! in a directed acyclic graph, with edges to its premises. This is better suited for elaboration.
+
+use super::{
+    printer::StreamingPrinter, AnchorArg, Dialect, ProofArg, ProofCommand, ProofStep, Rc, Subproof,
+    Term,
+};
+use indexmap::IndexSet;
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    hash::Hash,
+    io,
+    ops::Range,
+};
+use thiserror::Error;
+
+/// The error type for proof graphs whose nodes have inconsistent depths.
+#[derive(Debug, PartialEq, Eq, Error)]
+pub enum DepthError {
+    /// A premise of a step, or a command outside a subproof that is used inside it, is deeper than
+    /// the step or subproof. If the premise is the command implicitly referenced by the last step
+    /// of a subproof, its depth must instead be the same as the step's.
+    #[error("premise '{premise}' at depth {premise_depth} is used by '{step}' at depth {depth}")]
+    InvalidPremise {
+        step: String,
+        depth: usize,
+        premise: String,
+        premise_depth: usize,
+    },
+
+    /// A step discharges a command that is not in the same subproof.
+    #[error(
+        "step '{step}' at depth {depth} discharges '{discharged}' at depth {discharged_depth}"
+    )]
+    InvalidDischarge {
+        step: String,
+        depth: usize,
+        discharged: String,
+        discharged_depth: usize,
+    },
+
+    /// The last step of a subproof has depth zero, so it can't be inside a subproof.
+    #[error("subproof '{0}' ends in a step at depth 0")]
+    SubproofAtDepthZero(String),
+}
+
+/// The error type for proof graphs whose steps discharge invalid commands, or whose subproofs
+/// leave assumptions undischarged.
+#[derive(Debug, PartialEq, Eq, Error)]
+pub enum DischargeError {
+    /// A step discharges a command that is not an `assume` command.
+    #[error("step '{step}' discharges '{discharged}', which is not an assumption")]
+    NotAnAssumption { step: String, discharged: String },
+
+    /// A step discharges an assumption that was not made in the subproof that encloses it.
+    #[error("step '{step}' discharges '{discharged}', which is not local to its subproof")]
+    NotLocal { step: String, discharged: String },
+
+    /// The last step of a subproof uses the `subproof` rule, but doesn't discharge an assumption
+    /// that is used in the subproof.
+    #[error("subproof '{subproof}' does not discharge assumption '{assumption}'")]
+    UndischargedAssumption {
+        subproof: String,
+        assumption: String,
+    },
+}
+
+/// The error type for proof graphs that contain a cycle. It holds the ids of the nodes that form
+/// the cycle, in order, starting and ending with the same node.
+#[derive(Debug, PartialEq, Eq, Error)]
+#[error("proof graph contains a cycle: {}", .0.join(" -> "))]
+pub struct CyclicProofError(pub Vec<String>);
+
+/// A node in the graph representation of a proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProofNode {
+    /// An `assume` command.
+    Assume {
+        id: String,
+        depth: usize,
+        term: Rc<Term>,
+    },
+
+    /// A `step` command.
+    Step(StepNode),
+
+    /// A subproof.
+    Subproof(SubproofNode),
+}
+
+impl ProofNode {
+    /// Converts a series of proof commands into the graph representation, returning the node
+    /// that corresponds to the last command. Commands that are not transitively used by the last
+    /// command are not reachable from the returned node.
+    ///
+    /// This panics if `commands` is empty, or if any premise index does not refer to a previous
+    /// command. See [`Proof::validate_structure`](super::Proof::validate_structure).
+    pub fn from_commands(commands: &[ProofCommand]) -> Rc<ProofNode> {
+        let mut stack = Vec::new();
+        let (nodes, _) = build_nodes(commands, &mut stack);
+        nodes.last().expect("proof has no commands").clone()
+    }
+
+    /// Same as [`ProofNode::from_commands`], but the graph is first built in an arena, where
+    /// nodes borrow their data from `commands` and refer to each other by index. Only the nodes
+    /// that are reachable from the last command are then converted into `Rc<ProofNode>`s, so no
+    /// data is cloned and nothing is allocated for the commands that are not used. This is faster
+    /// when many commands are unused, but slightly slower when most of them are used, since the
+    /// arena is built in addition to the nodes.
+    ///
+    /// This panics under the same conditions as [`ProofNode::from_commands`].
+    pub fn from_commands_in_arena(commands: &[ProofCommand]) -> Rc<ProofNode> {
+        let mut arena = NodeArena::default();
+        let (nodes, _) = arena.build(commands, &mut Vec::new());
+        let root = *nodes.last().expect("proof has no commands");
+        arena.into_node(root)
+    }
+
+    /// Converts the graph rooted at this node back into a series of proof commands, addressing
+    /// premises using `(depth, index)` pairs. The commands are ordered such that every premise of
+    /// a command precedes it, and only commands reachable from this node are included.
+    ///
+    /// The depths of the nodes must be consistent. See [`ProofNode::validate_depths`].
+    pub fn into_commands(&self) -> Vec<ProofCommand> {
+        debug_assert_eq!(Ok(()), self.validate_depths());
+
+        let mut commands = Vec::new();
+        // The root node is never used as a premise, so it's fine to copy it into a new `Rc`
+        build_commands(&Rc::new(self.clone()), |c| {
+            commands.push(c);
+            Ok::<_, Infallible>(())
+        })
+        .unwrap();
+        commands
+    }
+
+    /// Writes the graph rooted at this node to `dest` as a series of proof commands, using the
+    /// given dialect. The output is the same as printing the result of
+    /// [`ProofNode::into_commands`], but each top-level command is written as soon as it is
+    /// produced, so the converted proof is never held in memory all at once.
+    ///
+    /// See [`print_proof`](super::print_proof) for the meaning of `use_sharing`.
+    pub fn write_streaming(
+        &self,
+        dest: &mut dyn io::Write,
+        dialect: Dialect,
+        use_sharing: bool,
+    ) -> io::Result<()> {
+        debug_assert_eq!(Ok(()), self.validate_depths());
+
+        let mut printer = StreamingPrinter::new(dest, dialect, use_sharing);
+        build_commands(&Rc::new(self.clone()), |c| printer.write_command(&c))
+    }
+
+    /// Writes the graph rooted at this node to `out` in the DOT format, to be rendered with
+    /// Graphviz. Each `assume` and `step` command is a node labeled with its id and rule, and
+    /// each subproof is a cluster containing the commands inside it. Every step has an edge to
+    /// each of its premises, a dashed edge to each command it discharges, and, if it is the last
+    /// step of a subproof, a dotted edge to the command that precedes it. A command used by
+    /// multiple steps appears only once. Subproofs used as premises are represented by their last
+    /// step.
+    pub fn to_dot(&self, out: &mut dyn io::Write) -> io::Result<()> {
+        struct Cluster {
+            label: String,
+            depth: usize,
+            parent: usize,
+            nodes: Vec<usize>,
+            children: Vec<usize>,
+        }
+
+        fn write_cluster(
+            out: &mut dyn io::Write,
+            clusters: &[Cluster],
+            labels: &[String],
+            index: usize,
+            indent: usize,
+        ) -> io::Result<()> {
+            let cluster = &clusters[index];
+            let pad = "  ".repeat(indent);
+            for &n in &cluster.nodes {
+                writeln!(out, "{pad}n{n} [label=\"{}\"];", labels[n])?;
+            }
+            for &c in &cluster.children {
+                writeln!(out, "{pad}subgraph cluster_{c} {{")?;
+                writeln!(out, "{pad}  label=\"{}\";", clusters[c].label)?;
+                write_cluster(out, clusters, labels, c, indent + 1)?;
+                writeln!(out, "{pad}}}")?;
+            }
+            Ok(())
+        }
+
+        fn escape(s: &str) -> String {
+            s.replace('\\', "\\\\").replace('"', "\\\"")
+        }
+
+        // The first cluster holds the commands that are not inside any subproof
+        let mut clusters = vec![Cluster {
+            label: String::new(),
+            depth: 0,
+            parent: 0,
+            nodes: Vec::new(),
+            children: Vec::new(),
+        }];
+        let mut labels = Vec::new();
+        let mut ids: HashMap<*const ProofNode, usize> = HashMap::new();
+        let mut seen_subproofs: HashSet<*const ProofNode> = HashSet::new();
+        let mut edges: Vec<(usize, &ProofNode, &str)> = Vec::new();
+
+        let mut todo = vec![(self, 0)];
+        while let Some((node, mut cluster)) = todo.pop() {
+            // Premises may be outside the subproof of the step that uses them, so the cluster of
+            // each node is determined by its depth
+            while clusters[cluster].depth > node.depth() {
+                cluster = clusters[cluster].parent;
+            }
+            match node {
+                ProofNode::Subproof(s) => {
+                    if !seen_subproofs.insert(node) {
+                        continue;
+                    }
+                    let new_cluster = clusters.len();
+                    clusters.push(Cluster {
+                        label: escape(node.id()),
+                        depth: node.depth() + 1,
+                        parent: cluster,
+                        nodes: Vec::new(),
+                        children: Vec::new(),
+                    });
+                    clusters[cluster].children.push(new_cluster);
+                    todo.push((s.last_step.as_ref(), new_cluster));
+                    todo.extend(s.outbound_premises.iter().map(|p| (p.as_ref(), cluster)));
+                }
+                _ if ids.contains_key(&(node as *const _)) => continue,
+                ProofNode::Assume { id, .. } => {
+                    ids.insert(node, labels.len());
+                    clusters[cluster].nodes.push(labels.len());
+                    labels.push(format!("{}\\nassume", escape(id)));
+                }
+                ProofNode::Step(s) => {
+                    let index = labels.len();
+                    ids.insert(node, index);
+                    clusters[cluster].nodes.push(index);
+                    labels.push(format!("{}\\n{}", escape(&s.id), escape(&s.rule)));
+
+                    let premises = s.premises.iter().map(|p| (p, ""));
+                    let discharge = s.discharge.iter().map(|p| (p, " [style=dashed]"));
+                    let previous = s.previous_step.iter().map(|p| (p, " [style=dotted]"));
+                    for (p, style) in premises.chain(discharge).chain(previous) {
+                        edges.push((index, p.as_ref(), style));
+                        todo.push((p.as_ref(), cluster));
+                    }
+                }
+            }
+        }
+
+        writeln!(out, "digraph proof {{")?;
+        write_cluster(out, &clusters, &labels, 0, 1)?;
+        for (from, mut to, style) in edges {
+            // Subproofs are represented by their last step
+            while let ProofNode::Subproof(s) = to {
+                to = s.last_step.as_ref();
+            }
+            writeln!(out, "  n{from} -> n{}{style};", ids[&(to as *const _)])?;
+        }
+        writeln!(out, "}}")
+    }
+
+    /// Returns the unique id of this node.
+    ///
+    /// For subproofs, this is the id of the last step in the subproof.
+    pub fn id(&self) -> &str {
+        match self {
+            ProofNode::Assume { id, .. } => id,
+            ProofNode::Step(s) => &s.id,
+            ProofNode::Subproof(s) => s.last_step.id(),
+        }
+    }
+
+    /// Returns the depth of this node, that is, the number of subproofs that enclose it.
+    ///
+    /// For subproofs, this is the depth of the subproof itself, which is one less than the depth
+    /// of the commands inside it.
+    pub fn depth(&self) -> usize {
+        match self {
+            ProofNode::Assume { depth, .. } => *depth,
+            ProofNode::Step(s) => s.depth,
+            ProofNode::Subproof(s) => s.last_step.depth() - 1,
+        }
+    }
+
+    /// Returns the clause of this node.
+    ///
+    /// For `assume` nodes, this is a unit clause containing the assumed term; for steps, it's the
+    /// conclusion clause; and for subproofs, it's the conclusion clause of the last step in the
+    /// subproof.
+    pub fn clause(&self) -> &[Rc<Term>] {
+        match self {
+            ProofNode::Assume { term, .. } => std::slice::from_ref(term),
+            ProofNode::Step(s) => &s.clause,
+            ProofNode::Subproof(s) => s.last_step.clause(),
+        }
+    }
+
+    /// Checks that the depths of all nodes reachable from this node are consistent. That is, every
+    /// premise of a step is at most as deep as the step, every discharged command and the command
+    /// implicitly referenced by the last step in a subproof are as deep as the step, and every
+    /// command outside a subproof that is used inside it is at most as deep as the subproof.
+    ///
+    /// If any node is inconsistent, this returns an error describing the first one found.
+    pub fn validate_depths(&self) -> Result<(), DepthError> {
+        fn depth(node: &ProofNode) -> Result<usize, DepthError> {
+            match node {
+                ProofNode::Subproof(s) if s.last_step.depth() == 0 => {
+                    Err(DepthError::SubproofAtDepthZero(node.id().to_owned()))
+                }
+                _ => Ok(node.depth()),
+            }
+        }
+
+        let mut seen: HashSet<*const ProofNode> = HashSet::new();
+        let mut todo = vec![self];
+        while let Some(node) = todo.pop() {
+            if !seen.insert(node) {
+                continue;
+            }
+            let node_depth = depth(node)?;
+            let invalid_premise = |p: &ProofNode, premise_depth| DepthError::InvalidPremise {
+                step: node.id().to_owned(),
+                depth: node_depth,
+                premise: p.id().to_owned(),
+                premise_depth,
+            };
+            match node {
+                ProofNode::Assume { .. } => (),
+                ProofNode::Step(s) => {
+                    for p in &s.premises {
+                        let d = depth(p)?;
+                        if d > node_depth {
+                            return Err(invalid_premise(p, d));
+                        }
+                    }
+                    if let Some(p) = &s.previous_step {
+                        let d = depth(p)?;
+                        if d != node_depth {
+                            return Err(invalid_premise(p, d));
+                        }
+                    }
+                    for p in &s.discharge {
+                        let d = depth(p)?;
+                        if d != node_depth {
+                            return Err(DepthError::InvalidDischarge {
+                                step: s.id.clone(),
+                                depth: node_depth,
+                                discharged: p.id().to_owned(),
+                                discharged_depth: d,
+                            });
+                        }
+                    }
+                    let next = s
+                        .premises
+                        .iter()
+                        .chain(&s.previous_step)
+                        .chain(&s.discharge);
+                    todo.extend(next.map(AsRef::as_ref));
+                }
+                ProofNode::Subproof(s) => {
+                    for p in &s.outbound_premises {
+                        let d = depth(p)?;
+                        if d > node_depth {
+                            return Err(invalid_premise(p, d));
+                        }
+                    }
+                    todo.push(&s.last_step);
+                    todo.extend(s.outbound_premises.iter().map(AsRef::as_ref));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the discharged commands of all steps reachable from this node are valid. That
+    /// is, every discharged command is an assumption made in the subproof that encloses the step,
+    /// and every subproof that ends in a step using the `subproof` rule discharges all assumptions
+    /// made in it. Since assumptions that are never used are not part of the graph, only the ones
+    /// reachable from the last step of the subproof need to be discharged.
+    ///
+    /// If any node is invalid, this returns an error describing the first one found.
+    pub fn validate_discharges(&self) -> Result<(), DischargeError> {
+        let mut seen: HashSet<*const ProofNode> = HashSet::new();
+        let mut todo = vec![self];
+        while let Some(node) = todo.pop() {
+            if !seen.insert(node) {
+                continue;
+            }
+            match node {
+                ProofNode::Assume { .. } => (),
+                ProofNode::Step(s) => {
+                    for d in &s.discharge {
+                        if !d.is_assume() {
+                            return Err(DischargeError::NotAnAssumption {
+                                step: s.id.clone(),
+                                discharged: d.id().to_owned(),
+                            });
+                        }
+                        if s.depth == 0 || d.depth() != s.depth {
+                            return Err(DischargeError::NotLocal {
+                                step: s.id.clone(),
+                                discharged: d.id().to_owned(),
+                            });
+                        }
+                    }
+                    let next = s
+                        .premises
+                        .iter()
+                        .chain(&s.previous_step)
+                        .chain(&s.discharge);
+                    todo.extend(next.map(AsRef::as_ref));
+                }
+                ProofNode::Subproof(s) => {
+                    if let ProofNode::Step(last) = s.last_step.as_ref() {
+                        if last.rule == "subproof" {
+                            let is_discharged = |a: &ProofNode| {
+                                last.discharge.iter().any(|d| std::ptr::eq(d.as_ref(), a))
+                            };
+                            let local = s.local_assumptions();
+                            if let Some(a) = local.into_iter().find(|a| !is_discharged(a)) {
+                                return Err(DischargeError::UndischargedAssumption {
+                                    subproof: node.id().to_owned(),
+                                    assumption: a.id().to_owned(),
+                                });
+                            }
+                        }
+                    }
+                    todo.push(&s.last_step);
+                    todo.extend(s.outbound_premises.iter().map(AsRef::as_ref));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that no node reachable from this node depends on itself, either directly or through
+    /// other nodes. The dependencies of a step are its premises, its discharged commands and its
+    /// previous step, and the dependencies of a subproof are its last step and its outbound
+    /// premises.
+    ///
+    /// Since a node can't be changed once it is shared, graphs built through `Rc`s are always
+    /// acyclic. However, passes that walk the graph, like [`mutate`](crate::elaborator::mutate),
+    /// would never terminate on a cyclic graph, so they use this to validate their input. If a
+    /// cycle is found, the error holds the ids of the nodes that form it.
+    pub fn check_acyclic(&self) -> Result<(), CyclicProofError> {
+        let roots = self.dependencies().into_iter().cloned().collect();
+        let edges = |node: &Rc<ProofNode>| node.dependencies().into_iter().cloned().collect();
+        match find_cycle(roots, edges) {
+            Some(cycle) => Err(CyclicProofError(
+                cycle.iter().map(|n| n.id().to_owned()).collect(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the nodes that this node depends on. For steps, these are its premises, discharged
+    /// commands and previous step, and for subproofs, its last step and outbound premises.
+    pub(crate) fn dependencies(&self) -> Vec<&Rc<ProofNode>> {
+        match self {
+            ProofNode::Assume { .. } => Vec::new(),
+            ProofNode::Step(s) => s
+                .premises
+                .iter()
+                .chain(&s.discharge)
+                .chain(&s.previous_step)
+                .collect(),
+            ProofNode::Subproof(s) => std::iter::once(&s.last_step)
+                .chain(&s.outbound_premises)
+                .collect(),
+        }
+    }
+
+    /// Returns the names of all rules used by steps reachable from this node, including steps
+    /// inside subproofs. The order of the rules is unspecified.
+    pub fn rules_used(&self) -> IndexSet<String> {
+        let mut rules = IndexSet::new();
+        let mut seen: HashSet<*const ProofNode> = HashSet::new();
+        let mut todo = vec![self];
+        while let Some(node) = todo.pop() {
+            if !seen.insert(node) {
+                continue;
+            }
+            match node {
+                ProofNode::Assume { .. } => (),
+                ProofNode::Step(s) => {
+                    if !rules.contains(&s.rule) {
+                        rules.insert(s.rule.clone());
+                    }
+                    let next = s
+                        .premises
+                        .iter()
+                        .chain(&s.previous_step)
+                        .chain(&s.discharge);
+                    todo.extend(next.map(AsRef::as_ref));
+                }
+                ProofNode::Subproof(s) => {
+                    todo.push(&s.last_step);
+                    todo.extend(s.outbound_premises.iter().map(AsRef::as_ref));
+                }
+            }
+        }
+        rules
+    }
+
+    /// Walks the graph reachable from this node, calling the visitor's methods for each node.
+    ///
+    /// Nodes are visited in post-order, that is, the premises of a step are always visited before
+    /// the step itself. Nodes that are shared by multiple commands are visited only once. For each
+    /// subproof, the nodes outside of it that are used as premises inside it are visited first,
+    /// then [`ProofVisitor::visit_subproof`] is called, followed by the nodes inside the subproof,
+    /// and finally [`ProofVisitor::end_subproof`].
+    pub fn accept(&self, visitor: &mut impl ProofVisitor) {
+        enum Frame<'a> {
+            Visit(&'a ProofNode),
+            Finish(&'a ProofNode),
+            EnterSubproof(&'a SubproofNode),
+        }
+
+        let mut seen: HashSet<*const ProofNode> = HashSet::new();
+        let mut todo = vec![Frame::Visit(self)];
+        while let Some(frame) = todo.pop() {
+            let node = match frame {
+                Frame::Visit(node) => node,
+                Frame::Finish(ProofNode::Step(s)) => {
+                    visitor.visit_step(s);
+                    continue;
+                }
+                Frame::Finish(ProofNode::Subproof(s)) => {
+                    visitor.end_subproof(s);
+                    continue;
+                }
+                Frame::Finish(ProofNode::Assume { .. }) => unreachable!(),
+                Frame::EnterSubproof(s) => {
+                    visitor.visit_subproof(s);
+                    continue;
+                }
+            };
+            if !seen.insert(node) {
+                continue;
+            }
+            match node {
+                ProofNode::Assume { id, depth, term } => visitor.visit_assume(id, *depth, term),
+                ProofNode::Step(s) => {
+                    todo.push(Frame::Finish(node));
+                    let next = s
+                        .premises
+                        .iter()
+                        .chain(&s.previous_step)
+                        .chain(&s.discharge);
+                    // Premises are pushed in reverse so they are visited in their original order
+                    todo.extend(next.rev().map(|p| Frame::Visit(p)));
+                }
+                ProofNode::Subproof(s) => {
+                    todo.push(Frame::Finish(node));
+                    todo.push(Frame::Visit(&s.last_step));
+                    todo.push(Frame::EnterSubproof(s));
+                    todo.extend(s.outbound_premises.iter().rev().map(|p| Frame::Visit(p)));
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if the node is an `assume` command.
+    pub fn is_assume(&self) -> bool {
+        matches!(self, ProofNode::Assume { .. })
+    }
+
+    /// Returns `true` if the node is a `step` command.
+    pub fn is_step(&self) -> bool {
+        matches!(self, ProofNode::Step(_))
+    }
+
+    /// Returns `true` if the node is a subproof.
+    pub fn is_subproof(&self) -> bool {
+        matches!(self, ProofNode::Subproof(_))
+    }
+}
+
+/// A visitor over the graph representation of a proof, used with [`ProofNode::accept`].
+///
+/// All methods do nothing by default, so implementors only need to override the ones they care
+/// about.
+pub trait ProofVisitor {
+    /// Called for each `assume` command.
+    fn visit_assume(&mut self, _id: &str, _depth: usize, _term: &Rc<Term>) {}
+
+    /// Called for each `step` command, after all of its premises were visited.
+    fn visit_step(&mut self, _step: &StepNode) {}
+
+    /// Called when entering a subproof, before any of the commands inside it are visited.
+    fn visit_subproof(&mut self, _subproof: &SubproofNode) {}
+
+    /// Called when leaving a subproof, after all of the commands inside it were visited.
+    fn end_subproof(&mut self, _subproof: &SubproofNode) {}
+}
+
+/// A `step` command in the graph representation of a proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepNode {
+    /// The step id.
+    pub id: String,
+
+    /// The depth of the step, that is, the number of subproofs that enclose it.
+    pub depth: usize,
+
+    /// The conclusion clause.
+    pub clause: Vec<Rc<Term>>,
+
+    /// The rule used by the step.
+    pub rule: String,
+
+    /// The premises of the step, given via the `:premises` attribute.
+    pub premises: Vec<Rc<ProofNode>>,
+
+    /// The step arguments, given via the `:args` attribute.
+    pub args: Vec<ProofArg>,
+
+    /// The local premises that this step discharges, given via the `:discharge` attribute.
+    pub discharge: Vec<Rc<ProofNode>>,
+
+    /// If this step is the last step in a subproof, this holds the command that precedes it in
+    /// the subproof, since it may be implicitly referenced by the step.
+    pub previous_step: Option<Rc<ProofNode>>,
+
+    /// The weight of the step, given via the `:weight` attribute, if any.
+    pub weight: Option<u64>,
+}
+
+/// A subproof in the graph representation of a proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubproofNode {
+    /// The last step in the subproof.
+    pub last_step: Rc<ProofNode>,
+
+    /// The arguments of the subproof.
+    pub args: Vec<AnchorArg>,
+
+    /// The commands outside the subproof that are used as premises by any command inside it,
+    /// including in nested subproofs.
+    pub outbound_premises: Vec<Rc<ProofNode>>,
+
+    /// Subproof id used for context hashing purposes.
+    pub context_id: usize,
+}
+
+impl SubproofNode {
+    /// Returns the `assume` commands made in this subproof that are reachable from its last step,
+    /// not including the ones in nested subproofs.
+    fn local_assumptions(&self) -> Vec<&ProofNode> {
+        let depth = self.last_step.depth();
+        let mut result = Vec::new();
+        let mut seen: HashSet<*const ProofNode> = HashSet::new();
+        let mut todo: Vec<&ProofNode> = vec![&self.last_step];
+        while let Some(node) = todo.pop() {
+            if node.depth() != depth || !seen.insert(node) {
+                continue;
+            }
+            match node {
+                ProofNode::Assume { .. } => result.push(node),
+                ProofNode::Step(s) => {
+                    let next = s
+                        .premises
+                        .iter()
+                        .chain(&s.previous_step)
+                        .chain(&s.discharge);
+                    todo.extend(next.map(AsRef::as_ref));
+                }
+                ProofNode::Subproof(s) => {
+                    todo.extend(s.outbound_premises.iter().map(AsRef::as_ref));
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Searches for a cycle in the graph reachable from `roots`, where the outgoing edges of each node
+/// are given by `edges`. If a cycle is found, returns the nodes that form it, starting and ending
+/// with the same node.
+///
+/// This is a depth-first search that keeps the current path in an explicit stack, so deep graphs
+/// don't overflow the call stack. A cycle exists exactly when an edge leads back to a node that is
+/// still on the path.
+pub(super) fn find_cycle<K, F>(roots: Vec<K>, mut edges: F) -> Option<Vec<K>>
+where
+    K: Clone + Eq + Hash,
+    F: FnMut(&K) -> Vec<K>,
+{
+    let mut done: HashSet<K> = HashSet::new();
+    for root in roots {
+        if done.contains(&root) {
+            continue;
+        }
+        let mut on_path: HashSet<K> = HashSet::from([root.clone()]);
+        let root_edges = edges(&root).into_iter();
+        let mut path = vec![(root, root_edges)];
+        while let Some((_, next)) = path.last_mut() {
+            match next.next() {
+                Some(node) if on_path.contains(&node) => {
+                    let start = path.iter().position(|(n, _)| *n == node).unwrap();
+                    let mut cycle: Vec<_> = path[start..].iter().map(|(n, _)| n.clone()).collect();
+                    cycle.push(node);
+                    return Some(cycle);
+                }
+                Some(node) if done.contains(&node) => (),
+                Some(node) => {
+                    on_path.insert(node.clone());
+                    let node_edges = edges(&node).into_iter();
+                    path.push((node, node_edges));
+                }
+                None => {
+                    let (node, _) = path.pop().unwrap();
+                    on_path.remove(&node);
+                    done.insert(node);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Builds the nodes for the commands in a subproof, given the nodes for the subproofs that
+/// enclose it. Returns the nodes for each command, and the nodes from the enclosing subproofs
+/// that are used as premises inside it.
+fn build_nodes(
+    commands: &[ProofCommand],
+    stack: &mut Vec<Vec<Rc<ProofNode>>>,
+) -> (Vec<Rc<ProofNode>>, IndexSet<Rc<ProofNode>>) {
+    let depth = stack.len();
+    let mut outbound = IndexSet::new();
+    stack.push(Vec::with_capacity(commands.len()));
+
+    for (i, command) in commands.iter().enumerate() {
+        let node = match command {
+            ProofCommand::Assume { id, term } => ProofNode::Assume {
+                id: id.clone(),
+                depth,
+                term: term.clone(),
+            },
+            ProofCommand::Step(s) => {
+                let mut get_node = |&(d, j): &(usize, usize)| {
+                    let node = stack[d][j].clone();
+                    if d < depth {
+                        outbound.insert(node.clone());
+                    }
+                    node
+                };
+                let premises = s.premises.iter().map(&mut get_node).collect();
+                let discharge = s.discharge.iter().map(&mut get_node).collect();
+
+                let is_end_step = depth > 0 && i == commands.len() - 1;
+                let previous_step = (is_end_step && i > 0).then(|| stack[depth][i - 1].clone());
+
+                ProofNode::Step(StepNode {
+                    id: s.id.clone(),
+                    depth,
+                    clause: s.clause.clone(),
+                    rule: s.rule.clone(),
+                    premises,
+                    args: s.args.clone(),
+                    discharge,
+                    previous_step,
+                    weight: s.weight,
+                })
+            }
+            ProofCommand::Subproof(s) => {
+                let (inner, inner_outbound) = build_nodes(&s.commands, stack);
+                outbound.extend(inner_outbound.iter().filter(|n| n.depth() < depth).cloned());
+                ProofNode::Subproof(SubproofNode {
+                    last_step: inner.last().expect("subproof has no commands").clone(),
+                    args: s.args.clone(),
+                    outbound_premises: inner_outbound.into_iter().collect(),
+                    context_id: s.context_id,
+                })
+            }
+        };
+        stack.last_mut().unwrap().push(Rc::new(node));
+    }
+
+    (stack.pop().unwrap(), outbound)
+}
+
+/// A node in a [`NodeArena`]. Each node borrows the command it was built from, and refers to other
+/// nodes by their indices in the arena. The edges of a node are a range in the arena's edge list.
+enum ArenaNode<'a> {
+    Assume {
+        id: &'a str,
+        depth: usize,
+        term: &'a Rc<Term>,
+    },
+    Step {
+        step: &'a ProofStep,
+        depth: usize,
+        /// The premises of the step, followed by the commands it discharges.
+        edges: Range<usize>,
+        previous_step: Option<usize>,
+    },
+    Subproof {
+        subproof: &'a Subproof,
+        depth: usize,
+        last_step: usize,
+        /// The outbound premises of the subproof.
+        edges: Range<usize>,
+    },
+}
+
+/// An arena used by [`ProofNode::from_commands_in_arena`] to build a proof graph. All nodes are
+/// stored in a single vector, and all edges in another, so building the graph only needs a few
+/// large allocations, instead of one for each node. Every node is added after the nodes it refers
+/// to, so they always have smaller indices.
+#[derive(Default)]
+struct NodeArena<'a> {
+    nodes: Vec<ArenaNode<'a>>,
+    edges: Vec<usize>,
+}
+
+impl<'a> NodeArena<'a> {
+    fn depth(&self, node: usize) -> usize {
+        match &self.nodes[node] {
+            ArenaNode::Assume { depth, .. }
+            | ArenaNode::Step { depth, .. }
+            | ArenaNode::Subproof { depth, .. } => *depth,
+        }
+    }
+
+    /// Adds the nodes for the commands in a subproof to the arena, in the same way as
+    /// `build_nodes`. Returns the indices of the nodes for each command, and of the nodes from the
+    /// enclosing subproofs that are used as premises inside it.
+    fn build(
+        &mut self,
+        commands: &'a [ProofCommand],
+        stack: &mut Vec<Vec<usize>>,
+    ) -> (Vec<usize>, IndexSet<usize>) {
+        let depth = stack.len();
+        let mut outbound = IndexSet::new();
+        stack.push(Vec::with_capacity(commands.len()));
+
+        for (i, command) in commands.iter().enumerate() {
+            let node = match command {
+                ProofCommand::Assume { id, term } => ArenaNode::Assume { id, depth, term },
+                ProofCommand::Step(s) => {
+                    let start = self.edges.len();
+                    for &(d, j) in s.premises.iter().chain(&s.discharge) {
+                        let node = stack[d][j];
+                        if d < depth {
+                            outbound.insert(node);
+                        }
+                        self.edges.push(node);
+                    }
+                    let is_end_step = depth > 0 && i == commands.len() - 1;
+                    ArenaNode::Step {
+                        step: s,
+                        depth,
+                        edges: start..self.edges.len(),
+                        previous_step: (is_end_step && i > 0).then(|| stack[depth][i - 1]),
+                    }
+                }
+                ProofCommand::Subproof(s) => {
+                    let (inner, inner_outbound) = self.build(&s.commands, stack);
+                    outbound.extend(inner_outbound.iter().filter(|&&n| self.depth(n) < depth));
+                    let start = self.edges.len();
+                    self.edges.extend(inner_outbound);
+                    ArenaNode::Subproof {
+                        subproof: s,
+                        depth,
+                        last_step: *inner.last().expect("subproof has no commands"),
+                        edges: start..self.edges.len(),
+                    }
+                }
+            };
+            stack.last_mut().unwrap().push(self.nodes.len());
+            self.nodes.push(node);
+        }
+
+        (stack.pop().unwrap(), outbound)
+    }
+
+    /// Converts the nodes reachable from `root` into `Rc<ProofNode>`s, and returns the node for
+    /// `root`. Since nodes always refer to nodes with smaller indices, the reachable nodes are
+    /// converted in increasing order of index, so the nodes each one refers to are always
+    /// converted before it.
+    fn into_node(self, root: usize) -> Rc<ProofNode> {
+        let mut reachable = vec![false; root + 1];
+        let mut todo = vec![root];
+        while let Some(i) = todo.pop() {
+            if std::mem::replace(&mut reachable[i], true) {
+                continue;
+            }
+            match &self.nodes[i] {
+                ArenaNode::Assume { .. } => (),
+                ArenaNode::Step { edges, previous_step, .. } => {
+                    todo.extend(&self.edges[edges.clone()]);
+                    todo.extend(previous_step);
+                }
+                ArenaNode::Subproof { last_step, edges, .. } => {
+                    todo.push(*last_step);
+                    todo.extend(&self.edges[edges.clone()]);
+                }
+            }
+        }
+
+        let mut converted: Vec<Option<Rc<ProofNode>>> = vec![None; root + 1];
+        for i in (0..=root).filter(|&i| reachable[i]) {
+            let get = |j: &usize| converted[*j].clone().unwrap();
+            let node = match &self.nodes[i] {
+                ArenaNode::Assume { id, depth, term } => ProofNode::Assume {
+                    id: (*id).to_owned(),
+                    depth: *depth,
+                    term: (*term).clone(),
+                },
+                ArenaNode::Step { step, depth, edges, previous_step } => {
+                    let (premises, discharge) =
+                        self.edges[edges.clone()].split_at(step.premises.len());
+                    ProofNode::Step(StepNode {
+                        id: step.id.clone(),
+                        depth: *depth,
+                        clause: step.clause.clone(),
+                        rule: step.rule.clone(),
+                        premises: premises.iter().map(get).collect(),
+                        args: step.args.clone(),
+                        discharge: discharge.iter().map(get).collect(),
+                        previous_step: previous_step.as_ref().map(get),
+                        weight: step.weight,
+                    })
+                }
+                ArenaNode::Subproof { subproof, last_step, edges, .. } => {
+                    ProofNode::Subproof(SubproofNode {
+                        last_step: get(last_step),
+                        args: subproof.args.clone(),
+                        outbound_premises: self.edges[edges.clone()].iter().map(get).collect(),
+                        context_id: subproof.context_id,
+                    })
+                }
+            };
+            converted[i] = Some(Rc::new(node));
+        }
+        converted[root].take().unwrap()
+    }
+}
+
+/// Converts the graph rooted at `root` into proof commands. Top-level commands are passed to `emit`
+/// as soon as they are produced, instead of being collected.
+fn build_commands<E>(
+    root: &Rc<ProofNode>,
+    mut emit: impl FnMut(ProofCommand) -> Result<(), E>,
+) -> Result<(), E> {
+    enum Action {
+        Visit(Rc<ProofNode>),
+        Emit(Rc<ProofNode>),
+        CloseSubproof(Rc<ProofNode>),
+    }
+
+    // The commands in each open subproof. The commands in the root proof are emitted instead, so
+    // only their number is kept
+    let mut frames: Vec<Vec<ProofCommand>> = vec![Vec::new()];
+    let mut num_root_commands = 0;
+    let mut indices: HashMap<Rc<ProofNode>, (usize, usize)> = HashMap::new();
+    let mut todo = vec![Action::Visit(root.clone())];
+
+    while let Some(action) = todo.pop() {
+        let (node, command) = match action {
+            Action::Visit(node) => {
+                if indices.contains_key(&node) {
+                    continue;
+                }
+                // Dependencies are pushed in reverse order, so they are emitted in the original
+                // order. All commands from outer subproofs that are used inside a subproof must be
+                // emitted before the subproof is opened
+                let dependencies: Vec<_> = match node.as_ref() {
+                    ProofNode::Assume { .. } => Vec::new(),
+                    ProofNode::Step(s) => s
+                        .discharge
+                        .iter()
+                        .chain(&s.premises)
+                        .chain(&s.previous_step)
+                        .cloned()
+                        .collect(),
+                    ProofNode::Subproof(s) => s.outbound_premises.clone(),
+                };
+                todo.push(Action::Emit(node));
+                todo.extend(dependencies.into_iter().rev().map(Action::Visit));
+                continue;
+            }
+            Action::Emit(node) => {
+                if indices.contains_key(&node) {
+                    continue;
+                }
+                let command = match node.as_ref() {
+                    ProofNode::Assume { id, term, .. } => {
+                        ProofCommand::Assume { id: id.clone(), term: term.clone() }
+                    }
+                    ProofNode::Step(s) => ProofCommand::Step(ProofStep {
+                        id: s.id.clone(),
+                        clause: s.clause.clone(),
+                        rule: s.rule.clone(),
+                        premises: s.premises.iter().map(|p| indices[p]).collect(),
+                        args: s.args.clone(),
+                        discharge: s.discharge.iter().map(|p| indices[p]).collect(),
+                        weight: s.weight,
+                    }),
+                    ProofNode::Subproof(s) => {
+                        frames.push(Vec::new());
+                        let last_step = s.last_step.clone();
+                        todo.push(Action::CloseSubproof(node));
+                        todo.push(Action::Visit(last_step));
+                        continue;
+                    }
+                };
+                (node, command)
+            }
+            Action::CloseSubproof(node) => {
+                let ProofNode::Subproof(s) = node.as_ref() else {
+                    unreachable!()
+                };
+                let commands = frames.pop().unwrap();
+                let command = ProofCommand::Subproof(Subproof {
+                    commands,
+                    args: s.args.clone(),
+                    context_id: s.context_id,
+                });
+                (node, command)
+            }
+        };
+
+        let depth = node.depth();
+        if depth == 0 {
+            indices.insert(node, (0, num_root_commands));
+            num_root_commands += 1;
+            emit(command)?;
+        } else {
+            indices.insert(node, (depth, frames[depth].len()));
+            frames[depth].push(command);
+        }
+    }
+
+    Ok(())
+}
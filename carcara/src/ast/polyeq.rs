@@ -74,6 +74,10 @@ pub fn alpha_equiv(a: &Rc<Term>, b: &Rc<Term>, time: &mut Duration) -> bool {
 }
 
 /// A configurable comparator for polyequality and alpha equivalence.
+///
+/// The comparator caches the result of every pair of terms it compares, so when many comparisons
+/// are done between terms that share subterms, it is better to reuse the same comparator for all
+/// of them, calling [`Polyeq::eq`] directly.
 pub struct PolyeqComparator {
     // In order to check alpha-equivalence, we can't use a simple global cache. For instance, let's
     // say we are comparing the following terms for alpha equivalence:
@@ -94,11 +98,19 @@ pub struct PolyeqComparator {
     // but it will be `(< $1 $0)` in `b`. If we just rely on the cache, we will incorrectly
     // determine that `a` and `b` are alpha-equivalent.  To account for that, we use a more
     // complicated caching system, based on a `HashMapStack`. We push a new scope every time we
-    // enter a binder term, and pop it as we exit. This unfortunately means that equalities derived
-    // inside a binder term can't be reused outside of it, degrading performance. If we are not
+    // enter a binder term, and pop it as we exit. Lookups only consider the innermost scope, since
+    // results cached in an outer scope were computed with a different De Bruijn map, and may not
+    // hold inside the binder. This unfortunately means that equalities derived inside a binder
+    // term can't be reused outside of it, and vice versa, degrading performance. If we are not
     // checking for alpha-equivalence, we never push an additional scope to this `HashMapStack`,
     // meaning it functions as a simple hash map.
-    cache: HashMapStack<(Rc<Term>, Rc<Term>), ()>,
+    //
+    // We cache both positive and negative results. Since terms are hash consed, they are usually
+    // heavily shared, and without caching negative results, comparing two different terms could
+    // take exponential time. For example, when comparing `(= t t)` and `(= u u)` modulo
+    // reordering, the comparison between `t` and `u` would be done twice, once for each possible
+    // ordering of the arguments, and the same would happen again for each subterm of `t` and `u`.
+    cache: HashMapStack<(Rc<Term>, Rc<Term>), bool>,
     is_mod_reordering: bool,
     de_bruijn_map: Option<DeBruijnMap>,
     is_mod_nary: bool,
@@ -154,6 +166,12 @@ impl PolyeqComparator {
                     .as_mut()
                     .unwrap()
                     .insert(a_var.0.clone(), b_var.0.clone());
+
+                // In `let` terms, the values in the binding list may contain variables that are
+                // now bound, so any results cached while comparing the previous values may no
+                // longer be correct
+                self.cache.pop_scope();
+                self.cache.push_scope();
             }
             let result = Polyeq::eq(self, a_inner, b_inner);
 
@@ -284,17 +302,16 @@ impl Polyeq for Rc<Term> {
             return true;
         }
 
-        // We first check the cache to see if these terms were already determined to be equal
-        if comp.cache.get(&(a.clone(), b.clone())).is_some() {
-            return true;
+        // We first check the cache to see if these terms were already compared in the current
+        // binder scope
+        if let Some(&result) = comp.cache.get_in_innermost(1, &(a.clone(), b.clone())) {
+            return result;
         }
 
         comp.current_depth += 1;
         comp.max_depth = std::cmp::max(comp.max_depth, comp.current_depth);
         let result = Polyeq::eq(comp, a.as_ref(), b.as_ref());
-        if result {
-            comp.cache.insert((a.clone(), b.clone()), ());
-        }
+        comp.cache.insert((a.clone(), b.clone()), result);
         comp.current_depth -= 1;
         result
     }
@@ -450,6 +467,7 @@ impl Polyeq for ProofStep {
             && a.premises == b.premises
             && Polyeq::eq(comp, &a.args, &b.args)
             && a.discharge == b.discharge
+            && a.weight == b.weight
     }
 }
 
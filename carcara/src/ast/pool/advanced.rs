@@ -1,5 +1,5 @@
 use super::super::{Rc, Term};
-use super::{PrimitivePool, TermPool};
+use super::{PrimitivePool, TermInfo, TermPool};
 use indexmap::IndexSet;
 use std::sync::{Arc, RwLock};
 
@@ -65,6 +65,14 @@ impl TermPool for ContextPool {
             .unwrap()
             .free_vars_with_priorities(term, [&self.global_pool])
     }
+
+    fn term_info(&mut self, term: &Rc<Term>) -> TermInfo {
+        // The information only depends on the term structure, so it can come from either pool
+        if let Some(info) = self.global_pool.info_cache.get(term) {
+            return *info;
+        }
+        self.inner.write().unwrap().term_info(term)
+    }
 }
 
 // =========================================================================
@@ -133,4 +141,8 @@ impl TermPool for LocalPool {
             ],
         )
     }
+
+    fn term_info(&mut self, term: &Rc<Term>) -> TermInfo {
+        self.inner.term_info(term)
+    }
 }
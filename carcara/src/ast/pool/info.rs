@@ -0,0 +1,171 @@
+//! Structural information about terms that is computed once, the first time it is requested.
+
+use crate::ast::{BindingList, Rc, Sort, Term};
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    hash::{Hash, Hasher},
+    mem::discriminant,
+};
+
+/// Structural information about a term, computed and cached by
+/// [`PrimitivePool::term_info`](super::PrimitivePool::term_info).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TermInfo {
+    /// A hash of the term structure. Unlike the hash of an `Rc<Term>`, which only depends on its
+    /// address, this is the same for equal terms in different pools.
+    pub hash: u64,
+
+    /// The number of nodes in the term, when seen as a tree, not counting sorts. Shared subterms
+    /// are counted once for each occurrence. This saturates at `usize::MAX`.
+    pub size: usize,
+}
+
+/// A term that is hashed and compared by its structure, instead of by reference like `Rc<Term>`.
+///
+/// In a single pool, equal terms are always the same `Rc`, so they can be used directly as keys.
+/// Equal terms from different pools, however, are different keys. Using this struct as the key
+/// instead, structurally equal terms share an entry, no matter what pool they come from. Keys are
+/// created with [`PrimitivePool::structural_key`](super::PrimitivePool::structural_key), using
+/// the term's cached hash, so hashing a key doesn't traverse the term. Keys with the same
+/// hash are confirmed to be equal by a structural comparison.
+#[derive(Debug, Clone)]
+pub struct StructuralKey {
+    term: Rc<Term>,
+    hash: u64,
+}
+
+impl StructuralKey {
+    pub(super) fn new(term: Rc<Term>, info: TermInfo) -> Self {
+        Self { term, hash: info.hash }
+    }
+
+    /// Returns the term this key was created from.
+    pub fn term(&self) -> &Rc<Term> {
+        &self.term
+    }
+}
+
+impl PartialEq for StructuralKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && structurally_equal(&self.term, &other.term)
+    }
+}
+
+impl Eq for StructuralKey {}
+
+impl Hash for StructuralKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash.hash(state);
+    }
+}
+
+/// Returns `true` if `a` and `b` are structurally equal, even if they are in different pools.
+fn structurally_equal(a: &Rc<Term>, b: &Rc<Term>) -> bool {
+    let mut seen: HashSet<(*const Term, *const Term)> = HashSet::new();
+    let mut todo = vec![(a, b)];
+    while let Some((a, b)) = todo.pop() {
+        if a == b || !seen.insert((a.as_ref() as *const Term, b.as_ref() as *const Term)) {
+            continue;
+        }
+        if !same_head(a, b) {
+            return false;
+        }
+        todo.extend(children(a).into_iter().zip(children(b)));
+    }
+    true
+}
+
+/// Returns the direct subterms of `term`, including sorts of variables and bindings.
+pub(super) fn children(term: &Term) -> Vec<&Rc<Term>> {
+    match term {
+        Term::Const(_) => Vec::new(),
+        Term::Var(_, sort) => vec![sort],
+        Term::App(func, args) => std::iter::once(func).chain(args).collect(),
+        Term::Op(_, args) => args.iter().collect(),
+        Term::ParamOp { op_args, args, .. } => op_args.iter().chain(args).collect(),
+        Term::Sort(sort) => match sort {
+            Sort::Function(sorts) | Sort::Atom(_, sorts) => sorts.iter().collect(),
+            Sort::Array(key, value) => vec![key, value],
+            _ => Vec::new(),
+        },
+        Term::Binder(_, bindings, inner) | Term::Let(bindings, inner) => bindings
+            .iter()
+            .map(|(_, value)| value)
+            .chain(std::iter::once(inner))
+            .collect(),
+    }
+}
+
+/// Hashes the parts of `term` that are not subterms, that is, everything that `same_head`
+/// compares.
+fn hash_head(term: &Term, state: &mut impl Hasher) {
+    discriminant(term).hash(state);
+    match term {
+        Term::Const(c) => c.hash(state),
+        Term::Var(name, _) => name.hash(state),
+        Term::App(_, args) => args.len().hash(state),
+        Term::Op(op, args) => (op, args.len()).hash(state),
+        Term::ParamOp { op, op_args, args } => (op, op_args.len(), args.len()).hash(state),
+        Term::Sort(sort) => {
+            discriminant(sort).hash(state);
+            match sort {
+                Sort::Function(sorts) => sorts.len().hash(state),
+                Sort::Atom(name, sorts) => (name, sorts.len()).hash(state),
+                Sort::BitVec(width) => width.hash(state),
+                _ => (),
+            }
+        }
+        Term::Binder(binder, bindings, _) => {
+            binder.hash(state);
+            hash_names(bindings, state);
+        }
+        Term::Let(bindings, _) => hash_names(bindings, state),
+    }
+}
+
+fn hash_names(bindings: &BindingList, state: &mut impl Hasher) {
+    bindings.len().hash(state);
+    for (name, _) in bindings {
+        name.hash(state);
+    }
+}
+
+/// Returns `true` if the parts of `a` and `b` that are not subterms are equal. If this is the
+/// case, `a` and `b` also have the same number of children.
+pub(super) fn same_head(a: &Term, b: &Term) -> bool {
+    let same_names = |a: &BindingList, b: &BindingList| {
+        a.len() == b.len() && a.iter().zip(b.iter()).all(|((x, _), (y, _))| x == y)
+    };
+    match (a, b) {
+        (Term::Const(x), Term::Const(y)) => x == y,
+        (Term::Var(x, _), Term::Var(y, _)) => x == y,
+        (Term::App(_, xs), Term::App(_, ys)) => xs.len() == ys.len(),
+        (Term::Op(x, xs), Term::Op(y, ys)) => x == y && xs.len() == ys.len(),
+        (
+            Term::ParamOp { op: x, op_args: x_op_args, args: xs },
+            Term::ParamOp { op: y, op_args: y_op_args, args: ys },
+        ) => x == y && x_op_args.len() == y_op_args.len() && xs.len() == ys.len(),
+        (Term::Sort(x), Term::Sort(y)) => match (x, y) {
+            (Sort::Function(xs), Sort::Function(ys)) => xs.len() == ys.len(),
+            (Sort::Atom(x, xs), Sort::Atom(y, ys)) => x == y && xs.len() == ys.len(),
+            (Sort::BitVec(x), Sort::BitVec(y)) => x == y,
+            _ => discriminant(x) == discriminant(y),
+        },
+        (Term::Binder(x, xs, _), Term::Binder(y, ys, _)) => x == y && same_names(xs, ys),
+        (Term::Let(xs, _), Term::Let(ys, _)) => same_names(xs, ys),
+        _ => false,
+    }
+}
+
+/// Computes the information of `term`, given the information of each of its children, in the
+/// order returned by `children`.
+pub(super) fn compute(term: &Term, children: impl IntoIterator<Item = TermInfo>) -> TermInfo {
+    let mut state = DefaultHasher::new();
+    hash_head(term, &mut state);
+    let mut size: usize = if term.is_sort() { 0 } else { 1 };
+    for child in children {
+        child.hash.hash(&mut state);
+        size = size.saturating_add(child.size);
+    }
+    TermInfo { hash: state.finish(), size }
+}
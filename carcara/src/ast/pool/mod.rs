@@ -1,12 +1,18 @@
 //! This module implements `TermPool`, a structure that stores terms and implements hash consing.
 
 pub mod advanced;
+mod info;
 mod storage;
 
-use super::{Binder, Operator, Rc, Sort, Term};
+use super::{
+    ac, constant_folding, nnf, substitution, Binder, BindingList, Operator, Rc, Sort,
+    SubstitutionError, Term,
+};
 use crate::ast::{Constant, ParamOperator};
 use indexmap::{IndexMap, IndexSet};
+pub use info::{StructuralKey, TermInfo};
 use rug::Integer;
+use std::collections::HashSet;
 use storage::Storage;
 
 pub trait TermPool {
@@ -31,6 +37,12 @@ pub trait TermPool {
     /// just returns an `Rc` pointing to the existing allocation. This method also computes the
     /// term's sort, and adds it to the sort cache.
     fn add(&mut self, term: Term) -> Rc<Term>;
+    /// Returns the term that represents `sort`, adding it to the pool if needed. Sorts are stored
+    /// in the pool like any other term, so equal sorts, like two occurrences of `(_ BitVec 8)`, are
+    /// always interned into the same `Rc`.
+    fn intern_sort(&mut self, sort: Sort) -> Rc<Term> {
+        self.add(Term::Sort(sort))
+    }
     /// Takes a vector of terms and calls [`TermPool::add`] on each.
     fn add_all(&mut self, terms: Vec<Term>) -> Vec<Rc<Term>> {
         terms.into_iter().map(|t| self.add(t)).collect()
@@ -45,6 +57,72 @@ pub trait TermPool {
     /// This method uses a cache, so there is no additional cost to computing the free variables of
     /// a term multiple times.
     fn free_vars(&mut self, term: &Rc<Term>) -> IndexSet<Rc<Term>>;
+    /// Returns the structural information about `term`. See [`PrimitivePool::term_info`].
+    fn term_info(&mut self, term: &Rc<Term>) -> TermInfo;
+    /// Applies a capture-avoiding substitution to `term`, replacing each variable in the domain
+    /// of `map` with the term it maps to. Bound variables are renamed as needed to avoid captures.
+    /// See [`Substitution`](super::Substitution) for more details.
+    ///
+    /// This returns an error if any term in the domain of `map` is not a variable, or if any term
+    /// is mapped to a term of a different sort.
+    fn substitute(
+        &mut self,
+        term: &Rc<Term>,
+        map: IndexMap<Rc<Term>, Rc<Term>>,
+    ) -> Result<Rc<Term>, SubstitutionError> {
+        substitution::substitute(self, term, map)
+    }
+    /// Beta-reduces all applications of `lambda` terms in `term`, using capture-avoiding
+    /// substitution. Redexes that are created by the reduction are also reduced, so the result has
+    /// no redexes left. Applications that are not redexes, including ill-typed applications of
+    /// `lambda` terms, are left unchanged.
+    fn beta_reduce(&mut self, term: &Rc<Term>) -> Rc<Term> {
+        substitution::beta_reduce(self, term)
+    }
+    /// Converts a boolean term to negation normal form. Negations are pushed inwards over `and`,
+    /// `or`, `=>`, boolean `ite` and quantifiers, until they are only applied to atoms, and double
+    /// negations are eliminated. Implications are rewritten into disjunctions, and boolean `ite`
+    /// terms into conjunctions of disjunctions, duplicating the condition. Any other term,
+    /// including boolean equalities, is considered an atom.
+    fn to_nnf(&mut self, term: &Rc<Term>) -> Rc<Term> {
+        nnf::to_nnf(self, term)
+    }
+    /// Folds the arithmetic operations whose arguments are all integer or real constants, that is,
+    /// applications of `+`, `-`, `*`, `div` and `/`, into the constant they evaluate to. This is
+    /// done bottom-up, so constant subexpressions are folded even if the whole term is not
+    /// constant. Negative results are represented with the unary `-` operator, and divisions by
+    /// zero are left unchanged.
+    fn fold_constants(&mut self, term: &Rc<Term>) -> Rc<Term> {
+        constant_folding::fold_constants(self, term)
+    }
+    /// Canonicalizes the applications of the associative-commutative operators `and`, `or`, `+`
+    /// and `*`. Nested applications of the same operator are flattened, and the arguments are
+    /// sorted, so any two terms that are equal modulo associativity and commutativity of these
+    /// operators are mapped to the same term. The arguments are sorted by their structural hashes
+    /// (see [`TermPool::term_info`]), so their order doesn't depend on the pool. Arguments whose
+    /// hashes collide, however, are ordered by address.
+    fn canonicalize_ac(&mut self, term: &Rc<Term>) -> Rc<Term> {
+        ac::canonicalize_ac(self, term)
+    }
+}
+
+/// Statistics about the terms stored in a [`PrimitivePool`], returned by [`PrimitivePool::stats`].
+#[derive(Debug, Clone)]
+pub struct PoolStats {
+    /// The number of terms in the pool, not counting sorts.
+    pub num_terms: usize,
+
+    /// The number of sorts in the pool.
+    pub num_sorts: usize,
+
+    /// The depth of the deepest term in the pool. Constants, variables and sorts have depth zero.
+    pub max_depth: usize,
+
+    /// The term, other than a sort, with the highest `Rc` strong count, together with that count.
+    /// This includes the references held by the pool itself. If multiple terms have the highest
+    /// count, the first one added to the pool is chosen. This is `None` if the pool contains no
+    /// terms.
+    pub most_shared: Option<(Rc<Term>, usize)>,
 }
 
 /// A structure to store and manage all allocated terms.
@@ -61,6 +139,7 @@ pub struct PrimitivePool {
     pub(crate) storage: Storage,
     pub(crate) free_vars_cache: IndexMap<Rc<Term>, IndexSet<Rc<Term>>>,
     pub(crate) sorts_cache: IndexMap<Rc<Term>, Rc<Term>>,
+    pub(crate) info_cache: IndexMap<Rc<Term>, TermInfo>,
 }
 
 impl PrimitivePool {
@@ -70,6 +149,240 @@ impl PrimitivePool {
         Self::default()
     }
 
+    /// Computes statistics about the terms stored in the pool. This is useful to diagnose
+    /// excessive memory usage.
+    pub fn stats(&self) -> PoolStats {
+        let mut stats = PoolStats {
+            num_terms: 0,
+            num_sorts: 0,
+            max_depth: 0,
+            most_shared: None,
+        };
+
+        // Since a term can only be added to the pool after its subterms, the depth of each
+        // subterm is always computed before the depth of the terms that contain it
+        let mut depths: IndexMap<&Rc<Term>, usize> = IndexMap::new();
+        for term in self.storage.iter() {
+            if term.is_sort() {
+                stats.num_sorts += 1;
+                depths.insert(term, 0);
+                continue;
+            }
+            stats.num_terms += 1;
+
+            let children: Vec<&Rc<Term>> = match term.as_ref() {
+                Term::Const(_) | Term::Var(..) | Term::Sort(_) => Vec::new(),
+                Term::App(func, args) => std::iter::once(func).chain(args).collect(),
+                Term::Op(_, args) | Term::ParamOp { args, .. } => args.iter().collect(),
+                Term::Binder(_, _, inner) => vec![inner],
+                Term::Let(bindings, inner) => bindings
+                    .iter()
+                    .map(|(_, value)| value)
+                    .chain(std::iter::once(inner))
+                    .collect(),
+            };
+            let depth = children
+                .into_iter()
+                .map(|c| depths.get(c).map_or(0, |d| d + 1))
+                .max()
+                .unwrap_or(0);
+            stats.max_depth = std::cmp::max(stats.max_depth, depth);
+            depths.insert(term, depth);
+
+            let count = Rc::strong_count(term);
+            if stats.most_shared.as_ref().map_or(true, |(_, c)| count > *c) {
+                stats.most_shared = Some((term.clone(), count));
+            }
+        }
+        stats
+    }
+
+    /// Adds all terms in `terms` to the pool, returning them in the same order. The results are the
+    /// same as calling [`TermPool::add`] on each term, but the hashes of the terms are computed
+    /// up front, and the pool's internal maps are only grown once for the whole batch, which is
+    /// faster when adding many new terms at once.
+    pub fn add_terms_batch(&mut self, terms: Vec<Term>) -> Vec<Rc<Term>> {
+        let terms = self.storage.add_batch(terms);
+        let new = terms
+            .iter()
+            .filter(|t| !self.sorts_cache.contains_key(*t))
+            .count();
+        self.sorts_cache.reserve(new);
+        for t in &terms {
+            self.compute_sort(t);
+        }
+        terms
+    }
+
+    /// Returns the structural information about `term`. See [`TermInfo`].
+    ///
+    /// This information is not computed when terms are added to the pool, so adding terms has no
+    /// additional cost. Instead, it is computed the first time it is requested for a term or any
+    /// term that contains it, and then cached, so further requests take constant time. `term`
+    /// should be a term in this pool.
+    pub fn term_info(&mut self, term: &Rc<Term>) -> TermInfo {
+        if let Some(info) = self.info_cache.get(term) {
+            return *info;
+        }
+        self.compute_info(term)
+    }
+
+    /// Returns a key for `term` that is hashed and compared by structure. See [`StructuralKey`].
+    pub fn structural_key(&mut self, term: &Rc<Term>) -> StructuralKey {
+        StructuralKey::new(term.clone(), self.term_info(term))
+    }
+
+    /// Returns the size of `term`, as described in [`TermInfo::size`]. This uses the cached term
+    /// information, as described in [`PrimitivePool::term_info`].
+    pub fn term_size(&mut self, term: &Rc<Term>) -> usize {
+        self.term_info(term).size
+    }
+
+    /// Returns `true` if `a`, a term in this pool, is structurally equal to `b`, a term in `other`.
+    /// Terms in the same pool can simply be compared by reference, but terms in different pools
+    /// can't.
+    ///
+    /// Subterms are only compared structurally if their cached hashes and sizes match, so
+    /// unequal terms are usually rejected without descending into them. Since hashes may collide,
+    /// matching hashes are always confirmed by a structural comparison.
+    pub fn structurally_equal(
+        &mut self,
+        a: &Rc<Term>,
+        other: &mut PrimitivePool,
+        b: &Rc<Term>,
+    ) -> bool {
+        self.structurally_equal_counting(a, other, b, &mut 0)
+    }
+
+    /// Same as [`PrimitivePool::structurally_equal`], but increments `descents` for each pair of
+    /// subterms that is compared structurally.
+    pub(super) fn structurally_equal_counting(
+        &mut self,
+        a: &Rc<Term>,
+        other: &mut PrimitivePool,
+        b: &Rc<Term>,
+        descents: &mut usize,
+    ) -> bool {
+        // This computes the information of all subterms of `a` and `b` if needed, so the loop
+        // below can just read it from the caches
+        self.term_info(a);
+        other.term_info(b);
+
+        let mut seen: HashSet<(*const Term, *const Term)> = HashSet::new();
+        let mut todo = vec![(a, b)];
+        while let Some((a, b)) = todo.pop() {
+            if self.info_cache[a] != other.info_cache[b] {
+                return false;
+            }
+            if !seen.insert((a.as_ref() as *const Term, b.as_ref() as *const Term)) {
+                continue;
+            }
+            *descents += 1;
+            if !info::same_head(a, b) {
+                return false;
+            }
+            todo.extend(info::children(a).into_iter().zip(info::children(b)));
+        }
+        true
+    }
+
+    /// Adds a term from another pool to this pool, returning the equivalent term in this pool. All
+    /// subterms of `term`, including the sorts of variables and bindings, are also added, and any
+    /// that are already in this pool are reused. This is useful to move terms built in a scratch
+    /// pool into a main pool.
+    ///
+    /// Since `term` is hash consed in `from`, each distinct subterm is only imported once.
+    pub fn import(&mut self, term: &Rc<Term>, from: &PrimitivePool) -> Rc<Term> {
+        debug_assert!(from.storage.get(term).is_some_and(|t| t == term));
+        self.import_rec(term, &mut IndexMap::new())
+    }
+
+    fn import_rec(
+        &mut self,
+        term: &Rc<Term>,
+        cache: &mut IndexMap<Rc<Term>, Rc<Term>>,
+    ) -> Rc<Term> {
+        if let Some(imported) = cache.get(term) {
+            return imported.clone();
+        }
+        let imported = match term.as_ref() {
+            Term::Const(c) => Term::Const(c.clone()),
+            Term::Var(name, sort) => Term::Var(name.clone(), self.import_rec(sort, cache)),
+            Term::App(func, args) => {
+                let func = self.import_rec(func, cache);
+                Term::App(func, self.import_all(args, cache))
+            }
+            Term::Op(op, args) => Term::Op(*op, self.import_all(args, cache)),
+            Term::ParamOp { op, op_args, args } => Term::ParamOp {
+                op: *op,
+                op_args: self.import_all(op_args, cache),
+                args: self.import_all(args, cache),
+            },
+            Term::Sort(sort) => Term::Sort(match sort {
+                Sort::Function(sorts) => Sort::Function(self.import_all(sorts, cache)),
+                Sort::Atom(name, args) => Sort::Atom(name.clone(), self.import_all(args, cache)),
+                Sort::Array(x, y) => {
+                    Sort::Array(self.import_rec(x, cache), self.import_rec(y, cache))
+                }
+                other => other.clone(),
+            }),
+            Term::Binder(binder, bindings, inner) => {
+                let bindings = self.import_bindings(bindings, cache);
+                Term::Binder(*binder, bindings, self.import_rec(inner, cache))
+            }
+            Term::Let(bindings, inner) => {
+                let bindings = self.import_bindings(bindings, cache);
+                Term::Let(bindings, self.import_rec(inner, cache))
+            }
+        };
+        let imported = self.add(imported);
+        cache.insert(term.clone(), imported.clone());
+        imported
+    }
+
+    fn import_all(
+        &mut self,
+        terms: &[Rc<Term>],
+        cache: &mut IndexMap<Rc<Term>, Rc<Term>>,
+    ) -> Vec<Rc<Term>> {
+        terms.iter().map(|t| self.import_rec(t, cache)).collect()
+    }
+
+    fn import_bindings(
+        &mut self,
+        bindings: &BindingList,
+        cache: &mut IndexMap<Rc<Term>, Rc<Term>>,
+    ) -> BindingList {
+        let bindings = bindings.iter();
+        let bindings = bindings.map(|(name, value)| (name.clone(), self.import_rec(value, cache)));
+        BindingList(bindings.collect())
+    }
+
+    /// Computes the structural information of a term and of all its subterms that are not yet in
+    /// the information cache, and adds it to the cache. Since this may be called on arbitrarily
+    /// deep terms, the subterms are visited using an explicit stack, instead of recursively.
+    fn compute_info(&mut self, term: &Rc<Term>) -> TermInfo {
+        let mut todo = vec![(term.clone(), false)];
+        while let Some((current, children_done)) = todo.pop() {
+            if self.info_cache.contains_key(&current) {
+                continue;
+            }
+            let children = info::children(&current);
+            if children_done {
+                let children = children.into_iter().map(|c| self.info_cache[c]);
+                let result = info::compute(&current, children);
+                self.info_cache.insert(current, result);
+            } else {
+                todo.push((current.clone(), true));
+                let pending = children
+                    .into_iter()
+                    .filter(|c| !self.info_cache.contains_key(*c));
+                todo.extend(pending.map(|c| (c.clone(), false)));
+            }
+        }
+        self.info_cache[term]
+    }
+
     /// Computes the sort of a term and adds it to the sort cache.
     fn compute_sort(&mut self, term: &Rc<Term>) -> Rc<Term> {
         if let Some(sort) = self.sorts_cache.get(term) {
@@ -319,6 +632,12 @@ impl PrimitivePool {
                     let term = self.add_with_priorities((var.clone(), sort).into(), prior_pools);
                     vars.remove(&term);
                 }
+
+                // The bindings in a `let` term are parallel, so the variables in the values are
+                // never bound by the `let` itself
+                for (_, value) in bindings {
+                    vars.extend(self.free_vars_with_priorities(value, prior_pools));
+                }
                 vars
             }
             Term::Var(..) => {
@@ -354,4 +673,8 @@ impl TermPool for PrimitivePool {
     fn free_vars(&mut self, term: &Rc<Term>) -> IndexSet<Rc<Term>> {
         self.free_vars_with_priorities(term, [])
     }
+
+    fn term_info(&mut self, term: &Rc<Term>) -> TermInfo {
+        PrimitivePool::term_info(self, term)
+    }
 }
@@ -5,58 +5,129 @@
 //* clone it; otherwise, we allocate a new `Rc`.
 
 use crate::ast::*;
-use std::borrow::Borrow;
+use indexmap::Equivalent;
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, BuildHasherDefault, Hasher},
+};
 
 /// Since `ast::Rc` intentionally implements hashing and equality by reference (instead of by
-/// value), we cannot safely implement `Borrow<Term>` for `Rc<Term>`, so we cannot access a
-/// `HashSet<Rc<Term>>` using a `&Term` as a key. To go around that, we use this struct that wraps
-/// an `Rc<Term>` and that re-implements hashing and equality by value, meaning we can implement
-/// `Borrow<Term>` for it, and use it as the contents of the hash set instead.
+/// value), we cannot use an `Rc<Term>` directly as the contents of the hash set, which we access
+/// using a `&Term`. To go around that, we use this struct, that wraps an `Rc<Term>` together with
+/// the hash of the term it points to, and that re-implements equality by value. Since the hash is
+/// stored, it is computed only once for each term, and reused when inserting it or when the hash
+/// set grows.
 #[derive(Debug, Clone, Eq)]
-struct ByValue(Rc<Term>);
+struct Entry {
+    hash: u64,
+    term: Rc<Term>,
+}
 
-impl PartialEq for ByValue {
+impl PartialEq for Entry {
     fn eq(&self, other: &Self) -> bool {
-        self.0.as_ref() == other.0.as_ref()
+        self.hash == other.hash && self.term.as_ref() == other.term.as_ref()
     }
 }
 
-impl Hash for ByValue {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.0.as_ref().hash(state);
+impl Hash for Entry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
     }
 }
 
-impl Borrow<Term> for ByValue {
-    fn borrow(&self) -> &Term {
-        self.0.as_ref()
+/// A reference to a term together with its hash, used to look up entries in the hash set.
+struct Key<'a> {
+    hash: u64,
+    term: &'a Term,
+}
+
+impl Hash for Key<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl Equivalent<Entry> for Key<'_> {
+    fn equivalent(&self, entry: &Entry) -> bool {
+        self.hash == entry.hash && self.term == entry.term.as_ref()
+    }
+}
+
+/// A hasher that only accepts a single `u64`, and uses it as the hash. Entries in the hash set are
+/// hashed using their stored hashes, so they don't need to be hashed again.
+#[derive(Default)]
+struct PrecomputedHasher(u64);
+
+impl Hasher for PrecomputedHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, _: &[u8]) {
+        unreachable!("only precomputed hashes can be written")
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = n;
     }
 }
 
 #[derive(Debug, Clone, Default)]
-pub struct Storage(IndexSet<ByValue>);
+pub struct Storage {
+    set: IndexSet<Entry, BuildHasherDefault<PrecomputedHasher>>,
+    hasher: RandomState,
+}
 
 impl Storage {
-    pub fn add(&mut self, term: Term) -> Rc<Term> {
-        // If the `hash_set_entry` feature was stable, this would be much simpler to do using
-        // `get_or_insert_with` (and would avoid rehashing the term)
-        match self.0.get(&term) {
-            Some(t) => t.0.clone(),
+    fn hash(&self, term: &Term) -> u64 {
+        self.hasher.hash_one(term)
+    }
+
+    fn add_hashed(&mut self, hash: u64, term: Term) -> Rc<Term> {
+        // If the `hash_set_entry` feature was stable, this would be simpler to do using
+        // `get_or_insert_with`. Since the hash is precomputed, looking up the term and then
+        // inserting it only hashes the term once
+        match self.set.get(&Key { hash, term: &term }) {
+            Some(e) => e.term.clone(),
             None => {
-                let result = Rc::new(term);
-                self.0.insert(ByValue(result.clone()));
-                result
+                let term = Rc::new(term);
+                self.set.insert(Entry { hash, term: term.clone() });
+                term
             }
         }
     }
 
+    pub fn add(&mut self, term: Term) -> Rc<Term> {
+        self.add_hashed(self.hash(&term), term)
+    }
+
+    /// Adds all terms in `terms`, returning them in the same order. The hashes of all terms are
+    /// computed before any of them is added, and the storage is grown at most once for the whole
+    /// batch, so the existing terms are not moved multiple times while it grows.
+    pub fn add_batch(&mut self, terms: Vec<Term>) -> Vec<Rc<Term>> {
+        let hashes: Vec<_> = terms.iter().map(|t| self.hash(t)).collect();
+        let new = (hashes.iter().zip(&terms))
+            .filter(|&(&hash, term)| !self.set.contains(&Key { hash, term }))
+            .count();
+        self.set.reserve(new);
+        (hashes.into_iter().zip(terms))
+            .map(|(hash, term)| self.add_hashed(hash, term))
+            .collect()
+    }
+
     pub fn get(&self, term: &Term) -> Option<&Rc<Term>> {
-        self.0.get(term).map(|t| &t.0)
+        let key = Key { hash: self.hash(term), term };
+        self.set.get(&key).map(|e| &e.term)
+    }
+
+    /// Returns an iterator over the stored terms, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = &Rc<Term>> {
+        self.set.iter().map(|e| &e.term)
     }
 
     // This method is only necessary for the hash consing tests
     #[cfg(test)]
     pub fn into_vec(self) -> Vec<Rc<Term>> {
-        self.0.into_iter().map(|ByValue(t)| t).collect()
+        self.set.into_iter().map(|e| e.term).collect()
     }
 }
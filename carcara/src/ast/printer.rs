@@ -6,6 +6,7 @@ use crate::{
     utils::{is_symbol_character, DedupIterator},
 };
 use indexmap::IndexMap;
+use rug::{Integer, Rational};
 use std::{
     borrow::Cow,
     fmt, io,
@@ -14,17 +15,75 @@ use std::{
 
 pub static USE_SHARING_IN_TERM_DISPLAY: AtomicBool = AtomicBool::new(false);
 
+/// The syntactic conventions used when printing a proof. The two dialects differ only in syntax,
+/// and proofs printed in either of them can be parsed back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dialect {
+    /// The dialect used by cvc5, which follows the current Alethe specification. Assignments in
+    /// anchor arguments include the sort of the variable, as in `(:= (x Int) y)`.
+    #[default]
+    Cvc5,
+
+    /// The dialect used by older versions of veriT. Assignments in anchor arguments omit the sort
+    /// of the variable, as in `(:= x y)`.
+    VeriT,
+}
+
+/// The format used when printing real constants.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RealFormat {
+    /// Prints reals as exact fractions, as in `1/3`. Reals with an integer value are printed as
+    /// decimals, as in `2.0`.
+    #[default]
+    Exact,
+
+    /// Prints reals as SMT-LIB terms, as in `(/ 1 3)`. Negative values use the unary minus
+    /// operator, as in `(- (/ 1 3))`.
+    Smt,
+
+    /// Prints reals as SMT-LIB decimals, rounded to the given number of fractional digits, as in
+    /// `0.333`. Negative values use the unary minus operator. Note that this may lose precision, so
+    /// the printed proof may not be valid anymore.
+    Decimal(u32),
+}
+
 /// Prints a proof to the standard output.
 ///
 /// If `use_sharing` is `true`, terms that are used multiple times will make use of sharing. The
 /// first time a novel term appears, it receives a unique name using the `:named` attribute. After
 /// that, any occurrence of that term will simply use this name, instead of printing the whole term.
 pub fn print_proof(commands: &[ProofCommand], use_sharing: bool) -> io::Result<()> {
-    let mut stdout = io::stdout();
+    write_proof(&mut io::stdout(), commands, Dialect::default(), use_sharing)
+}
+
+/// Writes a proof to `dest`, using the given dialect. See [`print_proof`] for the meaning of
+/// `use_sharing`.
+pub fn write_proof(
+    dest: &mut dyn io::Write,
+    commands: &[ProofCommand],
+    dialect: Dialect,
+    use_sharing: bool,
+) -> io::Result<()> {
+    write_proof_with_real_format(dest, commands, dialect, RealFormat::default(), use_sharing)
+}
+
+/// Writes a proof to `dest`, using the given dialect and format for real constants. See
+/// [`print_proof`] for the meaning of `use_sharing`.
+pub fn write_proof_with_real_format(
+    dest: &mut dyn io::Write,
+    commands: &[ProofCommand],
+    dialect: Dialect,
+    real_format: RealFormat,
+    use_sharing: bool,
+) -> io::Result<()> {
     let mut printer = AlethePrinter {
-        inner: &mut stdout,
+        inner: dest,
         term_indices: use_sharing.then(IndexMap::new),
         term_sharing_variable_prefix: "@p_",
+        named_terms: None,
+        original_clauses: None,
+        dialect,
+        real_format,
     };
     printer.write_proof(commands)
 }
@@ -40,6 +99,10 @@ pub fn write_lia_smt_instance(
         inner: dest,
         term_indices: use_sharing.then(IndexMap::new),
         term_sharing_variable_prefix: "p_",
+        named_terms: None,
+        original_clauses: None,
+        dialect: Dialect::default(),
+        real_format: RealFormat::default(),
     };
     printer.write_lia_smt_instance(clause)
 }
@@ -60,6 +123,19 @@ impl<T: PrintWithSharing> PrintWithSharing for &T {
 
 impl PrintWithSharing for Rc<Term> {
     fn print_with_sharing(&self, p: &mut AlethePrinter) -> io::Result<()> {
+        // Named terms are introduced with the `:named` attribute the first time they appear, and
+        // afterwards are referenced by their name
+        if let Some((name, introduced)) = p.named_terms.as_mut().and_then(|n| n.get_mut(self)) {
+            let name = *name;
+            if *introduced {
+                return write!(p.inner, "{}", quote_symbol(name));
+            }
+            *introduced = true;
+            write!(p.inner, "(! ")?;
+            p.write_raw_term(self)?;
+            return write!(p.inner, " :named {})", quote_symbol(name));
+        }
+
         if let Some(indices) = &mut p.term_indices {
             // There are three cases where we don't use sharing when printing a term:
             //
@@ -107,7 +183,7 @@ impl PrintWithSharing for BindingList {
 
 impl PrintWithSharing for Constant {
     fn print_with_sharing(&self, p: &mut AlethePrinter) -> io::Result<()> {
-        write!(p.inner, "{}", self)
+        p.write_constant(self)
     }
 }
 
@@ -127,19 +203,97 @@ struct AlethePrinter<'a> {
     inner: &'a mut dyn io::Write,
     term_indices: Option<IndexMap<Rc<Term>, usize>>,
     term_sharing_variable_prefix: &'static str,
+
+    /// The names given to terms with the `:named` attribute, and whether each name was already
+    /// introduced in the output.
+    named_terms: Option<IndexMap<Rc<Term>, (&'a str, bool)>>,
+
+    /// The original conclusions of the steps whose literals were reordered by the parser, indexed
+    /// by the position of each step in the proof.
+    original_clauses: Option<&'a IndexMap<Vec<usize>, Vec<Rc<Term>>>>,
+
+    dialect: Dialect,
+    real_format: RealFormat,
+}
+
+/// A printer that writes the commands of a proof one top-level command at a time, without needing
+/// the whole proof in memory. Only the ids of the previously written top-level commands are kept,
+/// so premises that refer to them can be printed.
+pub(super) struct StreamingPrinter<'a> {
+    printer: AlethePrinter<'a>,
+    root_ids: Vec<String>,
+}
+
+impl<'a> StreamingPrinter<'a> {
+    pub(super) fn new(dest: &'a mut dyn io::Write, dialect: Dialect, use_sharing: bool) -> Self {
+        let printer = AlethePrinter {
+            inner: dest,
+            term_indices: use_sharing.then(IndexMap::new),
+            term_sharing_variable_prefix: "@p_",
+            named_terms: None,
+            original_clauses: None,
+            dialect,
+            real_format: RealFormat::default(),
+        };
+        Self { printer, root_ids: Vec::new() }
+    }
+
+    /// Writes a top-level command. Premises of depth zero in `command` are interpreted as indices
+    /// into the commands previously written by this printer.
+    pub(super) fn write_command(&mut self, command: &ProofCommand) -> io::Result<()> {
+        let command = std::slice::from_ref(command);
+        self.printer.write_commands(command, Some(&self.root_ids))?;
+        self.root_ids.push(command[0].id().to_owned());
+        Ok(())
+    }
 }
 
 impl<'a> PrintProof for AlethePrinter<'a> {
     fn write_proof(&mut self, commands: &[ProofCommand]) -> io::Result<()> {
+        self.write_commands(commands, None)
+    }
+}
+
+impl<'a> AlethePrinter<'a> {
+    /// Writes a series of commands. If `root_ids` is given, premises of depth zero are resolved
+    /// using it, instead of indexing into `commands`.
+    fn write_commands(
+        &mut self,
+        commands: &[ProofCommand],
+        root_ids: Option<&[String]>,
+    ) -> io::Result<()> {
         let mut iter = ProofIter::new(commands);
         while let Some(command) = iter.next() {
+            // Commands in a subproof are indented by two spaces for each level of nesting. The
+            // `anchor` command and the step that ends the subproof are kept at the outer level,
+            // so they line up with each other
+            let depth = if command.is_subproof() || iter.is_end_step() {
+                iter.depth() - 1
+            } else {
+                iter.depth()
+            };
+            write!(self.inner, "{:1$}", "", depth * 2)?;
+
             match command {
                 ProofCommand::Assume { id, term } => {
                     write!(self.inner, "(assume {} ", quote_symbol(id))?;
                     term.print_with_sharing(self)?;
                     write!(self.inner, ")")?;
                 }
-                ProofCommand::Step(s) => self.write_step(&mut iter, s)?,
+                ProofCommand::Step(s) => {
+                    let premise_id = |(depth, index): (usize, usize)| match root_ids {
+                        Some(ids) if depth == 0 => ids[index].as_str(),
+                        _ => iter.get_premise((depth, index)).id(),
+                    };
+
+                    // If the step's conclusion was changed after parsing, the original clause no
+                    // longer applies, so it is only used if it is a permutation of the conclusion
+                    let clause = match self.original_clauses.and_then(|c| c.get(&iter.position())) {
+                        Some(original) if is_permutation(original, &s.clause) => original,
+                        _ => &s.clause,
+                    };
+                    self.write_step(s, clause, premise_id)?;
+                }
                 ProofCommand::Subproof(s) => {
                     write!(self.inner, "(anchor :step {}", quote_symbol(command.id()))?;
 
@@ -160,7 +314,12 @@ impl<'a> PrintProof for AlethePrinter<'a> {
                                 }
                                 AnchorArg::Assign(var, value) => {
                                     write!(self.inner, "(:= ")?;
-                                    var.print_with_sharing(self)?;
+                                    match self.dialect {
+                                        Dialect::Cvc5 => var.print_with_sharing(self)?,
+                                        Dialect::VeriT => {
+                                            write!(self.inner, "{}", quote_symbol(&var.0))?;
+                                        }
+                                    }
                                     write!(self.inner, " ")?;
                                     value.print_with_sharing(self)?;
                                     write!(self.inner, ")")?;
@@ -178,9 +337,7 @@ impl<'a> PrintProof for AlethePrinter<'a> {
 
         Ok(())
     }
-}
 
-impl<'a> AlethePrinter<'a> {
     fn write_s_expr<H, T>(&mut self, head: &H, tail: &[T]) -> io::Result<()>
     where
         H: PrintWithSharing + ?Sized,
@@ -201,7 +358,7 @@ impl<'a> AlethePrinter<'a> {
 
     fn write_raw_term(&mut self, term: &Term) -> io::Result<()> {
         match term {
-            Term::Const(c) => write!(self.inner, "{}", c),
+            Term::Const(c) => self.write_constant(c),
             Term::Var(name, _) => write!(self.inner, "{}", quote_symbol(name)),
             Term::App(func, args) => self.write_s_expr(func, args),
             Term::Op(op, args) => {
@@ -240,10 +397,22 @@ impl<'a> AlethePrinter<'a> {
         }
     }
 
-    fn write_step(&mut self, iter: &mut ProofIter, step: &ProofStep) -> io::Result<()> {
-        write!(self.inner, "(step {} (cl", quote_symbol(&step.id))?;
+    fn write_constant(&mut self, constant: &Constant) -> io::Result<()> {
+        match constant {
+            Constant::Real(r) => write!(self.inner, "{}", DisplayReal(r, self.real_format)),
+            other => write!(self.inner, "{}", other),
+        }
+    }
 
-        for t in &step.clause {
+    /// Writes a step, with `clause` as its conclusion.
+    fn write_step<'b>(
+        &mut self,
+        step: &ProofStep,
+        clause: &[Rc<Term>],
+        premise_id: impl Fn((usize, usize)) -> &'b str,
+    ) -> io::Result<()> {
+        write!(self.inner, "(step {} (cl", quote_symbol(&step.id))?;
+        for t in clause {
             write!(self.inner, " ")?;
             t.print_with_sharing(self)?;
         }
@@ -252,10 +421,10 @@ impl<'a> AlethePrinter<'a> {
         write!(self.inner, " :rule {}", step.rule)?;
 
         if let [head, tail @ ..] = step.premises.as_slice() {
-            let id = iter.get_premise(*head).id();
+            let id = premise_id(*head);
             write!(self.inner, " :premises ({}", quote_symbol(id))?;
             for premise in tail {
-                let id = iter.get_premise(*premise).id();
+                let id = premise_id(*premise);
                 write!(self.inner, " {}", quote_symbol(id))?;
             }
             write!(self.inner, ")")?;
@@ -272,15 +441,19 @@ impl<'a> AlethePrinter<'a> {
         }
 
         if let [head, tail @ ..] = step.discharge.as_slice() {
-            let id = iter.get_premise(*head).id();
+            let id = premise_id(*head);
             write!(self.inner, " :discharge ({}", id)?;
             for discharge in tail {
-                let id = iter.get_premise(*discharge).id();
+                let id = premise_id(*discharge);
                 write!(self.inner, " {}", quote_symbol(id))?;
             }
             write!(self.inner, ")")?;
         }
 
+        if let Some(weight) = step.weight {
+            write!(self.inner, " :weight {}", weight)?;
+        }
+
         write!(self.inner, ")")?;
         Ok(())
     }
@@ -341,6 +514,21 @@ fn quote_symbol(symbol: &str) -> Cow<str> {
     }
 }
 
+/// Returns `true` if `a` and `b` contain the same terms, the same number of times each.
+fn is_permutation(a: &[Rc<Term>], b: &[Rc<Term>]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut counts: IndexMap<&Rc<Term>, isize> = IndexMap::new();
+    for t in a {
+        *counts.entry(t).or_default() += 1;
+    }
+    for t in b {
+        *counts.entry(t).or_default() -= 1;
+    }
+    counts.values().all(|&c| c == 0)
+}
+
 fn escape_string(string: &str) -> Cow<str> {
     if string.contains('"') {
         Cow::Owned(string.replace('"', "\"\""))
@@ -359,6 +547,10 @@ impl fmt::Display for Term {
             inner: &mut buf,
             term_indices: use_sharing.then(IndexMap::new),
             term_sharing_variable_prefix: "@p_",
+            named_terms: None,
+            original_clauses: None,
+            dialect: Dialect::default(),
+            real_format: RealFormat::default(),
         };
         printer.write_raw_term(self).unwrap();
         let result = std::str::from_utf8(&buf).unwrap();
@@ -366,6 +558,90 @@ impl fmt::Display for Term {
     }
 }
 
+impl Proof {
+    /// Prints the proof in the Alethe format, reintroducing the names given to terms with the
+    /// `:named` attribute. The first occurrence of each named term is annotated with its name, and
+    /// later occurrences are replaced by the name. Parsing the result gives back an equal proof.
+    ///
+    /// The conclusions of steps whose literals were reordered by the parser are printed in their
+    /// original order, as long as they still contain the same literals.
+    pub fn to_alethe_string(&self) -> String {
+        let mut buf = Vec::new();
+        let mut printer = AlethePrinter {
+            inner: &mut buf,
+            term_indices: None,
+            term_sharing_variable_prefix: "@p_",
+            named_terms: Some(
+                (self.named_terms.iter())
+                    .map(|(t, n)| (t.clone(), (n.as_str(), false)))
+                    .collect(),
+            ),
+            original_clauses: Some(&self.original_clauses),
+            dialect: Dialect::default(),
+            real_format: RealFormat::default(),
+        };
+        printer.write_proof(&self.commands).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+}
+
+impl Term {
+    /// Formats the term as a multi-line s-expression, such that, whenever possible, no line is
+    /// longer than `width` characters.
+    ///
+    /// Terms that fit in the remaining width are printed in a single line. Otherwise, the head of
+    /// the application is printed in the first line, followed by each argument in its own line,
+    /// indented by two spaces. For binder and `let` terms, the binding list is kept in the first
+    /// line. Sharing is never used.
+    pub fn pretty(&self, width: usize) -> String {
+        let mut result = String::new();
+        write_pretty(&mut result, self, 0, 0, width);
+        result
+    }
+}
+
+/// Writes `term` at the given indentation level. `closing` is the number of closing parentheses
+/// that will be written after the term, in the same line.
+fn write_pretty(out: &mut String, term: &Term, indent: usize, closing: usize, width: usize) {
+    use std::fmt::Write;
+
+    let flat = format!("{:#}", term);
+    if indent + flat.len() + closing <= width {
+        out.push_str(&flat);
+        return;
+    }
+
+    let (head, args): (String, &[Rc<Term>]) = match term {
+        Term::App(func, args) => (format!("{:#}", func), args),
+        Term::Op(op, args) if !args.is_empty() => (op.to_symbol().to_owned(), args),
+        Term::ParamOp { op, op_args, args } if !args.is_empty() => {
+            let mut head = format!("(_ {}", op);
+            for a in op_args {
+                write!(head, " {:#}", a).unwrap();
+            }
+            head.push(')');
+            (head, args)
+        }
+        Term::Binder(binder, bindings, inner) => (
+            format!("{} {}", binder, bindings),
+            std::slice::from_ref(inner),
+        ),
+        Term::Let(bindings, inner) => (format!("let {}", bindings), std::slice::from_ref(inner)),
+        _ => {
+            out.push_str(&flat);
+            return;
+        }
+    };
+
+    write!(out, "({}", head).unwrap();
+    for (i, a) in args.iter().enumerate() {
+        let closing = if i == args.len() - 1 { closing + 1 } else { 0 };
+        write!(out, "\n{:1$}", "", indent + 2).unwrap();
+        write_pretty(out, a, indent + 2, closing, width);
+    }
+    out.push(')');
+}
+
 impl fmt::Debug for Term {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Display::fmt(self, f)
@@ -376,20 +652,47 @@ impl fmt::Display for Constant {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Constant::Integer(i) => write!(f, "{}", i),
-            Constant::Real(r) => {
-                // TODO: add option to control whether we use GMP notation
-                if r.is_integer() && !r.is_negative() {
-                    write!(f, "{}.0", r.numer())
-                } else {
-                    write!(f, "{}/{}", r.numer(), r.denom())
-                }
-            }
+            Constant::Real(r) => write!(f, "{}", DisplayReal(r, RealFormat::Exact)),
             Constant::String(s) => write!(f, "\"{}\"", escape_string(s)),
             Constant::BitVec(val, width) => write!(f, "(_ bv{} {})", val, width), // TODO: comeback to this
         }
     }
 }
 
+/// A wrapper used to display a real constant in a given format.
+struct DisplayReal<'a>(&'a Rational, RealFormat);
+
+impl fmt::Display for DisplayReal<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let DisplayReal(r, format) = *self;
+        if format == RealFormat::Exact {
+            return if r.is_integer() && !r.is_negative() {
+                write!(f, "{}.0", r.numer())
+            } else {
+                write!(f, "{}/{}", r.numer(), r.denom())
+            };
+        }
+
+        if r.is_negative() {
+            write!(f, "(- {})", DisplayReal(&Rational::from(-r), format))
+        } else if let RealFormat::Decimal(digits) = format {
+            let scale = Integer::from(Integer::u_pow_u(10, digits));
+            let scaled = Rational::from(r * &scale).round().into_numer_denom().0;
+            let (integer, fraction) = scaled.div_rem(scale);
+            if digits == 0 {
+                write!(f, "{}.0", integer)
+            } else {
+                let fraction = fraction.to_string();
+                write!(f, "{}.{:0>2$}", integer, fraction, digits as usize)
+            }
+        } else if r.is_integer() {
+            write!(f, "{}.0", r.numer())
+        } else {
+            write!(f, "(/ {} {})", r.numer(), r.denom())
+        }
+    }
+}
+
 impl fmt::Display for Binder {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let s = match self {
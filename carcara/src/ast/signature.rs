@@ -0,0 +1,205 @@
+//! The arity and sort signatures of operators.
+
+use super::{Operator, Sort};
+
+/// The number of arguments an operator expects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    /// The operator expects exactly this many arguments.
+    Fixed(usize),
+
+    /// The operator is variadic, and expects at least this many arguments.
+    AtLeast(usize),
+}
+
+/// A class of sorts that an argument or result of an operator may have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKind {
+    Bool,
+    Int,
+    Real,
+    String,
+    RegLan,
+
+    /// Either `Int` or `Real`.
+    Numeric,
+
+    /// A bit vector sort, of any width.
+    BitVec,
+}
+
+impl SortKind {
+    /// Returns `true` if `sort` belongs to this kind.
+    pub fn matches(self, sort: &Sort) -> bool {
+        match self {
+            SortKind::Bool => *sort == Sort::Bool,
+            SortKind::Int => *sort == Sort::Int,
+            SortKind::Real => *sort == Sort::Real,
+            SortKind::String => *sort == Sort::String,
+            SortKind::RegLan => *sort == Sort::RegLan,
+            SortKind::Numeric => matches!(sort, Sort::Int | Sort::Real),
+            SortKind::BitVec => matches!(sort, Sort::BitVec(_)),
+        }
+    }
+}
+
+/// The constraints on the sorts of the arguments of an operator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgSorts {
+    /// The arguments are not constrained.
+    Any,
+
+    /// Each argument must be of the given kind, but they may have different sorts.
+    Each(SortKind),
+
+    /// The argument at each position must be of the corresponding kind.
+    Positional(&'static [SortKind]),
+
+    /// All arguments must have the same sort. If a kind is given, the first argument must be of
+    /// that kind.
+    AllEqual(Option<SortKind>),
+
+    /// The arguments of `ite`: a `Bool` condition, followed by two arguments of the same sort.
+    Ite,
+
+    /// The arguments of `select`: an array, followed by a term of its key sort.
+    Select,
+
+    /// The arguments of `store`: an array, followed by terms of its key and value sorts.
+    Store,
+}
+
+/// The signature of an operator, that is, the number of arguments it expects, and the sorts of its
+/// arguments and result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperatorSignature {
+    pub arity: Arity,
+    pub args: ArgSorts,
+
+    /// The kind of the result sort, or `None` if it depends on the sorts of the arguments.
+    pub result: Option<SortKind>,
+}
+
+impl Operator {
+    /// Returns the signature of this operator, as defined by the SMT-LIB standard. Note that some
+    /// parser options, like `allow_int_real_subtyping`, may relax these constraints.
+    pub fn signature(self) -> OperatorSignature {
+        use Arity::*;
+        use SortKind::*;
+
+        let (arity, args, result) = match self {
+            Operator::True | Operator::False => (Fixed(0), ArgSorts::Any, Some(Bool)),
+            Operator::Not => (Fixed(1), ArgSorts::Each(Bool), Some(Bool)),
+            Operator::Implies | Operator::And | Operator::Or | Operator::Xor => {
+                (AtLeast(2), ArgSorts::Each(Bool), Some(Bool))
+            }
+            Operator::Equals | Operator::Distinct => {
+                (AtLeast(2), ArgSorts::AllEqual(None), Some(Bool))
+            }
+            Operator::Ite => (Fixed(3), ArgSorts::Ite, None),
+
+            // The `-` operator, in particular, can be called with only one argument, in which case
+            // it means negation instead of subtraction
+            Operator::Sub => (AtLeast(1), ArgSorts::AllEqual(Some(Numeric)), None),
+            Operator::Add | Operator::Mult => (AtLeast(2), ArgSorts::AllEqual(Some(Numeric)), None),
+            Operator::IntDiv => (AtLeast(2), ArgSorts::AllEqual(Some(Int)), Some(Int)),
+            Operator::RealDiv => (AtLeast(2), ArgSorts::AllEqual(Some(Real)), Some(Real)),
+            Operator::Mod => (Fixed(2), ArgSorts::Positional(&[Int, Int]), Some(Int)),
+            Operator::Abs => (Fixed(1), ArgSorts::Each(Int), Some(Int)),
+            Operator::LessThan | Operator::GreaterThan | Operator::LessEq | Operator::GreaterEq => {
+                (AtLeast(2), ArgSorts::Each(Numeric), Some(Bool))
+            }
+            Operator::ToReal => (Fixed(1), ArgSorts::Each(Int), Some(Real)),
+            Operator::ToInt => (Fixed(1), ArgSorts::Each(Real), Some(Int)),
+            Operator::IsInt => (Fixed(1), ArgSorts::Each(Real), Some(Bool)),
+
+            Operator::Select => (Fixed(2), ArgSorts::Select, None),
+            Operator::Store => (Fixed(3), ArgSorts::Store, None),
+
+            Operator::StrConcat => (AtLeast(2), ArgSorts::Each(String), Some(String)),
+            Operator::StrLen | Operator::StrToCode | Operator::StrToInt => {
+                (Fixed(1), ArgSorts::Each(String), Some(Int))
+            }
+            Operator::StrIsDigit => (Fixed(1), ArgSorts::Each(String), Some(Bool)),
+            Operator::StrLessThan
+            | Operator::StrLessEq
+            | Operator::PrefixOf
+            | Operator::SuffixOf
+            | Operator::Contains => (Fixed(2), ArgSorts::Each(String), Some(Bool)),
+            Operator::CharAt => (Fixed(2), ArgSorts::Positional(&[String, Int]), Some(String)),
+            Operator::Substring => (
+                Fixed(3),
+                ArgSorts::Positional(&[String, Int, Int]),
+                Some(String),
+            ),
+            Operator::IndexOf => (
+                Fixed(3),
+                ArgSorts::Positional(&[String, String, Int]),
+                Some(Int),
+            ),
+            Operator::Replace | Operator::ReplaceAll => {
+                (Fixed(3), ArgSorts::Each(String), Some(String))
+            }
+            Operator::ReplaceRe | Operator::ReplaceReAll => (
+                Fixed(3),
+                ArgSorts::Positional(&[String, RegLan, String]),
+                Some(String),
+            ),
+            Operator::StrFromCode | Operator::StrFromInt => {
+                (Fixed(1), ArgSorts::Each(Int), Some(String))
+            }
+
+            Operator::StrToRe => (Fixed(1), ArgSorts::Each(String), Some(RegLan)),
+            Operator::StrInRe => (
+                Fixed(2),
+                ArgSorts::Positional(&[String, RegLan]),
+                Some(Bool),
+            ),
+            Operator::ReNone | Operator::ReAll | Operator::ReAllChar => {
+                (Fixed(0), ArgSorts::Any, Some(RegLan))
+            }
+            Operator::ReConcat
+            | Operator::ReUnion
+            | Operator::ReIntersection
+            | Operator::ReDiff => (AtLeast(2), ArgSorts::Each(RegLan), Some(RegLan)),
+            Operator::ReKleeneClosure
+            | Operator::ReComplement
+            | Operator::ReKleeneCross
+            | Operator::ReOption => (Fixed(1), ArgSorts::Each(RegLan), Some(RegLan)),
+            Operator::ReRange => (Fixed(2), ArgSorts::Each(String), Some(RegLan)),
+
+            Operator::BvNot | Operator::BvNeg => (Fixed(1), ArgSorts::Each(BitVec), None),
+            Operator::BvConcat => (AtLeast(2), ArgSorts::Each(BitVec), None),
+            Operator::BvBbTerm => (AtLeast(1), ArgSorts::AllEqual(Some(Bool)), None),
+            Operator::BvAdd
+            | Operator::BvMul
+            | Operator::BvAnd
+            | Operator::BvOr
+            | Operator::BvXor => (AtLeast(2), ArgSorts::AllEqual(Some(BitVec)), None),
+            Operator::BvULt
+            | Operator::BvULe
+            | Operator::BvUGt
+            | Operator::BvUGe
+            | Operator::BvSLt
+            | Operator::BvSLe
+            | Operator::BvSGt
+            | Operator::BvSGe => (Fixed(2), ArgSorts::AllEqual(Some(BitVec)), Some(Bool)),
+            Operator::BvUDiv
+            | Operator::BvURem
+            | Operator::BvShl
+            | Operator::BvLShr
+            | Operator::BvNAnd
+            | Operator::BvNOr
+            | Operator::BvXNor
+            | Operator::BvComp
+            | Operator::BvSub
+            | Operator::BvSDiv
+            | Operator::BvSRem
+            | Operator::BvSMod
+            | Operator::BvAShr => (Fixed(2), ArgSorts::AllEqual(Some(BitVec)), None),
+
+            Operator::RareList => (AtLeast(0), ArgSorts::AllEqual(None), None),
+        };
+        OperatorSignature { arity, args, result }
+    }
+}
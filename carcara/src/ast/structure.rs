@@ -0,0 +1,106 @@
+//! Structural validation of proofs, independent of the semantics of each rule.
+
+use super::{ProofCommand, ProofStep};
+use thiserror::Error;
+
+/// The error type for proofs that are structurally malformed.
+#[derive(Debug, PartialEq, Eq, Error)]
+pub enum StructureError {
+    /// A premise index does not refer to a command that precedes the step.
+    #[error("premise ({1}, {2}) of step '{0}' does not refer to a previous command")]
+    InvalidPremise(String, usize, usize),
+
+    /// A discharge index does not refer to a command that precedes the step.
+    #[error("discharged command ({1}, {2}) of step '{0}' does not refer to a previous command")]
+    InvalidDischarge(String, usize, usize),
+
+    /// A discharge index refers to a command that is not an `assume` command.
+    #[error("step '{0}' discharges command '{1}', which is not an assumption")]
+    DischargeNotAssume(String, String),
+
+    /// A subproof contains no commands.
+    #[error("subproof has no commands")]
+    EmptySubproof,
+
+    /// The last command in a subproof is not a `step` command.
+    #[error("subproof must end in a step, but ends in '{0}'")]
+    SubproofDoesNotEndInStep(String),
+}
+
+/// Validates the structure of a series of proof commands. See [`Proof::validate_structure`].
+///
+/// [`Proof::validate_structure`]: super::Proof::validate_structure
+pub(super) fn validate_commands(commands: &[ProofCommand]) -> Result<(), StructureError> {
+    // For each open subproof, we keep its commands and the number of commands that were already
+    // visited in it. For the outer subproofs, this number is the index of the subproof that is
+    // currently open, so only the commands that come before it are valid premises
+    let mut stack: Vec<(&[ProofCommand], usize)> = Vec::new();
+    validate_subproof(&mut stack, commands)
+}
+
+fn validate_subproof<'a>(
+    stack: &mut Vec<(&'a [ProofCommand], usize)>,
+    commands: &'a [ProofCommand],
+) -> Result<(), StructureError> {
+    stack.push((commands, 0));
+    for (i, command) in commands.iter().enumerate() {
+        stack.last_mut().unwrap().1 = i;
+        match command {
+            ProofCommand::Assume { .. } => (),
+            ProofCommand::Step(step) => validate_step(stack, step)?,
+            ProofCommand::Subproof(s) => {
+                match s.commands.last() {
+                    None => return Err(StructureError::EmptySubproof),
+                    Some(ProofCommand::Step(_)) => (),
+                    Some(other) => {
+                        return Err(StructureError::SubproofDoesNotEndInStep(
+                            other.id().to_owned(),
+                        ))
+                    }
+                }
+                validate_subproof(stack, &s.commands)?;
+            }
+        }
+    }
+    stack.pop();
+    Ok(())
+}
+
+fn validate_step(
+    stack: &[(&[ProofCommand], usize)],
+    step: &ProofStep,
+) -> Result<(), StructureError> {
+    let get = |(depth, index): (usize, usize)| {
+        let &(commands, visited) = stack.get(depth)?;
+        (index < visited).then(|| &commands[index])
+    };
+
+    for &(depth, index) in &step.premises {
+        if get((depth, index)).is_none() {
+            return Err(StructureError::InvalidPremise(
+                step.id.clone(),
+                depth,
+                index,
+            ));
+        }
+    }
+    for &(depth, index) in &step.discharge {
+        match get((depth, index)) {
+            None => {
+                return Err(StructureError::InvalidDischarge(
+                    step.id.clone(),
+                    depth,
+                    index,
+                ))
+            }
+            Some(c) if !c.is_assume() => {
+                return Err(StructureError::DischargeNotAssume(
+                    step.id.clone(),
+                    c.id().to_owned(),
+                ))
+            }
+            Some(_) => (),
+        }
+    }
+    Ok(())
+}
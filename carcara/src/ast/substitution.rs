@@ -57,7 +57,11 @@ impl Substitution {
 
     /// Constructs a singleton substitution mapping `x` to `t`. This returns an error if the sorts
     /// of the given terms are not the same, or if `x` is not a variable term.
-    pub fn single(pool: &mut dyn TermPool, x: Rc<Term>, t: Rc<Term>) -> SubstitutionResult<Self> {
+    pub fn single<P: TermPool + ?Sized>(
+        pool: &mut P,
+        x: Rc<Term>,
+        t: Rc<Term>,
+    ) -> SubstitutionResult<Self> {
         let mut this = Self::empty();
         this.insert(pool, x, t)?;
         Ok(this)
@@ -66,8 +70,8 @@ impl Substitution {
     /// Constructs a new substitution from an arbitrary mapping of terms to other terms. This
     /// returns an error if any term in the left-hand side is not a variable, or if any term is
     /// mapped to a term of a different sort.
-    pub fn new(
-        pool: &mut dyn TermPool,
+    pub fn new<P: TermPool + ?Sized>(
+        pool: &mut P,
         map: IndexMap<Rc<Term>, Rc<Term>>,
     ) -> SubstitutionResult<Self> {
         for (k, v) in &map {
@@ -93,9 +97,9 @@ impl Substitution {
 
     /// Extends the substitution by adding a new mapping from `x` to `t`. This returns an error if
     /// the sorts of the given terms are not the same, or if `x` is not a variable term.
-    pub(crate) fn insert(
+    pub(crate) fn insert<P: TermPool + ?Sized>(
         &mut self,
-        pool: &mut dyn TermPool,
+        pool: &mut P,
         x: Rc<Term>,
         t: Rc<Term>,
     ) -> SubstitutionResult<()> {
@@ -137,7 +141,7 @@ impl Substitution {
 
     /// Computes which binder variables will need to be renamed, and stores the result in
     /// `self.should_be_renamed`.
-    fn compute_should_be_renamed(&mut self, pool: &mut dyn TermPool) {
+    fn compute_should_be_renamed<P: TermPool + ?Sized>(&mut self, pool: &mut P) {
         if self.should_be_renamed.is_some() {
             return;
         }
@@ -172,27 +176,76 @@ impl Substitution {
     }
 
     /// Applies the substitution to `term`, and returns the result as a new term.
-    pub fn apply(&mut self, pool: &mut dyn TermPool, term: &Rc<Term>) -> Rc<Term> {
+    pub fn apply<P: TermPool + ?Sized>(&mut self, pool: &mut P, term: &Rc<Term>) -> Rc<Term> {
+        // To avoid stack overflows on deeply nested terms, the term is traversed iteratively, in
+        // post-order, so the substitution is always applied to the arguments of a term before it
+        // is applied to the term itself. The arguments are pushed in reverse, so they are visited
+        // from left to right. Binder and `let` terms are still handled recursively, but
+        // their bodies are traversed iteratively as well
+        let mut stack = vec![(term.clone(), false)];
+        while let Some((current, args_done)) = stack.pop() {
+            if self.get(&current).is_some() {
+                continue;
+            }
+            if args_done {
+                let result = self.apply_to_args(pool, &current);
+
+                // Since frequently a term will have more than one identical subterms, we insert
+                // the calculated substitution in the cache hash map so it may be reused later. This
+                // means we don't re-visit already seen terms, so this method traverses the term as
+                // a DAG, not as a tree
+                self.cache.insert(current, result);
+                continue;
+            }
+            let args: &[Rc<Term>] = match current.as_ref() {
+                Term::App(func, args) => {
+                    stack.push((current.clone(), true));
+                    stack.push((func.clone(), false));
+                    args
+                }
+                Term::Op(_, args)
+                | Term::ParamOp { args, .. }
+                | Term::Sort(Sort::Atom(_, args) | Sort::Function(args)) => {
+                    stack.push((current.clone(), true));
+                    args
+                }
+                Term::Sort(Sort::Array(x, y)) => {
+                    stack.push((current.clone(), true));
+                    stack.push((y.clone(), false));
+                    stack.push((x.clone(), false));
+                    &[]
+                }
+                _ => {
+                    stack.push((current.clone(), true));
+                    &[]
+                }
+            };
+            stack.extend(args.iter().rev().map(|a| (a.clone(), false)));
+        }
+        self.get(term).unwrap().clone()
+    }
+
+    /// Returns the result of applying the substitution to `term`, if it was already computed, or if
+    /// `term` is in the domain of the substitution.
+    fn get(&self, term: &Rc<Term>) -> Option<&Rc<Term>> {
+        self.cache.get(term).or_else(|| self.map.get(term))
+    }
+
+    /// Applies the substitution to `term`, assuming it was already applied to all of its arguments.
+    fn apply_to_args<P: TermPool + ?Sized>(&mut self, pool: &mut P, term: &Rc<Term>) -> Rc<Term> {
         macro_rules! apply_to_sequence {
             ($sequence:expr) => {
                 $sequence
                     .iter()
-                    .map(|a| self.apply(pool, a))
+                    .map(|a| self.get(a).unwrap().clone())
                     .collect::<Vec<_>>()
             };
         }
 
-        if let Some(t) = self.cache.get(term) {
-            return t.clone();
-        }
-        if let Some(t) = self.map.get(term) {
-            return t.clone();
-        }
-
-        let result = match term.as_ref() {
+        match term.as_ref() {
             Term::App(func, args) => {
                 let new_args = apply_to_sequence!(args);
-                let new_func = self.apply(pool, func);
+                let new_func = self.get(func).unwrap().clone();
                 pool.add(Term::App(new_func, new_args))
             }
             Term::Op(op, args) => {
@@ -203,6 +256,7 @@ impl Substitution {
                 self.apply_to_binder(pool, term, *binder, binding_list.as_ref(), inner)
             }
             Term::Let(binding_list, inner) => {
+                self.compute_should_be_renamed(pool);
                 let (new_bindings, mut renaming) =
                     self.rename_binding_list(pool, binding_list, true);
                 let new_term = if renaming.is_empty() {
@@ -228,24 +282,21 @@ impl Substitution {
                 let new_args = apply_to_sequence!(args);
                 pool.add(Term::Sort(Sort::Atom(sort.clone(), new_args)))
             }
+            Term::Sort(Sort::Function(sorts)) => {
+                let new_sorts = apply_to_sequence!(sorts);
+                pool.add(Term::Sort(Sort::Function(new_sorts)))
+            }
             Term::Sort(Sort::Array(x, y)) => {
-                let [x, y] = [x, y].map(|s| self.apply(pool, s));
+                let [x, y] = [x, y].map(|s| self.get(s).unwrap().clone());
                 pool.add(Term::Sort(Sort::Array(x, y)))
             }
             Term::Sort(_) => term.clone(),
-        };
-
-        // Since frequently a term will have more than one identical subterms, we insert the
-        // calculated substitution in the cache hash map so it may be reused later. This means we
-        // don't re-visit already seen terms, so this method traverses the term as a DAG, not as a
-        // tree
-        self.cache.insert(term.clone(), result.clone());
-        result
+        }
     }
 
-    fn can_skip_instead_of_renaming(
+    fn can_skip_instead_of_renaming<P: TermPool + ?Sized>(
         &self,
-        pool: &mut dyn TermPool,
+        pool: &mut P,
         binding_list: &[SortedVar],
     ) -> bool {
         // Note: this method assumes that `binding_list` is a "sort" binding list. "Value" lists add
@@ -272,9 +323,9 @@ impl Substitution {
     }
 
     /// Applies the substitution to a binder term, renaming any bound variables as needed.
-    fn apply_to_binder(
+    fn apply_to_binder<P: TermPool + ?Sized>(
         &mut self,
-        pool: &mut dyn TermPool,
+        pool: &mut P,
         original_term: &Rc<Term>,
         binder: Binder,
         binding_list: &[SortedVar],
@@ -313,9 +364,9 @@ impl Substitution {
     /// returns a clone of the binding list and an empty substitution. The name chosen when renaming
     /// a variable is the old name with `'` appended. If the binding list is a "value" list, like in
     /// a `let` or `lambda` term, `is_value_list` should be true.
-    fn rename_binding_list(
+    fn rename_binding_list<P: TermPool + ?Sized>(
         &mut self,
-        pool: &mut dyn TermPool,
+        pool: &mut P,
         binding_list: &[SortedVar],
         is_value_list: bool,
     ) -> (BindingList, Self) {
@@ -360,9 +411,10 @@ impl Substitution {
                 }
 
                 // If the binding list is a "value" list, we need to apply the current substitution
-                // to each variable's value
+                // to each variable's value. Since the bindings are parallel, the values are not in
+                // the scope of the binding list, so the renaming is not applied to them
                 let new_value = if is_value_list {
-                    new_substitution.apply(pool, value)
+                    self.apply(pool, value)
                 } else {
                     value.clone()
                 };
@@ -373,6 +425,92 @@ impl Substitution {
     }
 }
 
+/// Constructs a substitution from `map` and applies it to `term`. This is used to implement
+/// [`TermPool::substitute`].
+pub(super) fn substitute<P: TermPool + ?Sized>(
+    pool: &mut P,
+    term: &Rc<Term>,
+    map: IndexMap<Rc<Term>, Rc<Term>>,
+) -> SubstitutionResult<Rc<Term>> {
+    Ok(Substitution::new(pool, map)?.apply(pool, term))
+}
+
+/// Beta-reduces every redex in `term`, including redexes that are created by reducing other
+/// redexes. This is used to implement [`TermPool::beta_reduce`].
+pub(super) fn beta_reduce<P: TermPool + ?Sized>(pool: &mut P, term: &Rc<Term>) -> Rc<Term> {
+    fn reduce<P: TermPool + ?Sized>(
+        pool: &mut P,
+        cache: &mut IndexMap<Rc<Term>, Rc<Term>>,
+        term: &Rc<Term>,
+    ) -> Rc<Term> {
+        if let Some(t) = cache.get(term) {
+            return t.clone();
+        }
+
+        let mut reduce_all = |pool: &mut P, args: &[Rc<Term>]| -> Vec<Rc<Term>> {
+            args.iter().map(|a| reduce(pool, cache, a)).collect()
+        };
+        let result = match term.as_ref() {
+            Term::App(func, args) => {
+                let new_args = reduce_all(pool, args);
+                let new_func = reduce(pool, cache, func);
+                match new_func.as_ref() {
+                    Term::Binder(Binder::Lambda, bindings, body)
+                        if bindings.len() == new_args.len() =>
+                    {
+                        let map = bindings
+                            .iter()
+                            .zip(&new_args)
+                            .map(|(var, arg)| (pool.add(var.clone().into()), arg.clone()))
+                            .collect();
+
+                        // If any argument has the wrong sort, the application is ill-typed, so we
+                        // don't reduce it. The reductions already made in the function and
+                        // arguments are kept
+                        match Substitution::new(pool, map) {
+                            Ok(mut s) => {
+                                let body = s.apply(pool, body);
+                                reduce(pool, cache, &body)
+                            }
+                            Err(_) => pool.add(Term::App(new_func, new_args)),
+                        }
+                    }
+                    _ => pool.add(Term::App(new_func, new_args)),
+                }
+            }
+            Term::Op(op, args) => {
+                let new_args = reduce_all(pool, args);
+                pool.add(Term::Op(*op, new_args))
+            }
+            Term::ParamOp { op, op_args, args } => {
+                let new_args = reduce_all(pool, args);
+                pool.add(Term::ParamOp {
+                    op: *op,
+                    op_args: op_args.clone(),
+                    args: new_args,
+                })
+            }
+            Term::Binder(binder, bindings, inner) => {
+                let new_inner = reduce(pool, cache, inner);
+                pool.add(Term::Binder(*binder, bindings.clone(), new_inner))
+            }
+            Term::Let(bindings, inner) => {
+                let new_bindings = bindings
+                    .iter()
+                    .map(|(var, value)| (var.clone(), reduce(pool, cache, value)))
+                    .collect();
+                let new_inner = reduce(pool, cache, inner);
+                pool.add(Term::Let(BindingList(new_bindings), new_inner))
+            }
+            Term::Const(_) | Term::Var(..) | Term::Sort(_) => term.clone(),
+        };
+        cache.insert(term.clone(), result.clone());
+        result
+    }
+
+    reduce(pool, &mut IndexMap::new(), term)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -448,7 +586,92 @@ mod tests {
             // In theory, since x does not appear in this term, renaming y to y' is unnecessary
             "(forall ((y Int)) (> y 0))" [x -> y] => "(forall ((y' Int)) (> y' 0))",
 
-            // TODO: Add tests for `choice`, `let`, and `lambda` terms
+            // Other binders
+            "(exists ((y Int)) (> y x))" [x -> y] => "(exists ((y' Int)) (> y' y))",
+            "(choice ((y Int)) (> y x))" [x -> y] => "(choice ((y' Int)) (> y' y))",
+            "(lambda ((y Int)) (+ y x))" [x -> y] => "(lambda ((y' Int)) (+ y' y))",
+            "(let ((y 0)) (> y x))" [x -> y] => "(let ((y' 0)) (> y' y))",
+            "(let ((z x)) (> z 0))" [x -> y] => "(let ((z y)) (> z 0))",
+            "(let ((y x)) (> y x))" [x -> y] => "(let ((y' y)) (> y' y))",
         }
     }
+
+    #[test]
+    fn test_pool_substitute() {
+        let mut pool = PrimitivePool::new();
+        let [original, x, y, p, expected] = crate::parser::tests::parse_terms(
+            &mut pool,
+            "(declare-fun x () Int) (declare-fun y () Int) (declare-fun p () Bool)",
+            [
+                "(and (forall ((y Int)) (> y x)) (exists ((y Int)) (< y x)) (= x y))",
+                "x",
+                "y",
+                "p",
+                "(and (forall ((y' Int)) (> y' y)) (exists ((y' Int)) (< y' y)) (= y y))",
+            ],
+        );
+
+        let map: IndexMap<_, _> = [(x.clone(), y)].into_iter().collect();
+        assert_eq!(Ok(expected), pool.substitute(&original, map));
+
+        let map: IndexMap<_, _> = [(x.clone(), p.clone())].into_iter().collect();
+        assert_eq!(
+            Err(SubstitutionError::DifferentSorts(x, p)),
+            pool.substitute(&original, map)
+        );
+    }
+
+    #[test]
+    fn test_beta_reduce() {
+        let mut pool = PrimitivePool::new();
+        let definitions = "
+            (declare-fun y () Int)
+            (declare-fun f (Int) Int)
+        ";
+        let cases = [
+            ("((lambda ((x Int)) (+ x 1)) 2)", "(+ 2 1)"),
+            ("((lambda ((x Int) (z Int)) (- x z)) 2 y)", "(- 2 y)"),
+            ("(f ((lambda ((x Int)) (* x x)) y))", "(f (* y y))"),
+            (
+                "((lambda ((x Int)) (forall ((y Int)) (> y x))) y)",
+                "(forall ((y' Int)) (> y' y))",
+            ),
+            (
+                "((lambda ((x Int)) ((lambda ((z Int)) (+ x z)) x)) 3)",
+                "(+ 3 3)",
+            ),
+            (
+                "(lambda ((x Int)) ((lambda ((z Int)) z) x))",
+                "(lambda ((x Int)) x)",
+            ),
+        ];
+        for (original, expected) in cases {
+            let [original, expected] =
+                crate::parser::tests::parse_terms(&mut pool, definitions, [original, expected]);
+            assert_eq!(expected, pool.beta_reduce(&original));
+        }
+
+        // Terms with no redexes are left unchanged
+        let [term] = crate::parser::tests::parse_terms(
+            &mut pool,
+            definitions,
+            ["(forall ((x Int)) (= (f x) (+ y 1)))"],
+        );
+        assert_eq!(term, pool.beta_reduce(&term));
+
+        // Ill-typed applications are not reduced, but redexes inside them still are. Since the
+        // parser rejects ill-typed applications, we have to build this one manually
+        let [func, arg, reduced_arg] = crate::parser::tests::parse_terms(
+            &mut pool,
+            definitions,
+            [
+                "(lambda ((x Int)) x)",
+                "((lambda ((z Bool)) z) true)",
+                "true",
+            ],
+        );
+        let original = pool.add(Term::App(func.clone(), vec![arg]));
+        let expected = pool.add(Term::App(func, vec![reduced_arg]));
+        assert_eq!(expected, pool.beta_reduce(&original));
+    }
 }
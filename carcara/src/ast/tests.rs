@@ -1,8 +1,19 @@
 use crate::{
-    ast::{pool::PrimitivePool, Polyeq, PolyeqComparator, TermPool},
-    parser::tests::parse_terms,
+    ast::{
+        canonicalize_binders, linear::LinearCombination, node::find_cycle, pool::PrimitivePool,
+        tracing_polyeq_mod_nary, write_proof, write_proof_with_real_format, CyclicProofError,
+        DepthError, Dialect, DischargeError, Operator, OperatorSignature, Polyeq, PolyeqComparator,
+        ProofCommand, ProofMetrics, ProofNode, ProofVisitor, Rc, RealFormat, Sort, StepNode,
+        StructureError, SubproofNode, Term, TermPool,
+    },
+    parser::{
+        self,
+        tests::{parse_proof, parse_terms},
+    },
 };
-use indexmap::IndexSet;
+use indexmap::{IndexMap, IndexSet};
+use rug::Rational;
+use std::time::Duration;
 
 #[test]
 fn test_free_vars() {
@@ -32,6 +43,13 @@ fn test_free_vars() {
             ("(forall ((a Int)) (forall ((b Int)) (= a b)))", &[]),
             ("(and (forall ((a Int)) (= a 0)) (= a 0))", &["a"]),
             ("(and (= a 0) (forall ((a Int)) (= a 0)))", &["a"]),
+            ("(forall ((x Int)) (> x b))", &["b"]),
+            ("(exists ((x Int)) (> x b))", &["b"]),
+            ("(choice ((x Int)) (> x b))", &["b"]),
+            ("(lambda ((x Int)) (> x b))", &["b"]),
+            ("(let ((x a)) (> x b))", &["b", "a"]),
+            ("(let ((a b)) (= a 0))", &["b"]),
+            ("(let ((x a) (y x)) (> x y))", &["a", "x"]),
         ],
     );
 }
@@ -125,3 +143,1570 @@ fn test_polyeq() {
         TestType::ModNary,
     );
 }
+
+#[test]
+fn test_polyeq_pointer_equality() {
+    // Since terms are hash consed, identical terms are compared by pointer, without descending
+    // into their structure. We use the maximum depth reached by the comparator to measure that
+    let definitions = "
+        (declare-fun f (Int Int) Int)
+        (declare-fun a () Int)
+        (declare-fun b () Int)
+        (declare-fun p () Bool)
+        (declare-fun q () Bool)
+    ";
+    let mut pool = PrimitivePool::new();
+    let [big, t, u] = parse_terms(
+        &mut pool,
+        definitions,
+        [
+            "(= (f (f a b) (f b a)) (f (f b a) (f a b)))",
+            "(and (= (f (f a b) (f b a)) (f (f b a) (f a b))) p)",
+            "(and (= (f (f a b) (f b a)) (f (f b a) (f a b))) q)",
+        ],
+    );
+    let mut time = Duration::ZERO;
+
+    // Comparing a term with itself does no structural work
+    assert_eq!((true, 0), tracing_polyeq_mod_nary(&big, &big, &mut time));
+
+    // This also applies to identical subterms at any depth. Here, the comparator only has to
+    // descend into the `and` terms and compare `p` and `q`, and stops at the shared subterm
+    assert_eq!((false, 2), tracing_polyeq_mod_nary(&t, &u, &mut time));
+}
+
+#[test]
+fn test_annotations_are_ignored() {
+    // Annotations are removed by the parser, so annotated and unannotated copies of a term are
+    // represented by the same term, and a name introduced by `:named` is replaced by its term
+    let definitions = "
+        (declare-fun p () Bool)
+        (declare-fun q () Bool)
+        (declare-fun f (Int) Int)
+    ";
+    let cases = [
+        ("(! (and p q) :named n)", "(and p q)"),
+        ("(or (! p :named m) (not m))", "(or p (not p))"),
+        (
+            "(forall ((x Int)) (! (> (f x) 0) :pattern ((f x))))",
+            "(forall ((x Int)) (> (f x) 0))",
+        ),
+        (
+            "(forall ((x Int)) (! (> (f x) 0) :pattern ((f x)) :named k))",
+            "(forall ((x Int)) (> (f x) 0))",
+        ),
+    ];
+    let mut pool = PrimitivePool::new();
+    for (i, (a, b)) in cases.iter().enumerate() {
+        let [a, b] = parse_terms(&mut pool, definitions, [a, b]);
+        assert_eq!(a, b, "test case #{i} failed");
+    }
+
+    // This also holds when comparing terms modulo alpha-equivalence
+    let [a, b] = parse_terms(
+        &mut pool,
+        definitions,
+        [
+            "(forall ((x Int)) (! (> (f x) 0) :pattern ((f x))))",
+            "(forall ((y Int)) (> (f y) 0))",
+        ],
+    );
+    let mut comp = PolyeqComparator::new(true, true, false);
+    assert!(Polyeq::eq(&mut comp, &a, &b));
+}
+
+#[test]
+fn test_canonicalize_binders() {
+    fn run_tests(definitions: &str, cases: &[(&str, &str, bool)]) {
+        let mut pool = PrimitivePool::new();
+        for (i, &(a, b, expected)) in cases.iter().enumerate() {
+            let [a, b] = parse_terms(&mut pool, definitions, [a, b]);
+            let got = canonicalize_binders(&mut pool, &a) == canonicalize_binders(&mut pool, &b);
+            assert_eq!(expected, got, "test case #{i} failed: `{a}`, `{b}`");
+        }
+    }
+    run_tests(
+        "(declare-fun p (Int) Bool)
+        (declare-fun a () Int)
+        (declare-fun b () Int)",
+        &[
+            ("(forall ((x Int)) (p x))", "(forall ((y Int)) (p y))", true),
+            ("(forall ((x Int)) (p a))", "(forall ((y Int)) (p a))", true),
+            (
+                "(forall ((x Int)) (p x))",
+                "(exists ((x Int)) (p x))",
+                false,
+            ),
+            (
+                "(forall ((x Int)) (p a))",
+                "(forall ((x Int)) (p b))",
+                false,
+            ),
+            (
+                "(forall ((x Int) (y Int)) (= x y))",
+                "(forall ((y Int) (x Int)) (= y x))",
+                true,
+            ),
+            (
+                "(forall ((x Int) (y Int)) (= x y))",
+                "(forall ((x Int) (y Int)) (= y x))",
+                false,
+            ),
+            (
+                "(and (forall ((x Int)) (p x)) (forall ((y Int)) (p y)))",
+                "(and (forall ((z Int)) (p z)) (forall ((z Int)) (p z)))",
+                true,
+            ),
+            (
+                "(forall ((x Int)) (forall ((x Int)) (p x)))",
+                "(forall ((x Int)) (forall ((y Int)) (p y)))",
+                true,
+            ),
+            (
+                "(forall ((x Int)) (and (p x) (forall ((x Int)) (p x))))",
+                "(forall ((y Int)) (and (p y) (forall ((x Int)) (p y))))",
+                false,
+            ),
+            ("(let ((x a)) (p x))", "(let ((y a)) (p y))", true),
+            ("(let ((x a)) (p x))", "(let ((x b)) (p x))", false),
+            (
+                "(forall ((x Int)) (let ((y x)) (p y)))",
+                "(forall ((z Int)) (let ((x z)) (p x)))",
+                true,
+            ),
+        ],
+    );
+
+    // Free variables named like the canonical names must not be confused with bound variables
+    run_tests(
+        "(declare-fun @c0 () Int)
+        (declare-fun @c0' () Int)",
+        &[
+            (
+                "(forall ((x Int)) (= x @c0))",
+                "(forall ((x Int)) (= x x))",
+                false,
+            ),
+            (
+                "(forall ((x Int)) (= x @c0'))",
+                "(forall ((y Int)) (= y @c0'))",
+                true,
+            ),
+            (
+                "(forall ((x Int) (y Int)) (= y @c0))",
+                "(forall ((x Int) (y Int)) (= y x))",
+                false,
+            ),
+        ],
+    );
+}
+
+#[test]
+fn test_map_children() {
+    fn run_tests(definitions: &str, cases: &[(&str, &str)]) {
+        let mut pool = PrimitivePool::new();
+        for &(term, expected) in cases {
+            let [term, expected] = parse_terms(&mut pool, definitions, [term, expected]);
+
+            // Replaces every child that is the constant `1` with the constant `2`
+            let [one, two] = [1, 2].map(|n| pool.add(Term::new_int(n)));
+            let got = term.map_children(&mut pool, |child| {
+                if *child == one {
+                    two.clone()
+                } else {
+                    child.clone()
+                }
+            });
+            assert_eq!(expected, got, "`{term}` was mapped to `{got}`");
+        }
+    }
+    run_tests(
+        "(declare-fun f (Int Int) Int)
+        (declare-fun g () (Array Int Int))
+        (declare-fun x () Int)",
+        &[
+            ("1", "1"),
+            ("x", "x"),
+            ("(+ 1 x 1)", "(+ 2 x 2)"),
+            ("(+ 1 (- 1))", "(+ 2 (- 1))"),
+            ("(f 1 x)", "(f 2 x)"),
+            ("(select g 1)", "(select g 2)"),
+            ("(forall ((y Int)) (= 1 y))", "(forall ((y Int)) (= 1 y))"),
+            ("(lambda ((y Int)) 1)", "(lambda ((y Int)) 2)"),
+            ("(let ((y 1) (z x)) 1)", "(let ((y 2) (z x)) 2)"),
+            ("(let ((y 1)) (+ y 1))", "(let ((y 2)) (+ y 1))"),
+            (
+                "((_ re.^ 1) (str.to_re \"a\"))",
+                "((_ re.^ 1) (str.to_re \"a\"))",
+            ),
+        ],
+    );
+}
+
+#[test]
+fn test_validate_structure() {
+    fn step(command: &mut ProofCommand) -> &mut crate::ast::ProofStep {
+        match command {
+            ProofCommand::Step(s) => s,
+            _ => panic!(),
+        }
+    }
+    fn subproof(command: &mut ProofCommand) -> &mut Vec<ProofCommand> {
+        match command {
+            ProofCommand::Subproof(s) => &mut s.commands,
+            _ => panic!(),
+        }
+    }
+
+    let mut pool = PrimitivePool::new();
+    let proof = parse_proof(
+        &mut pool,
+        "
+        (assume h1 (= 0 1))
+        (anchor :step t2)
+        (assume t2.a0 (= 1 2))
+        (step t2.t1 (cl (= 0 2)) :rule trans :premises (h1 t2.a0))
+        (step t2 (cl (not (= 1 2)) (= 0 2)) :rule subproof :discharge (t2.a0))
+        (step t3 (cl) :rule hole :premises (h1 t2))
+    ",
+    );
+    assert!(proof.validate_structure().is_ok());
+
+    let run_test = |f: &dyn Fn(&mut Vec<ProofCommand>), expected: StructureError| {
+        let mut proof = proof.clone();
+        f(&mut proof.commands);
+        match proof.validate_structure() {
+            Err(crate::Error::Structure(e)) => assert_eq!(expected, e),
+            other => panic!("expected structure error, got {:?}", other),
+        }
+    };
+
+    // Premise refers to the step itself
+    run_test(
+        &|c| step(&mut c[2]).premises = vec![(0, 2)],
+        StructureError::InvalidPremise("t3".into(), 0, 2),
+    );
+
+    // Premise refers to a later step
+    run_test(
+        &|c| step(&mut subproof(&mut c[1])[1]).premises = vec![(1, 2)],
+        StructureError::InvalidPremise("t2.t1".into(), 1, 2),
+    );
+
+    // Premise index is out of bounds
+    run_test(
+        &|c| step(&mut c[2]).premises = vec![(0, 42)],
+        StructureError::InvalidPremise("t3".into(), 0, 42),
+    );
+
+    // Premise refers to a depth that is not open
+    run_test(
+        &|c| step(&mut c[2]).premises = vec![(1, 0)],
+        StructureError::InvalidPremise("t3".into(), 1, 0),
+    );
+
+    // Premise refers to the subproof that encloses the step
+    run_test(
+        &|c| step(&mut subproof(&mut c[1])[1]).premises = vec![(0, 1)],
+        StructureError::InvalidPremise("t2.t1".into(), 0, 1),
+    );
+
+    // Discharged command is out of bounds
+    run_test(
+        &|c| step(&mut subproof(&mut c[1])[2]).discharge = vec![(1, 5)],
+        StructureError::InvalidDischarge("t2".into(), 1, 5),
+    );
+
+    // Discharged command is not an assumption
+    run_test(
+        &|c| step(&mut subproof(&mut c[1])[2]).discharge = vec![(1, 1)],
+        StructureError::DischargeNotAssume("t2".into(), "t2.t1".into()),
+    );
+
+    // Subproof is empty
+    run_test(
+        &|c| subproof(&mut c[1]).clear(),
+        StructureError::EmptySubproof,
+    );
+
+    // Subproof does not end in a step
+    run_test(
+        &|c| {
+            subproof(&mut c[1]).truncate(1);
+        },
+        StructureError::SubproofDoesNotEndInStep("t2.a0".into()),
+    );
+}
+
+#[test]
+fn test_proof_node_round_trip() {
+    fn run_tests(cases: &[&str]) {
+        for proof in cases {
+            let mut pool = PrimitivePool::new();
+            let proof = parse_proof(&mut pool, proof);
+            let node = ProofNode::from_commands(&proof.commands);
+            assert_eq!(proof.commands, node.into_commands());
+        }
+    }
+    run_tests(&[
+        "(assume h1 true)
+        (assume h2 true)
+        (anchor :step t3)
+        (step t3.t1 (cl) :rule rule-name :premises (h1 h2))
+        (step t3.t2 (cl) :rule rule-name :premises (t3.t1 h1 h2))
+        (step t3 (cl) :rule rule-name :premises (h1 t3.t1 h2 t3.t2))",
+        "(assume h1 (= 0 1))
+        (anchor :step t2)
+        (assume t2.a0 (= 1 2))
+        (anchor :step t2.t1)
+        (step t2.t1.t1 (cl (= 0 2)) :rule trans :premises (h1 t2.a0))
+        (step t2.t1 (cl (= 0 2)) :rule rule-name)
+        (step t2 (cl (not (= 1 2)) (= 0 2)) :rule subproof :discharge (t2.a0))
+        (step t3 (cl) :rule rule-name :premises (h1 t2))",
+    ]);
+
+    // Commands that are not used to derive the last command are removed
+    let mut pool = PrimitivePool::new();
+    let proof = parse_proof(
+        &mut pool,
+        "(assume h1 true)
+        (assume h2 false)
+        (step t3 (cl) :rule rule-name :premises (h2))",
+    );
+    let commands = ProofNode::from_commands(&proof.commands).into_commands();
+    let ids: Vec<_> = commands.iter().map(ProofCommand::id).collect();
+    assert_eq!(["h2", "t3"], ids.as_slice());
+}
+
+#[test]
+fn test_proof_node_from_commands_in_arena() {
+    let cases = [
+        "(assume h1 true)
+        (assume h2 true)
+        (anchor :step t3)
+        (step t3.t1 (cl) :rule rule-name :premises (h1 h2))
+        (step t3.t2 (cl) :rule rule-name :premises (t3.t1 h1 h2))
+        (step t3 (cl) :rule rule-name :premises (h1 t3.t1 h2 t3.t2))",
+        "(assume h1 (= 0 1))
+        (assume h2 (= 1 1))
+        (anchor :step t3 :args ((x Int)))
+        (assume t3.a0 (= 1 2))
+        (anchor :step t3.t1)
+        (step t3.t1.t1 (cl (= 0 2)) :rule trans :premises (h1 t3.a0))
+        (step t3.t1 (cl (= 0 2)) :rule rule-name)
+        (step t3 (cl (not (= 1 2)) (= 0 2)) :rule subproof :discharge (t3.a0))
+        (step t4 (cl) :rule rule-name :premises (h1 h2 t3) :args (1 2) :weight 3)",
+    ];
+    for proof in cases {
+        let mut pool = PrimitivePool::new();
+        let proof = parse_proof(&mut pool, proof);
+        let expected = ProofNode::from_commands(&proof.commands);
+        let got = ProofNode::from_commands_in_arena(&proof.commands);
+
+        // The two graphs are made of different `Rc`s, so they are compared through their `Debug`
+        // representations, which include every field of every reachable node
+        assert_eq!(format!("{:?}", expected), format!("{:?}", got));
+        assert_eq!(proof.commands, got.into_commands());
+    }
+
+    // Nodes used by multiple commands are shared, and unused commands are not converted
+    let mut pool = PrimitivePool::new();
+    let proof = parse_proof(
+        &mut pool,
+        "(assume h1 true)
+        (assume h2 false)
+        (step t3 (cl) :rule rule-name :premises (h2))
+        (step t4 (cl) :rule rule-name :premises (h2 t3))",
+    );
+    let root = ProofNode::from_commands_in_arena(&proof.commands);
+    let ProofNode::Step(t4) = root.as_ref() else {
+        panic!();
+    };
+    let ProofNode::Step(t3) = t4.premises[1].as_ref() else {
+        panic!();
+    };
+    assert_eq!(t3.premises[0], t4.premises[0]);
+    let ids: Vec<_> = root
+        .into_commands()
+        .iter()
+        .map(|c| c.id().to_owned())
+        .collect();
+    assert_eq!(["h2", "t3", "t4"], ids.as_slice());
+}
+
+#[test]
+fn test_pretty() {
+    let mut pool = PrimitivePool::new();
+    let [term] = parse_terms(
+        &mut pool,
+        "(declare-fun p () Bool) (declare-fun q () Bool) (declare-fun r () Bool)",
+        ["(and p (and q (and r (and p (and q (and r (forall ((x Int)) (= x x))))))))"],
+    );
+
+    assert_eq!(term.to_string(), term.pretty(200));
+    let expected = "(and
+  p
+  (and
+    q
+    (and
+      r
+      (and
+        p
+        (and
+          q
+          (and
+            r
+            (forall ((x Int))
+              (= x x))))))))";
+    assert_eq!(expected, term.pretty(40));
+    assert!(term.pretty(40).lines().all(|l| l.len() <= 40));
+
+    // The binding list of a binder stays in the first line
+    let [term] = parse_terms(
+        &mut pool,
+        "(declare-fun f (Int Int) Int)",
+        ["(forall ((x Int) (y Int)) (= (f x y) (f y x)))"],
+    );
+    let expected = "(forall ((x Int) (y Int))
+  (= (f x y) (f y x)))";
+    assert_eq!(expected, term.pretty(30));
+}
+
+#[test]
+fn test_write_proof_dialects() {
+    let mut pool = PrimitivePool::new();
+    let proof = parse_proof(
+        &mut pool,
+        "(anchor :step t1 :args ((y Int) (:= (x Int) y)))
+        (step t1.t1 (cl (= x y)) :rule refl)
+        (step t1 (cl (= (forall ((x Int)) (= x 0)) (forall ((y Int)) (= y 0)))) :rule bind)",
+    );
+
+    let print = |dialect| {
+        let mut buf = Vec::new();
+        write_proof(&mut buf, &proof.commands, dialect, false).unwrap();
+        String::from_utf8(buf).unwrap()
+    };
+    let cvc5 = print(Dialect::Cvc5);
+    let verit = print(Dialect::VeriT);
+    assert!(cvc5.starts_with("(anchor :step t1 :args ((y Int) (:= (x Int) y)))"));
+    assert!(verit.starts_with("(anchor :step t1 :args ((y Int) (:= x y)))"));
+
+    for printed in [cvc5, verit] {
+        assert_eq!(proof.commands, parse_proof(&mut pool, &printed).commands);
+    }
+}
+
+#[test]
+fn test_write_proof_subproofs() {
+    let mut pool = PrimitivePool::new();
+    let input = "(assume h1 true)
+(assume h2 true)
+(anchor :step t3 :args ((x Int)))
+  (step t3.t1 (cl) :rule rule-name :premises (h1 h2))
+  (anchor :step t3.t2 :args ((:= (y Int) x)))
+    (step t3.t2.t1 (cl (= y x)) :rule refl)
+  (step t3.t2 (cl) :rule rule-name :premises (t3.t1 h1 h2))
+(step t3 (cl) :rule rule-name :premises (h1 t3.t1 h2 t3.t2))
+";
+    let proof = parse_proof(&mut pool, input);
+
+    let mut buf = Vec::new();
+    write_proof(&mut buf, &proof.commands, Dialect::Cvc5, false).unwrap();
+    let printed = String::from_utf8(buf).unwrap();
+    assert_eq!(input, printed);
+    assert_eq!(proof.commands, parse_proof(&mut pool, &printed).commands);
+
+    // Proofs written one top-level command at a time are indented in the same way
+    let mut streamed = Vec::new();
+    ProofNode::from_commands(&proof.commands)
+        .write_streaming(&mut streamed, Dialect::Cvc5, false)
+        .unwrap();
+    assert_eq!(input, String::from_utf8(streamed).unwrap());
+}
+
+#[test]
+fn test_write_proof_real_formats() {
+    let mut pool = PrimitivePool::new();
+    let [third, minus_two_thirds, two] = [(1, 3), (-2, 3), (2, 1)].map(|(n, d)| {
+        let r = Rational::from((n, d));
+        pool.add(Term::new_real(r))
+    });
+    let term = pool.add(Term::Op(
+        Operator::LessThan,
+        vec![minus_two_thirds, third, two],
+    ));
+    let commands = [ProofCommand::Assume { id: "h1".to_owned(), term }];
+
+    let cases = [
+        (RealFormat::Exact, "(< -2/3 1/3 2.0)"),
+        (RealFormat::Smt, "(< (- (/ 2 3)) (/ 1 3) 2.0)"),
+        (RealFormat::Decimal(3), "(< (- 0.667) 0.333 2.000)"),
+        (RealFormat::Decimal(0), "(< (- 1.0) 0.0 2.0)"),
+    ];
+    for (format, expected) in cases {
+        let mut buf = Vec::new();
+        write_proof_with_real_format(&mut buf, &commands, Dialect::Cvc5, format, false).unwrap();
+        let expected = format!("(assume h1 {})\n", expected);
+        assert_eq!(expected, String::from_utf8(buf).unwrap());
+    }
+}
+
+#[cfg(feature = "serde")]
+mod json {
+    use super::*;
+    use crate::ast::{from_json, node_from_json, node_to_json, to_json, JsonError};
+
+    #[test]
+    fn test_to_json() {
+        let problem = "
+            (declare-fun f (Int) Int)
+            (declare-fun a () Int)
+            (declare-fun p () Bool)
+            (assert p)
+        ";
+        let proof = "
+            (assume h1 (= (f a) (f a)))
+            (anchor :step t2 :args ((x String) (:= (y Int) a)))
+            (step t2.t1 (cl (= x \"s\")) :rule hole)
+            (step t2 (cl (forall ((z Int)) (= z 1))) :rule bind :args ((- 1) (:= k a)))
+            (step t3 (cl) :rule hole :premises (h1 t2))
+        ";
+        let (_, proof, _) =
+            crate::parser::parse_instance(problem.as_bytes(), proof.as_bytes(), Default::default())
+                .unwrap();
+
+        let int = r#"{"type":"sort","sort":{"kind":"int"}}"#;
+        let a = format!(r#"{{"type":"var","name":"a","sort":{}}}"#, int);
+        let fa = format!(
+            r#"{{"type":"app","func":{{"type":"var","name":"f","sort":{{"type":"sort","sort":{{"kind":"function","sorts":[{int},{int}]}}}}}},"args":[{a}]}}"#,
+        );
+        let string = r#"{"type":"sort","sort":{"kind":"string"}}"#;
+        let one = r#"{"type":"const","kind":"int","value":"1"}"#;
+        let expected = [
+            r#"{"premises":[{"type":"var","name":"p","sort":{"type":"sort","sort":{"kind":"bool"}}}],"commands":["#.to_owned(),
+            format!(
+                r#"{{"type":"assume","id":"h1","term":{{"type":"op","op":"=","args":[{},{}]}}}},"#,
+                fa, fa
+            ),
+            format!(
+                r#"{{"type":"subproof","args":[{{"type":"variable","name":"x","sort":{string}}},{{"type":"assign","name":"y","sort":{int},"term":{a}}}],"commands":["#,
+            ),
+            format!(
+                r#"{{"type":"step","id":"t2.t1","clause":[{{"type":"op","op":"=","args":[{{"type":"var","name":"x","sort":{string}}},{{"type":"const","kind":"string","value":"s"}}]}}],"rule":"hole","premises":[],"args":[],"discharge":[]}},"#,
+            ),
+            format!(
+                r#"{{"type":"step","id":"t2","clause":[{{"type":"binder","binder":"forall","bindings":[{{"name":"z","sort":{int}}}],"term":{{"type":"op","op":"=","args":[{{"type":"var","name":"z","sort":{int}}},{one}]}}}}],"rule":"bind","premises":[],"args":[{{"type":"term","term":{{"type":"op","op":"-","args":[{one}]}}}},{{"type":"assign","name":"k","term":{a}}}],"discharge":[]}}]}},"#,
+            ),
+            r#"{"type":"step","id":"t3","clause":[],"rule":"hole","premises":[[0,0],[0,1]],"args":[],"discharge":[]}],"named_terms":[]}"#.to_owned(),
+        ]
+        .concat();
+        assert_eq!(expected, to_json(&proof));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let problem = "
+            (declare-fun f (Int) Int)
+            (declare-fun a () Int)
+            (declare-fun p () Bool)
+            (declare-fun b () (_ BitVec 4))
+            (declare-fun s () (Array Int String))
+            (assert (! p :named q))
+        ";
+        let proof = "
+            (assume h1 (= (f a) (f 1)))
+            (assume h2 (= (select s 1) \"1\"))
+            (anchor :step t3 :args ((x Real) (:= (y Int) a)))
+            (step t3.t1 (cl (= x 1.5)) :rule hole)
+            (step t3 (cl (forall ((z Int)) (let ((w z)) (= w 1)))) :rule bind :args ((- 1) (:= k a)))
+            (step t4 (cl (= b #b1010) (= ((_ extract 1 0) b) #b10) (= \"esc\"\"a\\pe\" \"\")) :rule hole
+                :premises (h1 t3) :weight 3)
+        ";
+        let (_, mut proof, mut pool) =
+            crate::parser::parse_instance(problem.as_bytes(), proof.as_bytes(), Default::default())
+                .unwrap();
+
+        // As if the parser had reordered the literals of `t4`
+        let ProofCommand::Step(t4) = &proof.commands[3] else {
+            panic!("expected step");
+        };
+        let original = t4.clause.iter().rev().cloned().collect();
+        proof.original_clauses.insert(vec![3], original);
+
+        // The integer `1` and the string `"1"` must be written differently
+        let json = to_json(&proof);
+        assert!(json.contains(r#"{"type":"const","kind":"int","value":"1"}"#));
+        assert!(json.contains(r#"{"type":"const","kind":"string","value":"1"}"#));
+
+        let read = from_json(&mut pool, &json).unwrap();
+        assert_eq!(proof.premises, read.premises);
+        assert_eq!(proof.commands, read.commands);
+        assert_eq!(proof.named_terms, read.named_terms);
+        assert_eq!(proof.original_clauses, read.original_clauses);
+        assert_eq!(json, to_json(&read));
+
+        let node = ProofNode::from_commands(&proof.commands);
+        let json = node_to_json(&node);
+        let read = node_from_json(&mut pool, &json).unwrap();
+        assert_eq!(node.into_commands(), read.into_commands());
+
+        assert!(matches!(
+            from_json(&mut pool, r#"{"premises":[],"commands":[]}"#),
+            Err(JsonError::Json(_))
+        ));
+        assert!(matches!(
+            from_json(
+                &mut pool,
+                r#"{"premises":[{"type":"foo"}],"commands":[],"named_terms":[]}"#
+            ),
+            Err(JsonError::Json(_))
+        ));
+        assert!(matches!(
+            from_json(&mut pool, r#"{"premises":[]"#),
+            Err(JsonError::Json(_))
+        ));
+        assert!(matches!(
+            node_from_json(&mut pool, r#"{"commands":[]}"#),
+            Err(JsonError::InvalidValue("commands"))
+        ));
+    }
+
+    #[test]
+    fn test_json_invalid_input() {
+        let mut pool = PrimitivePool::new();
+        let bool_sort = r#"{"type":"sort","sort":{"kind":"bool"}}"#;
+        let p = format!(r#"{{"type":"var","name":"p","sort":{}}}"#, bool_sort);
+        let read = |pool: &mut PrimitivePool, commands: &str| {
+            let json = format!(
+                r#"{{"premises":[],"commands":[{}],"named_terms":[]}}"#,
+                commands
+            );
+            from_json(pool, &json)
+        };
+        let step = |premises: &str| {
+            format!(
+                r#"{{"type":"step","id":"t1","clause":[],"rule":"hole","premises":{},"args":[],"discharge":[]}}"#,
+                premises
+            )
+        };
+        let assume = format!(r#"{{"type":"assume","id":"h1","term":{}}}"#, p);
+
+        let valid = format!("{},{}", assume, step("[[0,0]]"));
+        assert!(read(&mut pool, &valid).is_ok());
+
+        // Premise indices must refer to previous commands, and must fit in a `usize`
+        let cases = [
+            step("[[0,0]]"),
+            format!("{},{}", assume, step("[[0,1]]")),
+            format!("{},{}", assume, step("[[1,0]]")),
+        ];
+        for commands in cases {
+            assert!(matches!(
+                read(&mut pool, &commands),
+                Err(JsonError::Structure(_))
+            ));
+        }
+        for premises in ["[[0,-1]]", "[[0,1.0]]", "[[0,100000000000000000000]]"] {
+            let commands = format!("{},{}", assume, step(premises));
+            assert!(matches!(
+                read(&mut pool, &commands),
+                Err(JsonError::Json(_))
+            ));
+        }
+
+        // Subproofs must end in a step
+        let subproof = format!(r#"{{"type":"subproof","args":[],"commands":[{}]}}"#, assume);
+        assert!(matches!(
+            read(&mut pool, &subproof),
+            Err(JsonError::Structure(_))
+        ));
+
+        // Terms must be well sorted
+        let one = r#"{"type":"const","kind":"int","value":"1"}"#;
+        let cases = [
+            format!(r#"{{"type":"op","op":"not","args":[{}]}}"#, one),
+            format!(r#"{{"type":"var","name":"x","sort":{}}}"#, one),
+            format!(r#"{{"type":"app","func":{},"args":[{}]}}"#, p, one),
+        ];
+        for term in cases {
+            let commands = format!(r#"{{"type":"assume","id":"h1","term":{}}}"#, term);
+            assert!(read(&mut pool, &commands).is_err());
+        }
+
+        // Strings with unpaired surrogates are rejected
+        let commands = format!(r#"{{"type":"assume","id":"\ud800","term":{}}}"#, p);
+        assert!(matches!(
+            read(&mut pool, &commands),
+            Err(JsonError::Json(_))
+        ));
+
+        // Deeply nested input is rejected instead of overflowing the stack
+        let depth = 100_000;
+        let term = format!(
+            "{}{}{}",
+            r#"{"type":"op","op":"not","args":["#.repeat(depth),
+            p,
+            "]}".repeat(depth)
+        );
+        let commands = format!(r#"{{"type":"assume","id":"h1","term":{}}}"#, term);
+        assert!(matches!(
+            read(&mut pool, &commands),
+            Err(JsonError::Json(_))
+        ));
+    }
+}
+
+#[test]
+fn test_polyeq_shared_terms() {
+    // Builds two terms of the form `(= t t)` nested to the given depth, that only differ in their
+    // innermost term. Since subterms are shared, each term has only `depth + 1` distinct subterms,
+    // but a comparison that doesn't cache its results would take time exponential in the depth
+    fn build(pool: &mut PrimitivePool, leaf: Rc<Term>, depth: usize) -> Rc<Term> {
+        (0..depth).fold(leaf, |t, _| {
+            pool.add(Term::Op(Operator::Equals, vec![t.clone(), t]))
+        })
+    }
+
+    let mut pool = PrimitivePool::new();
+    let definitions = "(declare-fun a () Int) (declare-fun b () Int)";
+    let [a, b, a_eq_b, b_eq_a] =
+        parse_terms(&mut pool, definitions, ["a", "b", "(= a b)", "(= b a)"]);
+
+    let mut comp = PolyeqComparator::new(true, false, false);
+    let [x, y] = [a, b].map(|leaf| build(&mut pool, leaf, 40));
+    assert!(!Polyeq::eq(&mut comp, &x, &y));
+
+    let mut comp = PolyeqComparator::new(true, true, false);
+    let [x, y] = [a_eq_b, b_eq_a].map(|leaf| build(&mut pool, leaf, 40));
+    assert!(Polyeq::eq(&mut comp, &x, &y));
+}
+
+#[test]
+fn test_polyeq_cache_across_binders() {
+    let mut pool = PrimitivePool::new();
+    let definitions = "(declare-fun x () Int) (declare-fun y () Int)";
+    let [x_lt_y, y_lt_x, a, b] = parse_terms(
+        &mut pool,
+        definitions,
+        [
+            "(< x y)",
+            "(< y x)",
+            "(forall ((x Int) (y Int)) (< x y))",
+            "(forall ((y Int) (x Int)) (< y x))",
+        ],
+    );
+
+    // Outside of a binder, `(< x y)` and `(< y x)` are different, but inside the quantifiers they
+    // are alpha-equivalent. The result of the first comparison must not be reused in the second
+    let mut comp = PolyeqComparator::new(false, true, false);
+    assert!(!Polyeq::eq(&mut comp, &x_lt_y, &y_lt_x));
+    assert!(Polyeq::eq(&mut comp, &a, &b));
+
+    // The same holds in the opposite direction, for results cached inside the binder
+    assert!(!Polyeq::eq(&mut comp, &x_lt_y, &y_lt_x));
+}
+
+#[test]
+fn test_operator_symbol_round_trip() {
+    let mut seen = std::collections::HashSet::new();
+    for &op in Operator::ALL {
+        let symbol = op.to_symbol();
+        assert!(seen.insert(symbol), "symbol '{}' is used twice", symbol);
+        assert_eq!(Some(op), Operator::from_symbol(symbol));
+        assert_eq!(Ok(op), symbol.parse());
+        assert_eq!(symbol, op.to_string());
+    }
+    assert_eq!(None, Operator::from_symbol("not-an-operator"));
+}
+
+#[test]
+fn test_operator_signature() {
+    use crate::ast::{ArgSorts, Arity, SortKind};
+
+    let not = Operator::Not.signature();
+    assert_eq!(Arity::Fixed(1), not.arity);
+    assert_eq!(ArgSorts::Each(SortKind::Bool), not.args);
+    assert_eq!(Some(SortKind::Bool), not.result);
+
+    let and = Operator::And.signature();
+    assert_eq!(Arity::AtLeast(2), and.arity);
+    assert_eq!(ArgSorts::Each(SortKind::Bool), and.args);
+    assert_eq!(Some(SortKind::Bool), and.result);
+
+    let OperatorSignature { arity, args, result } = Operator::Mod.signature();
+    assert_eq!(Arity::Fixed(2), arity);
+    assert_eq!(ArgSorts::Positional(&[SortKind::Int, SortKind::Int]), args);
+    assert_eq!(Some(SortKind::Int), result);
+
+    // Every positional signature must list a sort for each argument
+    for &op in Operator::ALL {
+        if let ArgSorts::Positional(kinds) = op.signature().args {
+            assert_eq!(Arity::Fixed(kinds.len()), op.signature().arity, "{}", op);
+        }
+    }
+}
+
+#[test]
+fn test_term_free_vars() {
+    let mut pool = PrimitivePool::new();
+    let [term, y] = parse_terms(
+        &mut pool,
+        "(declare-fun y () Int)",
+        ["(forall ((x Int)) (> x y))", "y"],
+    );
+    let expected: IndexSet<_> = [y].into_iter().collect();
+    assert_eq!(expected, term.free_vars(&mut pool));
+}
+
+#[test]
+fn test_term_sorts() {
+    let definitions = "
+        (declare-fun a () Int)
+        (declare-fun r () Real)
+        (declare-fun f (Int) Real)
+        (declare-fun p () Bool)
+    ";
+    let cases = [
+        ("(+ 1 2)", "Int"),
+        ("(= 1 2)", "Bool"),
+        ("(f (+ a 1))", "Real"),
+        ("(forall ((x Int)) (> x a))", "Bool"),
+        ("(exists ((x Real)) (= x r))", "Bool"),
+        ("(ite p r 1.0)", "Real"),
+        ("(let ((x a)) (* x x))", "Int"),
+        ("(choice ((x Int)) (> x 0))", "Int"),
+    ];
+
+    let mut pool = PrimitivePool::new();
+    for (term, sort) in cases {
+        let [term] = parse_terms(&mut pool, definitions, [term]);
+        assert_eq!(sort, pool.sort(&term).to_string());
+    }
+
+    let [lambda] = parse_terms(
+        &mut pool,
+        definitions,
+        ["(lambda ((x Int) (y Real)) (f x))"],
+    );
+    let [int_sort, real_sort] = [Sort::Int, Sort::Real].map(|s| pool.add(Term::Sort(s)));
+    let expected = Sort::Function(vec![int_sort, real_sort.clone(), real_sort]);
+    assert_eq!(expected, *pool.sort(&lambda).as_sort().unwrap());
+}
+
+#[test]
+fn test_to_nnf() {
+    let definitions = "
+        (declare-fun p () Bool)
+        (declare-fun q () Bool)
+        (declare-fun r () Bool)
+        (declare-fun P (Int) Bool)
+        (declare-fun a () Int)
+    ";
+    let cases = [
+        ("(not (and p (=> q r)))", "(or (not p) (and q (not r)))"),
+        ("(not (not p))", "p"),
+        ("(not (not (not p)))", "(not p)"),
+        ("(=> p q r)", "(or (not p) (not q) r)"),
+        ("(not (or p (not q)))", "(and (not p) q)"),
+        ("(not true)", "false"),
+        ("(ite p q r)", "(and (or (not p) q) (or p r))"),
+        (
+            "(not (ite p q r))",
+            "(and (or (not p) (not q)) (or p (not r)))",
+        ),
+        (
+            "(not (forall ((x Int)) (=> (P x) (exists ((y Int)) (P y)))))",
+            "(exists ((x Int)) (and (P x) (forall ((y Int)) (not (P y)))))",
+        ),
+        ("(not (= p (not q)))", "(not (= p (not q)))"),
+        ("(= a (ite p 1 2))", "(= a (ite p 1 2))"),
+    ];
+
+    let mut pool = PrimitivePool::new();
+    for (original, expected) in cases {
+        let [original, expected] = parse_terms(&mut pool, definitions, [original, expected]);
+        let got = pool.to_nnf(&original);
+        assert_eq!(expected, got, "{} != {}", expected, got);
+
+        // Converting a term that is already in NNF does nothing
+        assert_eq!(got, pool.to_nnf(&got));
+    }
+}
+
+#[test]
+fn test_fold_constants() {
+    let definitions = "
+        (declare-fun x () Int)
+        (declare-fun r () Real)
+    ";
+    let cases = [
+        ("(* 2 (+ 1 2))", "6"),
+        ("(+ x 1)", "(+ x 1)"),
+        ("(+ x (* 2 3))", "(+ x 6)"),
+        ("(< x (- 10 (* 3 4)))", "(< x (- 2))"),
+        ("(- (- 3))", "3"),
+        ("(div 7 2)", "3"),
+        ("(div (- 7) 2)", "(- 4)"),
+        ("(+ r (/ 1.0 4.0))", "(+ r 0.25)"),
+        ("(div x 0)", "(div x 0)"),
+        ("(div 1 0)", "(div 1 0)"),
+        ("(/ 1.0 0.0)", "(/ 1.0 0.0)"),
+    ];
+
+    let mut pool = PrimitivePool::new();
+    for (original, expected) in cases {
+        let [original, expected] = parse_terms(&mut pool, definitions, [original, expected]);
+        let got = pool.fold_constants(&original);
+        assert_eq!(expected, got, "{expected} != {got}");
+    }
+}
+
+#[test]
+fn test_canonicalize_ac() {
+    let definitions = "
+        (declare-fun a () Int)
+        (declare-fun b () Int)
+        (declare-fun c () Int)
+        (declare-fun p () Bool)
+        (declare-fun q () Bool)
+        (declare-fun r () Bool)
+    ";
+    let equal = [
+        ("(+ a (+ b c))", "(+ c (+ a b))"),
+        ("(+ (+ a b) c)", "(+ a b c)"),
+        ("(* a (* b c))", "(* (* c b) a)"),
+        ("(and p (and q r))", "(and r q p)"),
+        ("(or (or p q) (not (or r p)))", "(or (not (or p r)) q p)"),
+        ("(= (+ a b) (* b c))", "(= (+ b a) (* c b))"),
+        (
+            "(forall ((x Int)) (> (+ x a) b))",
+            "(forall ((x Int)) (> (+ a x) b))",
+        ),
+    ];
+    let different = [
+        ("(- a (- b c))", "(- (- a b) c)"),
+        ("(- a b)", "(- b a)"),
+        ("(+ a (* b c))", "(+ (+ a b) c)"),
+        ("(and p (or q r))", "(and p q r)"),
+    ];
+
+    let mut pool = PrimitivePool::new();
+    for (a, b) in equal {
+        let [a, b] = parse_terms(&mut pool, definitions, [a, b]);
+        let (a, b) = (pool.canonicalize_ac(&a), pool.canonicalize_ac(&b));
+        assert_eq!(a, b, "{a} != {b}");
+    }
+    for (a, b) in different {
+        let [a, b] = parse_terms(&mut pool, definitions, [a, b]);
+        let (a, b) = (pool.canonicalize_ac(&a), pool.canonicalize_ac(&b));
+        assert_ne!(a, b, "{a} == {b}");
+    }
+
+    // Flattening keeps all arguments, even repeated ones
+    let [term] = parse_terms(&mut pool, definitions, ["(+ a (+ a b))"]);
+    let got = pool.canonicalize_ac(&term);
+    assert!(matches!(got.as_ref(), Term::Op(Operator::Add, args) if args.len() == 3));
+
+    // The arguments are ordered by their structural hashes, so the order is the same in different
+    // pools, no matter in what order the terms were added
+    let mut other = PrimitivePool::new();
+    let [a] = parse_terms(&mut pool, definitions, ["(or (and p q r) (+ a b c))"]);
+    let [b] = parse_terms(&mut other, definitions, ["(or (+ c b a) (and r q p))"]);
+    let (a, b) = (pool.canonicalize_ac(&a), other.canonicalize_ac(&b));
+    assert_eq!(a.to_string(), b.to_string());
+}
+
+#[test]
+fn test_linear_combination() {
+    let mut pool = PrimitivePool::new();
+    let [term, x, y, nonlinear] = parse_terms(
+        &mut pool,
+        "(declare-fun x () Int) (declare-fun y () Int)",
+        ["(+ (* 2 x) (- y) 3)", "x", "y", "(+ x (* x y))"],
+    );
+
+    let got = LinearCombination::from_term(&mut pool, &term).unwrap();
+    let expected: IndexMap<_, _> = [(x, Rational::from(2)), (y, Rational::from(-1))]
+        .into_iter()
+        .collect();
+    assert_eq!(expected, got.coefficients);
+    assert_eq!(Rational::from(3), got.constant);
+    assert!(got.is_integer);
+    assert_eq!(term, got.to_term(&mut pool));
+
+    // Coefficients of the same term are collected, and removed if they add up to zero
+    let [term, expected] = parse_terms(
+        &mut pool,
+        "(declare-fun x () Real) (declare-fun y () Real)",
+        [
+            "(- (* 2.0 (+ x y 1.0)) (* y 2.0) (/ 1.0 2.0))",
+            "(+ (* 2.0 x) 1.5)",
+        ],
+    );
+    let got = LinearCombination::from_term(&mut pool, &term).unwrap();
+    assert!(!got.is_integer);
+    assert_eq!(expected, got.to_term(&mut pool));
+
+    assert_eq!(None, LinearCombination::from_term(&mut pool, &nonlinear));
+    let not_arithmetic = pool.bool_true();
+    assert_eq!(
+        None,
+        LinearCombination::from_term(&mut pool, &not_arithmetic)
+    );
+}
+
+#[test]
+fn test_add_terms_batch() {
+    fn vars(sort: &Rc<Term>, n: usize) -> impl Iterator<Item = Term> + '_ {
+        (0..n).map(move |i| Term::new_var(format!("a{i}"), sort.clone()))
+    }
+
+    let mut pool = PrimitivePool::new();
+    let int_sort = pool.add(Term::Sort(Sort::Int));
+
+    // The batch may contain terms that are already in the pool, as well as repeated terms
+    let first = pool.add(vars(&int_sort, 1).next().unwrap());
+    let batch = pool.add_terms_batch(vars(&int_sort, 10_000).chain(vars(&int_sort, 10)).collect());
+    assert_eq!(10_010, batch.len());
+    assert_eq!(first, batch[0]);
+    assert_eq!(batch[..10], batch[10_000..]);
+
+    let sequential: Vec<_> = vars(&int_sort, 10_000).map(|t| pool.add(t)).collect();
+    assert_eq!(sequential, batch[..10_000]);
+    for t in &batch {
+        assert_eq!(int_sort, pool.sort(t));
+    }
+
+    let app = Term::Op(Operator::Add, batch[..10_000].to_vec());
+    let [batch_app] = pool.add_terms_batch(vec![app.clone()]).try_into().unwrap();
+    assert_eq!(pool.add(app), batch_app);
+}
+
+#[test]
+fn test_pool_import() {
+    let definitions = "
+        (declare-sort U 0)
+        (declare-fun f (U) Int)
+        (declare-fun a () U)
+    ";
+    let mut scratch = PrimitivePool::new();
+    let [term] = parse_terms(
+        &mut scratch,
+        definitions,
+        ["(forall ((x U)) (= (+ (f x) (f a)) (let ((y (f a))) y)))"],
+    );
+
+    let mut main = PrimitivePool::new();
+    let [existing] = parse_terms(&mut main, definitions, ["(f a)"]);
+    let imported = main.import(&term, &scratch);
+    assert_ne!(term, imported);
+    assert_eq!(format!("{:#}", term), format!("{:#}", imported));
+
+    // Subterms that were already in the main pool are reused, and importing again gives the same
+    // term
+    let [expected] = parse_terms(
+        &mut main,
+        definitions,
+        ["(forall ((x U)) (= (+ (f x) (f a)) (let ((y (f a))) y)))"],
+    );
+    assert_eq!(expected, imported);
+    assert_eq!(imported, main.import(&term, &scratch));
+    let Term::Binder(_, _, body) = imported.as_ref() else {
+        unreachable!()
+    };
+    assert!(body.as_op().unwrap().1[0]
+        .as_op()
+        .unwrap()
+        .1
+        .contains(&existing));
+}
+
+#[test]
+fn test_pool_term_info() {
+    let definitions = "
+        (declare-fun x () Int)
+        (declare-fun y () Int)
+        (declare-fun P (Int) Bool)
+    ";
+    let terms = [
+        "(+ x (* y x))",
+        "(+ x (* y y))",
+        "(+ x 1)",
+        "(forall ((z Int)) (P (+ z x)))",
+    ];
+    let mut first = PrimitivePool::new();
+    let a = parse_terms(&mut first, definitions, terms);
+
+    // Terms are added to the second pool in a different order, so they have different addresses
+    let mut second = PrimitivePool::new();
+    parse_terms(&mut second, definitions, ["(* y y)", "(P 1)"]);
+    let b = parse_terms(&mut second, definitions, terms);
+
+    // The information is only computed when it is first requested
+    assert!(first.info_cache.is_empty());
+
+    // Sizes count every occurrence of a subterm, but not sorts
+    let sizes: Vec<_> = a.iter().map(|t| first.term_size(t)).collect();
+    assert_eq!([5, 5, 3, 6].as_slice(), sizes);
+
+    for (x, y) in a.iter().zip(&b) {
+        assert_eq!(first.term_info(x), second.term_info(y));
+        assert!(first.structurally_equal(x, &mut second, y));
+    }
+    for (i, j) in [(0, 1), (1, 2), (2, 3)] {
+        assert!(!first.structurally_equal(&a[i], &mut second, &b[j]));
+    }
+
+    // If the hashes don't match, the terms are rejected without descending into them
+    let mut descents = 0;
+    assert!(!first.structurally_equal_counting(&a[0], &mut second, &b[1], &mut descents));
+    assert_eq!(0, descents);
+
+    // Otherwise, each distinct pair of subterms is compared once. For the first term, these are
+    // the term itself, `x`, `Int`, `(* y x)` and `y`
+    let mut descents = 0;
+    assert!(first.structurally_equal_counting(&a[0], &mut second, &b[0], &mut descents));
+    assert_eq!(5, descents);
+}
+
+#[test]
+fn test_pool_structural_key() {
+    use std::collections::HashMap;
+
+    let definitions = "(declare-fun x () Int) (declare-fun y () Int)";
+    let mut first = PrimitivePool::new();
+    let [a, b] = parse_terms(&mut first, definitions, ["(+ x (* y x))", "(+ x y)"]);
+    let mut second = PrimitivePool::new();
+    parse_terms(&mut second, definitions, ["(* y y)"]);
+    let [c] = parse_terms(&mut second, definitions, ["(+ x (* y x))"]);
+    assert_ne!(a, c);
+
+    let keys = [
+        first.structural_key(&a),
+        first.structural_key(&a),
+        second.structural_key(&c),
+        first.structural_key(&b),
+    ];
+    let mut by_reference: HashMap<Rc<Term>, usize> = HashMap::new();
+    let mut by_structure: HashMap<_, usize> = HashMap::new();
+    for key in keys {
+        *by_reference.entry(key.term().clone()).or_default() += 1;
+        *by_structure.entry(key).or_default() += 1;
+    }
+    assert_eq!(3, by_reference.len());
+    assert_eq!(2, by_structure.len());
+    assert_eq!(3, by_structure[&second.structural_key(&c)]);
+    assert_eq!(1, by_structure[&first.structural_key(&b)]);
+    assert_eq!(&a, first.structural_key(&a).term());
+}
+
+#[test]
+fn test_proof_node_validate_depths() {
+    let mut pool = PrimitivePool::new();
+    let proof = parse_proof(
+        &mut pool,
+        "(assume h1 (= 0 1))
+        (anchor :step t2)
+        (assume t2.a0 (= 1 2))
+        (step t2.t1 (cl (= 0 2)) :rule trans :premises (h1 t2.a0))
+        (step t2 (cl (not (= 1 2)) (= 0 2)) :rule subproof :discharge (t2.a0))
+        (step t3 (cl) :rule rule-name :premises (h1 t2))",
+    );
+    let root = ProofNode::from_commands(&proof.commands);
+    assert_eq!(Ok(()), root.validate_depths());
+
+    let ProofNode::Step(t3) = root.as_ref() else {
+        unreachable!()
+    };
+    let subproof = t3.premises[1].clone();
+    let ProofNode::Subproof(t2) = subproof.as_ref() else {
+        unreachable!()
+    };
+    let ProofNode::Step(t2_end) = t2.last_step.as_ref() else {
+        unreachable!()
+    };
+    let t2_t1 = t2_end.previous_step.clone().unwrap();
+
+    // A step outside the subproof can't use a step inside it
+    let mut corrupted = t3.clone();
+    corrupted.premises.push(t2_t1.clone());
+    assert_eq!(
+        Err(DepthError::InvalidPremise {
+            step: "t3".into(),
+            depth: 0,
+            premise: "t2.t1".into(),
+            premise_depth: 1,
+        }),
+        ProofNode::Step(corrupted).validate_depths()
+    );
+
+    // A step can only discharge assumptions in its own subproof
+    let mut corrupted = t3.clone();
+    corrupted.discharge.push(t3.premises[0].clone());
+    corrupted.discharge.push(t2_end.discharge[0].clone());
+    assert_eq!(
+        Err(DepthError::InvalidDischarge {
+            step: "t3".into(),
+            depth: 0,
+            discharged: "t2.a0".into(),
+            discharged_depth: 1,
+        }),
+        ProofNode::Step(corrupted).validate_depths()
+    );
+
+    // The last step of a subproof must be inside it
+    let mut corrupted = t2_end.clone();
+    corrupted.depth = 0;
+    let mut corrupted_subproof = t2.clone();
+    corrupted_subproof.last_step = Rc::new(ProofNode::Step(corrupted));
+    let mut corrupted_root = t3.clone();
+    corrupted_root.premises[1] = Rc::new(ProofNode::Subproof(corrupted_subproof));
+    assert_eq!(
+        Err(DepthError::SubproofAtDepthZero("t2".into())),
+        ProofNode::Step(corrupted_root).validate_depths()
+    );
+}
+
+#[test]
+fn test_proof_node_validate_discharges() {
+    fn validate(pool: &mut PrimitivePool, discharge: &str) -> Result<(), DischargeError> {
+        let proof = parse_proof(
+            pool,
+            &format!(
+                "(assume h1 (= 0 1))
+                (anchor :step t2)
+                (assume t2.a0 (= 1 2))
+                (step t2.t1 (cl (= 0 2)) :rule trans :premises (h1 t2.a0))
+                (step t2 (cl (not (= 1 2)) (= 0 2)) :rule subproof {discharge})
+                (step t3 (cl) :rule rule-name :premises (h1 t2))"
+            ),
+        );
+        ProofNode::from_commands(&proof.commands).validate_discharges()
+    }
+
+    let mut pool = PrimitivePool::new();
+    assert_eq!(Ok(()), validate(&mut pool, ":discharge (t2.a0)"));
+    assert_eq!(
+        Err(DischargeError::UndischargedAssumption {
+            subproof: "t2".into(),
+            assumption: "t2.a0".into(),
+        }),
+        validate(&mut pool, "")
+    );
+    assert_eq!(
+        Err(DischargeError::NotLocal {
+            step: "t2".into(),
+            discharged: "h1".into(),
+        }),
+        validate(&mut pool, ":discharge (t2.a0 h1)")
+    );
+    assert_eq!(
+        Err(DischargeError::NotAnAssumption {
+            step: "t2".into(),
+            discharged: "t2.t1".into(),
+        }),
+        validate(&mut pool, ":discharge (t2.a0 t2.t1)")
+    );
+}
+
+#[test]
+fn test_proof_node_check_acyclic() {
+    let mut pool = PrimitivePool::new();
+    let proof = parse_proof(
+        &mut pool,
+        "(assume h1 (= 0 1))
+        (anchor :step t2)
+        (assume t2.a0 (= 1 2))
+        (step t2.t1 (cl (= 0 2)) :rule trans :premises (h1 t2.a0))
+        (step t2 (cl (not (= 1 2)) (= 0 2)) :rule subproof :discharge (t2.a0))
+        (step t3 (cl) :rule rule-name :premises (h1 t2))",
+    );
+    assert_eq!(
+        Ok(()),
+        ProofNode::from_commands(&proof.commands).check_acyclic()
+    );
+
+    // Since cyclic graphs can't be built from `Rc`s, the search itself is tested on a graph given
+    // by its adjacency lists
+    let find = |edges: &[&[usize]]| find_cycle(vec![0], |&n| edges[n].to_vec());
+    assert_eq!(None, find(&[&[1, 2], &[3], &[3], &[]]));
+    assert_eq!(Some(vec![0, 0]), find(&[&[0]]));
+    assert_eq!(Some(vec![1, 3, 2, 1]), find(&[&[1], &[3], &[1], &[2]]));
+    assert_eq!(Some(vec![2, 4, 2]), find(&[&[1, 2], &[3], &[4], &[], &[2]]));
+
+    let error = CyclicProofError(vec!["t1".into(), "t2".into(), "t1".into()]);
+    assert_eq!(
+        "proof graph contains a cycle: t1 -> t2 -> t1",
+        error.to_string()
+    );
+}
+
+#[test]
+fn test_proof_node_write_streaming() {
+    let mut pool = PrimitivePool::new();
+    let proof = parse_proof(
+        &mut pool,
+        "(assume h1 (= 0 1))
+        (assume h2 (= 1 1))
+        (anchor :step t3)
+        (assume t3.a0 (= 1 2))
+        (anchor :step t3.t1)
+        (step t3.t1.t1 (cl (= 0 2)) :rule trans :premises (h1 t3.a0))
+        (step t3.t1 (cl (= 0 2)) :rule rule-name :premises (t3.t1.t1 h2))
+        (step t3 (cl (not (= 1 2)) (= 0 2)) :rule subproof :discharge (t3.a0))
+        (step t4 (cl) :rule rule-name :premises (h1 t3 h2))",
+    );
+    let node = ProofNode::from_commands(&proof.commands);
+
+    for (dialect, use_sharing) in [(Dialect::Cvc5, false), (Dialect::VeriT, true)] {
+        let mut expected = Vec::new();
+        write_proof(&mut expected, &node.into_commands(), dialect, use_sharing).unwrap();
+
+        let mut streamed = Vec::new();
+        node.write_streaming(&mut streamed, dialect, use_sharing)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(expected).unwrap(),
+            String::from_utf8(streamed).unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_proof_node_to_dot() {
+    let mut pool = PrimitivePool::new();
+    let proof = parse_proof(
+        &mut pool,
+        "(assume h1 (= 0 1))
+        (assume h2 (= 1 1))
+        (anchor :step t3)
+        (assume t3.a0 (= 1 2))
+        (anchor :step t3.t1)
+        (step t3.t1.t1 (cl (= 0 2)) :rule trans :premises (h1 t3.a0))
+        (step t3.t1 (cl (= 0 2)) :rule rule-name :premises (t3.t1.t1 h2))
+        (step t3 (cl (not (= 1 2)) (= 0 2)) :rule subproof :discharge (t3.a0))
+        (step t4 (cl) :rule rule-name :premises (h1 t3 h2))",
+    );
+    let node = ProofNode::from_commands(&proof.commands);
+    let mut output = Vec::new();
+    node.to_dot(&mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    // `h1` and `h2` are used by multiple steps, but appear only once
+    let count = |pattern: &str| output.lines().filter(|l| l.contains(pattern)).count();
+    assert_eq!(7, count("[label="));
+    assert_eq!(1, count("[label=\"h1\\nassume\"]"));
+    assert_eq!(1, count("[label=\"t3.t1.t1\\ntrans\"]"));
+    assert_eq!(2, count("subgraph cluster_"));
+    assert_eq!(10, count("->"));
+    assert_eq!(1, count("[style=dashed]"));
+    assert_eq!(2, count("[style=dotted]"));
+}
+
+#[test]
+fn test_dedup_assumptions() {
+    let mut pool = PrimitivePool::new();
+    let mut proof = parse_proof(
+        &mut pool,
+        "(assume h1 true)
+        (assume h2 true)
+        (assume h3 false)
+        (anchor :step t4)
+        (assume t4.a0 false)
+        (assume t4.a1 false)
+        (step t4.t1 (cl) :rule rule-name :premises (h2 h3 t4.a1))
+        (step t4 (cl) :rule rule-name :premises (h1 t4.t1 h2))
+        (step t5 (cl) :rule rule-name :premises (h3 t4 h2))",
+    );
+    let expected = parse_proof(
+        &mut pool,
+        "(assume h1 true)
+        (assume h3 false)
+        (anchor :step t4)
+        (assume t4.a0 false)
+        (assume t4.a1 false)
+        (step t4.t1 (cl) :rule rule-name :premises (h1 h3 t4.a1))
+        (step t4 (cl) :rule rule-name :premises (h1 t4.t1 h1))
+        (step t5 (cl) :rule rule-name :premises (h3 t4 h1))",
+    );
+
+    // Only top-level assumptions are merged
+    assert_eq!(1, proof.dedup_assumptions());
+    assert_eq!(expected.commands, proof.commands);
+    assert_eq!(0, proof.dedup_assumptions());
+}
+
+#[test]
+fn test_proof_metrics() {
+    let mut pool = PrimitivePool::new();
+    let proof = parse_proof(
+        &mut pool,
+        "(assume h1 true)
+        (assume h2 true)
+        (anchor :step t3)
+        (step t3.t1 (cl) :rule rule-name :premises (h1 h2))
+        (step t3.t2 (cl) :rule rule-name :premises (t3.t1 h1 h2))
+        (step t3 (cl) :rule rule-name :premises (h1 t3.t1 h2 t3.t2))",
+    );
+    let expected = ProofMetrics {
+        num_steps: 3,
+        num_assumptions: 2,
+        num_subproofs: 1,
+        max_depth: 1,
+        critical_path: 4,
+    };
+    assert_eq!(expected, proof.metrics());
+
+    let proof = parse_proof(
+        &mut pool,
+        "(assume h1 (= 0 1))
+        (anchor :step t2)
+        (assume t2.a0 (= 1 2))
+        (anchor :step t2.t1)
+        (step t2.t1.t1 (cl (= 0 2)) :rule trans :premises (h1 t2.a0))
+        (step t2.t1 (cl (= 0 2)) :rule rule-name :premises (t2.t1.t1))
+        (step t2 (cl (not (= 1 2)) (= 0 2)) :rule subproof :discharge (t2.a0))
+        (step t3 (cl) :rule rule-name :premises (h1 t2))",
+    );
+    // The last step of each subproof implicitly depends on the command before it, so the longest
+    // chain is `h1`, `t2.t1.t1`, `t2.t1`, `t2`, `t3`
+    let expected = ProofMetrics {
+        num_steps: 4,
+        num_assumptions: 2,
+        num_subproofs: 2,
+        max_depth: 2,
+        critical_path: 5,
+    };
+    assert_eq!(expected, proof.metrics());
+}
+
+#[test]
+fn test_prune_unused() {
+    use crate::checker;
+
+    let (prelude, proof, mut pool) = parser::parse_instance(
+        "(declare-fun p () Bool)
+        (declare-fun q () Bool)
+        (assert p)
+        (assert (not p))"
+            .as_bytes(),
+        "(assume h1 p)
+        (assume h2 (not p))
+        (step t3 (cl q (not q)) :rule hole)
+        (step t4 (cl) :rule resolution :premises (h1 h2))"
+            .as_bytes(),
+        parser::Config::new(),
+    )
+    .unwrap();
+
+    let pruned = proof.prune_unused().unwrap();
+    let ids: Vec<_> = pruned.commands.iter().map(ProofCommand::id).collect();
+    assert_eq!(ids, ["h1", "h2", "t4"]);
+    assert_eq!(pruned.premises, proof.premises);
+
+    let mut checker = checker::ProofChecker::new(&mut pool, checker::Config::new(), &prelude);
+    assert!(checker.check(&pruned).is_ok());
+
+    // A premise that doesn't refer to a previous command is an error, instead of a panic
+    let mut malformed = proof.clone();
+    let ProofCommand::Step(s) = &mut malformed.commands[3] else {
+        unreachable!();
+    };
+    s.premises.push((0, 3));
+    assert!(matches!(
+        malformed.prune_unused(),
+        Err(crate::Error::Structure(StructureError::InvalidPremise(id, 0, 3))) if id == "t4"
+    ));
+}
+
+#[test]
+fn test_rules_used() {
+    let problem = "
+        (declare-fun p () Bool)
+        (declare-fun q () Bool)
+        (declare-fun r () Bool)
+        (assert p)
+    ";
+    let proof = "
+        (assume h1 p)
+        (anchor :step t2)
+        (assume t2.h1 p)
+        (step t2.t2 (cl (not p) q) :rule hole)
+        (step t2.t3 (cl (not q) r) :rule hole)
+        (step t2.t4 (cl r) :rule resolution :premises (t2.h1 t2.t2 t2.t3) :args (p true q true))
+        (step t2 (cl (not p) r) :rule subproof :discharge (t2.h1))
+        (step t3 (cl (not r)) :rule hole)
+        (step t4 (cl) :rule resolution :premises (h1 t2 t3) :args (p true r true))
+    ";
+    let (_, proof, _) =
+        parser::parse_instance(problem.as_bytes(), proof.as_bytes(), parser::Config::new())
+            .unwrap();
+
+    let expected: IndexSet<String> = ["hole", "resolution", "subproof"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let got = proof.rules_used();
+    assert_eq!(expected, got);
+    assert!(expected.iter().eq(got.iter()));
+
+    let root = ProofNode::from_commands(&proof.commands);
+    assert_eq!(expected, root.rules_used());
+}
+
+#[test]
+fn test_proof_visitor() {
+    #[derive(Default)]
+    struct Counter {
+        assumes: usize,
+        steps: Vec<String>,
+        open_subproofs: usize,
+        max_open_subproofs: usize,
+    }
+
+    impl ProofVisitor for Counter {
+        fn visit_assume(&mut self, _: &str, _: usize, _: &Rc<Term>) {
+            self.assumes += 1;
+        }
+
+        fn visit_step(&mut self, step: &StepNode) {
+            assert_eq!(self.open_subproofs, step.depth);
+            self.steps.push(step.id.clone());
+        }
+
+        fn visit_subproof(&mut self, _: &SubproofNode) {
+            self.open_subproofs += 1;
+            self.max_open_subproofs = self.max_open_subproofs.max(self.open_subproofs);
+        }
+
+        fn end_subproof(&mut self, _: &SubproofNode) {
+            self.open_subproofs -= 1;
+        }
+    }
+
+    let mut pool = PrimitivePool::new();
+    let proof = parse_proof(
+        &mut pool,
+        "(assume h1 (= 0 1))
+        (assume h2 (= 1 2))
+        (assume h3 (= 2 3))
+        (step t4 (cl (= 0 2)) :rule trans :premises (h1 h2))
+        (anchor :step t5)
+        (step t5.t1 (cl (= 0 3)) :rule trans :premises (t4 h3))
+        (step t5.t2 (cl (= 0 3)) :rule trans :premises (h1 h2 t5.t1))
+        (step t5 (cl (= 0 3)) :rule subproof :premises (t4))
+        (step t6 (cl (= 0 3)) :rule trans :premises (t4 t5 h1))",
+    );
+    let root = ProofNode::from_commands(&proof.commands);
+
+    let mut counter = Counter::default();
+    root.accept(&mut counter);
+    assert_eq!(3, counter.assumes);
+    assert_eq!(
+        ["t4", "t5.t1", "t5.t2", "t5", "t6"],
+        counter.steps.as_slice()
+    );
+    assert_eq!(0, counter.open_subproofs);
+    assert_eq!(1, counter.max_open_subproofs);
+}
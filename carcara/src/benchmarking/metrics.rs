@@ -293,6 +293,7 @@ impl<K: Clone, T: MetricsUnit> Metrics<K, T> for OnlineMetrics<K, T> {
     }
 }
 
+#[derive(Debug, Clone)]
 pub struct OfflineMetrics<K, T = Duration> {
     data: Vec<(K, T)>,
 }
@@ -309,6 +310,16 @@ impl<K, T: MetricsUnit> OfflineMetrics<K, T> {
         let n = self.data.len();
         [n / 20, n / 4, n / 2, (n * 3) / 4, (n * 19) / 20].map(|i| &self.data[i])
     }
+
+    /// Returns the 50th, 90th and 99th percentiles of the samples, computed using the
+    /// nearest-rank method. Unlike `quartiles`, this doesn't reorder the samples.
+    pub fn percentiles(&self) -> [T; 3] {
+        assert!(!self.data.is_empty());
+        let mut values: Vec<T> = self.data.iter().map(|(_, v)| *v).collect();
+        values.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+        let n = values.len();
+        [50, 90, 99].map(|p| values[(p * n + 99) / 100 - 1])
+    }
 }
 
 impl<K, T: MetricsUnit> Default for OfflineMetrics<K, T> {
@@ -8,6 +8,16 @@ use indexmap::{map::Entry, IndexMap, IndexSet};
 use std::{fmt, hash::Hash, io, sync::Arc, time::Duration};
 
 fn combine_map<S, K, V, M>(mut a: IndexMap<S, M>, b: IndexMap<S, M>) -> IndexMap<S, M>
+where
+    S: Eq + Hash,
+    V: MetricsUnit,
+    M: Metrics<K, V> + Default,
+{
+    combine_map_in_place(&mut a, b);
+    a
+}
+
+fn combine_map_in_place<S, K, V, M>(a: &mut IndexMap<S, M>, b: IndexMap<S, M>)
 where
     S: Eq + Hash,
     V: MetricsUnit,
@@ -26,7 +36,6 @@ where
             }
         }
     }
-    a
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -54,6 +63,44 @@ pub struct RunMeasurement {
     pub polyeq: Duration,
     pub assume: Duration,
     pub assume_core: Duration,
+
+    /// The resident memory of the process, in bytes, sampled right after the instance was
+    /// checked, while its proof and term pool are still in memory. Unlike the peak memory of the
+    /// process, this is not affected by runs that finished before. However, when instances are
+    /// checked concurrently, it includes the memory used by the other runs in progress. This is
+    /// `None` if the platform does not support measuring it. See [`resident_memory`].
+    pub resident_memory: Option<usize>,
+}
+
+/// Returns the current resident memory of the process, in bytes. On Linux, this is the number of
+/// resident pages read from `/proc/self/statm`, multiplied by the page size. On other platforms,
+/// or if the files can't be read, this returns `None`.
+pub fn resident_memory() -> Option<usize> {
+    #[cfg(target_os = "linux")]
+    {
+        let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+        let pages: usize = statm.split_whitespace().nth(1)?.parse().ok()?;
+        Some(pages * page_size()?)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+/// Returns the page size, in bytes. To avoid depending on `libc`, this is read from the `AT_PAGESZ`
+/// entry of the auxiliary vector, in `/proc/self/auxv`.
+#[cfg(target_os = "linux")]
+fn page_size() -> Option<usize> {
+    const AT_PAGESZ: usize = 6;
+    const WORD_SIZE: usize = std::mem::size_of::<usize>();
+
+    let auxv = std::fs::read("/proc/self/auxv").ok()?;
+    auxv.chunks_exact(2 * WORD_SIZE).find_map(|entry| {
+        let (key, value) = entry.split_at(WORD_SIZE);
+        let key = usize::from_ne_bytes(key.try_into().unwrap());
+        (key == AT_PAGESZ).then(|| usize::from_ne_bytes(value.try_into().unwrap()))
+    })
 }
 
 #[derive(Debug, Default, Clone)]
@@ -75,11 +122,19 @@ pub struct OnlineBenchmarkResults {
     pub assume_core_time: OnlineMetrics<RunId>,
 
     pub polyeq_depths: OnlineMetrics<(), usize>,
+
+    // The online metrics only keep running sums, so to compute percentiles we have to retain the
+    // samples for each run
+    pub parsing_samples: OfflineMetrics<RunId>,
+    pub checking_samples: OfflineMetrics<RunId>,
+    pub total_samples: OfflineMetrics<RunId>,
+
     pub num_assumes: usize,
     pub num_easy_assumes: usize,
 
     pub is_holey: bool,
     pub had_error: bool,
+    pub num_timeouts: usize,
 }
 
 impl OnlineBenchmarkResults {
@@ -183,6 +238,18 @@ impl OnlineBenchmarkResults {
         println!("total accounted for: {}", accounted_for);
         println!("total:               {}", total);
 
+        if !self.total_samples.is_empty() {
+            println!("percentiles (p50 / p90 / p99):");
+            for (name, samples) in [
+                ("parsing", &self.parsing_samples),
+                ("checking", &self.checking_samples),
+                ("total", &self.total_samples),
+            ] {
+                let [p50, p90, p99] = samples.percentiles();
+                println!("    {: <17}{:?} / {:?} / {:?}", name, p50, p90, p99);
+            }
+        }
+
         let data_by_rule = self.step_time_by_rule();
         let mut data_by_rule: Vec<_> = data_by_rule.iter().collect();
         data_by_rule.sort_by_key(|(_, m)| if sort_by_total { m.total() } else { m.mean() });
@@ -284,6 +351,7 @@ pub struct CsvBenchmarkResults {
     step_time_by_rule: IndexMap<Arc<str>, OfflineMetrics<InternedStepId>>,
     is_holey: bool,
     num_errors: usize,
+    num_timeouts: usize,
 }
 
 impl CsvBenchmarkResults {
@@ -299,6 +367,10 @@ impl CsvBenchmarkResults {
         self.num_errors
     }
 
+    pub fn num_timeouts(&self) -> usize {
+        self.num_timeouts
+    }
+
     fn intern(&mut self, s: &str) -> Arc<str> {
         match self.strings.get(s) {
             Some(interned) => interned.clone(),
@@ -326,16 +398,17 @@ impl CsvBenchmarkResults {
         writeln!(
             dest,
             "proof_file,run_id,parsing,checking,elaboration,total_accounted_for,\
-            total,polyeq,polyeq_ratio,assume,assume_ratio"
+            total,polyeq,polyeq_ratio,assume,assume_ratio,resident_memory"
         )?;
 
         for (id, m) in data {
             let total_accounted_for = m.parsing + m.checking;
             let polyeq_ratio = m.polyeq.as_secs_f64() / m.checking.as_secs_f64();
             let assume_ratio = m.assume.as_secs_f64() / m.checking.as_secs_f64();
+            let resident_memory = m.resident_memory.map(|b| b.to_string()).unwrap_or_default();
             writeln!(
                 dest,
-                "{},{},{},{},{},{},{},{},{},{},{}",
+                "{},{},{},{},{},{},{},{},{},{},{},{}",
                 id.0,
                 id.1,
                 m.parsing.as_nanos(),
@@ -347,6 +420,7 @@ impl CsvBenchmarkResults {
                 polyeq_ratio,
                 m.assume.as_nanos(),
                 assume_ratio,
+                resident_memory,
             )?;
         }
 
@@ -362,14 +436,16 @@ impl CsvBenchmarkResults {
 
         writeln!(
             dest,
-            "rule,count,total,mean,lower_whisker,first_quartile,median,third_quartile,upper_whisker"
+            "rule,count,total,mean,lower_whisker,first_quartile,median,third_quartile,\
+            upper_whisker,p50,p90,p99"
         )?;
         for (rule, mut m) in data {
+            let [p50, p90, p99] = m.percentiles().map(|t| t.as_nanos());
             let [lower_whisker, first_quartile, median, third_quartile, upper_whisker] =
                 m.quartiles().map(|(_, t)| t.as_nanos());
             writeln!(
                 dest,
-                "{},{},{},{},{},{},{},{},{}",
+                "{},{},{},{},{},{},{},{},{},{},{},{}",
                 rule,
                 m.count(),
                 m.total().as_nanos(),
@@ -379,6 +455,9 @@ impl CsvBenchmarkResults {
                 median,
                 third_quartile,
                 upper_whisker,
+                p50,
+                p90,
+                p99,
             )?;
         }
         Ok(())
@@ -393,9 +472,24 @@ pub trait CollectResults {
     fn register_holey(&mut self);
     fn register_error(&mut self, error: &crate::Error);
 
+    /// Registers a run that was aborted because it exceeded the checking timeout. This is not
+    /// considered an error.
+    fn register_timeout(&mut self);
+
     fn combine(a: Self, b: Self) -> Self
     where
         Self: Sized;
+
+    /// Combines the results in `other` into `self`. This has the same effect as
+    /// [`CollectResults::combine`], but implementors may override it to reuse the allocations in
+    /// `self`, instead of building new results.
+    fn combine_in_place(&mut self, other: Self)
+    where
+        Self: Sized + Default,
+    {
+        let this = std::mem::take(self);
+        *self = Self::combine(this, other);
+    }
 }
 
 impl CollectResults for OnlineBenchmarkResults {
@@ -438,6 +532,7 @@ impl CollectResults for OnlineBenchmarkResults {
             polyeq,
             assume,
             assume_core,
+            resident_memory: _,
         } = measurement;
 
         self.parsing.add_sample(id, parsing);
@@ -447,6 +542,10 @@ impl CollectResults for OnlineBenchmarkResults {
         self.total_accounted_for.add_sample(id, parsing + checking);
         self.total.add_sample(id, total);
 
+        self.parsing_samples.add_sample(id, parsing);
+        self.checking_samples.add_sample(id, checking);
+        self.total_samples.add_sample(id, total);
+
         self.polyeq_time.add_sample(id, polyeq);
         self.assume_time.add_sample(id, assume);
         self.assume_core_time.add_sample(id, assume_core);
@@ -476,10 +575,14 @@ impl CollectResults for OnlineBenchmarkResults {
             assume_core_time: a.assume_core_time.combine(b.assume_core_time),
 
             polyeq_depths: a.polyeq_depths.combine(b.polyeq_depths),
+            parsing_samples: a.parsing_samples.combine(b.parsing_samples),
+            checking_samples: a.checking_samples.combine(b.checking_samples),
+            total_samples: a.total_samples.combine(b.total_samples),
             num_assumes: a.num_assumes + b.num_assumes,
             num_easy_assumes: a.num_easy_assumes + b.num_easy_assumes,
             is_holey: a.is_holey || b.is_holey,
             had_error: a.had_error || b.had_error,
+            num_timeouts: a.num_timeouts + b.num_timeouts,
         }
     }
 
@@ -490,6 +593,10 @@ impl CollectResults for OnlineBenchmarkResults {
     fn register_error(&mut self, _: &crate::Error) {
         self.had_error = true;
     }
+
+    fn register_timeout(&mut self) {
+        self.num_timeouts += 1;
+    }
 }
 
 impl CollectResults for CsvBenchmarkResults {
@@ -525,12 +632,197 @@ impl CollectResults for CsvBenchmarkResults {
         self.num_errors += 1;
     }
 
+    fn register_timeout(&mut self) {
+        self.num_timeouts += 1;
+    }
+
     fn combine(mut a: Self, b: Self) -> Self {
-        // This assumes that the same run never appears in both `a` and `b`. This should be the case
-        // in benchmarks anyway
-        a.runs.extend(b.runs);
-        a.step_time_by_rule = combine_map(a.step_time_by_rule, b.step_time_by_rule);
-        a.num_errors += b.num_errors;
+        a.combine_in_place(b);
         a
     }
+
+    fn combine_in_place(&mut self, other: Self) {
+        // This assumes that the same run never appears in both `self` and `other`. This should be
+        // the case in benchmarks anyway
+        self.runs.extend(other.runs);
+        combine_map_in_place(&mut self.step_time_by_rule, other.step_time_by_rule);
+        self.is_holey |= other.is_holey;
+        self.num_errors += other.num_errors;
+        self.num_timeouts += other.num_timeouts;
+    }
+}
+
+/// Benchmark results that are written as JSON, for use by external tools. This collects the same
+/// data as [`CsvBenchmarkResults`].
+#[derive(Default)]
+pub struct JsonBenchmarkResults(CsvBenchmarkResults);
+
+impl JsonBenchmarkResults {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn is_holey(&self) -> bool {
+        self.0.is_holey
+    }
+
+    pub fn num_errors(&self) -> usize {
+        self.0.num_errors
+    }
+
+    pub fn num_timeouts(&self) -> usize {
+        self.0.num_timeouts
+    }
+
+    /// Writes the results to `dest` as a JSON object. The `"runs"` field holds one record for
+    /// each run of each file, and the `"by_rule"` field holds aggregate statistics for each rule,
+    /// with the same fields as the CSV output. All times are in nanoseconds.
+    #[cfg(feature = "serde")]
+    pub fn write_json(self, dest: &mut dyn io::Write) -> io::Result<()> {
+        use serde::Serialize;
+
+        #[derive(Serialize)]
+        struct Output {
+            is_holey: bool,
+            num_errors: usize,
+            num_timeouts: usize,
+            runs: Vec<Run>,
+            by_rule: Vec<Rule>,
+        }
+
+        #[derive(Serialize)]
+        struct Run {
+            proof_file: String,
+            run_id: usize,
+            parsing: u128,
+            checking: u128,
+            elaboration: u128,
+            total_accounted_for: u128,
+            total: u128,
+            polyeq: u128,
+            polyeq_ratio: Option<f64>,
+            assume: u128,
+            assume_ratio: Option<f64>,
+            resident_memory: Option<usize>,
+        }
+
+        #[derive(Serialize)]
+        struct Rule {
+            rule: String,
+            count: usize,
+            total: u128,
+            mean: u128,
+            lower_whisker: u128,
+            first_quartile: u128,
+            median: u128,
+            third_quartile: u128,
+            upper_whisker: u128,
+            p50: u128,
+            p90: u128,
+            p99: u128,
+        }
+
+        // Ratios may be NaN if the checking time is zero, and those are not valid JSON numbers
+        fn ratio(a: Duration, b: Duration) -> Option<f64> {
+            Some(a.as_secs_f64() / b.as_secs_f64()).filter(|r| r.is_finite())
+        }
+
+        let CsvBenchmarkResults {
+            runs,
+            step_time_by_rule,
+            is_holey,
+            num_errors,
+            num_timeouts,
+            ..
+        } = self.0;
+
+        let runs = (runs.into_iter())
+            .map(|((proof_file, run_id), m)| Run {
+                proof_file: proof_file.to_string(),
+                run_id,
+                parsing: m.parsing.as_nanos(),
+                checking: m.checking.as_nanos(),
+                elaboration: m.elaboration.as_nanos(),
+                total_accounted_for: (m.parsing + m.checking).as_nanos(),
+                total: m.total.as_nanos(),
+                polyeq: m.polyeq.as_nanos(),
+                polyeq_ratio: ratio(m.polyeq, m.checking),
+                assume: m.assume.as_nanos(),
+                assume_ratio: ratio(m.assume, m.checking),
+                resident_memory: m.resident_memory,
+            })
+            .collect();
+
+        let mut by_rule: Vec<_> = step_time_by_rule.into_iter().collect();
+        by_rule.sort_unstable_by_key(|m| m.1.total());
+        let by_rule = (by_rule.into_iter())
+            .map(|(rule, mut m)| {
+                let [p50, p90, p99] = m.percentiles().map(|t| t.as_nanos());
+                let [lower_whisker, first_quartile, median, third_quartile, upper_whisker] =
+                    m.quartiles().map(|(_, t)| t.as_nanos());
+                Rule {
+                    rule: rule.to_string(),
+                    count: m.count(),
+                    total: m.total().as_nanos(),
+                    mean: m.mean().as_nanos(),
+                    lower_whisker,
+                    first_quartile,
+                    median,
+                    third_quartile,
+                    upper_whisker,
+                    p50,
+                    p90,
+                    p99,
+                }
+            })
+            .collect();
+
+        let output = Output {
+            is_holey,
+            num_errors,
+            num_timeouts,
+            runs,
+            by_rule,
+        };
+        serde_json::to_writer(&mut *dest, &output)?;
+        writeln!(dest)
+    }
+}
+
+impl CollectResults for JsonBenchmarkResults {
+    fn add_step_measurement(&mut self, file: &str, step_id: &str, rule: &str, time: Duration) {
+        self.0.add_step_measurement(file, step_id, rule, time);
+    }
+
+    fn add_assume_measurement(&mut self, file: &str, id: &str, is_easy: bool, time: Duration) {
+        self.0.add_assume_measurement(file, id, is_easy, time);
+    }
+
+    fn add_polyeq_depth(&mut self, depth: usize) {
+        self.0.add_polyeq_depth(depth);
+    }
+
+    fn add_run_measurement(&mut self, id: &RunId, measurement: RunMeasurement) {
+        self.0.add_run_measurement(id, measurement);
+    }
+
+    fn register_holey(&mut self) {
+        self.0.register_holey();
+    }
+
+    fn register_error(&mut self, error: &crate::Error) {
+        self.0.register_error(error);
+    }
+
+    fn register_timeout(&mut self) {
+        self.0.register_timeout();
+    }
+
+    fn combine(a: Self, b: Self) -> Self {
+        Self(CsvBenchmarkResults::combine(a.0, b.0))
+    }
+
+    fn combine_in_place(&mut self, other: Self) {
+        self.0.combine_in_place(other.0);
+    }
 }
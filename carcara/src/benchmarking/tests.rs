@@ -1,4 +1,4 @@
-use super::{Duration, Metrics, MetricsUnit, OfflineMetrics, OnlineMetrics};
+use super::{resident_memory, Duration, Metrics, MetricsUnit, OfflineMetrics, OnlineMetrics};
 use rand::{prelude::ThreadRng, Rng};
 use std::fmt;
 
@@ -135,3 +135,103 @@ fn test_metrics_combine() {
     // `Metrics::add` with that entry, which makes the numerical error small again
     run_tests(10_000, 1, 1.0e-6);
 }
+
+#[cfg(feature = "serde")]
+mod json {
+    use super::super::{CollectResults, JsonBenchmarkResults, RunMeasurement};
+    use super::Duration;
+
+    /// Builds two partial results for the same set of runs, split between them.
+    fn partial_json_results() -> [JsonBenchmarkResults; 2] {
+        let files = ["a.alethe", "dir/\"b\".alethe"];
+        let mut results = [JsonBenchmarkResults::new(), JsonBenchmarkResults::new()];
+        for (i, file) in files.iter().enumerate() {
+            for run in 0..3 {
+                let measurement = RunMeasurement {
+                    checking: Duration::from_nanos(i as u64 * 100),
+                    total: Duration::from_nanos(1000),
+                    ..Default::default()
+                };
+                results[run % 2].add_run_measurement(&((*file).to_owned(), run), measurement);
+                results[run % 2].add_step_measurement(file, "t1", "refl", Duration::from_nanos(10));
+            }
+        }
+        results[1].register_holey();
+        results[1].register_timeout();
+        results
+    }
+
+    fn to_json(results: JsonBenchmarkResults) -> String {
+        let mut buf = Vec::new();
+        results.write_json(&mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_json_benchmark_results() {
+        let [a, b] = partial_json_results();
+        let json = to_json(JsonBenchmarkResults::combine(a, b));
+
+        assert!(json.starts_with(
+            r#"{"is_holey":true,"num_errors":0,"num_timeouts":1,"runs":[{"proof_file":"#
+        ));
+        assert_eq!(6, json.matches(r#""proof_file":"#).count());
+        for file in [r#""a.alethe""#, r#""dir/\"b\".alethe""#] {
+            for run in 0..3 {
+                let record = format!(r#""proof_file":{},"run_id":{},"#, file, run);
+                assert_eq!(
+                    1,
+                    json.matches(&record).count(),
+                    "missing record {}",
+                    record
+                );
+            }
+        }
+
+        // The ratios for runs with no checking time are not finite, so they are written as `null`
+        assert!(json.contains(r#""polyeq_ratio":null"#));
+        assert!(json.ends_with(
+            r#""by_rule":[{"rule":"refl","count":6,"total":60,"mean":10,"lower_whisker":10,"first_quartile":10,"median":10,"third_quartile":10,"upper_whisker":10,"p50":10,"p90":10,"p99":10}]}
+"#
+        ));
+    }
+
+    #[test]
+    fn test_combine_in_place() {
+        let [a, b] = partial_json_results();
+        let expected = to_json(JsonBenchmarkResults::combine(a, b));
+
+        let [mut a, b] = partial_json_results();
+        a.combine_in_place(b);
+        assert_eq!(expected, to_json(a));
+    }
+}
+
+#[test]
+fn test_resident_memory() {
+    if cfg!(target_os = "linux") {
+        assert!(resident_memory().unwrap() > 0);
+    } else {
+        assert_eq!(None, resident_memory());
+    }
+}
+
+#[test]
+fn test_percentiles() {
+    let mut metrics = OfflineMetrics::new();
+    for i in (1..=100).rev() {
+        metrics.add_sample(&(), Duration::from_nanos(i));
+    }
+    let expected = [50, 90, 99].map(Duration::from_nanos);
+    assert_eq!(expected, metrics.percentiles());
+
+    let mut metrics = OfflineMetrics::new();
+    for i in [7.0, 3.0, 10.0, 1.0, 5.0, 2.0, 9.0, 4.0, 8.0, 6.0] {
+        metrics.add_sample(&(), i);
+    }
+    assert_eq!([5.0, 9.0, 10.0], metrics.percentiles());
+
+    let mut metrics = OfflineMetrics::new();
+    metrics.add_sample(&(), 42usize);
+    assert_eq!([42, 42, 42], metrics.percentiles());
+}
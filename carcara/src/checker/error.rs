@@ -159,6 +159,15 @@ pub enum CheckerError {
 
     #[error("unknown rule")]
     UnknownRule,
+
+    #[error("rule '{0}' is not allowed by the checker configuration")]
+    ForbiddenRule(String),
+
+    #[error("'hole' steps are not trusted by the checker configuration")]
+    UntrustedHole,
+
+    #[error("lemma '{0}' is not trusted by the checker configuration")]
+    UntrustedLemma(Rc<Term>),
 }
 
 /// Errors in which we expected two things to be equal but they weren't.
@@ -187,6 +196,9 @@ pub enum ResolutionError {
 
     #[error("pivot was not found in clause: '{0}'")]
     PivotNotFound(Rc<Term>),
+
+    #[error("pivot was found in clause with the wrong polarity: '{0}'")]
+    PivotWithWrongPolarity(Rc<Term>),
 }
 
 struct DisplayIndexedOp<'a>(&'a ParamOperator, &'a Vec<Rc<Term>>);
@@ -284,6 +296,9 @@ pub enum LinearArithmeticError {
     #[error("final disequality is not tautological: '{}'", DisplayLinearComb(.0, .1))]
     DisequalityIsNotTautology(Operator, LinearComb),
 
+    #[error("coefficient for disequality '{0}' must be non-negative, got '{1}'")]
+    NegativeCoefficient(Rc<Term>, Rational),
+
     #[error("expected term '{0}' to be less than term '{1}'")]
     ExpectedLessThan(Rc<Term>, Rc<Term>),
 
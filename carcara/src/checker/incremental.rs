@@ -0,0 +1,84 @@
+use super::rules::Premise;
+use crate::ast::*;
+use std::collections::HashMap;
+
+/// Everything the validity of a step depends on: its rule, clause and arguments, the clauses of
+/// its premises and discharged commands, and the arguments of all subproofs that enclose it.
+///
+/// Since terms are hash consed, comparing them only compares their pointers, so comparing two
+/// entries is cheap, even if the terms are large.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct StepEntry {
+    rule: String,
+    clause: Vec<Rc<Term>>,
+    args: Vec<ProofArg>,
+    premises: Vec<Vec<Rc<Term>>>,
+    previous_command: Option<Vec<Rc<Term>>>,
+    discharge: Vec<Vec<Rc<Term>>>,
+    anchors: Vec<Vec<AnchorArg>>,
+}
+
+/// A cache of the steps that were found to be valid in previous checks of a proof, used when
+/// re-checking a proof after some of its steps were changed.
+///
+/// Each step is identified by its id, and is only considered valid if its current entry is equal
+/// to the one stored when it was last checked. If a step is changed to conclude a different
+/// clause, all steps that use it as a premise or discharge it will also have a different entry,
+/// and will be checked again.
+#[derive(Debug, Default)]
+pub(super) struct IncrementalCache {
+    valid_steps: HashMap<String, StepEntry>,
+
+    /// The arguments of each open subproof, from the outermost to the innermost.
+    anchors: Vec<Vec<AnchorArg>>,
+
+    /// The id of the step that must be checked again, even if it is in the cache.
+    pub forced_step: String,
+
+    /// The number of steps that were actually checked during the last check.
+    pub num_checked_steps: usize,
+}
+
+impl IncrementalCache {
+    pub fn open_subproof(&mut self, args: &[AnchorArg]) {
+        self.anchors.push(args.to_vec());
+    }
+
+    pub fn close_subproof(&mut self) {
+        self.anchors.pop();
+    }
+
+    /// Closes all open subproofs. This must be called before each check, since a previous check
+    /// may have failed inside a subproof, before closing it.
+    pub fn close_all_subproofs(&mut self) {
+        self.anchors.clear();
+    }
+
+    pub fn entry(
+        &self,
+        step: &ProofStep,
+        premises: &[Premise],
+        previous_command: Option<Premise>,
+        discharge: &[&ProofCommand],
+    ) -> StepEntry {
+        StepEntry {
+            rule: step.rule.clone(),
+            clause: step.clause.clone(),
+            args: step.args.clone(),
+            premises: premises.iter().map(|p| p.clause.to_vec()).collect(),
+            previous_command: previous_command.map(|p| p.clause.to_vec()),
+            discharge: discharge.iter().map(|c| c.clause().to_vec()).collect(),
+            anchors: self.anchors.clone(),
+        }
+    }
+
+    /// Returns `true` if the step was already found to be valid, and doesn't need to be checked
+    /// again.
+    pub fn is_valid(&self, step: &ProofStep, entry: &StepEntry) -> bool {
+        step.id != self.forced_step && self.valid_steps.get(&step.id) == Some(entry)
+    }
+
+    pub fn insert(&mut self, step: &ProofStep, entry: StepEntry) {
+        self.valid_steps.insert(step.id.clone(), entry);
+    }
+}
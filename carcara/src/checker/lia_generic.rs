@@ -123,12 +123,23 @@ fn parse_and_check_solver_proof(
         expand_lets: true,
         allow_int_real_subtyping: true,
         allow_unary_logical_ops: true,
+        allow_unknown_symbols: false,
+        rename_shadowed: false,
+        max_term_depth: None,
+        normalize_clause_order: false,
     };
     let mut parser = parser::Parser::new(pool, config, problem)?;
     let (prelude, premises) = parser.parse_problem()?;
     parser.reset(proof)?;
     let commands = parser.parse_proof()?;
-    let proof = Proof { premises, commands };
+    let named_terms = parser.named_terms().clone();
+    let original_clauses = parser.original_clauses().clone();
+    let proof = Proof {
+        premises,
+        commands,
+        named_terms,
+        original_clauses,
+    };
 
     let config = Config::new().ignore_unknown_rules(true);
     ProofChecker::new(pool, config, &prelude).check(&proof)?;
@@ -242,6 +253,7 @@ fn insert_solver_proof(
             premises: Vec::new(),
             args: Vec::new(),
             discharge,
+            weight: None,
         },
         root_id,
     );
@@ -265,6 +277,7 @@ fn insert_solver_proof(
                 premises: Vec::new(),
                 args: Vec::new(),
                 discharge: Vec::new(),
+                weight: None,
             })
         })
         .collect();
@@ -276,6 +289,7 @@ fn insert_solver_proof(
         premises: Vec::new(),
         args: Vec::new(),
         discharge: Vec::new(),
+        weight: None,
     });
 
     let mut premises = vec![subproof];
@@ -290,5 +304,6 @@ fn insert_solver_proof(
         premises,
         args: Vec::new(),
         discharge: Vec::new(),
+        weight: None,
     });
 }
@@ -1,22 +1,34 @@
 pub mod error;
+mod incremental;
 mod lia_generic;
 mod parallel;
 mod rules;
+mod step_cache;
+#[cfg(test)]
+mod tests;
 
 use crate::{
     ast::*,
     benchmarking::{CollectResults, OnlineBenchmarkResults},
-    elaborator::Elaborator,
+    elaborator::{Elaborator, IdScheme},
     CarcaraResult, Error, LiaGenericOptions,
 };
 use error::{CheckerError, SubproofError};
+use incremental::IncrementalCache;
 use indexmap::IndexSet;
 pub use parallel::{scheduler::Scheduler, ParallelProofChecker};
-use rules::{ElaborationRule, Premise, Rule, RuleArgs, RuleResult};
+use rules::Rule;
+pub use rules::{
+    extras::clause_permutation,
+    resolution::{as_pivot, check as check_resolution, clause_is_tautological, Literal},
+    ElaborationRule, Premise, RuleArgs, RuleResult,
+};
 use std::{
+    collections::{HashMap, HashSet},
     fmt,
     time::{Duration, Instant},
 };
+use step_cache::StepCache;
 
 #[derive(Clone)]
 pub struct CheckerStatistics<'s, CR: CollectResults + Send + Default> {
@@ -50,6 +62,14 @@ pub struct Config {
     strict: bool,
     ignore_unknown_rules: bool,
     lia_options: Option<LiaGenericOptions>,
+    allowed_rules: Option<HashSet<String>>,
+    denied_rules: HashSet<String>,
+    timeout: Option<Duration>,
+    warn_tautologies: bool,
+    warn_unused_premises: bool,
+    id_scheme: IdScheme,
+    hole_policy: HolePolicy,
+    trusted_lemmas: Option<HashSet<Rc<Term>>>,
 }
 
 impl Config {
@@ -71,6 +91,197 @@ impl Config {
         self.lia_options = value.into();
         self
     }
+
+    /// If this is `Some`, only steps whose rule is in the set are accepted. Any other step will
+    /// fail with a `CheckerError::ForbiddenRule` error.
+    pub fn allowed_rules(mut self, value: impl Into<Option<HashSet<String>>>) -> Self {
+        self.allowed_rules = value.into();
+        self
+    }
+
+    /// Steps whose rule is in this set will fail with a `CheckerError::ForbiddenRule` error. This
+    /// takes precedence over `allowed_rules`.
+    pub fn denied_rules(mut self, value: HashSet<String>) -> Self {
+        self.denied_rules = value;
+        self
+    }
+
+    /// If this is `Some`, checking a proof will fail with an `Error::Timeout` error once it takes
+    /// longer than the given duration. The elapsed time is only checked between commands, so a
+    /// single slow step may still exceed the timeout.
+    pub fn timeout(mut self, value: impl Into<Option<Duration>>) -> Self {
+        self.timeout = value.into();
+        self
+    }
+
+    /// If this is `true`, the checker logs a warning whenever a `resolution` or `th_resolution`
+    /// step uses a premise whose clause is tautological, that is, contains both a literal and its
+    /// negation. Such premises are valid, but usually indicate redundant proof fragments.
+    pub fn warn_tautologies(mut self, value: bool) -> Self {
+        self.warn_tautologies = value;
+        self
+    }
+
+    /// If this is `true`, the checker emits a warning for each premise of a `resolution`,
+    /// `th_resolution` or `trans` step that does not contribute to its conclusion. For resolution
+    /// steps, these are the premises with no literal whose negation appears in another premise, and
+    /// for `trans` steps, the premises that are not needed to complete the transitivity chain. The
+    /// warnings can be retrieved with [`ProofChecker::warnings`] or
+    /// [`ParallelProofChecker::warnings`].
+    pub fn warn_unused_premises(mut self, value: bool) -> Self {
+        self.warn_unused_premises = value;
+        self
+    }
+
+    /// The scheme used to build the ids of the steps introduced when elaborating a proof.
+    pub fn id_scheme(mut self, value: IdScheme) -> Self {
+        self.id_scheme = value;
+        self
+    }
+
+    /// How steps that use the `hole` rule are handled. This is independent of
+    /// `ignore_unknown_rules`, so unknown rules can be trusted while holes are not.
+    pub fn hole_policy(mut self, value: HolePolicy) -> Self {
+        self.hole_policy = value;
+        self
+    }
+
+    /// If this is `Some`, it holds the lemmas that are accepted without proof in steps that use
+    /// the `lemma` or `trust` rules. The conclusion `(cl l_1 ... l_n)` of such a step is accepted
+    /// only if its disjunction `(or l_1 ... l_n)` is in the set, where a single literal is taken by
+    /// itself and the empty clause is taken as `false`. Otherwise, the step fails with a
+    /// `CheckerError::UntrustedLemma` error. Since accepted lemmas are not proven, they make the
+    /// proof holey. The terms must be added to the same pool used by the checker.
+    ///
+    /// If this is `None`, `lemma` and `trust` are handled like any other unknown rule. See
+    /// `ignore_unknown_rules`.
+    pub fn trusted_lemmas(mut self, value: impl Into<Option<HashSet<Rc<Term>>>>) -> Self {
+        self.trusted_lemmas = value.into();
+        self
+    }
+
+    /// Returns an `Error::Timeout` error if checking started at `start` has exceeded the timeout.
+    fn check_timeout(&self, start: Instant) -> CarcaraResult<()> {
+        match self.timeout {
+            Some(timeout) if start.elapsed() > timeout => Err(Error::Timeout(timeout)),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_rule_is_allowed(&self, rule: &str) -> RuleResult {
+        let is_allowed = !self.denied_rules.contains(rule)
+            && self
+                .allowed_rules
+                .as_ref()
+                .map_or(true, |a| a.contains(rule));
+        if is_allowed {
+            Ok(())
+        } else {
+            Err(CheckerError::ForbiddenRule(rule.to_owned()))
+        }
+    }
+
+    /// Applies the hole policy to a step that uses the `hole` rule. Returns an error if holes are
+    /// rejected. If they are only warned about, logs a warning and adds it to `warnings`.
+    fn check_hole(&self, step_id: &str, warnings: &mut Vec<CheckerWarning>) -> RuleResult {
+        match self.hole_policy {
+            HolePolicy::Accept => Ok(()),
+            HolePolicy::Reject => Err(CheckerError::UntrustedHole),
+            HolePolicy::Warn => {
+                log::warn!("step '{}' uses the 'hole' rule", step_id);
+                warnings.push(CheckerWarning::Hole { step: step_id.to_owned() });
+                Ok(())
+            }
+        }
+    }
+
+    /// If `warn_tautologies` is enabled and `step` is a resolution step, logs a warning for each of
+    /// its premises whose clause is tautological.
+    fn check_tautological_premises(&self, step: &ProofStep, premises: &[Premise]) {
+        if !self.warn_tautologies || !matches!(step.rule.as_str(), "resolution" | "th_resolution") {
+            return;
+        }
+        for p in premises.iter().filter(|p| clause_is_tautological(p.clause)) {
+            log::warn!(
+                "step '{}' uses tautological clause from premise '{}'",
+                step.id,
+                p.id
+            );
+        }
+    }
+
+    /// If `warn_unused_premises` is enabled, adds a warning to `warnings` for each premise of `step`
+    /// that does not contribute to its conclusion.
+    fn check_unused_premises(
+        &self,
+        step: &ProofStep,
+        premises: &[Premise],
+        warnings: &mut Vec<CheckerWarning>,
+    ) {
+        if !self.warn_unused_premises {
+            return;
+        }
+        let unused = match step.rule.as_str() {
+            "resolution" | "th_resolution" => rules::resolution::unused_premises(premises),
+            "trans" => rules::transitivity::unused_premises(&step.clause, premises),
+            _ => Vec::new(),
+        };
+        warnings.extend(unused.into_iter().map(|i| CheckerWarning::UnusedPremise {
+            step: step.id.clone(),
+            premise: premises[i].id.to_owned(),
+        }));
+    }
+
+    /// If `rule` is `lemma` or `trust`, and a set of trusted lemmas was given, returns an error if
+    /// the conclusion of the step is not a trusted lemma. Returns `None` if the step should be
+    /// checked like any other step.
+    fn check_trusted_lemma(
+        &self,
+        pool: &mut dyn TermPool,
+        rule: &str,
+        clause: &[Rc<Term>],
+    ) -> Option<RuleResult> {
+        let trusted = self.trusted_lemmas.as_ref()?;
+        if rule != "lemma" && rule != "trust" {
+            return None;
+        }
+        let lemma = match clause {
+            [] => pool.bool_false(),
+            [l] => l.clone(),
+            _ => pool.add(Term::Op(Operator::Or, clause.to_vec())),
+        };
+        if trusted.contains(&lemma) {
+            Some(Ok(()))
+        } else {
+            Some(Err(CheckerError::UntrustedLemma(lemma)))
+        }
+    }
+}
+
+/// How the checker handles steps that use the `hole` rule, which is used to indicate gaps in the
+/// proof. See [`Config::hole_policy`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HolePolicy {
+    /// `hole` steps are accepted, but the proof is reported as holey.
+    #[default]
+    Accept,
+
+    /// `hole` steps fail with a `CheckerError::UntrustedHole` error.
+    Reject,
+
+    /// `hole` steps are accepted, but a warning is emitted for each of them.
+    Warn,
+}
+
+/// A warning about a step that is valid, but may indicate a problem in the proof. Warnings are
+/// collected while checking, and can be retrieved with [`ProofChecker::warnings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckerWarning {
+    /// A premise of the step does not contribute to its conclusion, and could be removed.
+    UnusedPremise { step: String, premise: String },
+
+    /// The step uses the `hole` rule, and the hole policy is [`HolePolicy::Warn`].
+    Hole { step: String },
 }
 
 pub struct ProofChecker<'c> {
@@ -79,8 +290,12 @@ pub struct ProofChecker<'c> {
     prelude: &'c ProblemPrelude,
     context: ContextStack,
     elaborator: Option<Elaborator>,
+    elaboration_rules: HashMap<&'static str, ElaborationRule>,
+    step_cache: StepCache,
+    incremental: Option<IncrementalCache>,
     reached_empty_clause: bool,
     is_holey: bool,
+    warnings: Vec<CheckerWarning>,
 }
 
 impl<'c> ProofChecker<'c> {
@@ -91,11 +306,30 @@ impl<'c> ProofChecker<'c> {
             prelude,
             context: ContextStack::new(),
             elaborator: None,
+            elaboration_rules: Self::default_elaboration_rules(),
+            step_cache: StepCache::default(),
+            incremental: None,
             reached_empty_clause: false,
             is_holey: false,
+            warnings: Vec::new(),
         }
     }
 
+    /// Registers an elaboration rule that will be used to elaborate steps with the given rule
+    /// name, replacing the default elaboration for that rule, if there is one. Steps whose rule
+    /// has no elaboration rule are left unchanged by the elaborator.
+    ///
+    /// Note that the rule name must also be known by the checker, since steps are still checked
+    /// before elaboration.
+    pub fn register_elaboration_rule(&mut self, rule_name: &'static str, rule: ElaborationRule) {
+        self.elaboration_rules.insert(rule_name, rule);
+    }
+
+    /// Returns the warnings emitted during the last check.
+    pub fn warnings(&self) -> &[CheckerWarning] {
+        &self.warnings
+    }
+
     pub fn check(&mut self, proof: &Proof) -> CarcaraResult<bool> {
         self.check_impl(
             proof,
@@ -103,6 +337,22 @@ impl<'c> ProofChecker<'c> {
         )
     }
 
+    /// Checks the proof again after some of its steps were changed, reusing the results of
+    /// previous calls to this method. Steps that were already found to be valid are skipped, unless
+    /// they or any of their premises changed. The step with id `step_id` is always checked again.
+    ///
+    /// The first time this method is called on a checker, all steps are checked.
+    pub fn recheck_from(&mut self, proof: &Proof, step_id: &str) -> CarcaraResult<bool> {
+        let cache = self
+            .incremental
+            .get_or_insert_with(IncrementalCache::default);
+        step_id.clone_into(&mut cache.forced_step);
+        cache.num_checked_steps = 0;
+        self.reached_empty_clause = false;
+        self.is_holey = false;
+        self.check(proof)
+    }
+
     pub fn check_with_stats<CR: CollectResults + Send + Default>(
         &mut self,
         proof: &Proof,
@@ -116,10 +366,20 @@ impl<'c> ProofChecker<'c> {
         proof: &Proof,
         mut stats: Option<&mut CheckerStatistics<CR>>,
     ) -> CarcaraResult<bool> {
+        let start = Instant::now();
+        self.warnings.clear();
+
+        // A previous check may have failed inside a subproof, leaving its context on the stack
+        self.context = ContextStack::new();
+        if let Some(cache) = &mut self.incremental {
+            cache.close_all_subproofs();
+        }
+
         // Similarly to the parser, to avoid stack overflows in proofs with many nested subproofs,
         // we check the subproofs iteratively, instead of recursively
         let mut iter = proof.iter();
         while let Some(command) = iter.next() {
+            self.config.check_timeout(start)?;
             match command {
                 ProofCommand::Step(step) => {
                     let is_end_of_subproof = iter.is_end_step();
@@ -140,6 +400,7 @@ impl<'c> ProofChecker<'c> {
                             inner: e,
                             rule: step.rule.clone(),
                             step: step.id.clone(),
+                            clause: step.clause.clone(),
                         })?;
 
                     // If this is the last command of a subproof, we have to pop the subproof
@@ -150,6 +411,9 @@ impl<'c> ProofChecker<'c> {
                         if let Some(elaborator) = &mut self.elaborator {
                             elaborator.close_subproof();
                         }
+                        if let Some(cache) = &mut self.incremental {
+                            cache.close_subproof();
+                        }
                     }
 
                     if step.clause.is_empty() {
@@ -165,6 +429,9 @@ impl<'c> ProofChecker<'c> {
                     if let Some(elaborator) = &mut self.elaborator {
                         elaborator.open_subproof(s.commands.len());
                     }
+                    if let Some(cache) = &mut self.incremental {
+                        cache.open_subproof(&s.args);
+                    }
 
                     if let Some(stats) = &mut stats {
                         let rule_name = match s.commands.last() {
@@ -185,6 +452,7 @@ impl<'c> ProofChecker<'c> {
                             inner: CheckerError::Assume(term.clone()),
                             rule: "assume".into(),
                             step: id.clone(),
+                            clause: vec![term.clone()],
                         });
                     }
                 }
@@ -198,7 +466,13 @@ impl<'c> ProofChecker<'c> {
     }
 
     pub fn check_and_elaborate(&mut self, mut proof: Proof) -> CarcaraResult<(bool, Proof)> {
-        self.elaborator = Some(Elaborator::new());
+        // Premises that don't refer to a previous command may form cycles, which would make the
+        // elaborator loop forever, so we validate the proof structure before doing anything
+        proof.validate_structure()?;
+
+        let mut elaborator = Elaborator::with_id_scheme(self.config.id_scheme.clone());
+        elaborator.reserve_ids(&proof);
+        self.elaborator = Some(elaborator);
         let result = self.check(&proof);
 
         // We reset `self.elaborator` before returning any errors encountered while checking so
@@ -216,7 +490,11 @@ impl<'c> ProofChecker<'c> {
         mut proof: Proof,
         stats: &'s mut CheckerStatistics<CR>,
     ) -> CarcaraResult<(bool, Proof)> {
-        self.elaborator = Some(Elaborator::new());
+        proof.validate_structure()?;
+
+        let mut elaborator = Elaborator::with_id_scheme(self.config.id_scheme.clone());
+        elaborator.reserve_ids(&proof);
+        self.elaborator = Some(elaborator);
         let result = self.check_with_stats(&proof, stats);
 
         // We reset `self.elaborator` before returning any errors encountered while checking so we
@@ -322,6 +600,8 @@ impl<'c> ProofChecker<'c> {
         let time = Instant::now();
         let mut polyeq_time = Duration::ZERO;
 
+        self.config.check_rule_is_allowed(&step.rule)?;
+
         if !step.discharge.is_empty() && step.rule != "subproof" {
             return Err(CheckerError::Subproof(SubproofError::DischargeInWrongRule));
         }
@@ -346,6 +626,15 @@ impl<'c> ProofChecker<'c> {
                     elaborator.unchanged(&step.clause);
                 }
             }
+        } else if let Some(result) =
+            (self.config).check_trusted_lemma(self.pool, &step.rule, &step.clause)
+        {
+            // Trusted lemmas are accepted without proof, so they make the proof holey
+            result?;
+            self.is_holey = true;
+            if let Some(elaborator) = &mut self.elaborator {
+                elaborator.unchanged(&step.clause);
+            }
         } else {
             let rule = match Self::get_rule(&step.rule, self.config.strict) {
                 Some(r) => r,
@@ -354,6 +643,17 @@ impl<'c> ProofChecker<'c> {
                     if let Some(elaborator) = &mut self.elaborator {
                         elaborator.unchanged(&step.clause);
                     }
+
+                    // We still record the step in the statistics, so rule coverage reports can
+                    // include the rules that were ignored
+                    if let Some(s) = stats {
+                        s.results.add_step_measurement(
+                            s.file_name,
+                            &step.id,
+                            &step.rule,
+                            time.elapsed(),
+                        );
+                    }
                     return Ok(());
                 }
                 None => return Err(CheckerError::UnknownRule),
@@ -361,6 +661,7 @@ impl<'c> ProofChecker<'c> {
 
             if step.rule == "hole" {
                 self.is_holey = true;
+                self.config.check_hole(&step.id, &mut self.warnings)?;
             }
 
             let premises: Vec<_> = step
@@ -371,11 +672,21 @@ impl<'c> ProofChecker<'c> {
                     Premise::new(p, command)
                 })
                 .collect();
+
+            // If an alpha-equivalent step was already found to be valid, or if this step was
+            // found to be valid in a previous check and has not changed since, we don't need to
+            // check it again
             let discharge: Vec<_> = step
                 .discharge
                 .iter()
                 .map(|&i| iter.get_premise(i))
                 .collect();
+            let entry = (self.incremental.as_ref())
+                .map(|cache| cache.entry(step, &premises, previous_command, &discharge));
+            let is_cached = self.step_cache.check(self.pool, step)
+                || (self.incremental.as_ref())
+                    .zip(entry.as_ref())
+                    .is_some_and(|(cache, e)| cache.is_valid(step, e));
 
             let rule_args = RuleArgs {
                 conclusion: &step.clause,
@@ -389,16 +700,32 @@ impl<'c> ProofChecker<'c> {
             };
 
             if let Some(elaborator) = &mut self.elaborator {
-                if let Some(elaboration_rule) = Self::get_elaboration_rule(&step.rule) {
+                if let Some(elaboration_rule) = self.elaboration_rules.get(step.rule.as_str()) {
                     elaboration_rule(rule_args, step.id.clone(), elaborator)?;
+                    elaborator.set_elaborated_weight(step.weight);
                     elaborated = true;
                 } else {
-                    rule(rule_args)?;
+                    if !is_cached {
+                        rule(rule_args)?;
+                    }
                     elaborator.unchanged(&step.clause);
                 }
-            } else {
+            } else if !is_cached {
                 rule(rule_args)?;
             }
+
+            if !is_cached {
+                self.step_cache.insert(self.pool, step);
+            }
+            self.config.check_tautological_premises(step, &premises);
+            self.config
+                .check_unused_premises(step, &premises, &mut self.warnings);
+            if let Some((cache, e)) = self.incremental.as_mut().zip(entry) {
+                if !is_cached {
+                    cache.num_checked_steps += 1;
+                }
+                cache.insert(step, e);
+            }
         }
 
         if iter.is_end_step() {
@@ -470,6 +797,7 @@ impl<'c> ProofChecker<'c> {
             "eq_congruent_pred" => congruence::eq_congruent_pred,
             "distinct_elim" => clausification::distinct_elim,
             "la_rw_eq" => linear_arithmetic::la_rw_eq,
+            "la_generic" if strict => linear_arithmetic::strict_la_generic,
             "la_generic" => linear_arithmetic::la_generic,
             "la_disequality" => linear_arithmetic::la_disequality,
             "la_totality" => linear_arithmetic::la_totality,
@@ -570,19 +898,52 @@ impl<'c> ProofChecker<'c> {
         })
     }
 
-    fn get_elaboration_rule(rule_name: &str) -> Option<ElaborationRule> {
+    fn default_elaboration_rules() -> HashMap<&'static str, ElaborationRule> {
         use rules::*;
 
-        Some(match rule_name {
-            "eq_transitive" => transitivity::elaborate_eq_transitive,
-            "resolution" | "th_resolution" => resolution::elaborate_resolution,
-            "refl" => reflexivity::elaborate_refl,
-            "trans" => transitivity::elaborate_trans,
-            _ => return None,
-        })
+        let rules: [(_, ElaborationRule); 6] = [
+            ("eq_transitive", transitivity::elaborate_eq_transitive),
+            ("resolution", resolution::elaborate_resolution),
+            ("th_resolution", resolution::elaborate_resolution),
+            ("refl", reflexivity::elaborate_refl),
+            ("trans", transitivity::elaborate_trans),
+            ("cong", congruence::elaborate_cong),
+        ];
+        rules.into_iter().collect()
     }
 }
 
+/// Checks a single step in isolation, given the clauses of its premises. This dispatches directly
+/// to the checking function of `rule`, without needing to construct a whole proof.
+///
+/// Since the step is checked outside of any proof, rules that end subproofs or that depend on the
+/// subproof context (like `bind` or `subproof`) cannot be checked with this function. Rules are
+/// checked in non-strict mode, and this returns `CheckerError::UnknownRule` if `rule` is not a
+/// known rule.
+pub fn check_step(
+    pool: &mut dyn TermPool,
+    rule: &str,
+    clause: &[Rc<Term>],
+    premises: &[&[Rc<Term>]],
+    args: &[ProofArg],
+) -> RuleResult {
+    let rule = ProofChecker::get_rule(rule, false).ok_or(CheckerError::UnknownRule)?;
+    let premises: Vec<_> = (premises.iter().enumerate())
+        .map(|(i, &clause)| Premise { id: "", clause, index: (0, i) })
+        .collect();
+    let mut polyeq_time = Duration::ZERO;
+    rule(RuleArgs {
+        conclusion: clause,
+        premises: &premises,
+        args,
+        pool,
+        context: &mut ContextStack::new(),
+        previous_command: None,
+        discharge: &[],
+        polyeq_time: &mut polyeq_time,
+    })
+}
+
 pub fn generate_lia_smt_instances(
     prelude: ProblemPrelude,
     proof: &Proof,
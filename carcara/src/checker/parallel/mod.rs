@@ -4,7 +4,7 @@ use super::{
     error::{CheckerError, SubproofError},
     lia_generic,
     rules::{Premise, RuleArgs, RuleResult},
-    Config, ProofChecker,
+    CheckerWarning, Config, ProofChecker,
 };
 use crate::benchmarking::{CollectResults, OnlineBenchmarkResults};
 use crate::checker::CheckerStatistics;
@@ -28,6 +28,7 @@ pub struct ParallelProofChecker<'c> {
     context: ContextStack,
     reached_empty_clause: bool,
     is_holey: bool,
+    warnings: Vec<CheckerWarning>,
     stack_size: usize,
 }
 
@@ -46,6 +47,7 @@ impl<'c> ParallelProofChecker<'c> {
             context: ContextStack::from_usage(context_usage),
             reached_empty_clause: false,
             is_holey: false,
+            warnings: Vec::new(),
             stack_size,
         }
     }
@@ -59,11 +61,20 @@ impl<'c> ParallelProofChecker<'c> {
             context: ContextStack::from_previous(&self.context),
             reached_empty_clause: false,
             is_holey: false,
+            warnings: Vec::new(),
             stack_size: self.stack_size,
         }
     }
 
+    /// Returns the warnings emitted during the last check. The warnings emitted by each thread are
+    /// grouped together, so they may not be in the same order as the steps in the proof.
+    pub fn warnings(&self) -> &[CheckerWarning] {
+        &self.warnings
+    }
+
     pub fn check(&mut self, proof: &Proof, scheduler: &Scheduler) -> CarcaraResult<bool> {
+        self.warnings.clear();
+
         // Used to estimulate threads to abort prematurely (only happens when a
         // thread already found out an invalid step)
         let premature_abort = Arc::new(AtomicBool::new(false));
@@ -83,15 +94,20 @@ impl<'c> ParallelProofChecker<'c> {
                     thread::Builder::new()
                         .name(format!("worker-{i}"))
                         .stack_size(self.stack_size)
-                        .spawn_scoped(s, move || -> CarcaraResult<(bool, bool)> {
-                            local_self.worker_thread_check(
-                                proof,
-                                schedule,
-                                local_pool,
-                                should_abort,
-                                None::<&mut CheckerStatistics<OnlineBenchmarkResults>>,
-                            )
-                        })
+                        .spawn_scoped(
+                            s,
+                            move || -> CarcaraResult<(bool, bool, Vec<CheckerWarning>)> {
+                                local_self
+                                    .worker_thread_check(
+                                        proof,
+                                        schedule,
+                                        local_pool,
+                                        should_abort,
+                                        None::<&mut CheckerStatistics<OnlineBenchmarkResults>>,
+                                    )
+                                    .map(|r| (r.0, r.1, local_self.warnings))
+                            },
+                        )
                         .unwrap()
                 })
                 .collect();
@@ -106,9 +122,10 @@ impl<'c> ParallelProofChecker<'c> {
                 .map(|t| t.join().unwrap())
                 .try_for_each(|opt| {
                     match opt {
-                        Ok((local_reached, local_holey)) => {
+                        Ok((local_reached, local_holey, local_warnings)) => {
                             // Mask the result booleans
                             (reached, holey) = (reached | local_reached, holey | local_holey);
+                            self.warnings.extend(local_warnings);
                             ControlFlow::Continue(())
                         }
                         Err(e) => {
@@ -135,6 +152,8 @@ impl<'c> ParallelProofChecker<'c> {
         scheduler: &Scheduler,
         stats: &mut CheckerStatistics<CR>,
     ) -> CarcaraResult<bool> {
+        self.warnings.clear();
+
         // Used to estimulate threads to abort prematurely (only happens when a
         // thread already found out an invalid step)
         let premature_abort = Arc::new(AtomicBool::new(false));
@@ -164,7 +183,7 @@ impl<'c> ParallelProofChecker<'c> {
                         .stack_size(self.stack_size)
                         .spawn_scoped(
                             s,
-                            move || -> CarcaraResult<(bool, bool, CheckerStatistics<CR>)> {
+                            move || -> CarcaraResult<(bool, bool, _, CheckerStatistics<CR>)> {
                                 local_self
                                     .worker_thread_check(
                                         proof,
@@ -173,7 +192,7 @@ impl<'c> ParallelProofChecker<'c> {
                                         should_abort,
                                         Some(&mut local_stats),
                                     )
-                                    .map(|r| (r.0, r.1, local_stats))
+                                    .map(|r| (r.0, r.1, local_self.warnings, local_stats))
                             },
                         )
                         .unwrap()
@@ -190,12 +209,12 @@ impl<'c> ParallelProofChecker<'c> {
                 .map(|t| t.join().unwrap())
                 .for_each(|opt| {
                     match opt {
-                        Ok((local_reached, local_holey, mut local_stats)) => {
+                        Ok((local_reached, local_holey, local_warnings, mut local_stats)) => {
+                            self.warnings.extend(local_warnings);
+
                             // Combine the statistics
-                            // Takes the external and local benchmark results to local variables and combine them
-                            let main = std::mem::take(&mut stats.results);
                             let to_merge = std::mem::take(&mut local_stats.results);
-                            stats.results = CR::combine(main, to_merge);
+                            stats.results.combine_in_place(to_merge);
 
                             // Make sure other times are updated
                             stats.elaboration_time += local_stats.elaboration_time;
@@ -237,10 +256,16 @@ impl<'c> ParallelProofChecker<'c> {
     ) -> CarcaraResult<(bool, bool)> {
         use std::sync::atomic::Ordering;
 
+        let start = Instant::now();
         let mut iter = schedule.iter(&proof.commands[..]);
         let mut last_depth = 0;
 
         while let Some(command) = iter.next() {
+            self.config.check_timeout(start).map_err(|e| {
+                should_abort.store(true, Ordering::Release);
+                e
+            })?;
+
             // If there is any depth difference between the current and last step
             while (last_depth - iter.depth() as i64 > 0)
                 || (last_depth - iter.depth() as i64 == 0
@@ -276,6 +301,7 @@ impl<'c> ParallelProofChecker<'c> {
                                 inner: e,
                                 rule: step.rule.clone(),
                                 step: step.id.clone(),
+                                clause: step.clause.clone(),
                             }
                         })?;
 
@@ -313,6 +339,7 @@ impl<'c> ParallelProofChecker<'c> {
                             inner: CheckerError::Assume(term.clone()),
                             rule: "assume".into(),
                             step: id.clone(),
+                            clause: vec![term.clone()],
                         });
                     }
                 }
@@ -406,6 +433,8 @@ impl<'c> ParallelProofChecker<'c> {
         let time = Instant::now();
         let mut polyeq_time = Duration::ZERO;
 
+        self.config.check_rule_is_allowed(&step.rule)?;
+
         if !step.discharge.is_empty() && step.rule != "subproof" {
             return Err(CheckerError::Subproof(SubproofError::DischargeInWrongRule));
         }
@@ -419,6 +448,12 @@ impl<'c> ParallelProofChecker<'c> {
                 log::warn!("encountered \"lia_generic\" rule, ignoring");
                 self.is_holey = true;
             }
+        } else if let Some(result) =
+            (self.config).check_trusted_lemma(pool, &step.rule, &step.clause)
+        {
+            // Trusted lemmas are accepted without proof, so they make the proof holey
+            result?;
+            self.is_holey = true;
         } else {
             let rule = match ProofChecker::get_rule(&step.rule, self.config.strict) {
                 Some(r) => r,
@@ -431,6 +466,7 @@ impl<'c> ParallelProofChecker<'c> {
 
             if step.rule == "hole" {
                 self.is_holey = true;
+                self.config.check_hole(&step.id, &mut self.warnings)?;
             }
 
             let premises: Vec<_> = step
@@ -459,6 +495,9 @@ impl<'c> ParallelProofChecker<'c> {
             };
 
             rule(rule_args)?;
+            self.config.check_tautological_premises(step, &premises);
+            self.config
+                .check_unused_premises(step, &premises, &mut self.warnings);
         }
 
         if iter.is_end_step() {
@@ -1,5 +1,6 @@
 use super::{
-    assert_clause_len, assert_num_premises, get_premise_term, CheckerError, RuleArgs, RuleResult,
+    assert_clause_len, assert_num_premises, get_premise_term, CheckerError, Elaborator, RuleArgs,
+    RuleResult,
 };
 use crate::{ast::*, checker::error::CongruenceError};
 
@@ -93,26 +94,42 @@ fn check_cong<'a, I>(premises: &[(&'a Rc<Term>, &'a Rc<Term>)], f_args: I, g_arg
 where
     I: IntoIterator<Item = &'a Rc<Term>>,
 {
-    let mut premises = premises.iter().peekable();
+    trace_cong(premises, f_args, g_args).map(|_| ())
+}
+
+/// Checks a `cong` step, and returns, for each pair of arguments, the index of the premise used to
+/// justify their equality, and whether that premise is flipped. If the arguments are directly
+/// equal and no premise was used, the entry is `None` instead.
+fn trace_cong<'a, I>(
+    premises: &[(&'a Rc<Term>, &'a Rc<Term>)],
+    f_args: I,
+    g_args: I,
+) -> Result<Vec<Option<(usize, bool)>>, CheckerError>
+where
+    I: IntoIterator<Item = &'a Rc<Term>>,
+{
+    let mut result = Vec::new();
+    let mut premises = premises.iter().enumerate().peekable();
     for (f_arg, g_arg) in f_args.into_iter().zip(g_args) {
         let expected = (f_arg.as_ref(), g_arg.as_ref());
         match premises.peek() {
             // If the next premise can justify that the arguments are equal, we consume it. We
             // prefer consuming the premise even if the arguments are directly equal
-            Some((t, u)) if expected == (t, u) || expected == (u, t) => {
+            Some(&(i, (t, u))) if expected == (t, u) || expected == (u, t) => {
+                result.push(Some((i, expected != (t, u))));
                 premises.next();
             }
 
             // If the arguments are directly equal, we simply continue to the next pair of
             // arguments
-            _ if f_arg == g_arg => (),
+            _ if f_arg == g_arg => result.push(None),
 
             // If the arguments are not directly equal, we needed a premise that can justify
             // their equality, so now we return an error
             None => {
                 return Err(CongruenceError::MissingPremise(f_arg.clone(), g_arg.clone()).into());
             }
-            Some((t, u)) => {
+            Some((_, (t, u))) => {
                 return Err(CongruenceError::PremiseDoesntJustifyArgs {
                     args: (f_arg.clone(), g_arg.clone()),
                     premise: ((*t).clone(), (*u).clone()),
@@ -124,45 +141,19 @@ where
 
     // At the end, all premises must have been consumed
     if premises.next().is_none() {
-        Ok(())
+        Ok(result)
     } else {
         Err(CongruenceError::TooManyPremises.into())
     }
 }
 
-pub fn cong(RuleArgs { conclusion, premises, .. }: RuleArgs) -> RuleResult {
-    assert_clause_len(conclusion, 1)?;
-    assert_num_premises(premises, 1..)?;
+/// The arguments of the two sides of a congruence.
+type CongArgs<'a> = (&'a [Rc<Term>], &'a [Rc<Term>]);
 
-    let premises: Vec<_> = premises
-        .iter()
-        .map(|premise| match_term_err!((= t u) = get_premise_term(premise)?))
-        .collect::<Result<_, _>>()?;
-
-    let (f, g) = match_term_err!((= f g) = &conclusion[0])?;
+/// Extracts the arguments of the two sides of the conclusion of a `cong` step, checking that they
+/// are applications of the same function or operator.
+fn get_cong_args<'a>(f: &'a Rc<Term>, g: &'a Rc<Term>) -> Result<CongArgs<'a>, CheckerError> {
     let (f_args, g_args) = match (f.as_ref(), g.as_ref()) {
-        // Because of the way veriT handles equality terms, when the `cong` rule is called with two
-        // equalities of two terms, the order of their arguments may be flipped. Because of that,
-        // we have to treat this special case separately
-        (Term::Op(Operator::Equals, f_args), Term::Op(Operator::Equals, g_args))
-            if f_args.len() == 2 && g_args.len() == 2 =>
-        {
-            // We have to test all four possibilities: neither f nor g are flipped, only f is
-            // flipped, only g is flipped, or both f and g are flipped
-            let f_args_flipped: &[_] = &[f_args[1].clone(), f_args[0].clone()];
-            let g_args_flipped: &[_] = &[g_args[1].clone(), g_args[0].clone()];
-
-            // We store the result of the first possibility (when neither arguments are flipped),
-            // because, if the checking fails in the end, we use it to get more sensible error
-            // messages
-            let original_result = check_cong(&premises, f_args, g_args);
-            let any_valid = original_result.is_ok()
-                || check_cong(&premises, f_args_flipped, g_args.as_slice()).is_ok()
-                || check_cong(&premises, f_args.as_slice(), g_args_flipped).is_ok()
-                || check_cong(&premises, f_args_flipped, g_args_flipped).is_ok();
-            return if any_valid { Ok(()) } else { original_result };
-        }
-
         (Term::App(f, f_args), Term::App(g, g_args)) => match f == g {
             true => Ok((f_args, g_args)),
             false => Err(CongruenceError::DifferentFunctions(f.clone(), g.clone())),
@@ -203,9 +194,119 @@ pub fn cong(RuleArgs { conclusion, premises, .. }: RuleArgs) -> RuleResult {
         f_args.len() == g_args.len(),
         CongruenceError::DifferentNumberOfArguments(f_args.len(), g_args.len())
     );
+    Ok((f_args, g_args))
+}
+
+/// Checks a `cong` step whose conclusion is an equality between two equalities. Because of the way
+/// veriT handles equality terms, in this case the order of their arguments may be flipped.
+fn check_cong_equalities(
+    premises: &[(&Rc<Term>, &Rc<Term>)],
+    (f_1, f_2): (&Rc<Term>, &Rc<Term>),
+    (g_1, g_2): (&Rc<Term>, &Rc<Term>),
+) -> RuleResult {
+    // We have to test all four possibilities: neither f nor g are flipped, only f is flipped, only
+    // g is flipped, or both f and g are flipped
+    let f_args: &[_] = &[f_1.clone(), f_2.clone()];
+    let g_args: &[_] = &[g_1.clone(), g_2.clone()];
+    let f_args_flipped: &[_] = &[f_2.clone(), f_1.clone()];
+    let g_args_flipped: &[_] = &[g_2.clone(), g_1.clone()];
+
+    // We store the result of the first possibility (when neither arguments are flipped), because,
+    // if the checking fails in the end, we use it to get more sensible error messages
+    let original_result = check_cong(premises, f_args, g_args);
+    let any_valid = original_result.is_ok()
+        || check_cong(premises, f_args_flipped, g_args).is_ok()
+        || check_cong(premises, f_args, g_args_flipped).is_ok()
+        || check_cong(premises, f_args_flipped, g_args_flipped).is_ok();
+    if any_valid {
+        Ok(())
+    } else {
+        original_result
+    }
+}
+
+pub fn cong(RuleArgs { conclusion, premises, .. }: RuleArgs) -> RuleResult {
+    assert_clause_len(conclusion, 1)?;
+    assert_num_premises(premises, 1..)?;
+
+    let premises: Vec<_> = premises
+        .iter()
+        .map(|premise| match_term_err!((= t u) = get_premise_term(premise)?))
+        .collect::<Result<_, _>>()?;
+
+    let (f, g) = match_term_err!((= f g) = &conclusion[0])?;
+    if let (Some(f), Some(g)) = (match_term!((= a b) = f), match_term!((= a b) = g)) {
+        return check_cong_equalities(&premises, f, g);
+    }
+
+    let (f_args, g_args) = get_cong_args(f, g)?;
     check_cong(&premises, f_args, g_args)
 }
 
+pub fn elaborate_cong(
+    RuleArgs { conclusion, premises, pool, .. }: RuleArgs,
+    command_id: String,
+    elaborator: &mut Elaborator,
+) -> RuleResult {
+    assert_clause_len(conclusion, 1)?;
+    assert_num_premises(premises, 1..)?;
+
+    let premise_equalities: Vec<_> = premises
+        .iter()
+        .map(|premise| match_term_err!((= t u) = get_premise_term(premise)?))
+        .collect::<Result<_, _>>()?;
+
+    let (f, g) = match_term_err!((= f g) = &conclusion[0])?;
+    let (f_args, g_args) = get_cong_args(f, g)?;
+    let trace = match trace_cong(&premise_equalities, f_args, g_args) {
+        Ok(trace) => trace,
+        Err(e) => {
+            // If the conclusion is an equality between two equalities, the step might still be
+            // valid if the arguments of these equalities are flipped. In that case, we can't
+            // elaborate the step without also introducing steps to flip the equalities, so we leave
+            // it unchanged
+            if let (Some(f), Some(g)) = (match_term!((= a b) = f), match_term!((= a b) = g)) {
+                check_cong_equalities(&premise_equalities, f, g)?;
+                elaborator.unchanged(conclusion);
+                return Ok(());
+            }
+            return Err(e);
+        }
+    };
+
+    // In the elaborated step, each pair of arguments is justified by exactly one premise, in the
+    // order of the arguments. Premises that are flipped are justified by a new `symm` step, and
+    // arguments that are directly equal are justified by a new `refl` step
+    let new_premises = trace
+        .into_iter()
+        .zip(f_args.iter().zip(g_args))
+        .map(|(entry, (f_arg, g_arg))| match entry {
+            Some((i, false)) => elaborator.map_index(premises[i].index),
+            Some((i, true)) => {
+                let (t, u) = premise_equalities[i];
+                let premise = elaborator.map_index(premises[i].index);
+                let id = elaborator.get_new_id(&command_id);
+                elaborator.add_symm_step(pool, premise, (t.clone(), u.clone()), id)
+            }
+            None => {
+                let id = elaborator.get_new_id(&command_id);
+                elaborator.add_refl_step(pool, f_arg.clone(), g_arg.clone(), id)
+            }
+        })
+        .collect();
+
+    elaborator.push_elaborated_step(ProofStep {
+        id: command_id,
+        clause: conclusion.into(),
+        rule: "cong".into(),
+        premises: new_premises,
+        args: Vec::new(),
+        discharge: Vec::new(),
+        weight: None,
+    });
+    Ok(())
+}
+
 pub fn ho_cong(RuleArgs { conclusion, premises, .. }: RuleArgs) -> RuleResult {
     use std::iter::once;
 
@@ -455,6 +556,57 @@ mod tests {
         }
     }
 
+    #[test]
+    fn elaborate_cong() {
+        use crate::checker::rules::run_elaboration_tests;
+
+        let definitions = "
+            (declare-sort T 0)
+            (declare-fun a () T)
+            (declare-fun b () T)
+            (declare-fun c () T)
+            (declare-fun d () T)
+            (declare-fun f (T Bool T) T)
+            (declare-fun p () Bool)
+            (declare-fun q () Bool)
+        ";
+        run_elaboration_tests(
+            "elaborate_cong",
+            definitions,
+            &[
+                (
+                    "(assume h1 (= a b)) (assume h2 (= p q)) (assume h3 (= c d))
+                    (step t4 (cl (= (f a p c) (f b q d))) :rule cong :premises (h1 h2 h3))",
+                    "(assume h1 (= a b)) (assume h2 (= p q)) (assume h3 (= c d))
+                    (step t4 (cl (= (f a p c) (f b q d))) :rule cong :premises (h1 h2 h3))",
+                ),
+                (
+                    "(assume h1 (= a b)) (assume h2 (= c d))
+                    (step t3 (cl (= (f a p c) (f b p d))) :rule cong :premises (h1 h2))",
+                    "(assume h1 (= a b)) (assume h2 (= c d))
+                    (step t3.t1 (cl (= p p)) :rule refl)
+                    (step t3 (cl (= (f a p c) (f b p d))) :rule cong :premises (h1 t3.t1 h2))",
+                ),
+                (
+                    "(assume h1 (= b a)) (assume h2 (= d c))
+                    (step t3 (cl (= (f a p c) (f b p d))) :rule cong :premises (h1 h2))",
+                    "(assume h1 (= b a)) (assume h2 (= d c))
+                    (step t3.t1 (cl (= a b)) :rule symm :premises (h1))
+                    (step t3.t2 (cl (= p p)) :rule refl)
+                    (step t3.t3 (cl (= c d)) :rule symm :premises (h2))
+                    (step t3 (cl (= (f a p c) (f b p d))) :rule cong
+                        :premises (t3.t1 t3.t2 t3.t3))",
+                ),
+                (
+                    "(assume h1 (= a b)) (assume h2 (= c d))
+                    (step t3 (cl (= (= c a) (= d b))) :rule cong :premises (h1 h2))",
+                    "(assume h1 (= a b)) (assume h2 (= c d))
+                    (step t3 (cl (= (= c a) (= d b))) :rule cong :premises (h1 h2))",
+                ),
+            ],
+        );
+    }
+
     #[test]
     fn ho_cong() {
         test_cases! {
@@ -5,7 +5,8 @@ use super::{
     EqualityError, RuleArgs, RuleResult,
 };
 use crate::{ast::*, checker::rules::assert_operation_len};
-use indexmap::IndexSet;
+use indexmap::{IndexMap, IndexSet};
+use std::collections::VecDeque;
 
 pub fn reordering(RuleArgs { conclusion, premises, .. }: RuleArgs) -> RuleResult {
     assert_num_premises(premises, 1)?;
@@ -24,6 +25,23 @@ pub fn reordering(RuleArgs { conclusion, premises, .. }: RuleArgs) -> RuleResult
     }
 }
 
+/// If the clause `b` is a reordering of the clause `a`, returns the permutation that maps `a` to
+/// `b`, that is, a vector `perm` such that `b[i] == a[perm[i]]` for every `i`. Repeated terms are
+/// matched in order, so every index of `a` appears exactly once in the permutation. Returns `None`
+/// if `b` is not a reordering of `a`.
+pub fn clause_permutation(a: &[Rc<Term>], b: &[Rc<Term>]) -> Option<Vec<usize>> {
+    if a.len() != b.len() {
+        return None;
+    }
+    let mut positions: IndexMap<&Rc<Term>, VecDeque<usize>> = IndexMap::new();
+    for (i, t) in a.iter().enumerate() {
+        positions.entry(t).or_default().push_back(i);
+    }
+    b.iter()
+        .map(|t| positions.get_mut(t)?.pop_front())
+        .collect()
+}
+
 pub fn symm(RuleArgs { conclusion, premises, .. }: RuleArgs) -> RuleResult {
     assert_num_premises(premises, 1)?;
     assert_clause_len(conclusion, 1)?;
@@ -231,6 +249,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn clause_permutation() {
+        use crate::{ast::PrimitivePool, parser::tests::parse_terms};
+
+        let mut pool = PrimitivePool::new();
+        let [p, q, r] = parse_terms(
+            &mut pool,
+            "(declare-fun p () Bool) (declare-fun q () Bool) (declare-fun r () Bool)",
+            ["p", "q", "r"],
+        );
+        let a = [p.clone(), q.clone(), p.clone(), r.clone()];
+
+        let b = [r.clone(), p.clone(), q.clone(), p.clone()];
+        assert_eq!(Some(vec![3, 0, 1, 2]), super::clause_permutation(&a, &b));
+        assert_eq!(Some(vec![0, 1, 2, 3]), super::clause_permutation(&a, &a));
+        assert_eq!(Some(vec![]), super::clause_permutation(&[], &[]));
+
+        // The number of occurrences of each term must be the same
+        let b = [r.clone(), q.clone(), q.clone(), p.clone()];
+        assert_eq!(None, super::clause_permutation(&a, &b));
+        assert_eq!(None, super::clause_permutation(&a, &a[1..]));
+        assert_eq!(None, super::clause_permutation(&[p, q], &[r.clone(), r]));
+    }
+
     #[test]
     fn symm() {
         test_cases! {
@@ -258,6 +258,17 @@ fn strengthen(op: Operator, disequality: &mut LinearComb, a: &Rational) -> Opera
 }
 
 pub fn la_generic(RuleArgs { conclusion, args, .. }: RuleArgs) -> RuleResult {
+    check_la_generic(conclusion, args, false)
+}
+
+/// A stricter version of the `la_generic` rule, that requires the coefficients of all
+/// disequalities to be non-negative. In the regular version, negative coefficients are accepted,
+/// and their absolute value is used instead.
+pub fn strict_la_generic(RuleArgs { conclusion, args, .. }: RuleArgs) -> RuleResult {
+    check_la_generic(conclusion, args, true)
+}
+
+fn check_la_generic(conclusion: &[Rc<Term>], args: &[ProofArg], strict: bool) -> RuleResult {
     assert_num_args(args, conclusion.len())?;
 
     let args: Vec<_> = args
@@ -296,10 +307,21 @@ pub fn la_generic(RuleArgs { conclusion, args, .. }: RuleArgs) -> RuleResult {
             // Step 5: Multiply disequality by a
             let a = match op {
                 Operator::Equals => a,
+                _ if strict && a < 0 => {
+                    return Err(LinearArithmeticError::NegativeCoefficient(phi.clone(), a).into())
+                }
                 _ => a.abs(),
             };
             disequality.mul(&a);
 
+            // If the coefficient is zero, the disequality becomes `0 > 0` or `0 >= 0`. In the
+            // first case, the disequality must not make the final disequality strict, as that
+            // would make any combination contradictory
+            let op = match op {
+                Operator::GreaterThan if a == 0 => Operator::GreaterEq,
+                _ => op,
+            };
+
             Ok((op, disequality))
         })
         .try_fold(
@@ -515,6 +537,12 @@ mod tests {
                 "(step t1 (cl (< (+ a b) 1.0) (> (+ a b c) 0.0))
                     :rule la_generic :args (1.0 (- 1.0)))": false,
             }
+            "Coefficient is zero" {
+                "(step t1 (cl (<= a 0.0)) :rule la_generic :args (0.0))": false,
+                "(step t1 (cl (< a 0.0)) :rule la_generic :args (0.0))": false,
+                "(step t1 (cl (<= a 0.0) (<= 0.0 1.0)) :rule la_generic :args (0.0 1.0))": true,
+                "(step t1 (cl (<= a 0.0) (< 0.0 0.0)) :rule la_generic :args (0.0 1.0))": false,
+            }
             "Edge case where the strengthening rules need to be stronger" {
                 "(step t1 (cl
                     (not (<= (- 1) n))
@@ -526,6 +554,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn strict_la_generic() {
+        test_cases! {
+            definitions = "
+                (declare-fun a () Real)
+                (declare-fun b () Real)
+                (declare-fun m () Int)
+            ",
+            strict = true,
+            "Simple working examples" {
+                "(step t1 (cl (> a 0.0) (<= a 0.0)) :rule la_generic :args (1.0 1.0))": true,
+                "(step t1 (cl (< (+ a b) 1.0) (< (- (+ a b)) 0.0))
+                    :rule la_generic :args (1.0 1.0))": true,
+                "(step t1 (cl (not (= a 1.0)) (not (= a 2.0)))
+                    :rule la_generic :args (1.0 (- 1.0)))": true,
+            }
+            "Negative coefficient for disequality" {
+                "(step t1 (cl (< (+ a b) 1.0) (> (+ a b) 0.0))
+                    :rule la_generic :args (1.0 (- 1.0)))": false,
+                "(step t1 (cl (not (<= m 0)) (not (>= m 1)))
+                    :rule la_generic :args ((- 1) 1))": false,
+            }
+            "Non-strict combination is not contradictory" {
+                "(step t1 (cl (< a 0.0) (< (- a) 0.0)) :rule la_generic :args (1.0 1.0))": false,
+                "(step t1 (cl (<= a 0.0) (<= (- a) 0.0)) :rule la_generic :args (1.0 1.0))": true,
+            }
+        }
+    }
+
     #[test]
     fn la_disequality() {
         test_cases! {
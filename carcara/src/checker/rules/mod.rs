@@ -15,19 +15,19 @@ pub type Rule = fn(RuleArgs) -> RuleResult;
 pub type ElaborationRule = fn(RuleArgs, String, &mut Elaborator) -> Result<(), CheckerError>;
 
 pub struct RuleArgs<'a> {
-    pub(super) conclusion: &'a [Rc<Term>],
-    pub(super) premises: &'a [Premise<'a>],
-    pub(super) args: &'a [ProofArg],
-    pub(super) pool: &'a mut dyn TermPool,
-    pub(super) context: &'a mut ContextStack,
+    pub conclusion: &'a [Rc<Term>],
+    pub premises: &'a [Premise<'a>],
+    pub args: &'a [ProofArg],
+    pub pool: &'a mut dyn TermPool,
+    pub context: &'a mut ContextStack,
 
     // For rules that end a subproof, we need to pass the previous command in the subproof that it
     // is closing, because it may be implicitly referenced, and it is not given as premises. If a
     // rule is not ending a subproof, this should be `None`.
-    pub(super) previous_command: Option<Premise<'a>>,
-    pub(super) discharge: &'a [&'a ProofCommand],
+    pub previous_command: Option<Premise<'a>>,
+    pub discharge: &'a [&'a ProofCommand],
 
-    pub(super) polyeq_time: &'a mut Duration,
+    pub polyeq_time: &'a mut Duration,
 }
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
@@ -158,7 +158,7 @@ fn assert_is_bool_constant(got: &Rc<Term>, expected: bool) -> RuleResult {
 }
 
 #[cfg(test)]
-fn run_tests(test_name: &str, definitions: &str, cases: &[(&str, bool)]) {
+fn run_tests(test_name: &str, definitions: &str, cases: &[(&str, bool)], strict: bool) {
     use crate::{checker, parser};
     use std::io::Cursor;
 
@@ -192,9 +192,11 @@ fn run_tests(test_name: &str, definitions: &str, cases: &[(&str, bool)]) {
             premises: Vec::new(),
             args: Vec::new(),
             discharge: Vec::new(),
+            weight: None,
         }));
 
-        let mut checker = checker::ProofChecker::new(&mut pool, checker::Config::new(), &prelude);
+        let config = checker::Config::new().strict(strict);
+        let mut checker = checker::ProofChecker::new(&mut pool, config, &prelude);
         let got = checker.check(&proof).is_ok();
         assert_eq!(
             *expected, got,
@@ -204,17 +206,93 @@ fn run_tests(test_name: &str, definitions: &str, cases: &[(&str, bool)]) {
     }
 }
 
+/// Checks and elaborates each proof in `cases`, and compares the result with the expected
+/// elaborated proof.
+#[cfg(test)]
+fn run_elaboration_tests(test_name: &str, definitions: &str, cases: &[(&str, &str)]) {
+    use crate::{checker, parser};
+    use std::io::Cursor;
+
+    fn parse_proof(pool: &mut PrimitivePool, definitions: &str, proof: &str) -> Vec<ProofCommand> {
+        let mut parser =
+            parser::Parser::new(pool, parser::Config::new(), definitions.as_bytes()).unwrap();
+        parser.parse_problem().unwrap();
+        parser.reset(proof.as_bytes()).unwrap();
+        let mut commands = parser.parse_proof().unwrap();
+
+        // See `run_tests`. Since the elaborator prunes all commands that are not used to reach
+        // the empty clause, the dummy step must use the last command in the proof as a premise
+        commands.push(ProofCommand::Step(ProofStep {
+            id: "end".into(),
+            clause: Vec::new(),
+            rule: "hole".into(),
+            premises: vec![(0, commands.len() - 1)],
+            args: Vec::new(),
+            discharge: Vec::new(),
+            weight: None,
+        }));
+        commands
+    }
+
+    for (i, (original, expected)) in cases.iter().enumerate() {
+        let (prelude, _, mut pool) = parser::parse_instance(
+            Cursor::new(definitions),
+            Cursor::new(""),
+            parser::Config::new(),
+        )
+        .unwrap_or_else(|e| panic!("parser error during test \"{}\": {}", test_name, e));
+
+        let commands = parse_proof(&mut pool, definitions, original);
+        let premises = commands
+            .iter()
+            .filter_map(|c| match c {
+                ProofCommand::Assume { term, .. } => Some(term.clone()),
+                _ => None,
+            })
+            .collect();
+        let proof = Proof {
+            premises,
+            commands,
+            named_terms: Default::default(),
+            original_clauses: Default::default(),
+        };
+
+        let mut checker = checker::ProofChecker::new(&mut pool, checker::Config::new(), &prelude);
+        let (_, elaborated) = checker
+            .check_and_elaborate(proof)
+            .unwrap_or_else(|e| panic!("checker error during test \"{}\": {}", test_name, e));
+
+        let expected = parse_proof(&mut pool, definitions, expected);
+        assert_eq!(
+            expected, elaborated.commands,
+            "elaboration test case \"{}\" index {} failed",
+            test_name, i
+        );
+    }
+}
+
 #[cfg(test)]
 macro_rules! test_cases {
     (
         definitions = $defs:expr,
         $($name:literal { $($proof:literal: $exp:literal,)* } )*
+    ) => {
+        test_cases! {
+            definitions = $defs,
+            strict = false,
+            $($name { $($proof: $exp,)* })*
+        }
+    };
+    (
+        definitions = $defs:expr,
+        strict = $strict:expr,
+        $($name:literal { $($proof:literal: $exp:literal,)* } )*
     ) => {{
         let definitions: &str = $defs;
         $({
             let name: &str = $name;
             let cases = [ $(($proof, $exp),)* ];
-            $crate::checker::rules::run_tests(name, definitions, &cases);
+            $crate::checker::rules::run_tests(name, definitions, &cases, $strict);
         })*
     }};
 }
@@ -367,6 +367,22 @@ mod tests {
                 "(step t1 (cl (or (not (forall ((x Real) (y Real)) (= x y))) (= a b)))
                     :rule forall_inst :args ((:= x a) b))": false,
             }
+            "Alpha-equivalent steps" {
+                "(step t1 (cl (or (not (forall ((x Real) (y Real)) (< x y))) (< a b)))
+                    :rule forall_inst :args ((:= x a) (:= y b)))
+                (step t2 (cl (or (not (forall ((z Real) (w Real)) (< z w))) (< a b)))
+                    :rule forall_inst :args ((:= w b) (:= z a)))": true,
+
+                "(step t1 (cl (or (not (forall ((x Real) (y Real)) (< x y))) (< a b)))
+                    :rule forall_inst :args ((:= x a) (:= y b)))
+                (step t2 (cl (or (not (forall ((z Real) (w Real)) (< z w))) (< a b)))
+                    :rule forall_inst :args ((:= z b) (:= w a)))": false,
+
+                "(step t1 (cl (or (not (forall ((x Real) (y Real)) (< x y))) (< a b)))
+                    :rule forall_inst :args ((:= x a) (:= y b)))
+                (step t2 (cl (or (not (forall ((z Real) (w Real)) (< z w))) (< a b)))
+                    :rule forall_inst :args ((:= x a) (:= y b)))": false,
+            }
         }
     }
 
@@ -125,19 +125,26 @@ pub fn elaborate_refl(
     if alpha_equiv(left, right, polyeq_time) {
         let equality_step =
             elaborate_equality(elaborator, pool, left, right, &command_id, polyeq_time);
-        let id = elaborator.get_new_id(&command_id);
 
-        // TODO: Elaborating the polyequality will add new commands to the accumulator, but
-        // currently we can't push them as the elaborated step directly, so we need to add this
-        // dummy `reordering` step.
-        elaborator.push_elaborated_step(ProofStep {
-            id,
-            clause: conclusion.to_vec(),
-            rule: "reordering".to_owned(),
-            premises: vec![equality_step],
-            args: Vec::new(),
-            discharge: Vec::new(),
-        });
+        // Elaborating the polyequality adds new commands to the accumulator. If the last of these
+        // is a step that already concludes the `refl` clause, we push it as the elaborated step
+        // directly. Otherwise (for example, when the last command is a `bind` subproof), we need
+        // to add this dummy `reordering` step.
+        if elaborator
+            .push_last_step_as_elaborated(equality_step, conclusion)
+            .is_none()
+        {
+            let id = elaborator.get_new_id(&command_id);
+            elaborator.push_elaborated_step(ProofStep {
+                id,
+                clause: conclusion.to_vec(),
+                rule: "reordering".to_owned(),
+                premises: vec![equality_step],
+                args: Vec::new(),
+                discharge: Vec::new(),
+                weight: None,
+            });
+        }
     } else {
         let id = elaborator.get_new_id(&command_id);
         let first_step = elaborator.add_refl_step(pool, left.clone(), new_left.clone(), id);
@@ -153,6 +160,7 @@ pub fn elaborate_refl(
                 premises: vec![first_step, second_step],
                 args: Vec::new(),
                 discharge: Vec::new(),
+                weight: None,
             });
         } else if alpha_equiv(&new_left, &new_right, polyeq_time) {
             let second_step =
@@ -168,6 +176,7 @@ pub fn elaborate_refl(
                 premises: vec![first_step, second_step, third_step],
                 args: Vec::new(),
                 discharge: Vec::new(),
+                weight: None,
             });
         } else {
             return Err(CheckerError::ReflexivityFailed(left.clone(), right.clone()));
@@ -259,4 +268,54 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn elaborate_refl() {
+        use crate::checker::rules::run_elaboration_tests;
+
+        let definitions = "
+            (declare-fun p (Int) Bool)
+            (declare-fun q (Int Int) Bool)
+        ";
+        run_elaboration_tests(
+            "elaborate_refl",
+            definitions,
+            &[
+                (
+                    "(step t1 (cl (= (forall ((x Int)) (p x)) (forall ((x Int)) (p x)))) :rule refl)",
+                    "(step t1 (cl (= (forall ((x Int)) (p x)) (forall ((x Int)) (p x)))) :rule refl)",
+                ),
+                (
+                    "(step t1 (cl (= (forall ((x Int)) (p x)) (forall ((y Int)) (p y)))) :rule refl)",
+                    "(anchor :step t1.t1 :args ((x Int) (y Int) (:= (x Int) y)))
+                    (step t1.t1.t1 (cl (= (p x) (p y))) :rule refl)
+                    (step t1.t1 (cl (= (forall ((x Int)) (p x)) (forall ((y Int)) (p y))))
+                        :rule bind)
+                    (step t1.t2 (cl (= (forall ((x Int)) (p x)) (forall ((y Int)) (p y))))
+                        :rule reordering :premises (t1.t1))",
+                ),
+                (
+                    "(step t1 (cl (= (exists ((x Int) (y Int)) (q x y))
+                        (exists ((y Int) (z Int)) (q y z)))) :rule refl)",
+                    "(anchor :step t1.t1 :args
+                        ((x Int) (y Int) (z Int) (:= (x Int) y) (:= (y Int) z)))
+                    (step t1.t1.t1 (cl (= (q x y) (q y z))) :rule refl)
+                    (step t1.t1 (cl (= (exists ((x Int) (y Int)) (q x y))
+                        (exists ((y Int) (z Int)) (q y z)))) :rule bind)
+                    (step t1.t2 (cl (= (exists ((x Int) (y Int)) (q x y))
+                        (exists ((y Int) (z Int)) (q y z)))) :rule reordering :premises (t1.t1))",
+                ),
+                (
+                    "(step t1 (cl (= (not (forall ((x Int)) (p x))) (not (forall ((y Int)) (p y)))))
+                        :rule refl)",
+                    "(anchor :step t1.t1 :args ((x Int) (y Int) (:= (x Int) y)))
+                    (step t1.t1.t1 (cl (= (p x) (p y))) :rule refl)
+                    (step t1.t1 (cl (= (forall ((x Int)) (p x)) (forall ((y Int)) (p y))))
+                        :rule bind)
+                    (step t1.t2 (cl (= (not (forall ((x Int)) (p x)))
+                        (not (forall ((y Int)) (p y))))) :rule cong :premises (t1.t1))",
+                ),
+            ],
+        );
+    }
 }
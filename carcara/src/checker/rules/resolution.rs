@@ -10,21 +10,89 @@ use crate::{
 use indexmap::{map::Entry, IndexMap, IndexSet};
 use std::iter::FromIterator;
 
-type ResolutionTerm<'a> = (u32, &'a Rc<Term>);
+/// A literal in a clause, represented by its atom together with the number of leading negations
+/// that were removed from it. For example, the term `(not (not p))` is represented by the atom `p`
+/// with two negations.
+///
+/// Keeping the number of negations, instead of only the polarity, allows the original term to be
+/// rebuilt exactly, and distinguishes between literals like `p` and `(not (not p))`, which are not
+/// considered complementary by the resolution rules.
+///
+/// # Examples
+///
+/// ```
+/// # use carcara::{ast::*, build_term, checker::Literal, match_term};
+/// let mut pool = PrimitivePool::new();
+/// let bool_sort = pool.add(Term::Sort(Sort::Bool));
+/// let p = pool.add(Term::new_var("p", bool_sort));
+/// let t = build_term!(pool, (not (not {p.clone()})));
+///
+/// let literal = Literal::new(&t);
+/// assert_eq!(literal.num_negations(), 2);
+/// assert_eq!(literal.atom(), &p);
+/// assert!(literal.polarity());
+///
+/// let negated = literal.negate();
+/// assert_eq!(negated.num_negations(), 3);
+/// assert!(!negated.polarity());
+/// assert_eq!(negated.to_term(&mut pool), build_term!(pool, (not {t.clone()})));
+/// assert_eq!(literal.to_term(&mut pool), t);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Literal<'a>(u32, &'a Rc<Term>);
+
+impl<'a> Literal<'a> {
+    /// Builds a literal from a term, removing all of its leading negations.
+    pub fn new(term: &'a Rc<Term>) -> Self {
+        let (n, atom) = term.remove_all_negations();
+        Self(n, atom)
+    }
+
+    /// Returns the number of leading negations of the literal.
+    pub fn num_negations(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns `true` if the literal has an even number of leading negations.
+    pub fn polarity(&self) -> bool {
+        self.0 % 2 == 0
+    }
+
+    /// Returns the literal's atom, that is, the term without its leading negations.
+    pub fn atom(&self) -> &'a Rc<Term> {
+        self.1
+    }
+
+    /// Returns the literal with one more leading negation.
+    pub fn negate(&self) -> Self {
+        Self(self.0 + 1, self.1)
+    }
+
+    /// Rebuilds the term represented by the literal, adding back its leading negations.
+    pub fn to_term(&self, pool: &mut dyn TermPool) -> Rc<Term> {
+        let mut term = self.1.clone();
+        for _ in 0..self.0 {
+            term = build_term!(pool, (not { term }));
+        }
+        term
+    }
+}
 
 /// A collection that can be used as a clause during resolution.
-trait ClauseCollection<'a>: FromIterator<ResolutionTerm<'a>> {
-    fn insert_term(&mut self, item: ResolutionTerm<'a>);
+trait ClauseCollection<'a>: FromIterator<Literal<'a>> {
+    fn insert_term(&mut self, item: Literal<'a>);
+
+    fn remove_term(&mut self, item: &Literal<'a>) -> bool;
 
-    fn remove_term(&mut self, item: &ResolutionTerm<'a>) -> bool;
+    fn contains_term(&self, item: &Literal<'a>) -> bool;
 }
 
-impl<'a> ClauseCollection<'a> for Vec<ResolutionTerm<'a>> {
-    fn insert_term(&mut self, item: ResolutionTerm<'a>) {
+impl<'a> ClauseCollection<'a> for Vec<Literal<'a>> {
+    fn insert_term(&mut self, item: Literal<'a>) {
         self.push(item);
     }
 
-    fn remove_term(&mut self, item: &ResolutionTerm<'a>) -> bool {
+    fn remove_term(&mut self, item: &Literal<'a>) -> bool {
         if let Some(pos) = self.iter().position(|x| x == item) {
             self.remove(pos);
             true
@@ -32,25 +100,24 @@ impl<'a> ClauseCollection<'a> for Vec<ResolutionTerm<'a>> {
             false
         }
     }
+
+    fn contains_term(&self, item: &Literal<'a>) -> bool {
+        self.contains(item)
+    }
 }
 
-impl<'a> ClauseCollection<'a> for IndexSet<ResolutionTerm<'a>> {
-    fn insert_term(&mut self, item: ResolutionTerm<'a>) {
+impl<'a> ClauseCollection<'a> for IndexSet<Literal<'a>> {
+    fn insert_term(&mut self, item: Literal<'a>) {
         self.insert(item);
     }
 
-    fn remove_term(&mut self, item: &ResolutionTerm<'a>) -> bool {
+    fn remove_term(&mut self, item: &Literal<'a>) -> bool {
         self.remove(item)
     }
-}
 
-/// Undoes the transformation done by `Rc<Term>::remove_all_negations`.
-fn unremove_all_negations(pool: &mut dyn TermPool, (n, term): ResolutionTerm) -> Rc<Term> {
-    let mut term = term.clone();
-    for _ in 0..n {
-        term = build_term!(pool, (not { term }));
+    fn contains_term(&self, item: &Literal<'a>) -> bool {
+        self.contains(item)
     }
-    term
 }
 
 pub fn resolution(rule_args: RuleArgs) -> RuleResult {
@@ -171,7 +238,7 @@ fn greedy_resolution(
                 false
             } else if try_eliminate(below) {
                 if tracing {
-                    pivot_trace.push((unremove_all_negations(pool, (n as u32 - 1, inner)), true));
+                    pivot_trace.push((Literal(n as u32 - 1, inner).to_term(pool), true));
                 }
                 true
             } else if try_eliminate(above) {
@@ -224,14 +291,17 @@ fn greedy_resolution(
             // Usually, we would expect the clause in the t4 step to be (cl f). This behavior may
             // be a bug in veriT, but it is still logically sound and happens often enough that it
             // is useful to support it here.
+            //
+            // When elaborating, we can only reconstruct the conclusion if it has more leading
+            // negations than the pivot, so in the opposite case we return an error instead
             if conclusion.len() == 1 {
                 let (j, conclusion) = conclusion.into_iter().next().unwrap();
-                if conclusion == *pivot && (i % 2) == (j % 2) {
+                if conclusion == *pivot && (i % 2) == (j % 2) && (!tracing || j > *i) {
                     return Ok(ResolutionTrace { not_not_added: true, pivot_trace });
                 }
             }
         }
-        let pivot = unremove_all_negations(pool, (*i as u32, pivot));
+        let pivot = Literal(*i as u32, pivot).to_term(pool);
         Err(ResolutionError::RemainingPivot(pivot).into())
     } else {
         // This is the general case, where all pivots have been eliminated. In this case, the
@@ -240,7 +310,7 @@ fn greedy_resolution(
             // By construction, the working clause is a subset of the conclusion. Therefore, we
             // only need to check that all terms in the conclusion are also in the working clause
             if !working_clause.contains(&(i, t)) {
-                let t = unremove_all_negations(pool, (i as u32, t));
+                let t = Literal(i as u32, t).to_term(pool);
                 return Err(ResolutionError::ExtraTermInConclusion(t).into());
             }
         }
@@ -294,16 +364,58 @@ pub fn resolution_with_args(
         conclusion, premises, args, pool, ..
     }: RuleArgs,
 ) -> RuleResult {
-    let resolution_result = apply_generic_resolution::<IndexSet<_>>(premises, args, pool)?;
+    assert_num_premises(premises, 2..)?;
+    assert_num_args(args, (premises.len() - 1) * 2)?;
+
+    let pivots = parse_pivots(args)?;
+    let clauses: Vec<_> = premises.iter().map(|p| p.clause).collect();
+    check_with_literals(pool, &clauses, &pivots, conclusion)
+}
+
+/// Checks that resolving the `premises` clauses in order, using the given `pivots`, results in the
+/// `conclusion` clause, modulo ordering and duplicate literals.
+///
+/// There must be exactly one pivot for each premise but the first. Each pivot is given together
+/// with its polarity: if it is `true`, the pivot must appear in the clause resulting from the
+/// previous resolutions, and its negation in the next premise; if it is `false`, it is the other
+/// way around.
+pub fn check(
+    pool: &mut dyn TermPool,
+    premises: &[&[Rc<Term>]],
+    pivots: &[(Rc<Term>, bool)],
+    conclusion: &[Rc<Term>],
+) -> RuleResult {
+    let pivots: Vec<_> = pivots.iter().map(|(t, p)| (Literal::new(t), *p)).collect();
+    check_with_literals(pool, premises, &pivots, conclusion)
+}
+
+/// A variant of [`check`] where the pivots are already given as literals.
+fn check_with_literals(
+    pool: &mut dyn TermPool,
+    premises: &[&[Rc<Term>]],
+    pivots: &[(Literal, bool)],
+    conclusion: &[Rc<Term>],
+) -> RuleResult {
+    if premises.is_empty() {
+        return Err(CheckerError::WrongNumberOfPremises((1..).into(), 0));
+    }
+    if pivots.len() != premises.len() - 1 {
+        let expected = premises.len() - 1;
+        return Err(CheckerError::WrongNumberOfArgs(
+            expected.into(),
+            pivots.len(),
+        ));
+    }
+    let resolution_result = apply_generic_resolution::<IndexSet<_>>(pool, premises, pivots)?;
 
-    let conclusion: IndexSet<_> = conclusion.iter().map(Rc::remove_all_negations).collect();
+    let conclusion: IndexSet<_> = conclusion.iter().map(Literal::new).collect();
 
     if let Some(extra) = conclusion.difference(&resolution_result).next() {
-        let extra = unremove_all_negations(pool, *extra);
+        let extra = extra.to_term(pool);
         return Err(ResolutionError::ExtraTermInConclusion(extra).into());
     }
     if let Some(missing) = resolution_result.difference(&conclusion).next() {
-        let missing = unremove_all_negations(pool, *missing);
+        let missing = missing.to_term(pool);
         return Err(ResolutionError::MissingTermInConclusion(missing).into());
     }
     Ok(())
@@ -316,11 +428,16 @@ pub fn strict_resolution(
 ) -> RuleResult {
     use std::cmp::Ordering;
 
-    let resolution_result = apply_generic_resolution::<Vec<_>>(premises, args, pool)?;
+    assert_num_premises(premises, 2..)?;
+    assert_num_args(args, (premises.len() - 1) * 2)?;
+
+    let pivots = parse_pivots(args)?;
+    let clauses: Vec<_> = premises.iter().map(|p| p.clause).collect();
+    let resolution_result = apply_generic_resolution::<Vec<_>>(pool, &clauses, &pivots)?;
 
     match conclusion.len().cmp(&resolution_result.len()) {
         Ordering::Less => {
-            let missing = unremove_all_negations(pool, resolution_result[conclusion.len()]);
+            let missing = resolution_result[conclusion.len()].to_term(pool);
             Err(ResolutionError::MissingTermInConclusion(missing).into())
         }
         Ordering::Greater => {
@@ -329,8 +446,8 @@ pub fn strict_resolution(
         }
         Ordering::Equal => {
             for (t, u) in resolution_result.into_iter().zip(conclusion) {
-                if t != u.remove_all_negations() {
-                    assert_eq(&unremove_all_negations(pool, t), u)?;
+                if t != Literal::new(u) {
+                    assert_eq(&t.to_term(pool), u)?;
                 }
             }
             Ok(())
@@ -338,39 +455,40 @@ pub fn strict_resolution(
     }
 }
 
+/// Reads a pivot given as arguments to a resolution step, from a pair of a pivot term and a
+/// boolean constant indicating its polarity. Returns an error if either argument is not a "term
+/// style" argument, or if the polarity is not a boolean constant.
+pub fn as_pivot<'a>(
+    pivot: &'a ProofArg,
+    polarity: &ProofArg,
+) -> Result<(Literal<'a>, bool), CheckerError> {
+    let pivot = Literal::new(pivot.as_term()?);
+    let polarity = polarity.as_term()?;
+    if polarity.is_bool_true() {
+        Ok((pivot, true))
+    } else if polarity.is_bool_false() {
+        Ok((pivot, false))
+    } else {
+        Err(CheckerError::ExpectedAnyBoolConstant(polarity.clone()))
+    }
+}
+
+/// Reads all the pivots given as arguments to a resolution step. See [`as_pivot`].
+fn parse_pivots(args: &[ProofArg]) -> Result<Vec<(Literal<'_>, bool)>, CheckerError> {
+    args.chunks(2)
+        .map(|chunk| as_pivot(&chunk[0], &chunk[1]))
+        .collect()
+}
+
 fn apply_generic_resolution<'a, C: ClauseCollection<'a>>(
-    premises: &'a [Premise],
-    args: &'a [ProofArg],
     pool: &mut dyn TermPool,
-) -> Result<C, CheckerError> {
-    assert_num_premises(premises, 2..)?;
-    let num_steps = premises.len() - 1;
-    assert_num_args(args, num_steps * 2)?;
-
-    let args: Vec<_> = args
-        .chunks(2)
-        .map(|chunk| {
-            let pivot = chunk[0].as_term()?.remove_all_negations();
-            let polarity = chunk[1].as_term()?;
-            let polarity = if polarity.is_bool_true() {
-                true
-            } else if polarity.is_bool_false() {
-                false
-            } else {
-                return Err(CheckerError::ExpectedAnyBoolConstant(polarity.clone()));
-            };
-            Ok((pivot, polarity))
-        })
-        .collect::<Result<_, _>>()?;
+    premises: &[&'a [Rc<Term>]],
+    pivots: &[(Literal<'a>, bool)],
+) -> Result<C, ResolutionError> {
+    let mut current = premises[0].iter().map(Literal::new).collect();
 
-    let mut current = premises[0]
-        .clause
-        .iter()
-        .map(Rc::remove_all_negations)
-        .collect();
-
-    for (premise, (pivot, polarity)) in premises[1..].iter().zip(args) {
-        binary_resolution(pool, &mut current, premise.clause, pivot, polarity)?;
+    for (&premise, &(pivot, polarity)) in premises[1..].iter().zip(pivots) {
+        binary_resolution(pool, &mut current, premise, pivot, polarity)?;
     }
 
     Ok(current)
@@ -380,23 +498,27 @@ fn binary_resolution<'a, C: ClauseCollection<'a>>(
     pool: &mut dyn TermPool,
     current: &mut C,
     next: &'a [Rc<Term>],
-    pivot: ResolutionTerm<'a>,
+    pivot: Literal<'a>,
     is_pivot_in_current: bool,
 ) -> Result<(), ResolutionError> {
-    let negated_pivot = (pivot.0 + 1, pivot.1);
+    let negated_pivot = pivot.negate();
     let (pivot_in_current, pivot_in_next) = if is_pivot_in_current {
         (pivot, negated_pivot)
     } else {
         (negated_pivot, pivot)
     };
     if !current.remove_term(&pivot_in_current) {
-        let p = unremove_all_negations(pool, pivot_in_current);
-        return Err(ResolutionError::PivotNotFound(p));
+        let p = pivot_in_current.to_term(pool);
+        return Err(if current.contains_term(&pivot_in_next) {
+            ResolutionError::PivotWithWrongPolarity(p)
+        } else {
+            ResolutionError::PivotNotFound(p)
+        });
     }
 
     let mut found = false;
     for t in next {
-        let t = t.remove_all_negations();
+        let t = Literal::new(t);
         if !found && t == pivot_in_next {
             found = true;
         } else {
@@ -404,8 +526,13 @@ fn binary_resolution<'a, C: ClauseCollection<'a>>(
         }
     }
     if !found {
-        let p = unremove_all_negations(pool, pivot_in_next);
-        return Err(ResolutionError::PivotNotFound(p));
+        let p = pivot_in_next.to_term(pool);
+        let has_wrong_polarity = next.iter().any(|t| Literal::new(t) == pivot_in_current);
+        return Err(if has_wrong_polarity {
+            ResolutionError::PivotWithWrongPolarity(p)
+        } else {
+            ResolutionError::PivotNotFound(p)
+        });
     }
     Ok(())
 }
@@ -428,6 +555,7 @@ pub fn elaborate_resolution(
                     premises: Vec::new(),
                     args: Vec::new(),
                     discharge: Vec::new(),
+                    weight: None,
                 });
                 let premises = vec![elaborator.map_index(premises[0].index), true_step];
                 elaborator.push_elaborated_step(ProofStep {
@@ -439,6 +567,7 @@ pub fn elaborate_resolution(
                         .map(|a| ProofArg::Term(pool.bool_constant(a)))
                         .to_vec(),
                     discharge: Vec::new(),
+                    weight: None,
                 });
                 return Ok(());
             }
@@ -459,12 +588,31 @@ pub fn elaborate_resolution(
         }
     }
 
+    let original_premises = premises;
     let mut premises: Vec<_> = premises.iter().dedup().copied().collect();
-    let ResolutionTrace { not_not_added, pivot_trace } =
-        greedy_resolution(conclusion, &premises, pool, true).or_else(|_| {
-            premises.reverse();
-            greedy_resolution(conclusion, &premises, pool, true)
-        })?;
+    let trace = greedy_resolution(conclusion, &premises, pool, true).or_else(|_| {
+        premises.reverse();
+        greedy_resolution(conclusion, &premises, pool, true)
+    });
+    let ResolutionTrace { not_not_added, pivot_trace } = match trace {
+        Ok(trace) => trace,
+        Err(traced_error) => {
+            // Some valid steps can't be reconstructed with explicit pivots, for example when the
+            // conclusion has fewer leading negations than the remaining pivot. In that case, we
+            // still check the step as the checker would, and leave it unchanged if it is valid
+            greedy_resolution(conclusion, original_premises, pool, false)
+                .map(|_| ())
+                .or_else(|_| {
+                    if rup_resolution(conclusion, original_premises) {
+                        Ok(())
+                    } else {
+                        Err(traced_error)
+                    }
+                })?;
+            elaborator.unchanged(conclusion);
+            return Ok(());
+        }
+    };
 
     let pivots = pivot_trace
         .into_iter()
@@ -484,6 +632,7 @@ pub fn elaborate_resolution(
         premises,
         args: pivots,
         discharge: Vec::new(),
+        weight: None,
     };
 
     if not_not_added {
@@ -527,6 +676,7 @@ pub fn elaborate_resolution(
             premises: Vec::new(),
             args: Vec::new(),
             discharge: Vec::new(),
+            weight: None,
         });
         let id = elaborator.get_new_id(&command_id);
         let second_not_not_step = elaborator.add_new_step(ProofStep {
@@ -536,6 +686,7 @@ pub fn elaborate_resolution(
             premises: Vec::new(),
             args: Vec::new(),
             discharge: Vec::new(),
+            weight: None,
         });
 
         // Finally, we add a new resolution step, refering to the preivous three, and concluding the
@@ -552,6 +703,7 @@ pub fn elaborate_resolution(
             premises: vec![resolution_step, first_not_not_step, second_not_not_step],
             args,
             discharge: Vec::new(),
+            weight: None,
         });
     } else {
         elaborator.push_elaborated_step(resolution_step);
@@ -559,21 +711,54 @@ pub fn elaborate_resolution(
     Ok(())
 }
 
-pub fn tautology(RuleArgs { conclusion, premises, .. }: RuleArgs) -> RuleResult {
-    assert_num_premises(premises, 1)?;
-    assert_clause_len(conclusion, 1)?;
-    assert_is_bool_constant(&conclusion[0], true)?;
+/// Returns the indices of the premises of a resolution step that don't take part in the resolution,
+/// that is, the premises that have no literal whose negation appears in another premise. These
+/// premises only contribute their literals to the conclusion, and are usually redundant.
+pub fn unused_premises(premises: &[Premise]) -> Vec<usize> {
+    let literals: Vec<IndexSet<_>> = premises
+        .iter()
+        .map(|p| {
+            let literals = p.clause.iter().map(Literal::new);
+            literals.map(|l| (l.num_negations(), l.atom())).collect()
+        })
+        .collect();
+    let in_other_premise = |i: usize, literal: (u32, &Rc<Term>)| {
+        (literals.iter().enumerate()).any(|(j, other)| j != i && other.contains(&literal))
+    };
+    (0..premises.len())
+        .filter(|&i| {
+            !literals[i].iter().any(|&(n, atom)| {
+                in_other_premise(i, (n + 1, atom)) || (n > 0 && in_other_premise(i, (n - 1, atom)))
+            })
+        })
+        .collect()
+}
 
-    let premise = premises[0].clause;
-    let mut seen = IndexSet::with_capacity(premise.len());
-    let with_negations_removed = premise.iter().map(Rc::remove_all_negations_with_polarity);
+/// Returns `true` if the clause contains both a literal and its negation. Leading negations are
+/// removed from each literal, so, for example, `(not (not p))` and `(not p)` are considered
+/// complementary.
+pub fn clause_is_tautological(clause: &[Rc<Term>]) -> bool {
+    let mut seen = IndexSet::with_capacity(clause.len());
+    let with_negations_removed = clause.iter().map(Rc::remove_all_negations_with_polarity);
     for (polarity, term) in with_negations_removed {
         if seen.contains(&(!polarity, term)) {
-            return Ok(());
+            return true;
         }
         seen.insert((polarity, term));
     }
-    Err(ResolutionError::TautologyFailed.into())
+    false
+}
+
+pub fn tautology(RuleArgs { conclusion, premises, .. }: RuleArgs) -> RuleResult {
+    assert_num_premises(premises, 1)?;
+    assert_clause_len(conclusion, 1)?;
+    assert_is_bool_constant(&conclusion[0], true)?;
+
+    if clause_is_tautological(premises[0].clause) {
+        Ok(())
+    } else {
+        Err(ResolutionError::TautologyFailed.into())
+    }
 }
 
 pub fn contraction(RuleArgs { conclusion, premises, .. }: RuleArgs) -> RuleResult {
@@ -879,4 +1064,220 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn elaborate_resolution_with_remaining_pivot() {
+        use crate::{checker, parser};
+
+        // Here, the pivot `(not (not p))` is never eliminated, but the step is still valid, since
+        // the conclusion is `p`. The elaborator can only reconstruct the case where the conclusion
+        // has more leading negations than the remaining pivot, so here it must leave the step
+        // unchanged
+        let (prelude, proof, mut pool) = parser::parse_instance(
+            "(declare-fun p () Bool) (declare-fun q () Bool)".as_bytes(),
+            "(step t1 (cl (not (not p)) q) :rule hole)
+            (step t2 (cl (not q)) :rule hole)
+            (step t3 (cl p) :rule resolution :premises (t1 t2))
+            (step t4 (cl) :rule hole :premises (t3))"
+                .as_bytes(),
+            parser::Config::new(),
+        )
+        .unwrap();
+
+        let mut checker = checker::ProofChecker::new(&mut pool, checker::Config::new(), &prelude);
+        assert!(checker.check(&proof).is_ok());
+
+        let mut checker = checker::ProofChecker::new(&mut pool, checker::Config::new(), &prelude);
+        let (_, elaborated) = checker.check_and_elaborate(proof.clone()).unwrap();
+        assert_eq!(proof.commands, elaborated.commands);
+
+        // The same must happen regardless of the order of the premises
+        let (prelude, proof, mut pool) = parser::parse_instance(
+            "(declare-fun p () Bool) (declare-fun q () Bool)".as_bytes(),
+            "(step t1 (cl (not q)) :rule hole)
+            (step t2 (cl q (not (not p))) :rule hole)
+            (step t3 (cl p) :rule resolution :premises (t1 t2))
+            (step t4 (cl) :rule hole :premises (t3))"
+                .as_bytes(),
+            parser::Config::new(),
+        )
+        .unwrap();
+
+        let mut checker = checker::ProofChecker::new(&mut pool, checker::Config::new(), &prelude);
+        let (_, elaborated) = checker.check_and_elaborate(proof.clone()).unwrap();
+        assert_eq!(proof.commands, elaborated.commands);
+
+        // Invalid steps must still be rejected
+        let (prelude, proof, mut pool) = parser::parse_instance(
+            "(declare-fun p () Bool) (declare-fun q () Bool)".as_bytes(),
+            "(step t1 (cl (not (not p)) q) :rule hole)
+            (step t2 (cl (not q)) :rule hole)
+            (step t3 (cl (not p)) :rule resolution :premises (t1 t2))
+            (step t4 (cl) :rule hole :premises (t3))"
+                .as_bytes(),
+            parser::Config::new(),
+        )
+        .unwrap();
+
+        let mut checker = checker::ProofChecker::new(&mut pool, checker::Config::new(), &prelude);
+        assert!(checker.check_and_elaborate(proof).is_err());
+    }
+
+    #[test]
+    fn check() {
+        use crate::{
+            ast::PrimitivePool,
+            checker::error::{CheckerError, ResolutionError},
+            parser::tests::parse_terms,
+        };
+
+        let mut pool = PrimitivePool::new();
+        let definitions = "
+            (declare-fun p () Bool)
+            (declare-fun q () Bool)
+            (declare-fun r () Bool)
+        ";
+        let [p, q, r, not_p, not_q] = parse_terms(
+            &mut pool,
+            definitions,
+            ["p", "q", "r", "(not p)", "(not q)"],
+        );
+
+        let first = [p.clone(), q.clone()];
+        let second = [not_p.clone(), r.clone()];
+        let third = [not_q.clone()];
+        let premises: [&[_]; 3] = [&first, &second, &third];
+        let pivots = [(p.clone(), true), (q.clone(), true)];
+
+        // Valid resolution, with the conclusion in a different order
+        let conclusion = [r.clone()];
+        assert!(super::check(&mut pool, &premises, &pivots, &conclusion).is_ok());
+        let conclusion = [q.clone(), r.clone()];
+        let result = super::check(&mut pool, &premises[..2], &pivots[..1], &conclusion);
+        assert!(result.is_ok());
+        let conclusion = [r.clone(), q.clone(), r.clone()];
+        let result = super::check(&mut pool, &premises[..2], &pivots[..1], &conclusion);
+        assert!(result.is_ok());
+
+        // Wrong number of pivots
+        let conclusion = [r.clone()];
+        let result = super::check(&mut pool, &premises, &pivots[..1], &conclusion);
+        assert!(matches!(result, Err(CheckerError::WrongNumberOfArgs(_, 1))));
+
+        // Pivot given with the wrong polarity
+        let wrong_polarity = [(p.clone(), false), (q.clone(), true)];
+        let result = super::check(&mut pool, &premises, &wrong_polarity, &conclusion);
+        assert!(matches!(
+            result,
+            Err(CheckerError::Resolution(ResolutionError::PivotWithWrongPolarity(t))) if t == not_p
+        ));
+
+        // Pivot doesn't appear in the premises
+        let missing_pivot = [(r.clone(), true), (q.clone(), true)];
+        let result = super::check(&mut pool, &premises, &missing_pivot, &conclusion);
+        assert!(matches!(
+            result,
+            Err(CheckerError::Resolution(ResolutionError::PivotNotFound(t))) if t == r
+        ));
+
+        // Conclusion has an extra term
+        let result = super::check(&mut pool, &premises, &pivots, &[r.clone(), p.clone()]);
+        assert!(matches!(
+            result,
+            Err(CheckerError::Resolution(ResolutionError::ExtraTermInConclusion(t))) if t == p
+        ));
+
+        // Conclusion is missing a term
+        let result = super::check(&mut pool, &premises, &pivots, &[]);
+        assert!(matches!(
+            result,
+            Err(CheckerError::Resolution(ResolutionError::MissingTermInConclusion(t))) if t == r
+        ));
+    }
+
+    #[test]
+    fn as_pivot() {
+        use crate::{
+            ast::{PrimitivePool, ProofArg, TermPool},
+            checker::error::CheckerError,
+            parser::tests::parse_terms,
+        };
+
+        let mut pool = PrimitivePool::new();
+        let [p, not_p, q] = parse_terms(
+            &mut pool,
+            "(declare-fun p () Bool) (declare-fun q () Bool)",
+            ["p", "(not p)", "q"],
+        );
+        let [t, f] = [pool.bool_true(), pool.bool_false()];
+
+        let pivot = ProofArg::Term(not_p.clone());
+        let (literal, polarity) = super::as_pivot(&pivot, &ProofArg::Term(f.clone())).unwrap();
+        assert_eq!((1, &p), (literal.num_negations(), literal.atom()));
+        assert!(!polarity);
+        let (_, polarity) = super::as_pivot(&pivot, &ProofArg::Term(t.clone())).unwrap();
+        assert!(polarity);
+
+        let assign = ProofArg::Assign("x".to_owned(), p.clone());
+        assert!(matches!(
+            super::as_pivot(&assign, &ProofArg::Term(t.clone())),
+            Err(CheckerError::ExpectedTermStyleArg(..))
+        ));
+        assert!(matches!(
+            super::as_pivot(&pivot, &assign),
+            Err(CheckerError::ExpectedTermStyleArg(..))
+        ));
+        assert!(matches!(
+            super::as_pivot(&pivot, &ProofArg::Term(q)),
+            Err(CheckerError::ExpectedAnyBoolConstant(_))
+        ));
+    }
+
+    #[test]
+    fn unused_premises() {
+        use crate::{ast::PrimitivePool, checker::Premise, parser::tests::parse_terms};
+
+        let mut pool = PrimitivePool::new();
+        let [p, q, r, not_p, not_not_q] = parse_terms(
+            &mut pool,
+            "(declare-fun p () Bool) (declare-fun q () Bool) (declare-fun r () Bool)",
+            ["p", "q", "r", "(not p)", "(not (not q))"],
+        );
+        let clauses = [vec![p, q.clone()], vec![not_p], vec![r], vec![not_not_q, q]];
+        let premises: Vec<_> = (clauses.iter().enumerate())
+            .map(|(i, c)| Premise { id: "", clause: c, index: (0, i) })
+            .collect();
+
+        // `p` is resolved against `(not p)`, but `r` does not appear negated anywhere, and `q` and
+        // `(not (not q))` are not complementary
+        assert_eq!(vec![2, 3], super::unused_premises(&premises));
+        assert_eq!(vec![0], super::unused_premises(&premises[..1]));
+    }
+
+    #[test]
+    fn clause_is_tautological() {
+        use crate::{ast::PrimitivePool, parser::tests::parse_terms};
+
+        let mut pool = PrimitivePool::new();
+        let definitions = "(declare-fun p () Bool) (declare-fun q () Bool)";
+        let [p, q, not_p, not_not_p, not_not_not_p] = parse_terms(
+            &mut pool,
+            definitions,
+            ["p", "q", "(not p)", "(not (not p))", "(not (not (not p)))"],
+        );
+
+        assert!(super::clause_is_tautological(&[p.clone(), not_p.clone()]));
+        assert!(super::clause_is_tautological(&[
+            q.clone(),
+            not_not_p.clone(),
+            not_p
+        ]));
+        assert!(super::clause_is_tautological(&[
+            not_not_p.clone(),
+            not_not_not_p
+        ]));
+        assert!(!super::clause_is_tautological(&[p.clone(), q]));
+        assert!(!super::clause_is_tautological(&[p, not_not_p]));
+        assert!(!super::clause_is_tautological(&[]));
+    }
 }
@@ -1,4 +1,6 @@
-use super::{assert_clause_len, get_premise_term, CheckerError, Elaborator, RuleArgs, RuleResult};
+use super::{
+    assert_clause_len, get_premise_term, CheckerError, Elaborator, Premise, RuleArgs, RuleResult,
+};
 use crate::ast::*;
 
 /// Function to find a transitive chain given a conclusion equality and a series of premise
@@ -160,6 +162,7 @@ pub fn elaborate_eq_transitive(
         premises: Vec::new(),
         args: Vec::new(),
         discharge: Vec::new(),
+        weight: None,
     };
     let new_eq_transitive_step = elaborator.add_new_step(new_eq_transitive_step);
     let mut latest_step_index = new_eq_transitive_step;
@@ -188,6 +191,7 @@ pub fn elaborate_eq_transitive(
             premises: vec![latest_step_index],
             args: Vec::new(),
             discharge: Vec::new(),
+            weight: None,
         };
         latest_step_index = elaborator.add_new_step(or_intro_step);
     }
@@ -199,6 +203,7 @@ pub fn elaborate_eq_transitive(
         premises: vec![latest_step_index],
         args: Vec::new(),
         discharge: Vec::new(),
+        weight: None,
     });
     Ok(())
 }
@@ -225,6 +230,7 @@ fn flip_eq_transitive_premises(
                 premises: Vec::new(),
                 args: Vec::new(),
                 discharge: Vec::new(),
+                weight: None,
             };
             (elaborator.add_new_step(new_step), pivot, to_introduce)
         })
@@ -266,6 +272,7 @@ fn flip_eq_transitive_premises(
         premises,
         args,
         discharge: Vec::new(),
+        weight: None,
     };
     (clause, elaborator.add_new_step(final_step))
 }
@@ -282,6 +289,27 @@ pub fn trans(RuleArgs { conclusion, premises, .. }: RuleArgs) -> RuleResult {
     find_chain(conclusion, &mut premises)
 }
 
+/// Returns the indices of the premises of a `trans` step that are not needed to complete the
+/// transitivity chain. If the step is not valid, this returns an empty vector.
+pub fn unused_premises(conclusion: &[Rc<Term>], premises: &[Premise]) -> Vec<usize> {
+    let find_unused = || -> Result<Vec<usize>, CheckerError> {
+        assert_clause_len(conclusion, 1)?;
+        let conclusion = match_term_err!((= t u) = &conclusion[0])?;
+        let mut premise_equalities: Vec<_> = premises
+            .iter()
+            .map(|premise| match_term_err!((= t u) = get_premise_term(premise)?))
+            .collect::<Result<_, _>>()?;
+
+        let mut indices: Vec<_> = (0..premises.len()).collect();
+        let (_, num_needed, _) =
+            find_and_trace_chain(conclusion, &mut premise_equalities, &mut indices)?;
+        let mut unused = indices.split_off(num_needed);
+        unused.sort_unstable();
+        Ok(unused)
+    };
+    find_unused().unwrap_or_default()
+}
+
 pub fn elaborate_trans(
     RuleArgs { conclusion, premises, pool, .. }: RuleArgs,
     command_id: String,
@@ -339,6 +367,7 @@ pub fn elaborate_trans(
         premises: new_premises,
         args: Vec::new(),
         discharge: Vec::new(),
+        weight: None,
     });
     Ok(())
 }
@@ -0,0 +1,76 @@
+use crate::ast::*;
+use indexmap::IndexSet;
+
+/// The rules whose validity is invariant under the renaming of bound variables, and that can
+/// therefore be cached modulo alpha-equivalence.
+///
+/// Most rules compare terms using syntactic equality, meaning that renaming the bound variables in
+/// one side of an equality may make a valid step invalid. Rules like `bind` also depend on the
+/// subproof they end, and can't be cached based only on their conclusion.
+const ALPHA_INVARIANT_RULES: &[&str] = &["forall_inst"];
+
+/// A step, with all its bound variables renamed to canonical names.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct StepKey {
+    rule: String,
+    clause: Vec<Rc<Term>>,
+
+    /// The arguments to the step. In `forall_inst` steps, each variable name is replaced by the
+    /// index of the quantifier binding it refers to.
+    args: Vec<(usize, Rc<Term>)>,
+}
+
+/// A cache of the steps that were already checked, indexed modulo alpha-equivalence.
+#[derive(Debug, Default)]
+pub(super) struct StepCache {
+    valid: IndexSet<StepKey>,
+}
+
+impl StepCache {
+    /// Returns `true` if a step alpha-equivalent to `step` was already checked and found to be
+    /// valid. If the step can't be cached, this returns `false` and does nothing.
+    pub fn check(&mut self, pool: &mut dyn TermPool, step: &ProofStep) -> bool {
+        match Self::key(pool, step) {
+            Some(key) => self.valid.contains(&key),
+            None => false,
+        }
+    }
+
+    /// Records `step` as valid, if it can be cached.
+    pub fn insert(&mut self, pool: &mut dyn TermPool, step: &ProofStep) {
+        if let Some(key) = Self::key(pool, step) {
+            self.valid.insert(key);
+        }
+    }
+
+    fn key(pool: &mut dyn TermPool, step: &ProofStep) -> Option<StepKey> {
+        if !ALPHA_INVARIANT_RULES.contains(&step.rule.as_str())
+            || !step.premises.is_empty()
+            || !step.discharge.is_empty()
+        {
+            return None;
+        }
+
+        // The `forall_inst` arguments refer to the quantifier bindings by name. Since these names
+        // are replaced during canonicalization, we must instead identify each binding by its index
+        let [conclusion] = step.clause.as_slice() else {
+            return None;
+        };
+        let ((bindings, _), _) = match_term!((or (not (forall ... phi)) psi) = conclusion)?;
+        let args = step
+            .args
+            .iter()
+            .map(|arg| {
+                let (name, value) = arg.as_assign().ok()?;
+                let index = bindings.iter().position(|(n, _)| n == name)?;
+                Some((index, canonicalize_binders(pool, value)))
+            })
+            .collect::<Option<_>>()?;
+
+        Some(StepKey {
+            rule: step.rule.clone(),
+            clause: vec![canonicalize_binders(pool, conclusion)],
+            args,
+        })
+    }
+}
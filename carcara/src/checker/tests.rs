@@ -0,0 +1,636 @@
+use super::{CheckerStatistics, Config, ProofChecker};
+use crate::{ast::*, parser};
+
+#[test]
+fn test_register_elaboration_rule() {
+    let run_test = |register: bool| {
+        let (prelude, proof, mut pool) = parser::parse_instance(
+            "(declare-fun a () Int)".as_bytes(),
+            "(step t1 (cl (= a a)) :rule refl)
+            (step t2 (cl) :rule hole :premises (t1))"
+                .as_bytes(),
+            parser::Config::new(),
+        )
+        .unwrap();
+        let mut checker = ProofChecker::new(&mut pool, Config::new(), &prelude);
+        if register {
+            checker.register_elaboration_rule("refl", |args, command_id, elaborator| {
+                elaborator.push_elaborated_step(ProofStep {
+                    id: command_id,
+                    clause: args.conclusion.to_vec(),
+                    rule: "hole".to_owned(),
+                    premises: Vec::new(),
+                    args: Vec::new(),
+                    discharge: Vec::new(),
+                    weight: None,
+                });
+                Ok(())
+            });
+        }
+        let (_, elaborated) = checker.check_and_elaborate(proof).unwrap();
+        match &elaborated.commands[0] {
+            ProofCommand::Step(s) => s.rule.clone(),
+            other => panic!("expected step, got {:?}", other),
+        }
+    };
+
+    assert_eq!("refl", run_test(false));
+    assert_eq!("hole", run_test(true));
+}
+
+#[test]
+fn test_elaborate_cyclic_proof() {
+    let (prelude, mut proof, mut pool) = parser::parse_instance(
+        "(declare-fun p () Bool)".as_bytes(),
+        "(step t1 (cl p) :rule hole)
+        (step t2 (cl p) :rule hole :premises (t1))
+        (step t3 (cl) :rule hole :premises (t2))"
+            .as_bytes(),
+        parser::Config::new(),
+    )
+    .unwrap();
+
+    // Make `t1` and `t2` depend on each other
+    match &mut proof.commands[0] {
+        ProofCommand::Step(s) => s.premises = vec![(0, 1)],
+        other => panic!("expected step, got {:?}", other),
+    }
+
+    let mut checker = ProofChecker::new(&mut pool, Config::new(), &prelude);
+    match checker.check_and_elaborate(proof) {
+        Err(crate::Error::Structure(StructureError::InvalidPremise(id, 0, 1))) => {
+            assert_eq!("t1", id);
+        }
+        other => panic!("expected structure error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_step_cache_free_variable_names() {
+    // The free constant `@c0` has the same name as the canonical name given to `x`, so the two
+    // steps would have the same cache key if it didn't avoid free variable names
+    let (prelude, proof, mut pool) = parser::parse_instance(
+        "(declare-fun @c0 () Int)".as_bytes(),
+        "(step t1 (cl (or (not (forall ((x Int)) (= x @c0))) (= 5 @c0)))
+            :rule forall_inst :args ((:= x 5)))
+        (step t2 (cl (or (not (forall ((x Int)) (= x x))) (= 5 @c0)))
+            :rule forall_inst :args ((:= x 5)))"
+            .as_bytes(),
+        parser::Config::new(),
+    )
+    .unwrap();
+    let mut checker = ProofChecker::new(&mut pool, Config::new(), &prelude);
+    match checker.check(&proof) {
+        Err(crate::Error::Checker { step, .. }) => assert_eq!("t2", step),
+        other => panic!("expected checker error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parallel_checker() {
+    use super::{ParallelProofChecker, Scheduler};
+    use std::sync::Arc;
+
+    let problem = "
+        (declare-fun p () Bool)
+        (declare-fun q () Bool)
+        (declare-fun P (Int) Bool)
+        (assert (and p q))
+        (assert (not p))
+    ";
+    let proof = "
+        (assume h1 (and p q))
+        (assume h2 (not p))
+        (anchor :step t3 :args ((x Int) (:= (y Int) x)))
+        (step t3.t1 (cl (= y x)) :rule refl)
+        (step t3.t2 (cl (= (P y) (P x))) :rule cong :premises (t3.t1))
+        (step t3 (cl (= (forall ((y Int)) (P y)) (forall ((x Int)) (P x)))) :rule bind)
+        (step t4 (cl p) :rule and :premises (h1) :args (0))
+        (step t5 (cl q) :rule and :premises (h1) :args (1))
+        (step t6 (cl) :rule resolution :premises (t4 h2))
+    ";
+
+    for num_threads in [1, 2, 4] {
+        let (prelude, proof, pool) =
+            parser::parse_instance(problem.as_bytes(), proof.as_bytes(), parser::Config::new())
+                .unwrap();
+        let (scheduler, context_usage) = Scheduler::new(num_threads, &proof);
+        let mut checker = ParallelProofChecker::new(
+            Arc::new(pool),
+            Config::new(),
+            &prelude,
+            &context_usage,
+            128 * 1024 * 1024,
+        );
+        let r = checker.check(&proof, &scheduler);
+        assert!(matches!(r, Ok(false)), "{:?}", r);
+    }
+}
+
+#[test]
+fn test_rule_statistics() {
+    use crate::benchmarking::{Metrics, OnlineBenchmarkResults};
+    use std::time::Duration;
+
+    let problem = "
+        (declare-fun p () Bool)
+        (declare-fun q () Bool)
+        (assert p)
+        (assert (not p))
+        (assert (or (not q) p))
+    ";
+    let proof = "
+        (assume h1 p)
+        (assume h2 (not p))
+        (assume h3 (or (not q) p))
+        (step t4 (cl (not q) p) :rule or :premises (h3))
+        (step t5 (cl (not q)) :rule resolution :premises (t4 h2))
+        (step t6 (cl q) :rule some-unknown-rule)
+        (step t7 (cl) :rule resolution :premises (h1 h2))
+    ";
+    let (prelude, proof, mut pool) =
+        parser::parse_instance(problem.as_bytes(), proof.as_bytes(), parser::Config::new())
+            .unwrap();
+
+    let mut stats = CheckerStatistics {
+        file_name: "test",
+        elaboration_time: Duration::ZERO,
+        polyeq_time: Duration::ZERO,
+        assume_time: Duration::ZERO,
+        assume_core_time: Duration::ZERO,
+        results: OnlineBenchmarkResults::new(),
+    };
+    let config = Config::new().ignore_unknown_rules(true);
+    let mut checker = ProofChecker::new(&mut pool, config, &prelude);
+    assert!(matches!(
+        checker.check_with_stats(&proof, &mut stats),
+        Ok(true)
+    ));
+
+    let by_rule = stats.results.step_time_by_rule();
+    let count = |rule: &str| by_rule.get(rule).map_or(0, Metrics::count);
+    assert_eq!(2, count("resolution"));
+    assert_eq!(1, count("or"));
+    assert_eq!(1, count("some-unknown-rule"));
+}
+
+#[test]
+fn test_allowed_and_denied_rules() {
+    use super::error::CheckerError;
+    use std::collections::HashSet;
+
+    let run_test = |config: Config| {
+        let (prelude, proof, mut pool) = parser::parse_instance(
+            "(declare-fun p () Bool) (assert p) (assert (not p))".as_bytes(),
+            "(assume h1 p)
+            (assume h2 (not p))
+            (step t3 (cl p) :rule hole)
+            (step t4 (cl) :rule resolution :premises (t3 h2))"
+                .as_bytes(),
+            parser::Config::new(),
+        )
+        .unwrap();
+        match ProofChecker::new(&mut pool, config, &prelude).check(&proof) {
+            Ok(_) => None,
+            Err(crate::Error::Checker {
+                inner: CheckerError::ForbiddenRule(r),
+                ..
+            }) => Some(r),
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    };
+    let set = |rules: &[&str]| -> HashSet<String> { rules.iter().map(|&r| r.to_owned()).collect() };
+
+    assert_eq!(None, run_test(Config::new()));
+    assert_eq!(None, run_test(Config::new().denied_rules(set(&["trans"]))));
+    assert_eq!(
+        Some("hole".to_owned()),
+        run_test(Config::new().denied_rules(set(&["hole"])))
+    );
+
+    let allowed = set(&["hole", "resolution"]);
+    assert_eq!(None, run_test(Config::new().allowed_rules(allowed.clone())));
+    assert_eq!(
+        Some("hole".to_owned()),
+        run_test(Config::new().allowed_rules(set(&["resolution"])))
+    );
+    assert_eq!(
+        Some("resolution".to_owned()),
+        run_test(
+            Config::new()
+                .allowed_rules(allowed)
+                .denied_rules(set(&["resolution"]))
+        )
+    );
+}
+
+#[test]
+fn test_function_definitions() {
+    let run_test = |apply_function_defs: bool, proof: &str| {
+        let config = parser::Config {
+            apply_function_defs,
+            ..parser::Config::new()
+        };
+        let (prelude, proof, mut pool) = parser::parse_instance(
+            "(declare-fun a () Int)
+            (define-fun f ((x Int)) Int (+ x 1))
+            (define-fun b () Int 2)
+            (assert (= (f a) b))"
+                .as_bytes(),
+            proof.as_bytes(),
+            config,
+        )
+        .unwrap();
+        ProofChecker::new(&mut pool, Config::new(), &prelude)
+            .check(&proof)
+            .is_ok()
+    };
+
+    // When function definitions are not applied, the defined symbols are uninterpreted, and their
+    // definitions can be introduced with `assume` commands
+    let proof = "(assume h1 (= (f a) b))
+        (assume h2 (= f (lambda ((x Int)) (+ x 1))))
+        (assume h3 (= b 2))
+        (step t4 (cl) :rule hole :premises (h1 h2 h3))";
+    assert!(run_test(false, proof));
+
+    let proof = "(assume h1 (= (+ a 1) 2))
+        (step t2 (cl) :rule hole :premises (h1))";
+    assert!(!run_test(false, proof));
+
+    // Otherwise, the definitions are expanded in both the problem and the proof
+    assert!(run_test(true, proof));
+    let proof = "(assume h1 (= (f a) b))
+        (step t2 (cl) :rule hole :premises (h1))";
+    assert!(run_test(true, proof));
+}
+
+#[test]
+fn test_error_reports_failing_step() {
+    let (prelude, proof, mut pool) = parser::parse_instance(
+        "(declare-fun a () Int) (declare-fun b () Int) (declare-fun c () Int)
+        (assert (= a b)) (assert (= b c))"
+            .as_bytes(),
+        "(assume h1 (= a b))
+        (assume h2 (= b c))
+        (step t3 (cl (= a c)) :rule trans :premises (h1 h2))
+        (step t4 (cl (= a b)) :rule trans :premises (h2))
+        (step t5 (cl) :rule hole :premises (t3 t4))"
+            .as_bytes(),
+        parser::Config::new(),
+    )
+    .unwrap();
+    let err = ProofChecker::new(&mut pool, Config::new(), &prelude)
+        .check(&proof)
+        .unwrap_err();
+    match &err {
+        crate::Error::Checker { step, rule, clause, .. } => {
+            assert_eq!("t4", step);
+            assert_eq!("trans", rule);
+            let clause: Vec<_> = clause.iter().map(ToString::to_string).collect();
+            assert_eq!(["(= a b)"], clause.as_slice());
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+    let message = err.to_string();
+    assert!(message.starts_with("checking failed on step 't4' with rule 'trans'"));
+    assert!(message.ends_with("\nconclusion: (cl (= a b))"));
+}
+
+#[test]
+fn test_recheck_from() {
+    let (prelude, mut proof, mut pool) = parser::parse_instance(
+        "(declare-fun a () Int) (declare-fun b () Int) (declare-fun c () Int)".as_bytes(),
+        "(step t1 (cl (= a a)) :rule refl)
+        (step t2 (cl (= b b)) :rule refl)
+        (step t3 (cl (= a a)) :rule contraction :premises (t1))
+        (step t4 (cl) :rule hole)"
+            .as_bytes(),
+        parser::Config::new(),
+    )
+    .unwrap();
+    let int_sort = pool.add(Term::Sort(Sort::Int));
+    let c = pool.add(Term::new_var("c", int_sort));
+    let c_equals_c = pool.add(Term::Op(Operator::Equals, vec![c.clone(), c]));
+
+    let mut checker = ProofChecker::new(&mut pool, Config::new(), &prelude);
+    let num_checked_steps = |checker: &mut ProofChecker, proof: &Proof, step_id: &str| {
+        checker.recheck_from(proof, step_id).unwrap();
+        checker.incremental.as_ref().unwrap().num_checked_steps
+    };
+
+    // The first check must check every step, and a second check only checks the forced step
+    assert_eq!(4, num_checked_steps(&mut checker, &proof, "t1"));
+    assert_eq!(1, num_checked_steps(&mut checker, &proof, "t2"));
+
+    // Changing `t1` invalidates `t3`, since it uses `t1` as a premise, but not `t2`
+    for i in [0, 2] {
+        match &mut proof.commands[i] {
+            ProofCommand::Step(s) => s.clause = vec![c_equals_c.clone()],
+            other => panic!("expected step, got {:?}", other),
+        }
+    }
+    assert_eq!(2, num_checked_steps(&mut checker, &proof, "t1"));
+
+    // If the edited step is no longer valid, rechecking it must fail
+    match &mut proof.commands[0] {
+        ProofCommand::Step(s) => s.rule = "contraction".to_owned(),
+        other => panic!("expected step, got {:?}", other),
+    }
+    assert!(checker.recheck_from(&proof, "t1").is_err());
+
+    // Changing a discharged assumption invalidates the step that discharges it
+    let (prelude, mut proof, mut pool) = parser::parse_instance(
+        "(declare-fun p () Bool) (declare-fun q () Bool)".as_bytes(),
+        "(anchor :step t1)
+        (assume t1.h1 p)
+        (step t1.t2 (cl q) :rule hole)
+        (step t1 (cl (not p) q) :rule subproof :discharge (t1.h1))
+        (step t2 (cl) :rule hole)"
+            .as_bytes(),
+        parser::Config::new(),
+    )
+    .unwrap();
+    let bool_sort = pool.add(Term::Sort(Sort::Bool));
+    let q = pool.add(Term::new_var("q", bool_sort));
+
+    let mut checker = ProofChecker::new(&mut pool, Config::new(), &prelude);
+    assert!(checker.recheck_from(&proof, "t1.t2").unwrap());
+    match &mut proof.commands[0] {
+        ProofCommand::Subproof(s) => match &mut s.commands[0] {
+            ProofCommand::Assume { term, .. } => *term = q,
+            other => panic!("expected assume, got {:?}", other),
+        },
+        other => panic!("expected subproof, got {:?}", other),
+    }
+    assert!(checker.recheck_from(&proof, "t1.t2").is_err());
+
+    // A failure inside a subproof must not leave its context behind for the next check. Otherwise,
+    // `t2` would be checked with the substitution `x -> y` and wrongly accepted
+    let (prelude, mut proof, mut pool) = parser::parse_instance(
+        "(declare-fun x () Int) (declare-fun y () Int)".as_bytes(),
+        "(anchor :step t1 :args ((:= (x Int) y)))
+        (step t1.t1 (cl (= x y)) :rule trans)
+        (step t1 (cl (= x x)) :rule hole)
+        (step t2 (cl (= x y)) :rule refl)
+        (step t3 (cl (= y y)) :rule refl)"
+            .as_bytes(),
+        parser::Config::new(),
+    )
+    .unwrap();
+
+    let mut checker = ProofChecker::new(&mut pool, Config::new(), &prelude);
+    assert!(checker.recheck_from(&proof, "t1.t1").is_err());
+    match &mut proof.commands[0] {
+        ProofCommand::Subproof(s) => match &mut s.commands[0] {
+            ProofCommand::Step(s) => s.rule = "refl".to_owned(),
+            other => panic!("expected step, got {:?}", other),
+        },
+        other => panic!("expected subproof, got {:?}", other),
+    }
+    match checker.recheck_from(&proof, "t1.t1") {
+        Err(crate::Error::Checker { step, .. }) => assert_eq!("t2", step),
+        other => panic!("expected checker error, got {:?}", other),
+    }
+
+    // Rechecking a valid top-level step after the failure must still succeed
+    proof.commands.remove(1);
+    assert!(checker.recheck_from(&proof, "t3").is_ok());
+}
+
+#[test]
+fn test_timeout() {
+    let proof: String = (1..=2000)
+        .map(|i| format!("(step t{} (cl (= a a)) :rule refl)\n", i))
+        .chain(std::iter::once("(step t2001 (cl) :rule hole)\n".to_owned()))
+        .collect();
+    let run_test = |timeout: Option<std::time::Duration>| {
+        let (prelude, proof, mut pool) = parser::parse_instance(
+            "(declare-fun a () Int)".as_bytes(),
+            proof.as_bytes(),
+            parser::Config::new(),
+        )
+        .unwrap();
+        let config = Config::new().timeout(timeout);
+        ProofChecker::new(&mut pool, config, &prelude).check(&proof)
+    };
+
+    let timeout = std::time::Duration::from_nanos(1);
+    match run_test(Some(timeout)) {
+        Err(crate::Error::Timeout(t)) => assert_eq!(timeout, t),
+        other => panic!("expected timeout error, got {:?}", other),
+    }
+    assert!(run_test(None).is_ok());
+}
+
+#[test]
+fn test_check_step() {
+    use super::{check_step, error::CheckerError};
+
+    let mut pool = PrimitivePool::new();
+    let [p, not_p, q, true_] = parser::tests::parse_terms(
+        &mut pool,
+        "(declare-fun p () Bool) (declare-fun q () Bool)",
+        ["p", "(not p)", "q", "true"],
+    );
+    let first = [p.clone(), q.clone()];
+    let second = [not_p];
+    let args = [ProofArg::Term(p.clone()), ProofArg::Term(true_)];
+
+    let valid = check_step(
+        &mut pool,
+        "resolution",
+        std::slice::from_ref(&q),
+        &[&first, &second],
+        &args,
+    );
+    assert!(valid.is_ok());
+
+    let broken = check_step(&mut pool, "resolution", &[p], &[&first, &second], &args);
+    assert!(broken.is_err());
+
+    let unknown = check_step(&mut pool, "not-a-rule", &[q], &[], &[]);
+    assert!(matches!(unknown, Err(CheckerError::UnknownRule)));
+}
+
+#[test]
+fn test_warn_unused_premises() {
+    use super::CheckerWarning;
+
+    let (prelude, proof, mut pool) = parser::parse_instance(
+        "(declare-fun p () Bool)
+        (declare-fun q () Bool)
+        (declare-fun a () Int)
+        (declare-fun b () Int)
+        (declare-fun c () Int)"
+            .as_bytes(),
+        "(step t1 (cl p q) :rule hole)
+        (step t2 (cl (not p)) :rule hole)
+        (step t3 (cl q) :rule hole)
+        (step t4 (cl q) :rule resolution :premises (t1 t2 t3))
+        (step t5 (cl (= a b)) :rule hole)
+        (step t6 (cl (= b c)) :rule hole)
+        (step t7 (cl (= a c)) :rule hole)
+        (step t8 (cl (= a c)) :rule trans :premises (t5 t7 t6))
+        (step t9 (cl) :rule hole)"
+            .as_bytes(),
+        parser::Config::new(),
+    )
+    .unwrap();
+
+    let mut checker = ProofChecker::new(&mut pool, Config::new(), &prelude);
+    checker.check(&proof).unwrap();
+    assert!(checker.warnings().is_empty());
+
+    let config = Config::new().warn_unused_premises(true);
+    let mut checker = ProofChecker::new(&mut pool, config, &prelude);
+    checker.check(&proof).unwrap();
+    let unused = |step: &str, premise: &str| CheckerWarning::UnusedPremise {
+        step: step.to_owned(),
+        premise: premise.to_owned(),
+    };
+    let expected = [unused("t4", "t3"), unused("t8", "t7")];
+    assert_eq!(expected.as_slice(), checker.warnings());
+
+    // The parallel checker groups the warnings by thread, so they may come in any order
+    let pool = std::sync::Arc::new(pool);
+    for num_threads in [1, 2] {
+        let (scheduler, context_usage) = super::Scheduler::new(num_threads, &proof);
+        let mut checker = super::ParallelProofChecker::new(
+            pool.clone(),
+            Config::new().warn_unused_premises(true),
+            &prelude,
+            &context_usage,
+            128 * 1024 * 1024,
+        );
+        checker.check(&proof, &scheduler).unwrap();
+        assert_eq!(expected.len(), checker.warnings().len());
+        assert!(expected.iter().all(|w| checker.warnings().contains(w)));
+    }
+}
+
+#[test]
+fn test_hole_policy() {
+    use super::{error::CheckerError, CheckerWarning, HolePolicy};
+
+    let (prelude, proof, mut pool) = parser::parse_instance(
+        "(declare-fun p () Bool) (assert p)".as_bytes(),
+        "(assume h1 p)
+        (step t2 (cl (not p)) :rule hole)
+        (step t3 (cl) :rule resolution :premises (h1 t2) :args (p true))"
+            .as_bytes(),
+        parser::Config::new(),
+    )
+    .unwrap();
+
+    // By default, holes are accepted, but the proof is reported as holey
+    let mut checker = ProofChecker::new(&mut pool, Config::new(), &prelude);
+    assert!(checker.check(&proof).unwrap());
+    assert!(checker.warnings().is_empty());
+
+    let warn_config = Config::new().hole_policy(HolePolicy::Warn);
+    let mut checker = ProofChecker::new(&mut pool, warn_config.clone(), &prelude);
+    assert!(checker.check(&proof).unwrap());
+    let expected = [CheckerWarning::Hole { step: "t2".to_owned() }];
+    assert_eq!(expected.as_slice(), checker.warnings());
+
+    // Holes are rejected even if unknown rules are ignored
+    let config = Config::new()
+        .hole_policy(HolePolicy::Reject)
+        .ignore_unknown_rules(true);
+    let mut checker = ProofChecker::new(&mut pool, config, &prelude);
+    assert!(matches!(
+        checker.check(&proof),
+        Err(crate::Error::Checker { inner: CheckerError::UntrustedHole, step, .. }) if step == "t2"
+    ));
+
+    // The parallel checker reports the same warnings
+    let pool = std::sync::Arc::new(pool);
+    for num_threads in [1, 2] {
+        let (scheduler, context_usage) = super::Scheduler::new(num_threads, &proof);
+        let mut checker = super::ParallelProofChecker::new(
+            pool.clone(),
+            warn_config.clone(),
+            &prelude,
+            &context_usage,
+            128 * 1024 * 1024,
+        );
+        assert!(checker.check(&proof, &scheduler).unwrap());
+        assert_eq!(expected.as_slice(), checker.warnings());
+    }
+}
+
+#[test]
+fn test_trusted_lemmas() {
+    use super::error::CheckerError;
+    use std::collections::HashSet;
+
+    let problem = "(declare-fun p () Bool) (declare-fun q () Bool) (assert p)";
+    let proof_text = "(assume h1 p)
+        (step t2 (cl (not p) q) :rule trust)
+        (step t3 (cl (not q)) :rule lemma)
+        (step t4 (cl) :rule resolution :premises (h1 t2 t3) :args (p true q true))";
+    let (prelude, proof, mut pool) = parser::parse_instance(
+        problem.as_bytes(),
+        proof_text.as_bytes(),
+        parser::Config::new(),
+    )
+    .unwrap();
+    let [or, not_q] = parser::tests::parse_terms(
+        &mut pool,
+        "(declare-fun p () Bool) (declare-fun q () Bool)",
+        ["(or (not p) q)", "(not q)"],
+    );
+
+    // Trusted lemmas are accepted without proof, so the proof is holey
+    let trusted: HashSet<_> = [or.clone(), not_q.clone()].into_iter().collect();
+    let config = Config::new().trusted_lemmas(trusted.clone());
+    let mut checker = ProofChecker::new(&mut pool, config, &prelude);
+    assert!(checker.check(&proof).unwrap());
+
+    // Lemmas are rejected unless they are trusted
+    let config = Config::new().trusted_lemmas([or.clone()].into_iter().collect::<HashSet<_>>());
+    let mut checker = ProofChecker::new(&mut pool, config, &prelude);
+    assert!(matches!(
+        checker.check(&proof),
+        Err(crate::Error::Checker { inner: CheckerError::UntrustedLemma(t), step, .. })
+            if step == "t3" && t == not_q
+    ));
+
+    // Without a set of trusted lemmas, `lemma` and `trust` are unknown rules
+    let mut checker = ProofChecker::new(&mut pool, Config::new(), &prelude);
+    assert!(matches!(
+        checker.check(&proof),
+        Err(crate::Error::Checker { inner: CheckerError::UnknownRule, step, .. }) if step == "t2"
+    ));
+    let config = Config::new().ignore_unknown_rules(true);
+    let mut checker = ProofChecker::new(&mut pool, config, &prelude);
+    assert!(checker.check(&proof).unwrap());
+
+    // Checking a single step never accepts a lemma, since there is no set of trusted lemmas
+    let lemma = super::check_step(&mut pool, "lemma", &[not_q], &[], &[]);
+    assert!(matches!(lemma, Err(CheckerError::UnknownRule)));
+
+    // The parallel checker handles lemmas in the same way
+    let pool = std::sync::Arc::new(pool);
+    let (scheduler, context_usage) = super::Scheduler::new(2, &proof);
+    let config = Config::new().trusted_lemmas(trusted);
+    let mut checker =
+        super::ParallelProofChecker::new(pool, config, &prelude, &context_usage, 128 * 1024 * 1024);
+    assert!(checker.check(&proof, &scheduler).unwrap());
+
+    // The trusted lemmas can also be given as text, through `CarcaraOptions`
+    let check = |lemmas: &str| {
+        let options = crate::CarcaraOptions {
+            trusted_lemmas: Some(lemmas.to_owned()),
+            ..crate::CarcaraOptions::new()
+        };
+        crate::check(problem.as_bytes(), proof_text.as_bytes(), options)
+    };
+    assert!(check("(or (not p) q) (not q)").unwrap());
+    assert!(matches!(
+        check("(or (not p) q)"),
+        Err(crate::Error::Checker { inner: CheckerError::UntrustedLemma(_), step, .. })
+            if step == "t3"
+    ));
+}
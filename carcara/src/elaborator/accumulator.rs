@@ -1,5 +1,28 @@
 use crate::ast::*;
-use std::fmt::Write;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+
+/// The policy used to build the ids of the steps synthesized during elaboration.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum IdScheme {
+    /// Ids are built by appending `.t{n}` to the id of the original step, once for each level of
+    /// nested subproofs introduced by the elaboration. For example, `t3.t1.t2`.
+    #[default]
+    Hierarchical,
+
+    /// Ids are built by appending a single `.t{n}` to the id of the original step, regardless of
+    /// nesting. The number is incremented for every id created for the same original step, so
+    /// ids like `t3.t1`, `t3.t2` and `t3.t3` are used even inside introduced subproofs.
+    Flat,
+
+    /// Ids are the given prefix followed by a number that is incremented for every id created in
+    /// the whole proof. For example, `e1`, `e2`, etc. Numbers that would produce an id already used
+    /// in the original proof are skipped, so the ids are unique even if the prefix is also used by
+    /// the original proof.
+    Prefixed(String),
+}
 
 #[derive(Debug, Default)]
 struct Frame {
@@ -9,11 +32,28 @@ struct Frame {
 #[derive(Debug)]
 pub struct Accumulator {
     stack: Vec<Frame>,
+    id_scheme: IdScheme,
+
+    /// For the `Flat` and `Prefixed` schemes, the last number used for each original step id or
+    /// prefix, respectively.
+    id_counters: HashMap<String, usize>,
+
+    /// The ids used in the original proof, which the `Prefixed` scheme must avoid.
+    reserved_ids: HashSet<String>,
 }
 
 impl Accumulator {
-    pub fn new() -> Self {
-        Self { stack: vec![Frame::default()] }
+    pub fn new(id_scheme: IdScheme) -> Self {
+        Self {
+            stack: vec![Frame::default()],
+            id_scheme,
+            id_counters: HashMap::new(),
+            reserved_ids: HashSet::new(),
+        }
+    }
+
+    pub fn reserve_ids<'a>(&mut self, ids: impl IntoIterator<Item = &'a str>) {
+        self.reserved_ids.extend(ids.into_iter().map(str::to_owned));
     }
 
     fn top_frame(&self) -> &Frame {
@@ -32,18 +72,53 @@ impl Accumulator {
         self.top_frame().commands.len()
     }
 
-    pub fn next_id(&self, root_id: &str) -> String {
-        let mut current = root_id.to_owned();
-        for f in &self.stack {
-            write!(&mut current, ".t{}", f.commands.len() + 1).unwrap();
+    pub fn next_id(&mut self, root_id: &str) -> String {
+        match &self.id_scheme {
+            IdScheme::Hierarchical => {
+                let mut current = root_id.to_owned();
+                for f in &self.stack {
+                    write!(&mut current, ".t{}", f.commands.len() + 1).unwrap();
+                }
+                current
+            }
+            IdScheme::Flat => {
+                let n = self.next_counter(root_id);
+                format!("{root_id}.t{n}")
+            }
+            IdScheme::Prefixed(prefix) => {
+                let prefix = prefix.clone();
+                loop {
+                    let id = format!("{prefix}{}", self.next_counter(&prefix));
+                    if !self.reserved_ids.contains(&id) {
+                        break id;
+                    }
+                }
+            }
         }
-        current
+    }
+
+    fn next_counter(&mut self, key: &str) -> usize {
+        let counter = self.id_counters.entry(key.to_owned()).or_default();
+        *counter += 1;
+        *counter
     }
 
     pub fn push_command(&mut self, command: ProofCommand) {
         self.top_frame_mut().commands.push(command);
     }
 
+    /// Removes the last command in the top frame, if it is a step that concludes `clause`.
+    pub fn pop_step_with_clause(&mut self, clause: &[Rc<Term>]) -> Option<ProofStep> {
+        let commands = &mut self.top_frame_mut().commands;
+        match commands.last() {
+            Some(ProofCommand::Step(s)) if s.clause == clause => match commands.pop() {
+                Some(ProofCommand::Step(s)) => Some(s),
+                _ => unreachable!(),
+            },
+            _ => None,
+        }
+    }
+
     pub fn open_subproof(&mut self) {
         self.stack.push(Frame::default());
     }
@@ -64,14 +139,10 @@ impl Accumulator {
         self.stack.pop();
     }
 
-    pub fn end(mut self) -> Vec<ProofCommand> {
+    /// Takes the commands accumulated so far, leaving the accumulator empty. The id counters are
+    /// kept, so ids created afterwards are still unique.
+    pub fn end(&mut self) -> Vec<ProofCommand> {
         assert!(self.depth() == 0);
-        self.stack.pop().unwrap().commands
-    }
-}
-
-impl Default for Accumulator {
-    fn default() -> Self {
-        Self::new()
+        std::mem::take(&mut self.top_frame_mut().commands)
     }
 }
@@ -1,10 +1,17 @@
 mod accumulator;
 mod diff;
+mod mutate;
 mod polyeq;
 mod pruning;
+#[cfg(test)]
+mod tests;
+mod translation;
 
+pub use accumulator::IdScheme;
 pub use diff::{apply_diff, CommandDiff, ProofDiff};
+pub use mutate::{mutate, mutate_with_cache, try_mutate, try_mutate_with_cache, ElaborationCache};
 pub use pruning::{prune_proof, slice_proof};
+pub use translation::{binarify_resolutions, expand_contractions};
 
 use crate::{ast::*, utils::HashMapStack};
 use accumulator::Accumulator;
@@ -46,13 +53,25 @@ impl Default for Elaborator {
 
 impl Elaborator {
     pub fn new() -> Self {
+        Self::with_id_scheme(IdScheme::default())
+    }
+
+    /// Creates an elaborator whose synthesized step ids follow the given scheme.
+    pub fn with_id_scheme(id_scheme: IdScheme) -> Self {
         Self {
             stack: vec![Frame::default()],
-            accumulator: Accumulator::new(),
+            accumulator: Accumulator::new(id_scheme),
             seen_clauses: HashMapStack::new(),
         }
     }
 
+    /// Marks the ids of all commands in `proof` as used, so that the ids synthesized during
+    /// elaboration don't clash with them.
+    pub fn reserve_ids(&mut self, proof: &Proof) {
+        self.accumulator
+            .reserve_ids(proof.iter().map(ProofCommand::id));
+    }
+
     fn top_frame(&self) -> &Frame {
         self.stack.last().unwrap()
     }
@@ -126,7 +145,7 @@ impl Elaborator {
 
         let clause = step.clause.clone();
         let elaboration = {
-            let mut added = std::mem::take(&mut self.accumulator).end();
+            let mut added = self.accumulator.end();
             added.push(ProofCommand::Step(step));
             CommandDiff::Step(added)
         };
@@ -141,6 +160,42 @@ impl Elaborator {
         (self.depth(), new_index)
     }
 
+    /// If `premise` refers to the last step added to the accumulator, and that step concludes
+    /// `clause`, pushes it as the elaborated step, instead of adding a new step that just repeats
+    /// its conclusion. Returns `None` if that is not possible, in which case nothing is changed.
+    pub fn push_last_step_as_elaborated(
+        &mut self,
+        premise: (usize, usize),
+        clause: &[Rc<Term>],
+    ) -> Option<(usize, usize)> {
+        if self.accumulator.depth() != 0 {
+            return None;
+        }
+        let frame = self.top_frame();
+        let last_index = frame.new_indices.len() as isize + frame.current_offset - 1;
+        if premise != (self.depth(), last_index as usize) {
+            return None;
+        }
+        let step = self.accumulator.pop_step_with_clause(clause)?;
+        self.top_frame_mut().current_offset -= 1;
+        Some(self.push_elaborated_step(step))
+    }
+
+    /// Sets the weight of the step that replaced the last command, if it was replaced. Elaboration
+    /// rules build the replacing step from scratch, so this is used to keep the `:weight` of the
+    /// original step. Assumes have no weight, so they are not affected.
+    pub fn set_elaborated_weight(&mut self, weight: Option<u64>) {
+        let frame = self.top_frame_mut();
+        let last_index = frame.current_index().checked_sub(1);
+        if let Some((index, CommandDiff::Step(commands))) = frame.diff.last_mut() {
+            if Some(*index) == last_index {
+                if let Some(ProofCommand::Step(s)) = commands.last_mut() {
+                    s.weight = weight;
+                }
+            }
+        }
+    }
+
     pub fn open_accumulator_subproof(&mut self) {
         self.seen_clauses.push_scope();
         self.accumulator.open_subproof();
@@ -230,6 +285,7 @@ impl Elaborator {
             premises: vec![original_premise],
             args: Vec::new(),
             discharge: Vec::new(),
+            weight: None,
         };
         self.add_new_step(step)
     }
@@ -249,6 +305,7 @@ impl Elaborator {
             premises: Vec::new(),
             args: Vec::new(),
             discharge: Vec::new(),
+            weight: None,
         };
         self.add_new_step(step)
     }
@@ -289,6 +346,7 @@ impl Elaborator {
                 premises: vec![equality_step],
                 args: Vec::new(),
                 discharge: Vec::new(),
+                weight: None,
             })
         };
 
@@ -300,6 +358,7 @@ impl Elaborator {
             premises: vec![new_assume, equiv1_step],
             args: vec![ProofArg::Term(premise), ProofArg::Term(pool.bool_true())],
             discharge: Vec::new(),
+            weight: None,
         })
     }
 
@@ -337,13 +396,17 @@ impl Elaborator {
     }
 
     pub fn end(&mut self, original: Vec<ProofCommand>) -> Vec<ProofCommand> {
+        let elaborated = apply_diff(self.take_diff(), original);
+        apply_diff(prune_proof(&elaborated), elaborated)
+    }
+
+    /// Returns the diff between the original proof and the elaborated proof, without pruning it.
+    fn take_diff(&mut self) -> ProofDiff {
         assert!(
             self.depth() == 0,
             "trying to end proof building before closing subproof"
         );
         let Frame { diff, new_indices, .. } = self.stack.pop().unwrap();
-        let diff = ProofDiff { commands: diff, new_indices };
-        let elaborated = apply_diff(diff, original);
-        apply_diff(prune_proof(&elaborated), elaborated)
+        ProofDiff { commands: diff, new_indices }
     }
 }
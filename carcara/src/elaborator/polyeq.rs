@@ -141,6 +141,7 @@ impl<'a> PolyeqElaborator<'a> {
                         premises: Vec::new(),
                         args: Vec::new(),
                         discharge: Vec::new(),
+                        weight: None,
                     },
                 )
             }
@@ -181,6 +182,7 @@ impl<'a> PolyeqElaborator<'a> {
                         premises,
                         args: Vec::new(),
                         discharge: Vec::new(),
+                        weight: None,
                     },
                 )
             }
@@ -231,6 +233,7 @@ impl<'a> PolyeqElaborator<'a> {
             premises,
             args: Vec::new(),
             discharge: Vec::new(),
+            weight: None,
         };
         self.inner.add_new_step(step)
     }
@@ -284,6 +287,7 @@ impl<'a> PolyeqElaborator<'a> {
                 premises: Vec::new(),
                 args: Vec::new(),
                 discharge: Vec::new(),
+                weight: None,
             };
             return self.inner.add_new_step(step);
         }
@@ -315,6 +319,7 @@ impl<'a> PolyeqElaborator<'a> {
             premises: Vec::new(),
             args: Vec::new(),
             discharge: Vec::new(),
+            weight: None,
         });
 
         let id = self.inner.get_new_id(self.root_id);
@@ -325,6 +330,7 @@ impl<'a> PolyeqElaborator<'a> {
             premises: vec![cong_step, equiv_step],
             args: Vec::new(),
             discharge: Vec::new(),
+            weight: None,
         })
     }
 
@@ -364,6 +370,7 @@ impl<'a> PolyeqElaborator<'a> {
                     premises: vec![inner_eq],
                     args: Vec::new(),
                     discharge: Vec::new(),
+                    weight: None,
                 }),
                 true,
             );
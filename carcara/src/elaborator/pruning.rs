@@ -116,6 +116,7 @@ pub fn slice_proof(
                     premises: Vec::new(),
                     args: Vec::new(),
                     discharge: Vec::new(),
+                    weight: None,
                 });
                 result_diff.push((i, CommandDiff::Step(vec![new_command])));
             } else if let Some(diff) = frame.subproof_diffs[i].take() {
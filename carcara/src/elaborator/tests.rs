@@ -0,0 +1,552 @@
+use super::{
+    accumulator::Accumulator, apply_diff, binarify_resolutions, expand_contractions, mutate,
+    mutate_with_cache, try_mutate, ElaborationCache, IdScheme,
+};
+use crate::{ast::*, checker, checker::error::CheckerError, parser, Error};
+
+#[test]
+fn test_elaborate_trans() {
+    let problem = "
+        (declare-fun a () Int)
+        (declare-fun b () Int)
+        (declare-fun c () Int)
+        (declare-fun d () Int)
+        (assert (= b a))
+        (assert (= b c))
+        (assert (= d c))
+    ";
+    let proof = "
+        (assume h1 (= b a))
+        (assume h2 (= b c))
+        (assume h3 (= d c))
+        (step t4 (cl (= a d)) :rule trans :premises (h1 h2 h3))
+        (step t5 (cl) :rule hole :premises (t4))
+    ";
+    let (prelude, proof, mut pool) =
+        parser::parse_instance(problem.as_bytes(), proof.as_bytes(), parser::Config::new())
+            .unwrap();
+
+    let mut checker = checker::ProofChecker::new(&mut pool, checker::Config::new(), &prelude);
+    let (_, elaborated) = checker.check_and_elaborate(proof).unwrap();
+
+    // The premises `h1` and `h3` are flipped by new `symm` steps
+    let symm_steps: Vec<_> = elaborated
+        .iter()
+        .filter_map(|c| match c {
+            ProofCommand::Step(s) if s.rule == "symm" => Some(s.clause[0].to_string()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(["(= a b)", "(= c d)"], symm_steps.as_slice());
+
+    let mut checker = checker::ProofChecker::new(&mut pool, checker::Config::new(), &prelude);
+    assert!(checker.check(&elaborated).is_ok());
+}
+
+#[test]
+fn test_elaboration_keeps_weights() {
+    let problem = "
+        (declare-fun a () Int)
+        (declare-fun b () Int)
+        (declare-fun c () Int)
+        (declare-fun p () Bool)
+        (assert (= b a))
+        (assert (= b c))
+        (assert p)
+        (assert (not p))
+    ";
+    let proof = "
+        (assume h1 (= b a))
+        (assume h2 (= b c))
+        (assume h3 p)
+        (assume h4 (not p))
+        (step t5 (cl (= a c)) :rule trans :premises (h1 h2) :weight 2)
+        (step t6 (cl (= a a)) :rule refl :weight 3)
+        (step t7 (cl (= a c)) :rule resolution :premises (h3 h4 t5) :weight 5)
+        (step t8 (cl) :rule hole :premises (t6 t7))
+    ";
+    let (prelude, proof, mut pool) =
+        parser::parse_instance(problem.as_bytes(), proof.as_bytes(), parser::Config::new())
+            .unwrap();
+
+    let mut checker = checker::ProofChecker::new(&mut pool, checker::Config::new(), &prelude);
+    let (_, elaborated) = checker.check_and_elaborate(proof).unwrap();
+
+    // `t5` and `t7` are replaced by new steps, and `t6` is left unchanged, but all of them keep
+    // their weights. The new steps added by the elaboration have no weight
+    let weights: Vec<_> = (elaborated.iter())
+        .filter_map(|c| match c {
+            ProofCommand::Step(s) => Some((s.id.as_str(), s.weight)),
+            _ => None,
+        })
+        .filter(|(_, w)| w.is_some())
+        .collect();
+    assert_eq!(
+        [("t5", Some(2)), ("t6", Some(3)), ("t7", Some(5))],
+        weights.as_slice()
+    );
+    assert_eq!(10, elaborated.total_weight());
+
+    // The weights are also kept when the elaborated proof is printed and parsed again
+    let mut output = Vec::new();
+    write_proof(&mut output, &elaborated.commands, Dialect::Cvc5, false).unwrap();
+    let mut parser =
+        parser::Parser::new(&mut pool, parser::Config::new(), problem.as_bytes()).unwrap();
+    parser.parse_problem().unwrap();
+    parser.reset(output.as_slice()).unwrap();
+    assert_eq!(elaborated.commands, parser.parse_proof().unwrap());
+}
+
+#[test]
+fn test_elaboration_is_deterministic() {
+    let problem = "
+        (declare-fun a () Int)
+        (declare-fun b () Int)
+        (declare-fun c () Int)
+        (declare-fun d () Int)
+        (assert (= b a))
+        (assert (= b c))
+        (assert (= d c))
+    ";
+    let proof = "
+        (assume h1 (= b a))
+        (assume h2 (= b c))
+        (assume h3 (= d c))
+        (anchor :step t4)
+        (step t4.t1 (cl (= a d)) :rule trans :premises (h1 h2 h3))
+        (step t4.t2 (cl (= a d)) :rule hole :premises (t4.t1 h2))
+        (step t4 (cl (= a d)) :rule hole)
+        (step t5 (cl (= a c)) :rule trans :premises (h2 h1))
+        (step t6 (cl) :rule hole :premises (t4 t5 h3))
+    ";
+
+    // Each run uses a new term pool, so terms and nodes are allocated at different addresses. The
+    // serialized output must not depend on them
+    let run = || {
+        let (prelude, proof, mut pool) =
+            parser::parse_instance(problem.as_bytes(), proof.as_bytes(), parser::Config::new())
+                .unwrap();
+        let mut checker = checker::ProofChecker::new(&mut pool, checker::Config::new(), &prelude);
+        let (_, elaborated) = checker.check_and_elaborate(proof).unwrap();
+
+        let root = ProofNode::from_commands(&elaborated.commands);
+        let mutated = mutate(&mut pool, &root, |_, node| node.clone());
+
+        let mut output = Vec::new();
+        write_proof(&mut output, &elaborated.commands, Dialect::Cvc5, true).unwrap();
+        write_proof(&mut output, &mutated.into_commands(), Dialect::Cvc5, true).unwrap();
+        String::from_utf8(output).unwrap()
+    };
+    let first = run();
+    for _ in 0..5 {
+        assert_eq!(first, run());
+    }
+}
+
+fn run_binarify_test(id_scheme: IdScheme, expected: &str) {
+    let problem = "
+        (declare-fun p () Bool)
+        (declare-fun q () Bool)
+        (declare-fun r () Bool)
+        (assert p)
+    ";
+    let proof = "
+        (assume h1 p)
+        (anchor :step t2)
+        (assume t2.h1 p)
+        (step t2.t2 (cl (not p) q) :rule hole)
+        (step t2.t3 (cl (not q) r) :rule hole)
+        (step t2.t4 (cl r) :rule resolution :premises (t2.h1 t2.t2 t2.t3) :args (p true q true))
+        (step t2 (cl (not p) r) :rule subproof :discharge (t2.h1))
+        (step t3 (cl (not r)) :rule hole)
+        (step t4 (cl) :rule resolution :premises (h1 t2 t3) :args (p true r true) :weight 2)
+    ";
+    let (prelude, mut proof, mut pool) =
+        parser::parse_instance(problem.as_bytes(), proof.as_bytes(), parser::Config::new())
+            .unwrap();
+    let diff = binarify_resolutions(&mut pool, &proof, id_scheme);
+    proof.commands = apply_diff(diff, proof.commands);
+
+    let mut parser =
+        parser::Parser::new(&mut pool, parser::Config::new(), problem.as_bytes()).unwrap();
+    parser.parse_problem().unwrap();
+    parser.reset(expected.as_bytes()).unwrap();
+    assert_eq!(parser.parse_proof().unwrap(), proof.commands);
+
+    // A subproof has the same id as its last step, so we only look at assumes and steps
+    let ids: Vec<_> = (proof.iter())
+        .filter(|c| !c.is_subproof())
+        .map(ProofCommand::id)
+        .collect();
+    let unique: std::collections::HashSet<_> = ids.iter().collect();
+    assert_eq!(ids.len(), unique.len());
+
+    let config = checker::Config::new().strict(true);
+    let mut checker = checker::ProofChecker::new(&mut pool, config, &prelude);
+    assert!(checker.check(&proof).is_ok());
+}
+
+#[test]
+fn test_binarify_resolutions() {
+    let expected = "
+        (assume h1 p)
+        (anchor :step t2)
+        (assume t2.h1 p)
+        (step t2.t2 (cl (not p) q) :rule hole)
+        (step t2.t3 (cl (not q) r) :rule hole)
+        (step t2.t4.t1 (cl q) :rule resolution :premises (t2.h1 t2.t2) :args (p true))
+        (step t2.t4 (cl r) :rule resolution :premises (t2.t4.t1 t2.t3) :args (q true))
+        (step t2 (cl (not p) r) :rule subproof :discharge (t2.h1))
+        (step t3 (cl (not r)) :rule hole)
+        (step t4.t1 (cl r) :rule resolution :premises (h1 t2) :args (p true))
+        (step t4 (cl) :rule resolution :premises (t4.t1 t3) :args (r true) :weight 2)
+    ";
+    run_binarify_test(IdScheme::Hierarchical, expected);
+
+    // Since binarization doesn't introduce subproofs, the flat scheme produces the same ids here.
+    // See `test_id_schemes_in_subproofs` for a case where they differ
+    let expected = "
+        (assume h1 p)
+        (anchor :step t2)
+        (assume t2.h1 p)
+        (step t2.t2 (cl (not p) q) :rule hole)
+        (step t2.t3 (cl (not q) r) :rule hole)
+        (step t2.t4.t1 (cl q) :rule resolution :premises (t2.h1 t2.t2) :args (p true))
+        (step t2.t4 (cl r) :rule resolution :premises (t2.t4.t1 t2.t3) :args (q true))
+        (step t2 (cl (not p) r) :rule subproof :discharge (t2.h1))
+        (step t3 (cl (not r)) :rule hole)
+        (step t4.t1 (cl r) :rule resolution :premises (h1 t2) :args (p true))
+        (step t4 (cl) :rule resolution :premises (t4.t1 t3) :args (r true) :weight 2)
+    ";
+    run_binarify_test(IdScheme::Flat, expected);
+
+    let expected = "
+        (assume h1 p)
+        (anchor :step t2)
+        (assume t2.h1 p)
+        (step t2.t2 (cl (not p) q) :rule hole)
+        (step t2.t3 (cl (not q) r) :rule hole)
+        (step e1 (cl q) :rule resolution :premises (t2.h1 t2.t2) :args (p true))
+        (step t2.t4 (cl r) :rule resolution :premises (e1 t2.t3) :args (q true))
+        (step t2 (cl (not p) r) :rule subproof :discharge (t2.h1))
+        (step t3 (cl (not r)) :rule hole)
+        (step e2 (cl r) :rule resolution :premises (h1 t2) :args (p true))
+        (step t4 (cl) :rule resolution :premises (e2 t3) :args (r true) :weight 2)
+    ";
+    run_binarify_test(IdScheme::Prefixed("e".to_owned()), expected);
+
+    // The ids `t2`, `t3` and `t4` are already used in the proof, so they are skipped
+    let expected = "
+        (assume h1 p)
+        (anchor :step t2)
+        (assume t2.h1 p)
+        (step t2.t2 (cl (not p) q) :rule hole)
+        (step t2.t3 (cl (not q) r) :rule hole)
+        (step t1 (cl q) :rule resolution :premises (t2.h1 t2.t2) :args (p true))
+        (step t2.t4 (cl r) :rule resolution :premises (t1 t2.t3) :args (q true))
+        (step t2 (cl (not p) r) :rule subproof :discharge (t2.h1))
+        (step t3 (cl (not r)) :rule hole)
+        (step t5 (cl r) :rule resolution :premises (h1 t2) :args (p true))
+        (step t4 (cl) :rule resolution :premises (t5 t3) :args (r true) :weight 2)
+    ";
+    run_binarify_test(IdScheme::Prefixed("t".to_owned()), expected);
+}
+
+#[test]
+fn test_binarify_duplicate_pivots() {
+    // The checker treats clauses as sets, so every copy of `p` must be removed from `t1`
+    let problem = "
+        (declare-fun p () Bool)
+        (declare-fun q () Bool)
+        (declare-fun r () Bool)
+    ";
+    let proof = "
+        (step t1 (cl p p q) :rule hole)
+        (step t2 (cl (not p) r) :rule hole)
+        (step t3 (cl (not q)) :rule hole)
+        (step t4 (cl r) :rule resolution :premises (t1 t2 t3) :args (p true q true))
+    ";
+    let expected = "
+        (step t1 (cl p p q) :rule hole)
+        (step t2 (cl (not p) r) :rule hole)
+        (step t3 (cl (not q)) :rule hole)
+        (step t4.t1 (cl q r) :rule resolution :premises (t1 t2) :args (p true))
+        (step t4 (cl r) :rule resolution :premises (t4.t1 t3) :args (q true))
+    ";
+    let (prelude, mut proof, mut pool) =
+        parser::parse_instance(problem.as_bytes(), proof.as_bytes(), parser::Config::new())
+            .unwrap();
+    let mut checker = checker::ProofChecker::new(&mut pool, checker::Config::new(), &prelude);
+    assert!(checker.check(&proof).is_ok());
+
+    let diff = binarify_resolutions(&mut pool, &proof, IdScheme::Hierarchical);
+    proof.commands = apply_diff(diff, proof.commands);
+
+    let mut parser =
+        parser::Parser::new(&mut pool, parser::Config::new(), problem.as_bytes()).unwrap();
+    parser.parse_problem().unwrap();
+    parser.reset(expected.as_bytes()).unwrap();
+    assert_eq!(parser.parse_proof().unwrap(), proof.commands);
+
+    let mut checker = checker::ProofChecker::new(&mut pool, checker::Config::new(), &prelude);
+    assert!(checker.check(&proof).is_ok());
+}
+
+#[test]
+fn test_id_schemes_in_subproofs() {
+    // Simulates the elaboration of a step `t5` into a new step followed by a new subproof with
+    // two steps, and returns the ids of the three synthesized steps
+    fn run(id_scheme: IdScheme, reserved: &[&str]) -> Vec<String> {
+        let step = |id: String| {
+            ProofCommand::Step(ProofStep {
+                id,
+                clause: Vec::new(),
+                rule: "hole".to_owned(),
+                premises: Vec::new(),
+                args: Vec::new(),
+                discharge: Vec::new(),
+                weight: None,
+            })
+        };
+        let mut acc = Accumulator::new(id_scheme);
+        acc.reserve_ids(reserved.iter().copied());
+
+        let first = acc.next_id("t5");
+        acc.push_command(step(first.clone()));
+        acc.open_subproof();
+        for _ in 0..2 {
+            let id = acc.next_id("t5");
+            acc.push_command(step(id));
+        }
+        let ProofCommand::Subproof(s) = acc.close_subproof(Vec::new(), "t5") else {
+            unreachable!()
+        };
+        let mut ids = vec![first];
+        ids.extend(s.commands.iter().map(|c| c.id().to_owned()));
+        ids
+    }
+
+    assert_eq!(
+        ["t5.t1", "t5.t2.t1", "t5.t2"],
+        run(IdScheme::Hierarchical, &[]).as_slice()
+    );
+    assert_eq!(
+        ["t5.t1", "t5.t2", "t5.t4"],
+        run(IdScheme::Flat, &[]).as_slice()
+    );
+    assert_eq!(
+        ["e1", "e3", "e5"],
+        run(IdScheme::Prefixed("e".to_owned()), &["e2"]).as_slice()
+    );
+}
+
+#[test]
+fn test_expand_contractions() {
+    let problem = "
+        (declare-fun p () Bool)
+        (declare-fun q () Bool)
+        (declare-fun r () Bool)
+    ";
+    let proof = "
+        (step t1 (cl p q p r q p) :rule hole)
+        (step t2 (cl p q r) :rule contraction :premises (t1) :weight 3)
+        (anchor :step t3)
+        (step t3.t1 (cl q r) :rule hole)
+        (step t3.t2 (cl r q) :rule contraction :premises (t3.t1))
+        (step t3 (cl r q) :rule hole)
+        (step t4 (cl) :rule hole :premises (t2 t3))
+    ";
+
+    // Since `t3.t2` has no duplicates, its first literal is used as the pivot. The tautology for
+    // this literal was already derived in `t2.t4`, so it is reused
+    let expected = "
+        (step t1 (cl p q p r q p) :rule hole)
+        (step t2.t1 (cl (= p p)) :rule refl)
+        (step t2.t2 (cl (not p) p) :rule equiv1 :premises (t2.t1))
+        (step t2.t3 (cl (= q q)) :rule refl)
+        (step t2.t4 (cl (not q) q) :rule equiv1 :premises (t2.t3))
+        (step t2 (cl p q r) :rule resolution :premises (t1 t2.t2 t2.t4) :args (p true q true) :weight 3)
+        (anchor :step t3)
+        (step t3.t1 (cl q r) :rule hole)
+        (step t3.t2 (cl r q) :rule resolution :premises (t3.t1 t2.t4) :args (q true))
+        (step t3 (cl r q) :rule hole)
+        (step t4 (cl) :rule hole :premises (t2 t3))
+    ";
+    let (prelude, mut proof, mut pool) =
+        parser::parse_instance(problem.as_bytes(), proof.as_bytes(), parser::Config::new())
+            .unwrap();
+
+    let diff = expand_contractions(&mut pool, &proof, IdScheme::default());
+    proof.commands = apply_diff(diff, proof.commands);
+
+    let mut parser =
+        parser::Parser::new(&mut pool, parser::Config::new(), problem.as_bytes()).unwrap();
+    parser.parse_problem().unwrap();
+    parser.reset(expected.as_bytes()).unwrap();
+    assert_eq!(parser.parse_proof().unwrap(), proof.commands);
+
+    let config = checker::Config::new().strict(true);
+    let mut checker = checker::ProofChecker::new(&mut pool, config, &prelude);
+    assert!(checker.check(&proof).is_ok());
+}
+
+#[test]
+fn test_mutate() {
+    let problem = "
+        (declare-fun p () Bool)
+        (declare-fun q () Bool)
+    ";
+    let proof = "
+        (assume h1 p)
+        (step t2 (cl q) :rule hole :premises (h1))
+        (step t3 (cl) :rule hole :premises (h1 t2))
+    ";
+    let (_, proof, mut pool) =
+        parser::parse_instance(problem.as_bytes(), proof.as_bytes(), parser::Config::new())
+            .unwrap();
+    let root = ProofNode::from_commands(&proof.commands);
+    assert_eq!(Ok(()), root.check_acyclic());
+
+    // Renames `t2`, which should cause `t3` to be rebuilt, but keep `h1` untouched
+    let mutated = mutate(&mut pool, &root, |_, node| match node.as_ref() {
+        ProofNode::Step(s) if s.id == "t2" => Rc::new(ProofNode::Step(StepNode {
+            id: "t2'".to_owned(),
+            ..s.clone()
+        })),
+        _ => node.clone(),
+    });
+    let (ProofNode::Step(old), ProofNode::Step(new)) = (root.as_ref(), mutated.as_ref()) else {
+        unreachable!()
+    };
+    assert_ne!(root, mutated);
+    assert_eq!(Ok(()), mutated.check_acyclic());
+    assert_eq!(old.premises[0], new.premises[0]);
+    assert_eq!("t2'", new.premises[1].id());
+
+    let ids: Vec<_> = mutated
+        .into_commands()
+        .iter()
+        .map(|c| c.id().to_owned())
+        .collect();
+    assert_eq!(["h1", "t2'", "t3"], ids.as_slice());
+}
+
+#[test]
+fn test_try_mutate() {
+    let problem = "
+        (declare-sort T 0)
+        (declare-fun a () T)
+        (declare-fun b () T)
+        (declare-fun c () T)
+    ";
+    let proof = "
+        (assume h1 (= a b))
+        (assume h2 (not (= b c)))
+        (step t3 (cl (= a c)) :rule trans :premises (h1 h2))
+        (step t4 (cl) :rule hole :premises (t3))
+    ";
+    let (_, proof, mut pool) =
+        parser::parse_instance(problem.as_bytes(), proof.as_bytes(), parser::Config::new())
+            .unwrap();
+    let root = ProofNode::from_commands(&proof.commands);
+    assert_eq!(Ok(()), root.check_acyclic());
+
+    let result = try_mutate(&mut pool, &root, |_, node| {
+        if let ProofNode::Step(s) = node.as_ref() {
+            if s.rule == "trans" {
+                for p in &s.premises {
+                    match_term_err!((= t u) = &p.clause()[0])?;
+                }
+            }
+        }
+        Ok(node.clone())
+    });
+    match result {
+        Err(Error::Checker { inner, rule, step, .. }) => {
+            assert!(matches!(inner, CheckerError::TermOfWrongForm(..)));
+            assert_eq!("trans", rule);
+            assert_eq!("t3", step);
+        }
+        _ => panic!("expected checker error"),
+    }
+
+    let result = try_mutate(&mut pool, &root, |_, node| Ok(node.clone()));
+    assert_eq!(root, result.unwrap());
+}
+
+#[test]
+fn test_mutate_with_cache() {
+    let problem = "
+        (declare-fun p () Bool)
+        (declare-fun q () Bool)
+    ";
+    let proof = "
+        (assume h1 p)
+        (step t2 (cl q) :rule hole :premises (h1))
+        (step t3 (cl p) :rule hole)
+        (step t4 (cl) :rule hole :premises (t2 t3))
+    ";
+    let (_, proof, mut pool) =
+        parser::parse_instance(problem.as_bytes(), proof.as_bytes(), parser::Config::new())
+            .unwrap();
+    let root = ProofNode::from_commands(&proof.commands);
+    assert_eq!(Ok(()), root.check_acyclic());
+    let premises = |node: &Rc<ProofNode>| match node.as_ref() {
+        ProofNode::Step(s) => s.premises.clone(),
+        _ => unreachable!(),
+    };
+    let rename = |target: &'static str| {
+        move |_: &mut dyn TermPool, node: &Rc<ProofNode>| match node.as_ref() {
+            ProofNode::Step(s) if s.id == target => {
+                let id = format!("{}'", s.id);
+                Rc::new(ProofNode::Step(StepNode { id, ..s.clone() }))
+            }
+            _ => node.clone(),
+        }
+    };
+
+    // The first pass only touches `t3`, and the second only touches `t4`, so the subtree rooted at
+    // `t2` must be shared by the original graph and the results of both passes. Each pass has its
+    // own cache, so their results are never mixed
+    let mut first_pass = ElaborationCache::new(rename("t3"));
+    let mut second_pass = ElaborationCache::new(rename("t4"));
+    let first = mutate_with_cache(&mut pool, &root, &mut first_pass);
+    let second = mutate_with_cache(&mut pool, &first, &mut second_pass);
+    assert_eq!(4, first_pass.len());
+    assert_eq!(4, second_pass.len());
+    assert_eq!(premises(&root)[0], premises(&first)[0]);
+    assert_eq!(premises(&root)[0], premises(&second)[0]);
+    assert_eq!("t3'", premises(&second)[1].id());
+    assert_eq!("t4'", second.id());
+    assert_eq!(Ok(()), second.check_acyclic());
+
+    // If the original graph is changed, running both passes again only visits the new nodes. Here,
+    // `t3` is replaced, which also creates a new node for `t4`
+    let mut calls = 0;
+    let mut counting_pass = ElaborationCache::new(|_: &mut dyn TermPool, node: &Rc<ProofNode>| {
+        calls += 1;
+        node.clone()
+    });
+    mutate_with_cache(&mut pool, &root, &mut counting_pass);
+    let [new_t3] = parser::tests::parse_terms(&mut pool, problem, ["q"]);
+    let edited = mutate(&mut pool, &root, |_, node| match node.as_ref() {
+        ProofNode::Step(s) if s.id == "t3" => Rc::new(ProofNode::Step(StepNode {
+            clause: vec![new_t3.clone()],
+            ..s.clone()
+        })),
+        _ => node.clone(),
+    });
+    assert_eq!(Ok(()), edited.check_acyclic());
+    mutate_with_cache(&mut pool, &edited, &mut counting_pass);
+    drop(counting_pass);
+    assert_eq!(4 + 2, calls);
+
+    let first = mutate_with_cache(&mut pool, &edited, &mut first_pass);
+    let second = mutate_with_cache(&mut pool, &first, &mut second_pass);
+    assert_eq!(6, first_pass.len());
+    assert_eq!(6, second_pass.len());
+    assert_eq!(premises(&root)[0], premises(&second)[0]);
+    assert_eq!("t3'", premises(&second)[1].id());
+    assert_eq!(vec![new_t3], premises(&second)[1].clause());
+    assert_eq!("t4'", second.id());
+}
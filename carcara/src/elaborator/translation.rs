@@ -0,0 +1,201 @@
+//! Passes that translate a proof into an equivalent proof that uses a smaller set of rules, or
+//! simpler forms of them, for use by checkers that don't support the full Alethe format.
+
+use super::{Elaborator, IdScheme, ProofDiff};
+use crate::ast::*;
+use indexmap::IndexSet;
+
+/// Runs a translation pass over the proof, calling `translate_step` on every step. This function
+/// should push the steps that replace the given step to the elaborator, or return `false` if the
+/// step is unchanged.
+fn translate<F>(
+    pool: &mut dyn TermPool,
+    proof: &Proof,
+    id_scheme: IdScheme,
+    mut translate_step: F,
+) -> ProofDiff
+where
+    F: FnMut(&mut dyn TermPool, &mut Elaborator, &ProofIter, &ProofStep) -> bool,
+{
+    let mut elaborator = Elaborator::with_id_scheme(id_scheme);
+    elaborator.reserve_ids(proof);
+    let mut iter = proof.iter();
+    while let Some(command) = iter.next() {
+        match command {
+            ProofCommand::Assume { term, .. } => elaborator.assume(term),
+            ProofCommand::Step(step) => {
+                if !translate_step(pool, &mut elaborator, &iter, step) {
+                    elaborator.unchanged(&step.clause);
+                }
+                if iter.is_end_step() {
+                    elaborator.close_subproof();
+                }
+            }
+            ProofCommand::Subproof(s) => elaborator.open_subproof(s.commands.len()),
+        }
+    }
+    elaborator.take_diff()
+}
+
+/// Replaces every `resolution` or `th_resolution` step with more than two premises by a chain of
+/// binary resolution steps, including steps inside subproofs. Only steps that have their pivots
+/// given as arguments are changed, and the intermediate steps are given new ids following
+/// `id_scheme`.
+///
+/// The returned diff can be applied to the proof using [`apply_diff`](super::apply_diff).
+pub fn binarify_resolutions(
+    pool: &mut dyn TermPool,
+    proof: &Proof,
+    id_scheme: IdScheme,
+) -> ProofDiff {
+    translate(pool, proof, id_scheme, binarify_step)
+}
+
+/// Replaces every `contraction` step by a `resolution` step, including steps inside subproofs.
+///
+/// For each literal `l` that is duplicated in the premise clause, a tautology `(cl (not l) l)` is
+/// derived using `refl` and `equiv1`, and the premise is resolved with it using `l` as the pivot.
+/// Since the `resolution` rule treats clauses as sets, this removes the duplicates. If the premise
+/// has no duplicates, its first literal is used as the pivot. New steps are given ids following
+/// `id_scheme`.
+///
+/// The returned diff can be applied to the proof using [`apply_diff`](super::apply_diff).
+pub fn expand_contractions(
+    pool: &mut dyn TermPool,
+    proof: &Proof,
+    id_scheme: IdScheme,
+) -> ProofDiff {
+    translate(pool, proof, id_scheme, expand_contraction_step)
+}
+
+/// Pushes the steps that replace a `contraction` step, returning `false` if the step is not a
+/// `contraction` step or its premise is empty.
+fn expand_contraction_step(
+    pool: &mut dyn TermPool,
+    elaborator: &mut Elaborator,
+    iter: &ProofIter,
+    step: &ProofStep,
+) -> bool {
+    let [premise] = step.premises.as_slice() else {
+        return false;
+    };
+    let premise_clause = iter.get_premise(*premise).clause();
+    if step.rule != "contraction" || premise_clause.is_empty() {
+        return false;
+    }
+
+    let mut seen = IndexSet::new();
+    let mut duplicates = IndexSet::new();
+    for t in premise_clause {
+        if !seen.insert(t) {
+            duplicates.insert(t);
+        }
+    }
+    if duplicates.is_empty() {
+        duplicates.insert(&premise_clause[0]);
+    }
+
+    let mut premises = vec![elaborator.map_index(*premise)];
+    let mut args = Vec::with_capacity(duplicates.len() * 2);
+    for &l in &duplicates {
+        let id = elaborator.get_new_id(&step.id);
+        let refl_step = elaborator.add_refl_step(pool, l.clone(), l.clone(), id);
+        let id = elaborator.get_new_id(&step.id);
+        premises.push(elaborator.add_new_step(ProofStep {
+            id,
+            clause: vec![build_term!(pool, (not {l.clone()})), l.clone()],
+            rule: "equiv1".to_owned(),
+            premises: vec![refl_step],
+            args: Vec::new(),
+            discharge: Vec::new(),
+            weight: None,
+        }));
+        args.extend([ProofArg::Term(l.clone()), ProofArg::Term(pool.bool_true())]);
+    }
+    elaborator.push_elaborated_step(ProofStep {
+        id: step.id.clone(),
+        clause: step.clause.clone(),
+        rule: "resolution".to_owned(),
+        premises,
+        args,
+        discharge: Vec::new(),
+        weight: step.weight,
+    });
+    true
+}
+
+/// Pushes the chain of binary resolution steps that replaces `step`, returning `false` if the
+/// step doesn't need to, or can't, be replaced.
+fn binarify_step(
+    pool: &mut dyn TermPool,
+    elaborator: &mut Elaborator,
+    iter: &ProofIter,
+    step: &ProofStep,
+) -> bool {
+    let is_resolution = step.rule == "resolution" || step.rule == "th_resolution";
+    if !is_resolution
+        || step.premises.len() <= 2
+        || step.args.len() != (step.premises.len() - 1) * 2
+    {
+        return false;
+    }
+
+    // First, we compute the conclusion of each intermediate step. If any of the pivots is not
+    // found, the step is invalid, so we leave it unchanged
+    let mut pivots = Vec::with_capacity(step.premises.len() - 1);
+    let mut clauses = Vec::with_capacity(step.premises.len() - 2);
+    let mut current = iter.get_premise(step.premises[0]).clause().to_vec();
+    for (&premise, arg) in step.premises[1..].iter().zip(step.args.chunks(2)) {
+        let (Some(pivot), Some(polarity)) = (arg[0].as_term().ok(), arg[1].as_term().ok()) else {
+            return false;
+        };
+        let negated = build_term!(pool, (not {pivot.clone()}));
+        let (in_current, in_next) = if polarity.is_bool_true() {
+            (pivot, &negated)
+        } else if polarity.is_bool_false() {
+            (&negated, pivot)
+        } else {
+            return false;
+        };
+
+        // The checker treats clauses as sets, so every copy of the pivot is removed
+        let next = iter.get_premise(premise).clause();
+        if !current.contains(in_current) || !next.contains(in_next) {
+            return false;
+        }
+        current.retain(|t| t != in_current);
+        current.extend(next.iter().filter(|&t| t != in_next).cloned());
+        pivots.push([arg[0].clone(), arg[1].clone()]);
+        clauses.push(current.clone());
+    }
+    clauses.pop();
+
+    // Then, we add the intermediate steps, and replace the original step with the last binary
+    // resolution step
+    let mut previous = elaborator.map_index(step.premises[0]);
+    for ((clause, &premise), args) in clauses.into_iter().zip(&step.premises[1..]).zip(&pivots) {
+        let id = elaborator.get_new_id(&step.id);
+        previous = elaborator.add_new_step(ProofStep {
+            id,
+            clause,
+            rule: step.rule.clone(),
+            premises: vec![previous, elaborator.map_index(premise)],
+            args: args.to_vec(),
+            discharge: Vec::new(),
+            weight: None,
+        });
+    }
+    elaborator.push_elaborated_step(ProofStep {
+        id: step.id.clone(),
+        clause: step.clause.clone(),
+        rule: step.rule.clone(),
+        premises: vec![
+            previous,
+            elaborator.map_index(*step.premises.last().unwrap()),
+        ],
+        args: pivots.last().unwrap().to_vec(),
+        discharge: Vec::new(),
+        weight: step.weight,
+    });
+    true
+}
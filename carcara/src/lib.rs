@@ -46,6 +46,7 @@ mod utils;
 use crate::benchmarking::{CollectResults, OnlineBenchmarkResults, RunMeasurement};
 use checker::{error::CheckerError, CheckerStatistics};
 use parser::{ParserError, Position};
+use std::collections::HashSet;
 use std::io;
 use std::time::{Duration, Instant};
 use thiserror::Error;
@@ -73,6 +74,21 @@ pub struct CarcaraOptions {
     /// to a function that expects a `Real` will still be an error.
     pub allow_int_real_subtyping: bool,
 
+    /// If `true`, undeclared symbols are declared as fresh uninterpreted constants instead of being
+    /// an error. The sort of each symbol is inferred from its first use, and later uses must agree
+    /// with it. See [`parser::Config::allow_unknown_symbols`] for details.
+    pub allow_unknown_symbols: bool,
+
+    /// If `true`, a `let` or quantifier that binds a name already bound by an enclosing binder is
+    /// renamed to a fresh name during parsing. In either case, shadowing is reported as a parser
+    /// warning.
+    pub rename_shadowed: bool,
+
+    /// If `Some`, terms nested more deeply than the given depth are rejected by the parser, instead
+    /// of possibly overflowing the stack while checking the proof. By default, this is `None`, and
+    /// [`parser::DEFAULT_MAX_TERM_DEPTH`] is a safe limit for the default stack size.
+    pub max_term_depth: Option<usize>,
+
     /// If `Some`, enables the checking/elaboration of `lia_generic` steps using an external solver.
     /// When checking a proof, this means calling the solver to solve the linear integer arithmetic
     /// problem, checking the proof, and discarding it. When elaborating, the proof will instead be
@@ -94,9 +110,19 @@ pub struct CarcaraOptions {
     /// holes. Normally, using an unknown rule is considered an error.
     pub ignore_unknown_rules: bool,
 
+    /// If `Some`, steps that use the `lemma` or `trust` rules are only accepted if their conclusion
+    /// is one of the given lemmas, and make the proof holey. The lemmas are given as a series of
+    /// SMT-LIB terms of sort `Bool`, which may use any symbol declared in the problem. If `None`,
+    /// these rules are treated like any other unknown rule. See [`checker::Config::trusted_lemmas`].
+    pub trusted_lemmas: Option<String>,
+
     /// If `true`, Carcará will log the check and elaboration statistics of any
     /// `check` or `check_and_elaborate` run. If `false` no statistics are logged.
     pub stats: bool,
+
+    /// If `Some`, checking a proof will be aborted with an [`Error::Timeout`] error once it takes
+    /// longer than the given duration.
+    pub timeout: Option<Duration>,
 }
 
 /// The options that control how `lia_generic` steps are checked/elaborated using an external
@@ -118,6 +144,36 @@ impl CarcaraOptions {
     }
 }
 
+/// The result of [`parse_instance`]. In addition to the results of [`parser::parse_instance`], this
+/// includes the trusted lemmas, if they were given.
+type ParsedInstance = (
+    ast::ProblemPrelude,
+    ast::Proof,
+    ast::PrimitivePool,
+    Option<HashSet<ast::Rc<ast::Term>>>,
+);
+
+/// Parses the problem and the proof. If `options.trusted_lemmas` is `Some`, this also parses the
+/// trusted lemmas, which may use any symbol declared in the problem.
+fn parse_instance<T: io::BufRead>(
+    problem: T,
+    proof: T,
+    config: parser::Config,
+    options: &CarcaraOptions,
+) -> CarcaraResult<ParsedInstance> {
+    match &options.trusted_lemmas {
+        Some(lemmas) => {
+            let (prelude, proof, pool, lemmas) =
+                parser::parse_instance_with_lemmas(problem, proof, lemmas, config)?;
+            Ok((prelude, proof, pool, Some(lemmas.into_iter().collect())))
+        }
+        None => {
+            let (prelude, proof, pool) = parser::parse_instance(problem, proof, config)?;
+            Ok((prelude, proof, pool, None))
+        }
+    }
+}
+
 fn wrap_parser_error_message(e: &ParserError, pos: &Position) -> String {
     // For unclosed subproof errors, we don't print the position
     if matches!(e, ParserError::UnclosedSubproof(_)) {
@@ -127,6 +183,14 @@ fn wrap_parser_error_message(e: &ParserError, pos: &Position) -> String {
     }
 }
 
+fn format_clause(clause: &[ast::Rc<ast::Term>]) -> String {
+    let mut result = "(cl".to_owned();
+    for term in clause {
+        result += &format!(" {}", term);
+    }
+    result + ")"
+}
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("IO error: {0}")]
@@ -135,17 +199,29 @@ pub enum Error {
     #[error("{}", wrap_parser_error_message(.0, .1))]
     Parser(ParserError, Position),
 
-    #[error("checking failed on step '{step}' with rule '{rule}': {inner}")]
+    #[error(
+        "checking failed on step '{step}' with rule '{rule}': {inner}\nconclusion: {}",
+        format_clause(.clause)
+    )]
     Checker {
         inner: CheckerError,
         rule: String,
         step: String,
+
+        /// The conclusion of the failing step. For `assume` commands, this is the assumed term.
+        clause: Vec<ast::Rc<ast::Term>>,
     },
 
+    #[error("malformed proof: {0}")]
+    Structure(#[from] ast::StructureError),
+
     // While this is a kind of checking error, it does not happen in a specific step like all other
     // checker errors, so we model it as a different variant
     #[error("checker error: proof does not conclude empty clause")]
     DoesNotReachEmptyClause,
+
+    #[error("checking timed out after {0:?}")]
+    Timeout(Duration),
 }
 
 pub fn check<T: io::BufRead>(problem: T, proof: T, options: CarcaraOptions) -> Result<bool, Error> {
@@ -158,14 +234,21 @@ pub fn check<T: io::BufRead>(problem: T, proof: T, options: CarcaraOptions) -> R
         expand_lets: options.expand_lets,
         allow_int_real_subtyping: options.allow_int_real_subtyping,
         allow_unary_logical_ops: !options.strict,
+        allow_unknown_symbols: options.allow_unknown_symbols,
+        rename_shadowed: options.rename_shadowed,
+        max_term_depth: options.max_term_depth,
+        normalize_clause_order: false,
     };
-    let (prelude, proof, mut pool) = parser::parse_instance(problem, proof, config)?;
+    let (prelude, proof, mut pool, trusted_lemmas) =
+        parse_instance(problem, proof, config, &options)?;
     run_measures.parsing = total.elapsed();
 
     let config = checker::Config::new()
         .strict(options.strict)
         .ignore_unknown_rules(options.ignore_unknown_rules)
-        .lia_options(options.lia_options);
+        .lia_options(options.lia_options)
+        .timeout(options.timeout)
+        .trusted_lemmas(trusted_lemmas);
 
     // Checking
     let checking = Instant::now();
@@ -195,6 +278,7 @@ pub fn check<T: io::BufRead>(problem: T, proof: T, options: CarcaraOptions) -> R
                 polyeq: checker_stats.polyeq_time,
                 assume: checker_stats.assume_time,
                 assume_core: checker_stats.assume_core_time,
+                resident_memory: benchmarking::resident_memory(),
             },
         );
         // Print the statistics
@@ -224,14 +308,20 @@ pub fn check_parallel<T: io::BufRead>(
         expand_lets: options.expand_lets,
         allow_int_real_subtyping: options.allow_int_real_subtyping,
         allow_unary_logical_ops: !options.strict,
+        allow_unknown_symbols: options.allow_unknown_symbols,
+        rename_shadowed: options.rename_shadowed,
+        max_term_depth: options.max_term_depth,
+        normalize_clause_order: false,
     };
-    let (prelude, proof, pool) = parser::parse_instance(problem, proof, config)?;
+    let (prelude, proof, pool, trusted_lemmas) = parse_instance(problem, proof, config, &options)?;
     run_measures.parsing = total.elapsed();
 
     let config = checker::Config::new()
         .strict(options.strict)
         .ignore_unknown_rules(options.ignore_unknown_rules)
-        .lia_options(options.lia_options);
+        .lia_options(options.lia_options)
+        .timeout(options.timeout)
+        .trusted_lemmas(trusted_lemmas);
 
     // Checking
     let checking = Instant::now();
@@ -270,6 +360,7 @@ pub fn check_parallel<T: io::BufRead>(
                 polyeq: checker_stats.polyeq_time,
                 assume: checker_stats.assume_time,
                 assume_core: checker_stats.assume_core_time,
+                resident_memory: benchmarking::resident_memory(),
             },
         );
         // Print the statistics
@@ -295,14 +386,21 @@ pub fn check_and_elaborate<T: io::BufRead>(
         expand_lets: options.expand_lets,
         allow_int_real_subtyping: options.allow_int_real_subtyping,
         allow_unary_logical_ops: !options.strict,
+        allow_unknown_symbols: options.allow_unknown_symbols,
+        rename_shadowed: options.rename_shadowed,
+        max_term_depth: options.max_term_depth,
+        normalize_clause_order: false,
     };
-    let (prelude, proof, mut pool) = parser::parse_instance(problem, proof, config)?;
+    let (prelude, proof, mut pool, trusted_lemmas) =
+        parse_instance(problem, proof, config, &options)?;
     run_measures.parsing = total.elapsed();
 
     let config = checker::Config::new()
         .strict(options.strict)
         .ignore_unknown_rules(options.ignore_unknown_rules)
-        .lia_options(options.lia_options);
+        .lia_options(options.lia_options)
+        .timeout(options.timeout)
+        .trusted_lemmas(trusted_lemmas);
 
     // Checking
     let checking = Instant::now();
@@ -332,6 +430,7 @@ pub fn check_and_elaborate<T: io::BufRead>(
                 polyeq: checker_stats.polyeq_time,
                 assume: checker_stats.assume_time,
                 assume_core: checker_stats.assume_core_time,
+                resident_memory: benchmarking::resident_memory(),
             },
         );
         // Print the statistics
@@ -1,7 +1,7 @@
 //! The types for parser errors.
 
 use crate::{
-    ast::{Constant, PrimitivePool, Rc, Sort, Term, TermPool},
+    ast::{Constant, Rc, Sort, SortKind, Term, TermPool},
     parser::Token,
     utils::Range,
 };
@@ -100,11 +100,25 @@ pub enum ParserError {
     #[error("step id '{0}' was repeated")]
     RepeatedStepId(String),
 
+    /// The body of a function definition refers to the function being defined, either directly
+    /// or through other function definitions.
+    #[error("function '{0}' is defined recursively")]
+    RecursiveDefinition(String),
+
     /// The number given as the arity in a `declare-sort` command is too large. This only happens
     /// if the number is too big to fit in a `usize`, so it almost never happens.
     #[error("{0} is not a valid sort arity")]
     InvalidSortArity(Integer),
 
+    /// The number given in the `:weight` attribute of a step is negative, or too large to fit in
+    /// a `u64`.
+    #[error("{0} is not a valid step weight")]
+    InvalidStepWeight(Integer),
+
+    /// A step has no `:rule` attribute.
+    #[error("step '{0}' has no rule")]
+    MissingRule(String),
+
     /// The parser encountered an empty subproof
     #[error("subproof '{0}' is empty")]
     EmptySubproof(String),
@@ -117,6 +131,28 @@ pub enum ParserError {
     #[error("subproof '{0}' was not closed")]
     UnclosedSubproof(String),
 
+    /// A subproof was never closed, and a step inside it had the id of the step that ends an outer
+    /// subproof. This happens when the `anchor` command of the inner subproof refers to the wrong
+    /// step id.
+    #[error(
+        "subproof '{0}' was not closed, but step '{1}' has the id that ends an outer subproof"
+    )]
+    MismatchedAnchor(String, String),
+
+    /// A term is nested more deeply than the maximum depth allowed by `Config::max_term_depth`.
+    #[error("term is nested more than {0} levels deep")]
+    TermTooDeep(usize),
+
+    /// An overloaded function was applied to arguments whose sorts match more than one of its
+    /// signatures. This carries the matching signatures.
+    #[error("application of overloaded function '{0}' is ambiguous, candidates are {}", display_sorts(.1))]
+    AmbiguousOverload(String, Vec<Sort>),
+
+    /// An overloaded function was applied to arguments whose sorts don't match any of its
+    /// signatures. This carries all of its signatures.
+    #[error("no signature of overloaded function '{0}' matches the arguments, candidates are {}", display_sorts(.1))]
+    NoMatchingOverload(String, Vec<Sort>),
+
     /// The parser encountered an unknown indexed operator.
     #[error("not a valid indexed operator: '{0}'")]
     InvalidIndexedOp(String),
@@ -124,6 +160,25 @@ pub enum ParserError {
     /// The parser encountered an unknown qualified operator.
     #[error("not a valid qualified operator: '{0}'")]
     InvalidQualifiedOp(String),
+
+    /// An error that happened while parsing a proof command. This carries the kind of the command
+    /// (e.g. `step`), its id or name if it was already known, and the original error.
+    #[error("in {kind}{}: {inner}", display_command_id(.id))]
+    InCommand {
+        kind: &'static str,
+        id: Option<String>,
+        inner: Box<ParserError>,
+    },
+}
+
+impl ParserError {
+    /// Returns the original error, removing the command context added by `InCommand`, if any.
+    pub fn without_context(&self) -> &ParserError {
+        match self {
+            ParserError::InCommand { inner, .. } => inner.without_context(),
+            other => other,
+        }
+    }
 }
 
 /// Returns an error if the length of `sequence` is not in the `expected` range.
@@ -139,6 +194,21 @@ where
     }
 }
 
+/// Returns an error if `got` is not of the sort kind `kind`.
+pub fn assert_sort_kind(kind: SortKind, got: &Sort) -> Result<(), ParserError> {
+    let expected = match kind {
+        SortKind::Bool => Sort::Bool,
+        SortKind::Int => Sort::Int,
+        SortKind::Real => Sort::Real,
+        SortKind::String => Sort::String,
+        SortKind::RegLan => Sort::RegLan,
+        SortKind::Numeric => return Ok(SortError::assert_one_of(&[Sort::Int, Sort::Real], got)?),
+        SortKind::BitVec if kind.matches(got) => return Ok(()),
+        SortKind::BitVec => return Err(ParserError::ExpectedBvSort(got.clone())),
+    };
+    Ok(SortError::assert_eq(&expected, got)?)
+}
+
 /// Returns an error if the value of `sequence` is not in the `expected` range.
 pub fn assert_indexed_op_args_value<R>(sequence: &[Constant], range: R) -> Result<(), ParserError>
 where
@@ -165,6 +235,19 @@ pub struct SortError {
     pub got: Sort,
 }
 
+/// Formats a list of sorts as a comma-separated list of quoted sorts.
+fn display_sorts(sorts: &[Sort]) -> String {
+    let sorts: Vec<_> = sorts.iter().map(|s| format!("'{}'", s)).collect();
+    sorts.join(", ")
+}
+
+fn display_command_id(id: &Option<String>) -> String {
+    match id {
+        Some(id) => format!(" '{}'", id),
+        None => String::new(),
+    }
+}
+
 impl fmt::Display for SortError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.expected.as_slice() {
@@ -215,7 +298,7 @@ impl SortError {
     }
 
     pub(crate) fn assert_array_sort(
-        pool: &mut PrimitivePool,
+        pool: &mut dyn TermPool,
         key: Option<&Sort>,
         value: Option<&Sort>,
         got: &Sort,
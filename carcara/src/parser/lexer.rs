@@ -75,6 +75,9 @@ pub enum Reserved {
     /// The `lambda` reserved word.
     Lambda,
 
+    /// The `par` reserved word, used to introduce sort parameters in datatype declarations.
+    Par,
+
     /// The `cl` reserved word.
     Cl,
 
@@ -96,6 +99,12 @@ pub enum Reserved {
     /// The `declare-sort` reserved word.
     DeclareSort,
 
+    /// The `declare-datatype` reserved word.
+    DeclareDatatype,
+
+    /// The `declare-datatypes` reserved word.
+    DeclareDatatypes,
+
     /// The `define-fun` reserved word.
     DefineFun,
 
@@ -128,6 +137,7 @@ impl_str_conversion_traits!(Reserved {
     Match: "match",
     Choice: "choice",
     Lambda: "lambda",
+    Par: "par",
     Cl: "cl",
     Assume: "assume",
     Step: "step",
@@ -135,6 +145,8 @@ impl_str_conversion_traits!(Reserved {
     DeclareFun: "declare-fun",
     DeclareConst: "declare-const",
     DeclareSort: "declare-sort",
+    DeclareDatatype: "declare-datatype",
+    DeclareDatatypes: "declare-datatypes",
     DefineFun: "define-fun",
     DefineFunRec: "define-fun-rec",
     DefineFunsRec: "define-funs-rec",
@@ -147,36 +159,214 @@ impl_str_conversion_traits!(Reserved {
 /// Represents a position (line and column numbers) in the source input.
 pub type Position = (usize, usize);
 
+/// A source of characters for the [`Lexer`], read one line at a time.
+///
+/// If a line ends in `\r\n`, the `\r` should be dropped, so input with Windows line endings is
+/// lexed the same as input with Unix line endings, even inside quoted symbols and string literals.
+pub trait LexerInput {
+    /// Discards the remaining characters of the current line, and advances to the next line.
+    /// Returns `false` if the end of the input was reached.
+    fn next_line(&mut self) -> io::Result<bool>;
+
+    /// Returns the next character in the current line, or `None` if there are no more characters
+    /// in it.
+    fn next_char_in_line(&mut self) -> Option<char>;
+}
+
+/// Collects the characters of a line read from the input, dropping the `\r` if the line ends in
+/// `\r\n`.
+fn line_chars(line: &str) -> std::vec::IntoIter<char> {
+    let mut chars: Vec<_> = line.chars().collect();
+    if chars.ends_with(&['\r', '\n']) {
+        chars.remove(chars.len() - 2);
+    }
+    chars.into_iter()
+}
+
+/// A [`LexerInput`] that reads from a type that implements `BufRead`. This allocates a new buffer
+/// for each line read.
+pub struct ReaderInput<R> {
+    reader: R,
+    line: std::vec::IntoIter<char>,
+}
+
+impl<R: BufRead> ReaderInput<R> {
+    /// Constructs a new `ReaderInput` from a type that implements `BufRead`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<R: BufRead> LexerInput for ReaderInput<R> {
+    fn next_line(&mut self) -> io::Result<bool> {
+        let mut buf = String::new();
+        let read = self.reader.read_line(&mut buf)?;
+        self.line = line_chars(&buf);
+        Ok(read != 0)
+    }
+
+    fn next_char_in_line(&mut self) -> Option<char> {
+        self.line.next()
+    }
+}
+
+/// A [`LexerInput`] that reads directly from a string slice held in memory. Unlike
+/// [`ReaderInput`], this doesn't allocate anything while reading.
+pub struct SliceInput<'a> {
+    rest: &'a str,
+    line: std::iter::Chain<std::str::Chars<'a>, std::option::IntoIter<char>>,
+}
+
+impl<'a> SliceInput<'a> {
+    /// Constructs a new `SliceInput` from a string slice.
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            rest: input,
+            line: "".chars().chain(None),
+        }
+    }
+
+    /// Constructs a new `SliceInput` from a byte slice. Returns an error if the input is not
+    /// valid UTF-8.
+    pub fn from_bytes(input: &'a [u8]) -> io::Result<Self> {
+        let input = std::str::from_utf8(input)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Self::new(input))
+    }
+}
+
+impl LexerInput for SliceInput<'_> {
+    fn next_line(&mut self) -> io::Result<bool> {
+        if self.rest.is_empty() {
+            self.line = "".chars().chain(None);
+            return Ok(false);
+        }
+        let end = self.rest.find('\n').map_or(self.rest.len(), |i| i + 1);
+        let (line, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        let (contents, newline) = if let Some(l) = line.strip_suffix("\r\n") {
+            (l, Some('\n'))
+        } else if let Some(l) = line.strip_suffix('\n') {
+            (l, Some('\n'))
+        } else {
+            (line, None)
+        };
+        self.line = contents.chars().chain(newline);
+        Ok(true)
+    }
+
+    fn next_char_in_line(&mut self) -> Option<char> {
+        self.line.next()
+    }
+}
+
+/// A [`LexerInput`] that reads from several inputs in order, as if they were a single input. Each
+/// input starts on a new line, and line numbers keep counting from one input to the next.
+///
+/// If the last line of an input doesn't end in a line break, one is added to it. Otherwise, a
+/// token at the end of an input would be joined with a token at the start of the next one.
+pub struct ChainedInput<I> {
+    current: Option<I>,
+    rest: std::vec::IntoIter<I>,
+
+    /// Whether the current line has already ended in a line break.
+    line_ended: bool,
+}
+
+impl<I: LexerInput> ChainedInput<I> {
+    /// Constructs a new `ChainedInput` that reads from each of `inputs` in order.
+    pub fn new(inputs: impl IntoIterator<Item = I>) -> Self {
+        let mut rest = inputs.into_iter().collect::<Vec<_>>().into_iter();
+        Self {
+            current: rest.next(),
+            rest,
+            line_ended: true,
+        }
+    }
+}
+
+impl<I: LexerInput> LexerInput for ChainedInput<I> {
+    fn next_line(&mut self) -> io::Result<bool> {
+        while let Some(input) = &mut self.current {
+            if input.next_line()? {
+                self.line_ended = false;
+                return Ok(true);
+            }
+            self.current = self.rest.next();
+        }
+        Ok(false)
+    }
+
+    fn next_char_in_line(&mut self) -> Option<char> {
+        // Only the last line of an input may not end in a line break
+        match self.current.as_mut()?.next_char_in_line() {
+            Some(c) => {
+                self.line_ended = c == '\n';
+                Some(c)
+            }
+            None if !self.line_ended => {
+                self.line_ended = true;
+                Some('\n')
+            }
+            None => None,
+        }
+    }
+}
+
 /// A lexer for the SMT-LIB and Alethe formats.
-pub struct Lexer<R> {
-    input: R,
-    current_line: Option<std::vec::IntoIter<char>>,
+pub struct Lexer<I> {
+    input: I,
     current_char: Option<char>,
     position: Position,
 }
 
-impl<R: BufRead> Lexer<R> {
+impl<R: BufRead> Lexer<ReaderInput<R>> {
     /// Constructs a new `Lexer` from a type that implements `BufRead`.
     ///
     /// This operation can fail if there is an IO error on the first token.
-    pub fn new(mut input: R) -> io::Result<Self> {
-        let mut buf = String::new();
-        let read = input.read_line(&mut buf)?;
-        if read == 0 {
+    pub fn new(input: R) -> io::Result<Self> {
+        Self::with_input(ReaderInput::new(input))
+    }
+}
+
+impl<'a> Lexer<SliceInput<'a>> {
+    /// Constructs a new `Lexer` that reads directly from a string slice. This is faster than
+    /// [`Lexer::new`], since it doesn't allocate a buffer for each line of the input.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(input: &'a str) -> Self {
+        // Reading from a `SliceInput` never fails
+        Self::with_input(SliceInput::new(input)).unwrap()
+    }
+
+    /// Constructs a new `Lexer` that reads directly from a byte slice. This is faster than
+    /// [`Lexer::new`], since it doesn't allocate a buffer for each line of the input.
+    ///
+    /// This operation can fail if the input is not valid UTF-8.
+    pub fn from_bytes(input: &'a [u8]) -> io::Result<Self> {
+        Self::with_input(SliceInput::from_bytes(input)?)
+    }
+}
+
+impl<I: LexerInput> Lexer<I> {
+    /// Constructs a new `Lexer` from any [`LexerInput`].
+    ///
+    /// This operation can fail if there is an IO error on the first token.
+    pub fn with_input(mut input: I) -> io::Result<Self> {
+        if input.next_line()? {
+            let current_char = input.next_char_in_line();
             Ok(Lexer {
                 input,
-                current_line: None,
-                current_char: None,
-                position: (0, 0),
+                current_char,
+                position: (1, 1),
             })
         } else {
-            let mut line = buf.chars().collect::<Vec<_>>().into_iter();
-            let current_char = line.next();
             Ok(Lexer {
                 input,
-                current_line: Some(line),
-                current_char,
-                position: (1, 1),
+                current_char: None,
+                position: (0, 0),
             })
         }
     }
@@ -184,35 +374,27 @@ impl<R: BufRead> Lexer<R> {
     /// Advances the lexer by one character, and returns the previous `current_char`.
     fn next_char(&mut self) -> io::Result<Option<char>> {
         // If there are no more characters in the current line, go to the next line
-        if let Some(line) = &self.current_line {
-            if line.as_slice().is_empty() {
-                self.next_line()?;
-            }
-        }
-
-        let new = if let Some(line) = &mut self.current_line {
-            self.position.1 += 1;
-            line.next()
-        } else {
-            None
+        let new = match self.input.next_char_in_line() {
+            Some(c) => Some(c),
+            None if self.next_line()? => self.input.next_char_in_line(),
+            None => None,
         };
+        if new.is_some() {
+            self.position.1 += 1;
+        }
         let old = std::mem::replace(&mut self.current_char, new);
         Ok(old)
     }
 
     /// Advances the lexer by one line, discarding the remaining contents of the current line.
-    fn next_line(&mut self) -> io::Result<()> {
-        let mut buf = String::new();
-        let read = self.input.read_line(&mut buf)?;
-        if read == 0 {
-            self.current_line = None;
-        } else {
-            let line = buf.chars().collect::<Vec<_>>().into_iter();
-            self.current_line = Some(line);
+    /// Returns `false` if the end of the input was reached.
+    fn next_line(&mut self) -> io::Result<bool> {
+        let advanced = self.input.next_line()?;
+        if advanced {
             self.position.0 += 1;
             self.position.1 = 0;
         }
-        Ok(())
+        Ok(advanced)
     }
 
     /// Reads characters while the given predicate returns `true`, and stores them in a `String`.
@@ -684,6 +866,88 @@ mod tests {
         assert_eq!(expected.as_slice(), lex_all(input));
     }
 
+    #[test]
+    fn test_unexpected_characters() {
+        assert!(matches!(
+            lex_one("`"),
+            Err(Error::Parser(ParserError::UnexpectedChar('`'), (1, 1)))
+        ));
+        assert!(matches!(
+            lex_one("\\"),
+            Err(Error::Parser(ParserError::UnexpectedChar('\\'), (1, 1)))
+        ));
+        assert!(matches!(
+            lex_one("\u{ab}"),
+            Err(Error::Parser(ParserError::UnexpectedChar('\u{ab}'), (1, 1)))
+        ));
+
+        // The error should point to the offending character, even if it's not the first token
+        let mut lex = Lexer::new(std::io::Cursor::new("(foo `)")).unwrap();
+        lex.next_token().unwrap();
+        lex.next_token().unwrap();
+        assert!(matches!(
+            lex.next_token(),
+            Err(Error::Parser(ParserError::UnexpectedChar('`'), (1, 6)))
+        ));
+    }
+
+    #[test]
+    fn test_crlf_line_endings() {
+        let input = "(assume h1 (= a |b\nc|)) ; comment\n(step t2 (cl \"x\ny\") :rule hole)\n";
+        let crlf = input.replace('\n', "\r\n");
+        assert_eq!(lex_all(input), lex_all(&crlf));
+
+        // Positions should also be the same, since the dropped `\r` is always at the end of a line
+        let positions = |input: &str| {
+            let mut lex = Lexer::new(std::io::Cursor::new(input)).unwrap();
+            let mut result = Vec::new();
+            loop {
+                let (tk, pos) = lex.next_token().unwrap();
+                if tk == Token::Eof {
+                    break;
+                }
+                result.push(pos);
+            }
+            result
+        };
+        assert_eq!(positions(input), positions(&crlf));
+    }
+
+    #[test]
+    fn test_slice_input() {
+        fn tokens<I: LexerInput>(mut lex: Lexer<I>) -> Vec<(Token, Position)> {
+            let mut result = Vec::new();
+            loop {
+                let (tk, pos) = lex.next_token().expect("lexer error during test");
+                if tk == Token::Eof {
+                    break;
+                }
+                result.push((tk, pos));
+            }
+            result
+        }
+
+        let cases = [
+            "",
+            "\n\n",
+            "; comment",
+            "(assume h1 (= a |b\nc|)) ; comment\n(step t2 (cl \"x\ny\") :rule hole)\n",
+            "(step t1 (cl (= (+ 1 2.5 -3) 1/2)) :rule hole :args (#b101 #xff \"\\u{61}\"))",
+            "symbol ;\r\n; comment\r\n\r\n  (a b)\r\n\"\r\n\" |\r\n|",
+            "no-trailing-newline\n  last-line",
+        ];
+        for input in cases {
+            let expected = tokens(Lexer::new(input.as_bytes()).unwrap());
+            assert_eq!(expected, tokens(Lexer::from_str(input)));
+            assert_eq!(
+                expected,
+                tokens(Lexer::from_bytes(input.as_bytes()).unwrap())
+            );
+        }
+
+        assert!(Lexer::from_bytes(&[b'a', 0xff]).is_err());
+    }
+
     #[test]
     fn test_reserved_words() {
         let input = "_ ! as let exists |_| |!| |as| |let| |exists|";
@@ -700,5 +964,30 @@ mod tests {
             Token::Symbol("exists".into()),
         ];
         assert_eq!(expected, lex_all(input));
+
+        let input = "match par declare-datatype declare-datatypes \
+            |match| |par| |declare-datatype| |declare-datatypes|";
+        let expected = vec![
+            Token::ReservedWord(Reserved::Match),
+            Token::ReservedWord(Reserved::Par),
+            Token::ReservedWord(Reserved::DeclareDatatype),
+            Token::ReservedWord(Reserved::DeclareDatatypes),
+            Token::Symbol("match".into()),
+            Token::Symbol("par".into()),
+            Token::Symbol("declare-datatype".into()),
+            Token::Symbol("declare-datatypes".into()),
+        ];
+        assert_eq!(expected, lex_all(input));
+
+        // Only a lone `_` is reserved, so it can be told apart from symbols that start with it, as
+        // in the patterns of `match` terms. Sort and operator names like `BitVec` are not reserved
+        let input = "_ _x BitVec par2";
+        let expected = vec![
+            Token::ReservedWord(Reserved::Underscore),
+            Token::Symbol("_x".into()),
+            Token::Symbol("BitVec".into()),
+            Token::Symbol("par2".into()),
+        ];
+        assert_eq!(expected, lex_all(input));
     }
 }
@@ -7,26 +7,66 @@ pub(crate) mod tests;
 use std::iter::Iterator;
 
 pub use error::{ParserError, SortError};
-pub use lexer::{Lexer, Position, Reserved, Token};
+pub use lexer::{
+    ChainedInput, Lexer, LexerInput, Position, ReaderInput, Reserved, SliceInput, Token,
+};
 
 use crate::{
     ast::*,
     utils::{HashCache, HashMapStack},
     CarcaraResult, Error,
 };
-use error::assert_num_args;
+use error::{assert_num_args, assert_sort_kind};
 use indexmap::{IndexMap, IndexSet};
 use rug::{Integer, Rational};
-use std::{io::BufRead, str::FromStr};
+use std::{collections::VecDeque, io::BufRead, str::FromStr};
 
 use self::error::assert_indexed_op_args_value;
 
+/// A suggested value for `Config::max_term_depth`. Terms this deep can be checked using the default
+/// stack size of 2 MiB that Rust gives to spawned threads.
+pub const DEFAULT_MAX_TERM_DEPTH: usize = 1_000;
+
 #[derive(Debug, Clone, Copy)]
 pub struct Config {
+    /// If `true`, applications of functions introduced by `define-fun` in the problem are replaced
+    /// by the function body, both in the problem and in the proof. Otherwise, defined functions are
+    /// kept as uninterpreted symbols, and for each definition a premise of the form `(= f (lambda
+    /// ((x S) ...) body))` is added to the problem, or `(= f body)` if the function takes no
+    /// arguments. Since this premise is part of the problem, the checker trusts any `assume`
+    /// command that introduces it.
     pub apply_function_defs: bool,
     pub expand_lets: bool,
     pub allow_int_real_subtyping: bool,
     pub allow_unary_logical_ops: bool,
+
+    /// If `true`, undeclared symbols are declared as fresh uninterpreted constants, instead of
+    /// causing a `ParserError::UndefinedIden` error. The sort of a symbol is inferred from its
+    /// first use: if it is an argument of an application, it takes the sort expected by the
+    /// function or operator, which may depend on the sorts of the other arguments, as in `(+ x
+    /// 1)`. If an undeclared symbol is applied, its parameters take the sorts of the arguments,
+    /// and its return sort is inferred in the same way. If the first use doesn't determine the
+    /// sort, `Bool` is used. Later uses must agree with the inferred sort.
+    pub allow_unknown_symbols: bool,
+    pub rename_shadowed: bool,
+
+    /// If this is `Some`, the parser returns a `ParserError::TermTooDeep` error when it encounters
+    /// a term nested more deeply than the given depth. The parser itself parses nested
+    /// applications iteratively, but many routines that operate on terms, like printing and polyeq
+    /// comparison, are recursive. This guards them against stack overflows on very deeply nested
+    /// terms. By default, this is `None`, and [`DEFAULT_MAX_TERM_DEPTH`] is a safe limit for the
+    /// default stack size.
+    pub max_term_depth: Option<usize>,
+
+    /// If `true`, the literals in the conclusion of each step are sorted into a canonical order,
+    /// given by their structural hashes (see [`TermInfo`](crate::ast::pool::TermInfo)). The original
+    /// order of the clauses that were changed can be retrieved with [`Parser::original_clauses`],
+    /// and is kept in [`Proof::original_clauses`], so [`Proof::to_alethe_string`] can print it.
+    /// This option is only meant for library users that need a canonical form of each clause, and
+    /// is not useful for checking: many rules depend on the order of the literals in the
+    /// conclusion, so steps that use these rules may no longer check after normalization. For the
+    /// same reason, it is not exposed in the command-line interface.
+    pub normalize_clause_order: bool,
 }
 
 impl Config {
@@ -36,10 +76,28 @@ impl Config {
             expand_lets: false,
             allow_int_real_subtyping: false,
             allow_unary_logical_ops: true,
+            allow_unknown_symbols: false,
+            rename_shadowed: false,
+            max_term_depth: None,
+            normalize_clause_order: false,
         }
     }
 }
 
+/// A warning about input that is valid, but may cause problems later on. Warnings are collected
+/// while parsing, and can be retrieved with [`Parser::warnings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParserWarning {
+    /// A `let` or quantifier binds a name that is already bound by an enclosing binder. If
+    /// `Config::rename_shadowed` is enabled, the inner binder is renamed, and `renamed_to` holds
+    /// its new name.
+    ShadowedBinder {
+        name: String,
+        position: Position,
+        renamed_to: Option<String>,
+    },
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self::new()
@@ -61,13 +119,272 @@ pub fn parse_instance<T: BufRead>(
     let (prelude, premises) = parser.parse_problem()?;
     parser.reset(proof)?;
     let commands = parser.parse_proof()?;
+    let named_terms = parser.named_terms().clone();
+    let original_clauses = parser.original_clauses().clone();
+
+    let proof = Proof {
+        premises,
+        commands,
+        named_terms,
+        original_clauses,
+    };
+    Ok((prelude, proof, pool))
+}
+
+/// Parses an SMT problem instance and its associated proof, like [`parse_instance`], and also a
+/// series of boolean terms from `lemmas`, such as the lemmas trusted by the checker. The terms in
+/// `lemmas` are parsed after the problem, so they may use any symbol declared in it.
+///
+/// This returns the parsed lemmas in addition to the results of [`parse_instance`].
+pub fn parse_instance_with_lemmas<'a, T: BufRead + 'a>(
+    problem: T,
+    proof: T,
+    lemmas: &'a str,
+    config: Config,
+) -> CarcaraResult<(ProblemPrelude, Proof, PrimitivePool, Vec<Rc<Term>>)> {
+    let mut pool = PrimitivePool::new();
+    let problem: Box<dyn BufRead + 'a> = Box::new(problem);
+    let mut parser = Parser::new(&mut pool, config, problem)?;
+    let (prelude, premises) = parser.parse_problem()?;
+    parser.reset(Box::new(lemmas.as_bytes()))?;
+    let lemmas = parser.parse_formulas()?;
+    parser.reset(Box::new(proof))?;
+    let commands = parser.parse_proof()?;
+    let named_terms = parser.named_terms().clone();
+    let original_clauses = parser.original_clauses().clone();
+
+    let proof = Proof {
+        premises,
+        commands,
+        named_terms,
+        original_clauses,
+    };
+    Ok((prelude, proof, pool, lemmas))
+}
 
-    let proof = Proof { premises, commands };
+/// Parses an SMT problem instance (in the SMT-LIB format) and a proof (in the Alethe format) that
+/// is split across several parts.
+///
+/// The parts are read in order, as if they were a single input, so there is no need to concatenate
+/// them beforehand. In particular, steps in one part may refer to steps in previous parts, and step
+/// ids must be unique across all parts. A part may end in the middle of a command, but not in the
+/// middle of a token, since the end of each part is treated as a line break.
+///
+/// The parts may be different types of readers, like files or in-memory buffers, so they are
+/// passed as boxed trait objects.
+pub fn parse_instance_multi<'a, T: BufRead + 'a>(
+    problem: T,
+    proof_parts: Vec<Box<dyn BufRead + 'a>>,
+    config: Config,
+) -> CarcaraResult<(ProblemPrelude, Proof, PrimitivePool)> {
+    let mut pool = PrimitivePool::new();
+    let problem: Box<dyn BufRead + 'a> = Box::new(problem);
+    let problem = ChainedInput::new([ReaderInput::new(problem)]);
+    let mut parser = Parser::from_lexer(&mut pool, config, Lexer::with_input(problem)?)?;
+    let (prelude, premises) = parser.parse_problem()?;
+    let proof = ChainedInput::new(proof_parts.into_iter().map(ReaderInput::new));
+    parser.reset_lexer(Lexer::with_input(proof)?)?;
+    let commands = parser.parse_proof()?;
+    let named_terms = parser.named_terms().clone();
+    let original_clauses = parser.original_clauses().clone();
+
+    let proof = Proof {
+        premises,
+        commands,
+        named_terms,
+        original_clauses,
+    };
     Ok((prelude, proof, pool))
 }
 
+/// Sort checks the arguments of an operation term, using the signature of the operator. The
+/// `allow_unary_logical_ops` and `allow_int_real_subtyping` options in `config` relax these checks.
+pub(crate) fn check_op_args(
+    pool: &mut dyn TermPool,
+    op: Operator,
+    args: &[Rc<Term>],
+    config: &Config,
+) -> Result<(), ParserError> {
+    let sorts: Vec<_> = args.iter().map(|t| pool.sort(t)).collect();
+    let sorts: Vec<_> = sorts.iter().map(|s| s.as_sort().unwrap()).collect();
+    let OperatorSignature { mut arity, args: mut arg_sorts, .. } = op.signature();
+
+    // If we are not in "strict" parsing mode, we allow these operators to be called with just
+    // one argument
+    if config.allow_unary_logical_ops && matches!(op, Operator::Or | Operator::And | Operator::Xor)
+    {
+        arity = Arity::AtLeast(1);
+    }
+
+    // If we are allowing Int/Real subtyping, the arithmetic operators (except for `div`) may
+    // receive a mix of Int and Real arguments
+    if config.allow_int_real_subtyping
+        && matches!(
+            arg_sorts,
+            ArgSorts::AllEqual(Some(SortKind::Numeric | SortKind::Real))
+        )
+    {
+        arg_sorts = ArgSorts::Each(SortKind::Numeric);
+    }
+
+    assert_num_args(args, arity)?;
+    match arg_sorts {
+        ArgSorts::Any => (),
+        ArgSorts::Each(kind) => {
+            for s in &sorts {
+                assert_sort_kind(kind, s)?;
+            }
+        }
+        ArgSorts::Positional(kinds) => {
+            for (&kind, s) in kinds.iter().zip(&sorts) {
+                assert_sort_kind(kind, s)?;
+            }
+        }
+        ArgSorts::AllEqual(kind) => {
+            if let (Some(kind), Some(first)) = (kind, sorts.first()) {
+                assert_sort_kind(kind, first)?;
+            }
+            SortError::assert_all_eq(&sorts)?;
+        }
+        ArgSorts::Ite => {
+            SortError::assert_eq(&Sort::Bool, sorts[0])?;
+            SortError::assert_eq(sorts[1], sorts[2])?;
+        }
+        ArgSorts::Select => {
+            SortError::assert_array_sort(pool, Some(sorts[1]), None, sorts[0])?;
+        }
+        ArgSorts::Store => {
+            SortError::assert_array_sort(pool, Some(sorts[1]), Some(sorts[2]), sorts[0])?;
+        }
+    }
+    Ok(())
+}
+
+/// Sort checks the arguments of an application of `function`.
+pub(crate) fn check_app_args(
+    pool: &dyn TermPool,
+    function: &Rc<Term>,
+    args: &[Rc<Term>],
+) -> Result<(), ParserError> {
+    let sort = pool.sort(function);
+    let sorts = {
+        let function_sort = sort.as_sort().unwrap();
+        if let Sort::Function(sorts) = function_sort {
+            sorts
+        } else {
+            // Function does not have function sort
+            return Err(ParserError::NotAFunction(function_sort.clone()));
+        }
+    };
+    assert_num_args(args, sorts.len() - 1)?;
+    for i in 0..args.len() {
+        SortError::assert_eq(
+            sorts[i].as_sort().unwrap(),
+            pool.sort(&args[i]).as_sort().unwrap(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Sort checks the arguments of an indexed operation term, like `((_ extract i j) x)`. This
+/// rejects `ArrayConst`, which is a qualified operator, and `BvConst`, which the parser turns into
+/// a constant.
+pub(crate) fn check_indexed_op_args(
+    pool: &dyn TermPool,
+    op: ParamOperator,
+    op_args: &[Constant],
+    args: &[Rc<Term>],
+) -> Result<(), ParserError> {
+    let sorts: Vec<_> = args.iter().map(|t| pool.sort(t)).collect();
+    let sorts: Vec<_> = sorts.iter().map(|s| s.as_sort().unwrap()).collect();
+    match op {
+        ParamOperator::BvExtract => {
+            /*
+            ((_ extract i j) (_ BitVec m) (_ BitVec n))
+
+            where
+            - i, j, m, n are numerals
+            - m > i ≥ j ≥ 0,
+            - n = i - j + 1
+             */
+            assert_num_args(op_args, 2)?;
+            assert_num_args(args, 1)?;
+            if !matches!(sorts[0], Sort::BitVec(_)) {
+                return Err(ParserError::ExpectedBvSort(sorts[0].clone()));
+            }
+            for arg in op_args {
+                SortError::assert_eq(&Sort::Int, &arg.sort())?;
+            }
+            assert_indexed_op_args_value(op_args, 0..)?;
+            let i = op_args[0].as_integer().unwrap();
+            let j = op_args[1].as_integer().unwrap();
+            let Sort::BitVec(m) = sorts[0].clone() else {
+                unreachable!()
+            };
+            if !(m > i && i >= j && j >= Integer::ZERO) {
+                return Err(ParserError::InvalidExtractArgs(
+                    i.to_usize().unwrap(),
+                    j.to_usize().unwrap(),
+                    m.to_usize().unwrap(),
+                ));
+            }
+        }
+        ParamOperator::BvBitOf | ParamOperator::ZeroExtend | ParamOperator::SignExtend => {
+            assert_num_args(op_args, 1)?;
+            assert_num_args(args, 1)?;
+            SortError::assert_eq(&Sort::Int, &op_args[0].sort())?;
+            if !matches!(sorts[0], Sort::BitVec(_)) {
+                return Err(ParserError::ExpectedBvSort(sorts[0].clone()));
+            }
+            assert_indexed_op_args_value(op_args, 0..)?;
+        }
+        ParamOperator::RePower => {
+            assert_num_args(op_args, 1)?;
+            assert_num_args(args, 1)?;
+            SortError::assert_eq(&Sort::Int, &op_args[0].sort())?;
+            SortError::assert_eq(&Sort::RegLan, sorts[0])?;
+            assert_indexed_op_args_value(op_args, 0..)?;
+        }
+        ParamOperator::ReLoop => {
+            assert_num_args(op_args, 2)?;
+            assert_num_args(args, 1)?;
+            for arg in op_args {
+                SortError::assert_eq(&Sort::Int, &arg.sort())?;
+            }
+            SortError::assert_eq(&Sort::RegLan, sorts[0])?;
+            assert_indexed_op_args_value(op_args, 0..)?;
+        }
+        ParamOperator::BvConst | ParamOperator::ArrayConst => {
+            return Err(ParserError::InvalidIndexedOp(op.to_string()))
+        }
+    }
+    Ok(())
+}
+
+/// Sort checks the arguments of a qualified operation term, like `((as const S) x)`.
+pub(crate) fn check_qualified_op_args(
+    pool: &mut dyn TermPool,
+    op: ParamOperator,
+    op_sort: &Rc<Term>,
+    args: &[Rc<Term>],
+) -> Result<(), ParserError> {
+    let sorts: Vec<_> = args.iter().map(|t| pool.sort(t)).collect();
+    let sorts: Vec<_> = sorts.iter().map(|s| s.as_sort().unwrap()).collect();
+    match op {
+        ParamOperator::ArrayConst => {
+            assert_num_args(args, 1)?;
+            SortError::assert_array_sort(pool, None, Some(sorts[0]), op_sort.as_sort().unwrap())?;
+        }
+        _ => return Err(ParserError::InvalidQualifiedOp(op.to_string())),
+    }
+    Ok(())
+}
+
 /// A function definition, from a `define-fun` command.
 struct FunctionDef {
+    /// The sort parameters of the definition, if it is parametric. Each one is represented as a
+    /// user-declared sort with arity zero.
+    sort_params: Vec<Rc<Term>>,
     params: Vec<SortedVar>,
     body: Rc<Term>,
 }
@@ -78,6 +395,9 @@ impl FunctionDef {
         if args.is_empty() {
             return Ok(self.body.clone());
         }
+        if !self.sort_params.is_empty() {
+            return self.instantiate(p, &args)?.apply(p, args);
+        }
 
         for (arg, param) in args.iter().zip(self.params.iter()) {
             SortError::assert_eq(param.1.as_sort().unwrap(), p.sort(arg).as_sort().unwrap())?;
@@ -99,6 +419,140 @@ impl FunctionDef {
             .apply(p, &self.body);
         Ok(result)
     }
+
+    /// Instantiates the sort parameters of a parametric definition with the sorts of `args`.
+    fn instantiate(&self, p: &mut PrimitivePool, args: &[Rc<Term>]) -> Result<Self, ParserError> {
+        let param_sorts: Vec<_> = self.params.iter().map(|(_, s)| s.clone()).collect();
+        let mut instantiation = instantiate_sort_params(p, &self.sort_params, &param_sorts, args)?;
+        let params = self
+            .params
+            .iter()
+            .map(|(n, s)| (n.clone(), instantiation.apply(p, s)))
+            .collect();
+        let body = instantiate_sorts(p, &self.body, &mut instantiation);
+        Ok(Self {
+            sort_params: Vec::new(),
+            params,
+            body,
+        })
+    }
+}
+
+/// Computes the instantiation of the sort parameters `params` for which each sort in `expected`
+/// becomes the sort of the corresponding term in `args`. The instantiation is returned as a
+/// substitution mapping each sort parameter to a sort. Returns an error if there is no such
+/// instantiation.
+fn instantiate_sort_params(
+    pool: &mut PrimitivePool,
+    params: &[Rc<Term>],
+    expected: &[Rc<Term>],
+    args: &[Rc<Term>],
+) -> Result<Substitution, ParserError> {
+    fn match_sort(
+        pattern: &Rc<Term>,
+        sort: &Rc<Term>,
+        params: &[Rc<Term>],
+        bindings: &mut IndexMap<Rc<Term>, Rc<Term>>,
+    ) -> bool {
+        if params.contains(pattern) {
+            return match bindings.get(pattern) {
+                Some(bound) => bound == sort,
+                None => {
+                    bindings.insert(pattern.clone(), sort.clone());
+                    true
+                }
+            };
+        }
+        match (pattern.as_sort().unwrap(), sort.as_sort().unwrap()) {
+            (Sort::Atom(a, a_args), Sort::Atom(b, b_args)) => {
+                a == b && match_sorts(a_args, b_args, params, bindings)
+            }
+            (Sort::Function(a), Sort::Function(b)) => match_sorts(a, b, params, bindings),
+            (Sort::Array(a_x, a_y), Sort::Array(b_x, b_y)) => {
+                match_sort(a_x, b_x, params, bindings) && match_sort(a_y, b_y, params, bindings)
+            }
+            _ => pattern == sort,
+        }
+    }
+
+    fn match_sorts(
+        patterns: &[Rc<Term>],
+        sorts: &[Rc<Term>],
+        params: &[Rc<Term>],
+        bindings: &mut IndexMap<Rc<Term>, Rc<Term>>,
+    ) -> bool {
+        patterns.len() == sorts.len()
+            && patterns
+                .iter()
+                .zip(sorts)
+                .all(|(p, s)| match_sort(p, s, params, bindings))
+    }
+
+    let mut bindings = IndexMap::new();
+    for (pattern, arg) in expected.iter().zip(args) {
+        let sort = pool.sort(arg);
+        if !match_sort(pattern, &sort, params, &mut bindings) {
+            return Err(SortError {
+                expected: vec![pattern.as_sort().unwrap().clone()],
+                got: sort.as_sort().unwrap().clone(),
+            }
+            .into());
+        }
+    }
+
+    // Since both the sort parameters and the sorts they are mapped to are of sort `Type`, creating
+    // this substitution can never fail
+    Ok(Substitution::new(pool, bindings).unwrap())
+}
+
+/// Applies the sort substitution `instantiation` to the sorts of all variables in `term`,
+/// including the ones bound by binders.
+fn instantiate_sorts(
+    pool: &mut PrimitivePool,
+    term: &Rc<Term>,
+    instantiation: &mut Substitution,
+) -> Rc<Term> {
+    macro_rules! apply_to_sequence {
+        ($sequence:expr) => {
+            $sequence
+                .iter()
+                .map(|t| instantiate_sorts(pool, t, instantiation))
+                .collect::<Vec<_>>()
+        };
+    }
+
+    let result = match term.as_ref() {
+        Term::Const(_) => return term.clone(),
+        Term::Sort(_) => return instantiation.apply(pool, term),
+        Term::Var(name, sort) => Term::Var(name.clone(), instantiation.apply(pool, sort)),
+        Term::App(func, args) => {
+            let func = instantiate_sorts(pool, func, instantiation);
+            Term::App(func, apply_to_sequence!(args))
+        }
+        Term::Op(op, args) => Term::Op(*op, apply_to_sequence!(args)),
+        Term::ParamOp { op, op_args, args } => Term::ParamOp {
+            op: *op,
+            op_args: apply_to_sequence!(op_args),
+            args: apply_to_sequence!(args),
+        },
+        Term::Binder(binder, bindings, inner) => {
+            let bindings = bindings
+                .iter()
+                .map(|(n, s)| (n.clone(), instantiation.apply(pool, s)))
+                .collect();
+            let inner = instantiate_sorts(pool, inner, instantiation);
+            Term::Binder(*binder, BindingList(bindings), inner)
+        }
+        Term::Let(bindings, inner) => {
+            let bindings = bindings
+                .iter()
+                .map(|(n, value)| (n.clone(), instantiate_sorts(pool, value, instantiation)))
+                .collect();
+            let inner = instantiate_sorts(pool, inner, instantiation);
+            Term::Let(BindingList(bindings), inner)
+        }
+    };
+    pool.add(result)
 }
 
 /// A sort definition, from a `define-sort` command.
@@ -107,6 +561,62 @@ struct SortDef {
     body: Rc<Term>,
 }
 
+/// The patterns given to a quantifier. Each pattern is a list of terms.
+type Patterns = Vec<Vec<Rc<Term>>>;
+
+/// The head of an application whose arguments are still being parsed. This determines how the
+/// term is built once all of its arguments are parsed.
+enum ApplicationHead {
+    Op(Operator),
+    Overloaded(String),
+    Parametric(String),
+    FunctionDef(String),
+    UnknownSymbol(String),
+    IndexedOp(ParamOperator, Vec<Constant>),
+    QualifiedOp(ParamOperator, Rc<Term>),
+
+    /// The head is an arbitrary term. It is parsed as if it were the first argument.
+    Term,
+}
+
+/// An application whose arguments are still being parsed, with the position of its head.
+struct PendingApplication {
+    head: ApplicationHead,
+    args: Vec<Rc<Term>>,
+    position: Position,
+}
+
+impl PendingApplication {
+    /// Returns `true` if no arguments were parsed yet, not counting the head.
+    fn has_no_args(&self) -> bool {
+        match self.head {
+            ApplicationHead::Term => self.args.len() < 2,
+            _ => self.args.is_empty(),
+        }
+    }
+}
+
+/// The result of parsing the start of an application. Some applications, like binder terms, are
+/// parsed completely when their head is parsed, so they are already a term.
+enum ApplicationStart {
+    Term(Rc<Term>),
+    Pending(PendingApplication),
+}
+
+/// An undeclared symbol, declared on its first use.
+struct UnknownSymbol {
+    /// The sort of the symbol. Until it is inferred, this is `Bool` for constants, and a function
+    /// sort with return sort `Bool` for applied symbols.
+    sort: Rc<Term>,
+
+    /// The number of times the symbol was used.
+    uses: usize,
+
+    /// If `false`, the sort of the symbol may still be inferred from the application in which it
+    /// was first used.
+    fixed: bool,
+}
+
 /// The state of the parser.
 ///
 /// This holds all the function, constant or sort declarations and definitions, as well as the term
@@ -118,6 +628,38 @@ struct ParserState {
     sort_declarations: HashMapStack<String, usize>,
     sort_defs: IndexMap<String, SortDef>,
     step_ids: HashMapStack<HashCache<String>, usize>,
+    named_terms: IndexMap<Rc<Term>, String>,
+
+    /// The undeclared symbols, declared on their first use when `Config::allow_unknown_symbols` is
+    /// enabled.
+    unknown_symbols: IndexMap<String, UnknownSymbol>,
+
+    /// The sort parameters of functions declared with a parametric signature, using `par`.
+    parametric_functions: IndexMap<String, Vec<Rc<Term>>>,
+
+    /// The signatures of functions that were declared more than once with different sorts. When
+    /// one of these is applied, the signature is chosen based on the sorts of the arguments.
+    overloads: IndexMap<String, Vec<Rc<Term>>>,
+
+    /// The names bound by the enclosing binders, mapped to the names they are bound as. These
+    /// differ only if the binder was renamed because of `Config::rename_shadowed`.
+    bound_names: HashMapStack<String, String>,
+    warnings: Vec<ParserWarning>,
+
+    /// The patterns given to quantifiers using the `:pattern` attribute on their bodies.
+    patterns: IndexMap<Rc<Term>, Patterns>,
+
+    /// The patterns in the last annotated term that was parsed, together with the term. If this
+    /// term is the body of a quantifier, the patterns are moved to `patterns`.
+    pending_patterns: Option<(Rc<Term>, Patterns)>,
+
+    /// The defining equations of the functions introduced in `define-fun-rec` and
+    /// `define-funs-rec` commands.
+    recursive_definitions: IndexMap<String, Rc<Term>>,
+
+    /// The original conclusions of the steps whose literals were reordered because of
+    /// `Config::normalize_clause_order`, indexed by the position of each step in the proof.
+    original_clauses: IndexMap<Vec<usize>, Vec<Rc<Term>>>,
 }
 
 /// A parser for the Alethe proof format.
@@ -127,17 +669,51 @@ pub struct Parser<'a, R> {
     lexer: Lexer<R>,
     current_token: Token,
     current_position: Position,
+
+    /// The token after `current_token`, if it was already read from the lexer by `peek_token`.
+    peeked_token: Option<(Token, Position)>,
+
+    /// Tokens that were already read, and must be read again before reading from the lexer. See
+    /// `replay_tokens`.
+    replayed_tokens: VecDeque<(Token, Position)>,
     state: ParserState,
     interpret_integers_as_reals: bool,
     problem: Option<(ProblemPrelude, IndexSet<Rc<Term>>)>,
+
+    /// The number of applications that enclose the term currently being parsed.
+    term_depth: usize,
 }
 
-impl<'a, R: BufRead> Parser<'a, R> {
+/// A parser that reads its input from a type that implements `BufRead`, as constructed by
+/// [`Parser::new`]. Code that used to name the parser type as `Parser<'a, R>`, for some `R:
+/// BufRead`, can name it as `ReaderParser<'a, R>` instead.
+pub type ReaderParser<'a, R> = Parser<'a, ReaderInput<R>>;
+
+impl<'a, R: BufRead> ReaderParser<'a, R> {
     /// Constructs a new `Parser` from a type that implements `BufRead`.
     ///
     /// This operation can fail if there is an IO or lexer error on the first token.
     pub fn new(pool: &'a mut PrimitivePool, config: Config, input: R) -> CarcaraResult<Self> {
-        let mut lexer = Lexer::new(input)?;
+        Self::from_lexer(pool, config, Lexer::new(input)?)
+    }
+
+    /// Resets the parser position and sets its input to `input`. This keeps the parser state,
+    /// including all function, constant and sort declarations.
+    pub fn reset(&mut self, input: R) -> CarcaraResult<()> {
+        self.reset_lexer(Lexer::new(input)?)
+    }
+}
+
+impl<'a, R: LexerInput> Parser<'a, R> {
+    /// Constructs a new `Parser` that reads tokens from the given lexer. Using a lexer created
+    /// with [`Lexer::from_str`] avoids allocating a buffer for each line of the input.
+    ///
+    /// This operation can fail if there is an IO or lexer error on the first token.
+    pub fn from_lexer(
+        pool: &'a mut PrimitivePool,
+        config: Config,
+        mut lexer: Lexer<R>,
+    ) -> CarcaraResult<Self> {
         let (current_token, current_position) = lexer.next_token()?;
         Ok(Parser {
             pool,
@@ -145,20 +721,61 @@ impl<'a, R: BufRead> Parser<'a, R> {
             lexer,
             current_token,
             current_position,
+            peeked_token: None,
+            replayed_tokens: VecDeque::new(),
             state: ParserState::default(),
             interpret_integers_as_reals: false,
             problem: None,
+            term_depth: 0,
         })
     }
 
-    /// Resets the parser position and sets its input to `input`. This keeps the parser state,
-    /// including all function, constant and sort declarations.
-    pub fn reset(&mut self, input: R) -> CarcaraResult<()> {
-        let mut lexer = Lexer::new(input)?;
+    /// Returns the functions defined so far using `define-fun-rec` or `define-funs-rec`, mapped to
+    /// their defining equations. Since expanding these definitions would not terminate, the
+    /// functions are instead declared as uninterpreted functions, and their defining equations are
+    /// also added as premises.
+    pub fn recursive_definitions(&self) -> &IndexMap<String, Rc<Term>> {
+        &self.state.recursive_definitions
+    }
+
+    /// Returns the warnings emitted while parsing so far.
+    pub fn warnings(&self) -> &[ParserWarning] {
+        &self.state.warnings
+    }
+
+    /// Returns the patterns given to `forall` and `exists` terms so far, using the `:pattern`
+    /// attribute on the quantifier body. Each pattern is a list of terms, and the patterns of each
+    /// quantifier are in the order they appear. If the same quantifier term appears multiple times
+    /// with different patterns, only the ones in its first occurrence are included.
+    pub fn patterns(&self) -> &IndexMap<Rc<Term>, Vec<Vec<Rc<Term>>>> {
+        &self.state.patterns
+    }
+
+    /// Returns the names given to terms using the `:named` attribute so far. If a term was given
+    /// multiple names, only the first one is included.
+    pub fn named_terms(&self) -> &IndexMap<Rc<Term>, String> {
+        &self.state.named_terms
+    }
+
+    /// Returns the original conclusions of the steps whose literals were reordered so far, when
+    /// `Config::normalize_clause_order` is enabled. Each step is identified by its position in the
+    /// proof, that is, its index in each of the subproofs that enclose it, starting from the root
+    /// proof. Since step ids may be reused in different subproofs, they can't be used instead.
+    /// Steps whose literals were already in canonical order are not included.
+    pub fn original_clauses(&self) -> &IndexMap<Vec<usize>, Vec<Rc<Term>>> {
+        &self.state.original_clauses
+    }
+
+    /// Resets the parser position and makes it read tokens from `lexer`. This keeps the parser
+    /// state, including all function, constant and sort declarations.
+    pub fn reset_lexer(&mut self, mut lexer: Lexer<R>) -> CarcaraResult<()> {
         let (current_token, current_position) = lexer.next_token()?;
         self.lexer = lexer;
         self.current_token = current_token;
         self.current_position = current_position;
+        self.peeked_token = None;
+        self.replayed_tokens.clear();
+        self.term_depth = 0;
         Ok(())
     }
 
@@ -166,17 +783,88 @@ impl<'a, R: BufRead> Parser<'a, R> {
     fn next_token(&mut self) -> CarcaraResult<(Token, Position)> {
         use std::mem::replace;
 
-        let (new_token, new_position) = self.lexer.next_token()?;
+        let (new_token, new_position) = match self.peeked_token.take() {
+            Some(peeked) => peeked,
+            None => self.read_token()?,
+        };
         let old_token = replace(&mut self.current_token, new_token);
         let old_position = replace(&mut self.current_position, new_position);
         Ok((old_token, old_position))
     }
 
+    /// Returns the token after `current_token`, without consuming any tokens.
+    fn peek_token(&mut self) -> CarcaraResult<&Token> {
+        if self.peeked_token.is_none() {
+            self.peeked_token = Some(self.read_token()?);
+        }
+        Ok(&self.peeked_token.as_ref().unwrap().0)
+    }
+
+    /// Reads a new token, either from the tokens being replayed or from the lexer.
+    fn read_token(&mut self) -> CarcaraResult<(Token, Position)> {
+        match self.replayed_tokens.pop_front() {
+            Some(token) => Ok(token),
+            None => Ok(self.lexer.next_token()?),
+        }
+    }
+
+    /// Makes the parser read `tokens` again, as if they came before the current token. The first
+    /// of them becomes the current token.
+    fn replay_tokens(&mut self, tokens: Vec<(Token, Position)>) {
+        use std::mem::replace;
+
+        let mut tokens = VecDeque::from(tokens);
+        let Some((first_token, first_position)) = tokens.pop_front() else {
+            return;
+        };
+        let old_token = replace(&mut self.current_token, first_token);
+        let old_position = replace(&mut self.current_position, first_position);
+        tokens.push_back((old_token, old_position));
+        tokens.extend(self.peeked_token.take());
+        tokens.append(&mut self.replayed_tokens);
+        self.replayed_tokens = tokens;
+    }
+
     /// Inserts a `SortedVar` into the parser symbol table.
     fn insert_sorted_var(&mut self, (symbol, sort): SortedVar) {
         self.state.symbol_table.insert(HashCache::new(symbol), sort);
     }
 
+    /// Binds `name` in the scope of the current `let` or quantifier, and returns the name the
+    /// variable should be bound as. If `name` is already bound by an enclosing binder, a warning is
+    /// emitted, and if `Config::rename_shadowed` is enabled, a fresh name is returned instead.
+    fn bind_name(&mut self, name: String, position: Position) -> String {
+        let bound_as = if self.state.bound_names.get(&name).is_some() {
+            let renamed_to = self.config.rename_shadowed.then(|| self.fresh_name(&name));
+            self.state.warnings.push(ParserWarning::ShadowedBinder {
+                name: name.clone(),
+                position,
+                renamed_to: renamed_to.clone(),
+            });
+            renamed_to.unwrap_or_else(|| name.clone())
+        } else {
+            name.clone()
+        };
+        self.state.bound_names.insert(name, bound_as.clone());
+        bound_as
+    }
+
+    /// Returns a name derived from `name` that is not declared or bound anywhere, by adding `'`
+    /// characters to it.
+    fn fresh_name(&self, name: &str) -> String {
+        let mut fresh = format!("{name}'");
+        while self
+            .state
+            .symbol_table
+            .get(&HashCache::new(fresh.clone()))
+            .is_some()
+            || self.state.bound_names.get(&fresh).is_some()
+        {
+            fresh.push('\'');
+        }
+        fresh
+    }
+
     /// Shortcut for `self.problem.as_mut().unwrap().0`
     fn prelude(&mut self) -> &mut ProblemPrelude {
         &mut self.problem.as_mut().unwrap().0
@@ -189,269 +877,273 @@ impl<'a, R: BufRead> Parser<'a, R> {
 
     /// Constructs and sort checks a variable term.
     fn make_var(&mut self, iden: String) -> Result<Rc<Term>, ParserError> {
+        let iden = if self.config.rename_shadowed {
+            self.state.bound_names.get(&iden).cloned().unwrap_or(iden)
+        } else {
+            iden
+        };
         let cached = HashCache::new(iden);
         let sort = match self.state.symbol_table.get(&cached) {
             Some(s) => s.clone(),
+            None if self.config.allow_unknown_symbols => {
+                let bool_sort = self.pool.intern_sort(Sort::Bool);
+                let iden = cached.unwrap();
+                let sort = self.use_unknown_symbol(&iden, bool_sort);
+                return Ok(self.pool.add(Term::Var(iden, sort)));
+            }
             None => return Err(ParserError::UndefinedIden(cached.unwrap())),
         };
         Ok(self.pool.add(Term::Var(cached.unwrap(), sort)))
     }
 
-    /// Constructs and sort checks an operation term.
-    fn make_op(&mut self, op: Operator, args: Vec<Rc<Term>>) -> Result<Rc<Term>, ParserError> {
-        let sorts: Vec<_> = args.iter().map(|t| self.pool.sort(t)).collect();
-        let sorts: Vec<_> = sorts.iter().map(|s| s.as_sort().unwrap()).collect();
-        match op {
-            Operator::True | Operator::False => assert_num_args(&args, 0)?,
-            Operator::Not => {
-                assert_num_args(&args, 1)?;
-                SortError::assert_eq(&Sort::Bool, sorts[0])?;
-            }
-            Operator::Implies => {
-                assert_num_args(&args, 2..)?;
-                for s in sorts {
-                    SortError::assert_eq(&Sort::Bool, s)?;
+    /// Returns `true` if `iden` is a symbol that is not declared, and is not an operator or a
+    /// defined function.
+    fn is_unknown_symbol(&self, iden: &str) -> bool {
+        Operator::from_symbol(iden).is_none()
+            && self.state.function_defs.get(iden).is_none()
+            && self
+                .state
+                .symbol_table
+                .get(&HashCache::new(iden.to_owned()))
+                .is_none()
+    }
+
+    /// Returns the sort of the undeclared symbol `iden`, and counts this use of it. The first time
+    /// a symbol is used, it is declared as a fresh uninterpreted constant of sort `sort`, which may
+    /// later be changed by `infer_unknown_symbol_sorts`.
+    fn use_unknown_symbol(&mut self, iden: &str, sort: Rc<Term>) -> Rc<Term> {
+        if let Some(symbol) = self.state.unknown_symbols.get_mut(iden) {
+            symbol.uses += 1;
+            return symbol.sort.clone();
+        }
+        log::warn!("undeclared symbol '{iden}' declared as a fresh constant");
+        let symbol = UnknownSymbol {
+            sort: sort.clone(),
+            uses: 1,
+            fixed: false,
+        };
+        self.state.unknown_symbols.insert(iden.to_owned(), symbol);
+        sort
+    }
+
+    /// If `term` is an undeclared symbol, or an application of one, whose sort is not yet fixed,
+    /// returns its name.
+    fn unfixed_unknown_symbol<'t>(&self, term: &'t Rc<Term>) -> Option<&'t str> {
+        let var = match term.as_ref() {
+            Term::App(func, _) => func,
+            _ => term,
+        };
+        let Term::Var(name, sort) = var.as_ref() else {
+            return None;
+        };
+        // A variable with the same name may be bound by a binder, so we also compare the sorts
+        match self.state.unknown_symbols.get(name) {
+            Some(symbol) if !symbol.fixed && symbol.sort == *sort => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Infers the sorts of the undeclared symbols in `args` that were first used in this
+    /// application, from the sorts expected by its head. If one of these symbols is applied, its
+    /// return sort is inferred instead. After this, the sorts of all undeclared symbols in `args`
+    /// are fixed.
+    fn infer_unknown_symbol_sorts(&mut self, head: &ApplicationHead, args: &mut [Rc<Term>]) {
+        // If the head is a term, it is the first argument, and it can't be inferred
+        let first = usize::from(matches!(head, ApplicationHead::Term));
+        for i in first..args.len() {
+            let Some(name) = self.unfixed_unknown_symbol(&args[i]).map(str::to_owned) else {
+                continue;
+            };
+            let occurrences: Vec<_> = (first..args.len())
+                .filter(|&j| self.unfixed_unknown_symbol(&args[j]) == Some(&name))
+                .collect();
+
+            // If the symbol was used somewhere else, its sort can no longer change
+            let symbol = &self.state.unknown_symbols[&name];
+            let (uses, old_sort) = (symbol.uses, symbol.sort.clone());
+            let expected = if occurrences.len() == uses {
+                self.expected_arg_sort(head, args, i)
+            } else {
+                None
+            };
+            let is_app = matches!(args[i].as_ref(), Term::App(..));
+            let new_sort = match (expected, old_sort.as_sort().unwrap()) {
+                (Some(expected), Sort::Function(sorts)) if is_app => {
+                    let mut sorts = sorts.clone();
+                    *sorts.last_mut().unwrap() = expected;
+                    self.pool.add(Term::Sort(Sort::Function(sorts)))
                 }
-            }
-            Operator::Or | Operator::And | Operator::Xor => {
-                // If we are not in "strict" parsing mode, we allow these operators to be called
-                // with just one argument
-                let range = if self.config.allow_unary_logical_ops {
-                    1..
-                } else {
-                    2..
-                };
-                assert_num_args(&args, range)?;
-                for s in sorts {
-                    SortError::assert_eq(&Sort::Bool, s)?;
+                (Some(expected), sort) if !is_app && !matches!(sort, Sort::Function(_)) => expected,
+                _ => old_sort.clone(),
+            };
+            if new_sort != old_sort {
+                let var = self.pool.add(Term::Var(name.clone(), new_sort.clone()));
+                for j in occurrences {
+                    args[j] = match args[j].as_ref() {
+                        Term::App(_, app_args) => {
+                            self.pool.add(Term::App(var.clone(), app_args.clone()))
+                        }
+                        _ => var.clone(),
+                    };
                 }
             }
-            Operator::Equals | Operator::Distinct => {
-                assert_num_args(&args, 2..)?;
-                SortError::assert_all_eq(&sorts)?;
-            }
-            Operator::Ite => {
-                assert_num_args(&args, 3)?;
-                SortError::assert_eq(&Sort::Bool, sorts[0])?;
-                SortError::assert_eq(sorts[1], sorts[2])?;
-            }
-            Operator::Add | Operator::Sub | Operator::Mult => {
-                // The `-` operator, in particular, can be called with only one argument, in which
-                // case it means negation instead of subtraction
-                if op == Operator::Sub {
-                    assert_num_args(&args, 1..)?;
-                } else {
-                    assert_num_args(&args, 2..)?;
-                }
+            let symbol = self.state.unknown_symbols.get_mut(&name).unwrap();
+            symbol.sort = new_sort;
+            symbol.fixed = true;
+        }
+    }
 
-                // All the arguments must be either Int or Real. Also, if we are not allowing
-                // Int/Real subtyping, all arguments must have the same sort
-                if self.config.allow_int_real_subtyping {
-                    for s in sorts {
-                        SortError::assert_one_of(&[Sort::Int, Sort::Real], s)?;
-                    }
-                } else {
-                    SortError::assert_one_of(&[Sort::Int, Sort::Real], sorts[0])?;
-                    SortError::assert_all_eq(&sorts)?;
-                }
-            }
-            Operator::IntDiv => {
-                assert_num_args(&args, 2..)?;
-                SortError::assert_eq(&Sort::Int, sorts[0])?;
-                SortError::assert_all_eq(&sorts)?;
-            }
-            Operator::RealDiv => {
-                assert_num_args(&args, 2..)?;
-
-                // Normally, the `/` operator may only receive Real arguments, but if we are
-                // allowing Int/Real subtyping, it may also receive Ints
-                if self.config.allow_int_real_subtyping {
-                    for s in sorts {
-                        SortError::assert_one_of(&[Sort::Int, Sort::Real], s)?;
-                    }
-                } else {
-                    SortError::assert_eq(&Sort::Real, sorts[0])?;
-                    SortError::assert_all_eq(&sorts)?;
-                }
+    /// Returns the sort expected for the argument at index `i` of an application, if it can be
+    /// determined from the head of the application and the other arguments.
+    fn expected_arg_sort(
+        &mut self,
+        head: &ApplicationHead,
+        args: &[Rc<Term>],
+        i: usize,
+    ) -> Option<Rc<Term>> {
+        // Returns the sort of the first argument in `range`, other than `i`, whose sort is known
+        let sibling = |parser: &mut Self, range: std::ops::Range<usize>| {
+            (range.filter(|&j| j != i))
+                .find(|&j| parser.unfixed_unknown_symbol(&args[j]).is_none())
+                .map(|j| parser.pool.sort(&args[j]))
+        };
+        let kind_sort = |parser: &mut Self, kind: SortKind| {
+            let sort = match kind {
+                SortKind::Bool => Sort::Bool,
+                SortKind::Int => Sort::Int,
+                SortKind::Real => Sort::Real,
+                SortKind::String => Sort::String,
+                SortKind::RegLan => Sort::RegLan,
+                SortKind::Numeric | SortKind::BitVec => return None,
+            };
+            Some(parser.pool.intern_sort(sort))
+        };
 
-                if let Some(r) = self.interpret_div_as_real_lit(&args[0], &args[1]) {
-                    return Ok(r);
+        match head {
+            ApplicationHead::Op(op) => match op.signature().args {
+                ArgSorts::Any => None,
+                ArgSorts::Each(kind) => kind_sort(self, kind).or_else(|| {
+                    // If the kind has many sorts, like `Numeric`, we use the sort of the other
+                    // arguments, if it is of that kind
+                    sibling(self, 0..args.len()).filter(|s| kind.matches(s.as_sort().unwrap()))
+                }),
+                ArgSorts::Positional(kinds) => kinds.get(i).and_then(|&k| kind_sort(self, k)),
+                ArgSorts::AllEqual(kind) => {
+                    sibling(self, 0..args.len()).or_else(|| kind.and_then(|k| kind_sort(self, k)))
                 }
-            }
-            Operator::Mod => {
-                assert_num_args(&args, 2)?;
-                SortError::assert_eq(&Sort::Int, sorts[0])?;
-                SortError::assert_eq(&Sort::Int, sorts[1])?;
-            }
-            Operator::Abs => {
-                assert_num_args(&args, 1)?;
-                SortError::assert_eq(&Sort::Int, sorts[0])?;
-            }
-            Operator::LessThan | Operator::GreaterThan | Operator::LessEq | Operator::GreaterEq => {
-                assert_num_args(&args, 2..)?;
-                // All the arguments must be either Int or Real sorted, but they don't need to all
-                // have the same sort
-                for s in sorts {
-                    SortError::assert_one_of(&[Sort::Int, Sort::Real], s)?;
-                }
-            }
-            Operator::ToReal => {
-                assert_num_args(&args, 1)?;
-                SortError::assert_eq(&Sort::Int, sorts[0])?;
-            }
-            Operator::ToInt | Operator::IsInt => {
-                assert_num_args(&args, 1)?;
-                SortError::assert_eq(&Sort::Real, sorts[0])?;
-            }
-            Operator::Select => {
-                assert_num_args(&args, 2)?;
-                SortError::assert_array_sort(self.pool, Some(sorts[1]), None, sorts[0])?;
-            }
-            Operator::Store => {
-                assert_num_args(&args, 3)?;
-                SortError::assert_array_sort(self.pool, Some(sorts[1]), Some(sorts[2]), sorts[0])?;
-            }
-            Operator::StrConcat => {
-                assert_num_args(&args, 2..)?;
-                for s in sorts {
-                    SortError::assert_eq(&Sort::String, s)?;
+                ArgSorts::Ite if i == 0 => kind_sort(self, SortKind::Bool),
+                ArgSorts::Ite => sibling(self, 1..args.len()),
+                ArgSorts::Select | ArgSorts::Store if i > 0 => {
+                    let array = sibling(self, 0..1)?;
+                    match array.as_sort()? {
+                        Sort::Array(index, _) if i == 1 => Some(index.clone()),
+                        Sort::Array(_, element) if i == 2 => Some(element.clone()),
+                        _ => None,
+                    }
                 }
-            }
-            Operator::StrLen | Operator::StrIsDigit | Operator::StrToCode | Operator::StrToInt => {
-                assert_num_args(&args, 1)?;
-                SortError::assert_eq(&Sort::String, sorts[0])?;
-            }
-            Operator::StrLessThan
-            | Operator::StrLessEq
-            | Operator::PrefixOf
-            | Operator::SuffixOf
-            | Operator::Contains
-            | Operator::ReRange => {
-                assert_num_args(&args, 2)?;
-                SortError::assert_eq(&Sort::String, sorts[0])?;
-                SortError::assert_eq(&Sort::String, sorts[1])?;
-            }
-            Operator::CharAt => {
-                assert_num_args(&args, 2)?;
-                SortError::assert_eq(&Sort::String, sorts[0])?;
-                SortError::assert_eq(&Sort::Int, sorts[1])?;
-            }
-            Operator::Substring => {
-                assert_num_args(&args, 3)?;
-                SortError::assert_eq(&Sort::String, sorts[0])?;
-                SortError::assert_eq(&Sort::Int, sorts[1])?;
-                SortError::assert_eq(&Sort::Int, sorts[2])?;
-            }
-            Operator::IndexOf => {
-                assert_num_args(&args, 3)?;
-                SortError::assert_eq(&Sort::String, sorts[0])?;
-                SortError::assert_eq(&Sort::String, sorts[1])?;
-                SortError::assert_eq(&Sort::Int, sorts[2])?;
-            }
-            Operator::Replace | Operator::ReplaceAll => {
-                assert_num_args(&args, 3)?;
-                SortError::assert_eq(&Sort::String, sorts[0])?;
-                SortError::assert_eq(&Sort::String, sorts[1])?;
-                SortError::assert_eq(&Sort::String, sorts[2])?;
-            }
-            Operator::StrFromCode | Operator::StrFromInt => {
-                assert_num_args(&args, 1)?;
-                SortError::assert_eq(&Sort::Int, sorts[0])?;
-            }
-            Operator::StrToRe => {
-                assert_num_args(&args, 1)?;
-                SortError::assert_eq(&Sort::String, sorts[0])?;
-            }
-            Operator::StrInRe => {
-                assert_num_args(&args, 2)?;
-                SortError::assert_eq(&Sort::String, sorts[0])?;
-                SortError::assert_eq(&Sort::RegLan, sorts[1])?;
-            }
-            Operator::ReNone | Operator::ReAll | Operator::ReAllChar => {
-                assert_num_args(&args, 0)?;
-            }
-            Operator::ReConcat
-            | Operator::ReUnion
-            | Operator::ReIntersection
-            | Operator::ReDiff => {
-                assert_num_args(&args, 2..)?;
-                for s in sorts {
-                    SortError::assert_eq(&Sort::RegLan, s)?;
+                ArgSorts::Select | ArgSorts::Store => None,
+            },
+            ApplicationHead::Term => {
+                let sort = self.pool.sort(&args[0]);
+                match sort.as_sort()? {
+                    Sort::Function(sorts) if i < sorts.len() => Some(sorts[i - 1].clone()),
+                    _ => None,
                 }
             }
-            Operator::ReKleeneClosure
-            | Operator::ReComplement
-            | Operator::ReKleeneCross
-            | Operator::ReOption => {
-                assert_num_args(&args, 1)?;
-                SortError::assert_eq(&Sort::RegLan, sorts[0])?;
-            }
-            Operator::ReplaceRe | Operator::ReplaceReAll => {
-                assert_num_args(&args, 3)?;
-                SortError::assert_eq(&Sort::String, sorts[0])?;
-                SortError::assert_eq(&Sort::RegLan, sorts[1])?;
-                SortError::assert_eq(&Sort::String, sorts[2])?;
-            }
-            Operator::BvNot | Operator::BvNeg => {
-                assert_num_args(&args, 1)?;
-                for s in sorts {
-                    if !matches!(s, Sort::BitVec(_)) {
-                        return Err(ParserError::ExpectedBvSort(s.clone()));
-                    }
+            ApplicationHead::FunctionDef(name) => {
+                let def = self.state.function_defs.get(name)?;
+                if !def.sort_params.is_empty() {
+                    return None;
                 }
+                def.params.get(i).map(|(_, sort)| sort.clone())
             }
-            Operator::BvBbTerm => {
-                assert_num_args(&args, 1..)?;
-                SortError::assert_eq(&Sort::Bool, sorts[0])?;
-                SortError::assert_all_eq(&sorts)?;
-            }
-            Operator::BvConcat => {
-                assert_num_args(&args, 2..)?;
-                for s in sorts {
-                    if !matches!(s, Sort::BitVec(_)) {
-                        return Err(ParserError::ExpectedBvSort(s.clone()));
-                    }
-                }
+            _ => None,
+        }
+    }
+
+    /// Constructs the variable term for the parametric function `name`, with its sort parameters
+    /// instantiated such that it can be applied to `args`.
+    fn instantiate_function(
+        &mut self,
+        name: String,
+        args: &[Rc<Term>],
+    ) -> Result<Rc<Term>, ParserError> {
+        let func = self.make_var(name.clone())?;
+        let sort = self.pool.sort(&func);
+        let Sort::Function(sorts) = sort.as_sort().unwrap() else {
+            return Err(ParserError::NotAFunction(sort.as_sort().unwrap().clone()));
+        };
+        assert_num_args(args, sorts.len() - 1)?;
+        let params = &self.state.parametric_functions[&name];
+        let mut instantiation =
+            instantiate_sort_params(self.pool, params, &sorts[..sorts.len() - 1], args)?;
+        let sort = instantiation.apply(self.pool, &sort);
+        Ok(self.pool.add(Term::Var(name, sort)))
+    }
+
+    /// If `name` was already declared with a sort other than `sort`, records both signatures as
+    /// overloads of `name`.
+    fn record_overload(&mut self, name: &str, sort: &Rc<Term>) {
+        let Some(previous) = self
+            .state
+            .symbol_table
+            .get(&HashCache::new(name.to_owned()))
+        else {
+            return;
+        };
+        if previous == sort {
+            return;
+        }
+        let previous = previous.clone();
+        let candidates = (self.state.overloads)
+            .entry(name.to_owned())
+            .or_insert_with(|| vec![previous]);
+        if !candidates.contains(sort) {
+            candidates.push(sort.clone());
+        }
+    }
+
+    /// Constructs the variable term for the overloaded function `name`, choosing the signature
+    /// whose parameter sorts are the sorts of `args`. Returns an error if no signature or more
+    /// than one signature matches.
+    fn resolve_overload(
+        &mut self,
+        name: String,
+        args: &[Rc<Term>],
+    ) -> Result<Rc<Term>, ParserError> {
+        let arg_sorts: Vec<_> = args.iter().map(|a| self.pool.sort(a)).collect();
+        let candidates = &self.state.overloads[&name];
+        let matching: Vec<_> = candidates
+            .iter()
+            .filter(|sort| match sort.as_sort().unwrap() {
+                Sort::Function(sorts) => sorts[..sorts.len() - 1] == arg_sorts[..],
+                _ => false,
+            })
+            .collect();
+        let to_sorts = |sorts: &[&Rc<Term>]| -> Vec<Sort> {
+            sorts.iter().map(|s| s.as_sort().unwrap().clone()).collect()
+        };
+        match matching.as_slice() {
+            [sort] => {
+                let sort = (*sort).clone();
+                Ok(self.pool.add(Term::Var(name, sort)))
             }
-            Operator::BvAdd
-            | Operator::BvMul
-            | Operator::BvAnd
-            | Operator::BvOr
-            | Operator::BvXor => {
-                assert_num_args(&args, 2..)?;
-                if !matches!(sorts[0], Sort::BitVec(_)) {
-                    return Err(ParserError::ExpectedBvSort(sorts[0].clone()));
-                }
-                SortError::assert_all_eq(&sorts)?;
+            [] => {
+                let candidates: Vec<_> = candidates.iter().collect();
+                Err(ParserError::NoMatchingOverload(name, to_sorts(&candidates)))
             }
-            Operator::BvUDiv
-            | Operator::BvURem
-            | Operator::BvShl
-            | Operator::BvLShr
-            | Operator::BvULt
-            | Operator::BvNAnd
-            | Operator::BvNOr
-            | Operator::BvXNor
-            | Operator::BvComp
-            | Operator::BvSub
-            | Operator::BvSDiv
-            | Operator::BvSRem
-            | Operator::BvSMod
-            | Operator::BvAShr
-            | Operator::BvULe
-            | Operator::BvUGt
-            | Operator::BvUGe
-            | Operator::BvSLt
-            | Operator::BvSLe
-            | Operator::BvSGt
-            | Operator::BvSGe => {
-                assert_num_args(&args, 2)?;
-                if !matches!(sorts[0], Sort::BitVec(_)) {
-                    return Err(ParserError::ExpectedBvSort(sorts[0].clone()));
-                }
-                SortError::assert_all_eq(&sorts)?;
+            _ => Err(ParserError::AmbiguousOverload(name, to_sorts(&matching))),
+        }
+    }
+
+    /// Constructs and sort checks an operation term.
+    fn make_op(&mut self, op: Operator, args: Vec<Rc<Term>>) -> Result<Rc<Term>, ParserError> {
+        check_op_args(self.pool, op, &args, &self.config)?;
+
+        if op == Operator::RealDiv {
+            if let Some(r) = self.interpret_div_as_real_lit(&args[0], &args[1]) {
+                return Ok(r);
             }
-            Operator::RareList => SortError::assert_all_eq(&sorts)?,
         }
         Ok(self.pool.add(Term::Op(op, args)))
     }
@@ -485,23 +1177,7 @@ impl<'a, R: BufRead> Parser<'a, R> {
         function: Rc<Term>,
         args: Vec<Rc<Term>>,
     ) -> Result<Rc<Term>, ParserError> {
-        let sort = self.pool.sort(&function);
-        let sorts = {
-            let function_sort = sort.as_sort().unwrap();
-            if let Sort::Function(sorts) = function_sort {
-                sorts
-            } else {
-                // Function does not have function sort
-                return Err(ParserError::NotAFunction(function_sort.clone()));
-            }
-        };
-        assert_num_args(&args, sorts.len() - 1)?;
-        for i in 0..args.len() {
-            SortError::assert_eq(
-                sorts[i].as_sort().unwrap(),
-                self.pool.sort(&args[i]).as_sort().unwrap(),
-            )?;
-        }
+        check_app_args(self.pool, &function, &args)?;
         Ok(self.pool.add(Term::App(function, args)))
     }
 
@@ -566,7 +1242,7 @@ impl<'a, R: BufRead> Parser<'a, R> {
     }
 
     /// Reads tokens until the matching closing parenthesis is reached.
-    fn read_until_close_parens(&mut self) -> CarcaraResult<Vec<Token>> {
+    fn read_until_close_parens(&mut self) -> CarcaraResult<Vec<(Token, Position)>> {
         let mut result = Vec::new();
         let mut parens_depth = 1;
         while parens_depth > 0 {
@@ -579,7 +1255,7 @@ impl<'a, R: BufRead> Parser<'a, R> {
                 }
                 _ => 0,
             };
-            result.push(token.0);
+            result.push(token);
         }
         Ok(result)
     }
@@ -594,32 +1270,36 @@ impl<'a, R: BufRead> Parser<'a, R> {
     fn ignore_remaining_attributes(&mut self) -> CarcaraResult<()> {
         while let Token::Keyword(_) = self.current_token {
             self.next_token()?;
-            match self.current_token {
-                // If we reached the closing parenthesis or the end of the file, we stop
-                Token::CloseParen | Token::Eof => break,
-
-                // If there is no value for this attribute, we may encounter the next attribute, in
-                // which case we must continue without consuming the keyword token
-                Token::Keyword(_) => (),
-
-                // If there is a single token as a value we consume it
-                Token::Symbol(_)
-                | Token::Numeral(_)
-                | Token::Decimal(_)
-                | Token::Bitvector { .. }
-                | Token::String(_)
-                | Token::ReservedWord(_) => {
-                    self.next_token()?;
-                }
+            self.ignore_attribute_value()?;
+        }
+        Ok(())
+    }
 
-                // And if the value is an s-expression we read tokens until it's closed
-                Token::OpenParen => {
-                    self.next_token()?;
-                    self.ignore_until_close_parens()?;
-                }
+    /// Consumes and ignores the value of an attribute, if it has one. This method assumes that the
+    /// attribute keyword was already consumed.
+    fn ignore_attribute_value(&mut self) -> CarcaraResult<()> {
+        match self.current_token {
+            // If we reached the closing parenthesis or the end of the file, there is no value
+            Token::CloseParen | Token::Eof => (),
+
+            // If there is no value for this attribute, we may encounter the next attribute, in
+            // which case we must continue without consuming the keyword token
+            Token::Keyword(_) => (),
+
+            // If there is a single token as a value we consume it
+            Token::Symbol(_)
+            | Token::Numeral(_)
+            | Token::Decimal(_)
+            | Token::Bitvector { .. }
+            | Token::String(_)
+            | Token::ReservedWord(_) => {
+                self.next_token()?;
             }
-            if self.current_token == Token::CloseParen {
-                break;
+
+            // And if the value is an s-expression we read tokens until it's closed
+            Token::OpenParen => {
+                self.next_token()?;
+                self.ignore_until_close_parens()?;
             }
         }
         Ok(())
@@ -645,7 +1325,13 @@ impl<'a, R: BufRead> Parser<'a, R> {
             self.expect_token(Token::OpenParen)?;
             match self.next_token()?.0 {
                 Token::ReservedWord(Reserved::DeclareFun) => {
-                    let (name, sort) = self.parse_declare_fun()?;
+                    let (name, sort_params, sort) = self.parse_declare_fun()?;
+                    if !sort_params.is_empty() {
+                        self.state
+                            .parametric_functions
+                            .insert(name.clone(), sort_params);
+                    }
+                    self.record_overload(&name, &sort);
                     self.insert_sorted_var((name.clone(), sort.clone()));
                     self.prelude().function_declarations.push((name, sort));
                 }
@@ -683,6 +1369,10 @@ impl<'a, R: BufRead> Parser<'a, R> {
                             ))
                         };
                         let sort = self.pool.sort(&lambda_term);
+                        if !func_def.sort_params.is_empty() {
+                            let params = func_def.sort_params;
+                            self.state.parametric_functions.insert(name.clone(), params);
+                        }
                         let var = (name, sort);
                         self.insert_sorted_var(var.clone());
                         let var_term = self.pool.add(var.into());
@@ -722,6 +1412,10 @@ impl<'a, R: BufRead> Parser<'a, R> {
                         (logic.contains("LRA") || logic.contains("NRA") || logic.contains("RDL"))
                             && !logic.contains('I');
                 }
+                Token::Symbol(s) if s == "reset" || s == "reset-assertions" => {
+                    self.expect_token(Token::CloseParen)?;
+                    self.reset_problem(s == "reset");
+                }
                 _ => {
                     // If the command is not one of the commands we care about, we just ignore it.
                     // We do that by reading tokens until the command parenthesis is closed
@@ -732,6 +1426,39 @@ impl<'a, R: BufRead> Parser<'a, R> {
         Ok(self.problem.take().unwrap())
     }
 
+    /// Sorts the literals in the conclusion of `step` by their structural hashes. If this changes
+    /// their order, the original clause is recorded, together with the `position` of the step in
+    /// the proof.
+    fn normalize_clause_order(&mut self, step: &mut ProofStep, position: Vec<usize>) {
+        let original = step.clause.clone();
+
+        // Literals whose hashes collide are ordered by address. Since terms are hash consed, this
+        // is a total order for all terms in the same pool, so the result doesn't depend on the
+        // original order
+        step.clause
+            .sort_by_key(|l| (self.pool.term_info(l).hash, l.as_ref() as *const Term));
+        if step.clause != original {
+            self.state.original_clauses.insert(position, original);
+        }
+    }
+
+    /// Clears all declarations, definitions and assertions made so far in the problem, as done by
+    /// the `reset-assertions` command. If `full` is `true`, this also clears the problem's logic,
+    /// as done by the `reset` command. In both cases, the problem starts over from an empty state.
+    fn reset_problem(&mut self, full: bool) {
+        let warnings = std::mem::take(&mut self.state.warnings);
+        self.state = ParserState { warnings, ..ParserState::default() };
+
+        let (prelude, premises) = self.problem.as_mut().unwrap();
+        premises.clear();
+        prelude.sort_declarations.clear();
+        prelude.function_declarations.clear();
+        if full {
+            prelude.logic = None;
+            self.interpret_integers_as_reals = false;
+        }
+    }
+
     /// Parses a proof in the Alethe format. All function, constant and sort declarations needed
     /// should already be in the parser state.
     pub fn parse_proof(&mut self) -> CarcaraResult<Vec<ProofCommand>> {
@@ -747,6 +1474,13 @@ impl<'a, R: BufRead> Parser<'a, R> {
 
         let mut finished_assumes = false;
 
+        // The first step that had the same id as the end step of an outer subproof, while an inner
+        // subproof was still open. Since step ids are scoped, this may be a valid step inside the
+        // inner subproof. It is only reported as a mismatched anchor if the inner subproof is never
+        // closed. Stores the depth of the stack, the end step of the inner subproof, the id of the
+        // step and its position
+        let mut mismatch_candidate = None;
+
         // Some solvers print the satisfiability result (unsat) together with the proof. To save the
         // user from having to remove this, we consume this first "unsat" token if it exists
         if self.current_token == Token::Symbol("unsat".into()) {
@@ -756,9 +1490,10 @@ impl<'a, R: BufRead> Parser<'a, R> {
         while self.current_token != Token::Eof {
             self.expect_token(Token::OpenParen)?;
             let (token, position) = self.next_token()?;
+            let in_command = self.command_context(&token)?;
             let (id, command) = match token {
                 Token::ReservedWord(Reserved::Assume) => {
-                    let (id, term) = self.parse_assume_command()?;
+                    let (id, term) = self.parse_assume_command().map_err(in_command)?;
                     if stack.len() == 1 && finished_assumes {
                         log::warn!("`assume` command '{}' appears after `step` commands", &id);
                     }
@@ -766,16 +1501,20 @@ impl<'a, R: BufRead> Parser<'a, R> {
                 }
                 Token::ReservedWord(Reserved::Step) => {
                     finished_assumes = true;
-                    let step = self.parse_step_command()?;
+                    let mut step = self.parse_step_command().map_err(in_command)?;
+                    if self.config.normalize_clause_order {
+                        let position = stack.iter().map(|(s, _)| s.commands.len()).collect();
+                        self.normalize_clause_order(&mut step, position);
+                    }
                     (step.id.clone(), ProofCommand::Step(step))
                 }
                 Token::ReservedWord(Reserved::DefineFun) => {
-                    let (name, func_def) = self.parse_define_fun()?;
+                    let (name, func_def) = self.parse_define_fun().map_err(in_command)?;
                     self.state.function_defs.insert(name, func_def);
                     continue;
                 }
                 Token::ReservedWord(Reserved::Anchor) => {
-                    let (end_step_id, args) = self.parse_anchor_command()?;
+                    let (end_step_id, args) = self.parse_anchor_command().map_err(in_command)?;
 
                     // When we encounter an `anchor` command, we push a new scope into the step ids
                     // symbol table, a fresh commands vector into the commands stack for the
@@ -798,13 +1537,35 @@ impl<'a, R: BufRead> Parser<'a, R> {
                 }
             };
             let id = HashCache::new(id);
-            if self.state.step_ids.get(&id).is_some() {
+
+            // Step ids only need to be unique in their own subproof, so we only search the
+            // innermost scope. The step that ends a subproof is added to the outer scope, so in
+            // that case we also need to search that scope
+            let ends_subproof = stack.last().unwrap().1 == *id.as_ref();
+            let scopes = if ends_subproof { 2 } else { 1 };
+            if self.state.step_ids.get_in_innermost(scopes, &id).is_some() {
                 return Err(Error::Parser(
                     ParserError::RepeatedStepId(id.unwrap()),
                     position,
                 ));
             }
 
+            // If this step has the id of the end step of an outer subproof, the `anchor` command of
+            // the innermost subproof may refer to a step id that doesn't match the step that should
+            // end it. This is a common mistake in proofs that were edited by hand
+            let mut outer_subproofs = stack.iter().skip(1).rev().skip(1);
+            if mismatch_candidate.is_none()
+                && outer_subproofs.any(|(_, end_step)| end_step == id.as_ref())
+            {
+                let inner_end_step = stack.last().unwrap().1.clone();
+                mismatch_candidate = Some((
+                    stack.len(),
+                    inner_end_step,
+                    id.as_ref().to_owned(),
+                    position,
+                ));
+            }
+
             let (top_subproof, top_end_step) = stack.last_mut().unwrap();
             top_subproof.commands.push(command);
             if top_end_step == id.as_ref() {
@@ -813,6 +1574,12 @@ impl<'a, R: BufRead> Parser<'a, R> {
                 self.state.symbol_table.pop_scope();
                 self.state.step_ids.pop_scope();
                 let (subproof, _) = stack.pop().unwrap();
+                if mismatch_candidate
+                    .as_ref()
+                    .is_some_and(|(d, ..)| *d > stack.len())
+                {
+                    mismatch_candidate = None;
+                }
 
                 // The subproof must contain at least two commands: the end step and the previous
                 // command it implicitly references
@@ -842,7 +1609,15 @@ impl<'a, R: BufRead> Parser<'a, R> {
             1 => Ok(stack.pop().unwrap().0.commands),
 
             // If there is more than one vector in the commands stack, we are inside a subproof
-            // that should be closed before the outer proof is finished
+            // that should be closed before the outer proof is finished. If a step that seemed to
+            // end an outer subproof was found in it, that is most likely the cause
+            _ if mismatch_candidate.is_some() => {
+                let (_, inner_end_step, id, position) = mismatch_candidate.unwrap();
+                Err(Error::Parser(
+                    ParserError::MismatchedAnchor(inner_end_step, id),
+                    position,
+                ))
+            }
             _ => Err(Error::Parser(
                 ParserError::UnclosedSubproof(stack.pop().unwrap().1),
                 self.current_position,
@@ -850,6 +1625,38 @@ impl<'a, R: BufRead> Parser<'a, R> {
         }
     }
 
+    /// Returns a function that adds the context of the proof command that starts with `token` to
+    /// a parser error. This must be called right after `token` is consumed, so the command id can
+    /// be read before the command is parsed.
+    fn command_context(&mut self, token: &Token) -> CarcaraResult<impl FnOnce(Error) -> Error> {
+        let kind = match token {
+            Token::ReservedWord(Reserved::Assume) => "assume",
+            Token::ReservedWord(Reserved::Step) => "step",
+            Token::ReservedWord(Reserved::DefineFun) => "define-fun",
+            Token::ReservedWord(Reserved::Anchor) => "anchor",
+            _ => "command",
+        };
+
+        // In `anchor` commands, the id comes after the `:step` keyword
+        let id = if kind == "anchor" && self.current_token == Token::Keyword("step".into()) {
+            self.peek_token()?
+        } else {
+            &self.current_token
+        };
+        let id = match id {
+            Token::Symbol(s) => Some(s.clone()),
+            _ => None,
+        };
+
+        Ok(move |err| match err {
+            Error::Parser(inner, pos) => {
+                let inner = Box::new(inner);
+                Error::Parser(ParserError::InCommand { kind, id, inner }, pos)
+            }
+            other => other,
+        })
+    }
+
     /// Parses an `assume` proof command. This method assumes that the `(` and `assume` tokens were
     /// already consumed.
     fn parse_assume_command(&mut self) -> CarcaraResult<(String, Rc<Term>)> {
@@ -865,50 +1672,78 @@ impl<'a, R: BufRead> Parser<'a, R> {
     fn parse_step_command(&mut self) -> CarcaraResult<ProofStep> {
         let id = self.expect_symbol()?;
         let clause = self.parse_clause()?;
-        self.expect_token(Token::Keyword("rule".into()))?;
-        let rule = match self.next_token()? {
-            (Token::Symbol(s), _) => s,
-            (Token::ReservedWord(r), _) => format!("{}", r),
-            (other, pos) => {
-                return Err(Error::Parser(ParserError::UnexpectedToken(other), pos));
-            }
-        };
 
-        let premises = if self.current_token == Token::Keyword("premises".into()) {
-            self.next_token()?;
-            self.expect_token(Token::OpenParen)?;
-            self.parse_sequence(Self::parse_step_premise, true)?
-        } else {
-            Vec::new()
-        };
+        // The attributes of a step may appear in any order, and unknown attributes are ignored
+        let mut rule: Option<String> = None;
+        let mut premises = Vec::new();
+        let mut args = Vec::new();
+        let mut discharge = Vec::new();
+        let mut weight = None;
+
+        // The tokens of the `:args` attribute, if it comes before the `:rule` attribute
+        let mut pending_args = None;
+        while matches!(self.current_token, Token::Keyword(_)) {
+            let (Token::Keyword(keyword), _) = self.next_token()? else {
+                unreachable!()
+            };
+            match keyword.as_str() {
+                "rule" => {
+                    rule = Some(match self.next_token()? {
+                        (Token::Symbol(s), _) => s,
+                        (Token::ReservedWord(r), _) => format!("{}", r),
+                        (other, pos) => {
+                            return Err(Error::Parser(ParserError::UnexpectedToken(other), pos));
+                        }
+                    });
+                }
+                "premises" => {
+                    self.expect_token(Token::OpenParen)?;
+                    premises = self.parse_sequence(Self::parse_step_premise, true)?;
+                }
+                "args" => {
+                    self.expect_token(Token::OpenParen)?;
 
-        let args = if self.current_token == Token::Keyword("args".into()) {
-            self.next_token()?;
-            self.expect_token(Token::OpenParen)?;
+                    // If the rule is `hole`, we want to allow any invalid arguments, so we read the
+                    // rest of the `:args` attribute without trying to parse anything. If the rule
+                    // is not known yet, we keep the tokens, and parse them once it is known
+                    pending_args = None;
+                    match rule.as_deref() {
+                        Some("hole") => {
+                            self.ignore_until_close_parens()?;
+                            args = Vec::new();
+                        }
+                        Some(_) => args = self.parse_sequence(Self::parse_proof_arg, true)?,
+                        None => pending_args = Some(self.read_until_close_parens()?),
+                    }
+                }
 
-            // If the rule is `hole`, we want to allow any invalid arguments, so we read the rest of
-            // the `:args` attribute without trying to parse anything
-            if rule == "hole" {
-                self.ignore_until_close_parens()?;
-                Vec::new()
-            } else {
-                self.parse_sequence(Self::parse_proof_arg, true)?
+                // For some rules (notably the `subproof` rule), there is also a `:discharge`
+                // attribute that takes a series of command ids, in addition to the regular premises
+                "discharge" => {
+                    self.expect_token(Token::OpenParen)?;
+                    discharge = self.parse_sequence(|p| p.parse_discharge_premise(&id), true)?;
+                }
+                "weight" => {
+                    let position = self.current_position;
+                    let n = self.expect_numeral()?;
+                    let w = n.to_u64().ok_or_else(|| {
+                        Error::Parser(ParserError::InvalidStepWeight(n.clone()), position)
+                    })?;
+                    weight = Some(w);
+                }
+                _ => self.ignore_attribute_value()?,
             }
-        } else {
-            Vec::new()
-        };
-
-        // For some rules (notably the `subproof` rule), there is also a `:discharge` attribute that
-        // takes a series of command ids, in addition to the regular premises
-        let discharge = if self.current_token == Token::Keyword("discharge".into()) {
-            self.next_token()?;
-            self.expect_token(Token::OpenParen)?;
-            self.parse_sequence(|p| p.parse_discharge_premise(&id), true)?
-        } else {
-            Vec::new()
+        }
+        let Some(rule) = rule else {
+            return Err(Error::Parser(
+                ParserError::MissingRule(id),
+                self.current_position,
+            ));
         };
-
-        self.ignore_remaining_attributes()?;
+        if let Some(tokens) = pending_args.filter(|_| rule != "hole") {
+            self.replay_tokens(tokens);
+            args = self.parse_sequence(Self::parse_proof_arg, true)?;
+        }
         self.expect_token(Token::CloseParen)?;
 
         Ok(ProofStep {
@@ -918,6 +1753,7 @@ impl<'a, R: BufRead> Parser<'a, R> {
             premises,
             args,
             discharge,
+            weight,
         })
     }
 
@@ -1011,10 +1847,12 @@ impl<'a, R: BufRead> Parser<'a, R> {
         })
     }
 
-    /// Parses a `declare-fun` proof command. Returns the function name and a term representing its
-    /// sort. This method assumes that the `(` and `declare-fun` tokens were already consumed.
-    fn parse_declare_fun(&mut self) -> CarcaraResult<(String, Rc<Term>)> {
+    /// Parses a `declare-fun` proof command. Returns the function name, its sort parameters if its
+    /// signature is parametric, and a term representing its sort. This method assumes that the `(`
+    /// and `declare-fun` tokens were already consumed.
+    fn parse_declare_fun(&mut self) -> CarcaraResult<(String, Vec<Rc<Term>>, Rc<Term>)> {
         let name = self.expect_symbol()?;
+        let sort_params = self.parse_sort_params()?;
         let sort = {
             self.expect_token(Token::OpenParen)?;
             let mut sorts = self.parse_sequence(Self::parse_sort, false)?;
@@ -1022,11 +1860,47 @@ impl<'a, R: BufRead> Parser<'a, R> {
             if sorts.len() == 1 {
                 sorts.into_iter().next().unwrap()
             } else {
-                self.pool.add(Term::Sort(Sort::Function(sorts)))
+                self.pool.intern_sort(Sort::Function(sorts))
             }
         };
+        if !sort_params.is_empty() {
+            self.end_sort_params()?;
+        }
         self.expect_token(Token::CloseParen)?;
-        Ok((name, sort))
+        Ok((name, sort_params, sort))
+    }
+
+    /// Parses the start of a parametric signature, of the form `(par (<symbol>+)`, if the next
+    /// tokens are one. Returns the sort parameters, each represented as a user-declared sort with
+    /// arity zero, or an empty vector if the signature is not parametric. The parameters are
+    /// declared in a new scope, which is popped by `end_sort_params`, after the rest of the
+    /// signature is parsed.
+    fn parse_sort_params(&mut self) -> CarcaraResult<Vec<Rc<Term>>> {
+        if self.current_token != Token::OpenParen
+            || self.peek_token()? != &Token::ReservedWord(Reserved::Par)
+        {
+            return Ok(Vec::new());
+        }
+        self.next_token()?;
+        self.next_token()?;
+        self.expect_token(Token::OpenParen)?;
+        let names = self.parse_sequence(Self::expect_symbol, true)?;
+
+        self.state.sort_declarations.push_scope();
+        let params = names
+            .into_iter()
+            .map(|name| {
+                self.state.sort_declarations.insert(name.clone(), 0);
+                self.pool.intern_sort(Sort::Atom(name, Vec::new()))
+            })
+            .collect();
+        Ok(params)
+    }
+
+    /// Ends a parametric signature started by `parse_sort_params`, consuming its closing `)`.
+    fn end_sort_params(&mut self) -> CarcaraResult<()> {
+        self.state.sort_declarations.pop_scope();
+        self.expect_token(Token::CloseParen)
     }
 
     /// Parses a declare-sort proof command. Returns the sort name and its arity. This method
@@ -1065,7 +1939,12 @@ impl<'a, R: BufRead> Parser<'a, R> {
     /// Parses a `define-fun` proof command. Returns the function name and its definition. This
     /// method assumes that the `(` and `define-fun` tokens were already consumed.
     fn parse_define_fun(&mut self) -> CarcaraResult<(String, FunctionDef)> {
-        let (name, params, return_sort) = self.parse_function_dec(false)?;
+        let position = self.current_position;
+        let name = self.expect_symbol()?;
+        let sort_params = self.parse_sort_params()?;
+        self.expect_token(Token::OpenParen)?;
+        let params = self.parse_sequence(Self::parse_sorted_var, false)?;
+        let return_sort = self.parse_sort()?;
 
         // In order to correctly parse the function body, we push a new scope to the symbol table
         // and add the functions arguments to it.
@@ -1076,15 +1955,36 @@ impl<'a, R: BufRead> Parser<'a, R> {
         let body = self.parse_term_expecting_sort(return_sort.as_sort().unwrap())?;
         self.state.symbol_table.pop_scope();
 
+        // Since function definitions are applied eagerly, the body of a definition never contains
+        // other defined functions. If there is a cycle in the definitions (which is only possible
+        // if the function was previously declared), the function name will appear as a free
+        // variable in its own body
+        let is_recursive = !params.iter().any(|(p, _)| *p == name)
+            && self
+                .pool
+                .free_vars(&body)
+                .iter()
+                .any(|v| v.as_var() == Some(name.as_str()));
+        if is_recursive {
+            return Err(Error::Parser(
+                ParserError::RecursiveDefinition(name),
+                position,
+            ));
+        }
+
+        if !sort_params.is_empty() {
+            self.end_sort_params()?;
+        }
         self.expect_token(Token::CloseParen)?;
 
-        Ok((name, FunctionDef { params, body }))
+        Ok((name, FunctionDef { sort_params, params, body }))
     }
 
-    /// Adds the premise corresponding to a `define-fun-rec` function definition.
+    /// Adds the premise corresponding to a `define-fun-rec` function definition, and records it as
+    /// the function's defining equation.
     fn add_define_fun_rec_premise(&mut self, name: String, params: Vec<SortedVar>, body: Rc<Term>) {
         let application = {
-            let cached = HashCache::new(name);
+            let cached = HashCache::new(name.clone());
             let func_sort = self.state.symbol_table.get(&cached).unwrap();
             let name = cached.unwrap();
             let func_term = self.pool.add((name, func_sort.clone()).into());
@@ -1106,6 +2006,9 @@ impl<'a, R: BufRead> Parser<'a, R> {
             self.pool
                 .add(Term::Binder(Binder::Forall, bindings, equality_term))
         };
+        self.state
+            .recursive_definitions
+            .insert(name, premise.clone());
         self.premises().insert(premise);
     }
 
@@ -1128,7 +2031,7 @@ impl<'a, R: BufRead> Parser<'a, R> {
                 param_sorts.push(return_sort.clone());
                 Sort::Function(param_sorts)
             };
-            let sort = self.pool.add(Term::Sort(sort));
+            let sort = self.pool.intern_sort(sort);
             self.insert_sorted_var((name.clone(), sort));
         }
 
@@ -1184,7 +2087,7 @@ impl<'a, R: BufRead> Parser<'a, R> {
     /// Parses an argument for a `step` command.
     fn parse_proof_arg(&mut self) -> CarcaraResult<ProofArg> {
         if self.current_token == Token::OpenParen {
-            self.next_token()?; // Consume `(` token
+            let (_, open_pos) = self.next_token()?; // Consume `(` token
 
             // If we encounter a `(` token, this could be an assignment argument of the form
             // `(:= <symbol> <term>)`, or a regular term that starts with `(`. Note that the
@@ -1199,7 +2102,7 @@ impl<'a, R: BufRead> Parser<'a, R> {
                 // If the first token is not `:=`, this argument is just a regular term. Since
                 // we already consumed the `(` token, we have to call `parse_application`
                 // instead of `parse_term`.
-                let term = self.parse_application()?;
+                let term = self.parse_application(open_pos)?;
                 Ok(ProofArg::Term(term))
             }
         } else {
@@ -1217,6 +2120,15 @@ impl<'a, R: BufRead> Parser<'a, R> {
         Ok((symbol, sort))
     }
 
+    /// Parses a series of terms of sort `Bool` until the end of the input.
+    pub fn parse_formulas(&mut self) -> CarcaraResult<Vec<Rc<Term>>> {
+        let mut result = Vec::new();
+        while self.current_token != Token::Eof {
+            result.push(self.parse_term_expecting_sort(&Sort::Bool)?);
+        }
+        Ok(result)
+    }
+
     /// Parses a term.
     pub fn parse_term(&mut self) -> CarcaraResult<Rc<Term>> {
         let term = match self.next_token()? {
@@ -1230,7 +2142,7 @@ impl<'a, R: BufRead> Parser<'a, R> {
                 return if let Some(func) = self.state.function_defs.get(&s) {
                     func.apply(self.pool, Vec::new())
                         .map_err(|err| Error::Parser(err, pos))
-                } else if let Ok(op) = Operator::from_str(&s) {
+                } else if let Some(op) = Operator::from_symbol(&s) {
                     let args = Vec::new();
 
                     self.make_op(op, args)
@@ -1239,7 +2151,7 @@ impl<'a, R: BufRead> Parser<'a, R> {
                     self.make_var(s).map_err(|err| Error::Parser(err, pos))
                 };
             }
-            (Token::OpenParen, _) => return self.parse_application(),
+            (Token::OpenParen, pos) => return self.parse_application(pos),
             (other, pos) => {
                 return Err(Error::Parser(ParserError::UnexpectedToken(other), pos));
             }
@@ -1274,28 +2186,41 @@ impl<'a, R: BufRead> Parser<'a, R> {
     fn parse_binder(&mut self, binder: Binder) -> CarcaraResult<Rc<Term>> {
         self.expect_token(Token::OpenParen)?;
         self.state.symbol_table.push_scope();
+        self.state.bound_names.push_scope();
+        let parse_bound_var = |p: &mut Self| -> CarcaraResult<SortedVar> {
+            let position = p.current_position;
+            let (name, sort) = p.parse_sorted_var()?;
+            let var = (p.bind_name(name, position), sort);
+            p.insert_sorted_var(var.clone());
+            Ok(var)
+        };
         let bindings = if binder == Binder::Choice {
-            let var = self.parse_sorted_var()?;
-            self.insert_sorted_var(var.clone());
+            let var = parse_bound_var(self)?;
             self.expect_token(Token::CloseParen)?;
             BindingList(vec![var])
         } else {
-            BindingList(self.parse_sequence(
-                |p| {
-                    let var = p.parse_sorted_var()?;
-                    p.insert_sorted_var(var.clone());
-                    Ok(var)
-                },
-                true,
-            )?)
+            BindingList(self.parse_sequence(parse_bound_var, true)?)
         };
+        self.state.pending_patterns = None;
         let term = match binder {
             Binder::Lambda => self.parse_term()?,
             _ => self.parse_term_expecting_sort(&Sort::Bool)?,
         };
+        self.state.bound_names.pop_scope();
         self.state.symbol_table.pop_scope();
         self.expect_token(Token::CloseParen)?;
-        Ok(self.pool.add(Term::Binder(binder, bindings, term)))
+
+        let pending_patterns = self.state.pending_patterns.take();
+        let result = self.pool.add(Term::Binder(binder, bindings, term.clone()));
+        if let Some((body, patterns)) = pending_patterns {
+            if body == term && matches!(binder, Binder::Forall | Binder::Exists) {
+                self.state
+                    .patterns
+                    .entry(result.clone())
+                    .or_insert(patterns);
+            }
+        }
+        Ok(result)
     }
 
     /// Parses a `let` term. This method assumes that the `(` and `let` tokens were already
@@ -1303,12 +2228,15 @@ impl<'a, R: BufRead> Parser<'a, R> {
     fn parse_let_term(&mut self) -> CarcaraResult<Rc<Term>> {
         self.expect_token(Token::OpenParen)?;
         self.state.symbol_table.push_scope();
+        self.state.bound_names.push_scope();
         let bindings = self.parse_sequence(
             |p| {
                 p.expect_token(Token::OpenParen)?;
+                let position = p.current_position;
                 let name = p.expect_symbol()?;
                 let value = p.parse_term()?;
                 let sort = p.pool.sort(&value);
+                let name = p.bind_name(name, position);
                 p.insert_sorted_var((name.clone(), sort));
                 p.expect_token(Token::CloseParen)?;
                 Ok((name, value))
@@ -1317,6 +2245,7 @@ impl<'a, R: BufRead> Parser<'a, R> {
         )?;
         let inner = self.parse_term()?;
         self.expect_token(Token::CloseParen)?;
+        self.state.bound_names.pop_scope();
         self.state.symbol_table.pop_scope();
 
         if self.config.expand_lets {
@@ -1341,10 +2270,12 @@ impl<'a, R: BufRead> Parser<'a, R> {
     /// Parses an annotated term, of the form `(! <term> <attribute>+)`. This method assumes that
     /// the `(` and `!` tokens were already consumed.
     ///
-    /// The two supported attributes are `:named` and `:pattern`, though the latter is ignored. If
-    /// any other attribute is present, an error will be returned.
+    /// The two supported attributes are `:named` and `:pattern`. If the annotated term is the body
+    /// of a quantifier, its patterns are attached to the quantifier term (see [`Parser::patterns`]),
+    /// otherwise they are ignored. Any other attribute is also ignored.
     fn parse_annotated_term(&mut self) -> CarcaraResult<Rc<Term>> {
         let inner = self.parse_term()?;
+        let mut patterns = Vec::new();
         self.parse_sequence(
             |p| {
                 let attribute = p.expect_keyword()?;
@@ -1354,10 +2285,17 @@ impl<'a, R: BufRead> Parser<'a, R> {
                         // definition that maps the name to the term
                         let name = p.expect_symbol()?;
                         let func_def = FunctionDef {
+                            sort_params: Vec::new(),
                             params: Vec::new(),
                             body: inner.clone(),
                         };
-                        p.state.function_defs.insert(name, func_def);
+                        p.state.function_defs.insert(name.clone(), func_def);
+                        p.state.named_terms.entry(inner.clone()).or_insert(name);
+                        Ok(())
+                    }
+                    "pattern" => {
+                        p.expect_token(Token::OpenParen)?;
+                        patterns.push(p.parse_sequence(Self::parse_term, true)?);
                         Ok(())
                     }
 
@@ -1382,6 +2320,9 @@ impl<'a, R: BufRead> Parser<'a, R> {
             },
             true,
         )?;
+        if !patterns.is_empty() {
+            self.state.pending_patterns = Some((inner.clone(), patterns));
+        }
         Ok(inner)
     }
 
@@ -1447,76 +2388,16 @@ impl<'a, R: BufRead> Parser<'a, R> {
         op_args: Vec<Constant>,
         args: Vec<Rc<Term>>,
     ) -> Result<Rc<Term>, ParserError> {
-        let sorts: Vec<_> = args.iter().map(|t| self.pool.sort(t)).collect();
-        let sorts: Vec<_> = sorts.iter().map(|s| s.as_sort().unwrap()).collect();
-        match &op {
-            ParamOperator::BvConst => {
-                assert_num_args(&op_args, 2)?;
-                assert_num_args(&args, 0)?;
-                let value = op_args[0].as_integer().unwrap();
-                let width = op_args[1].as_integer().unwrap();
-                assert_indexed_op_args_value(&[op_args[0].clone()], 0..)?;
-                assert_indexed_op_args_value(&[op_args[1].clone()], 1..)?;
-                return Ok(self.pool.add(Term::Const(Constant::BitVec(value, width))));
-            }
-            ParamOperator::BvExtract => {
-                /*
-                ((_ extract i j) (_ BitVec m) (_ BitVec n))
-
-                where
-                - i, j, m, n are numerals
-                - m > i ≥ j ≥ 0,
-                - n = i - j + 1
-                 */
-                assert_num_args(&op_args, 2)?;
-                assert_num_args(&args, 1)?;
-                if !matches!(sorts[0], Sort::BitVec(_)) {
-                    return Err(ParserError::ExpectedBvSort(sorts[0].clone()));
-                }
-                for arg in &op_args {
-                    SortError::assert_eq(&Sort::Int, &arg.sort())?;
-                }
-                assert_indexed_op_args_value(&op_args, 0..)?;
-                let i = op_args[0].as_integer().unwrap();
-                let j = op_args[1].as_integer().unwrap();
-                let Sort::BitVec(m) = sorts[0].clone() else {
-                    unreachable!()
-                };
-                if !(m > i && i >= j && j >= Integer::ZERO) {
-                    return Err(ParserError::InvalidExtractArgs(
-                        i.to_usize().unwrap(),
-                        j.to_usize().unwrap(),
-                        m.to_usize().unwrap(),
-                    ));
-                }
-            }
-            ParamOperator::BvBitOf | ParamOperator::ZeroExtend | ParamOperator::SignExtend => {
-                assert_num_args(&op_args, 1)?;
-                assert_num_args(&args, 1)?;
-                SortError::assert_eq(&Sort::Int, &op_args[0].sort())?;
-                if !matches!(sorts[0], Sort::BitVec(_)) {
-                    return Err(ParserError::ExpectedBvSort(sorts[0].clone()));
-                }
-                assert_indexed_op_args_value(&op_args, 0..)?;
-            }
-            ParamOperator::RePower => {
-                assert_num_args(&op_args, 1)?;
-                assert_num_args(&args, 1)?;
-                SortError::assert_eq(&Sort::Int, &op_args[0].sort())?;
-                SortError::assert_eq(&Sort::RegLan, sorts[0])?;
-                assert_indexed_op_args_value(&op_args, 0..)?;
-            }
-            ParamOperator::ReLoop => {
-                assert_num_args(&op_args, 2)?;
-                assert_num_args(&args, 1)?;
-                for arg in &op_args {
-                    SortError::assert_eq(&Sort::Int, &arg.sort())?;
-                }
-                SortError::assert_eq(&Sort::RegLan, sorts[0])?;
-                assert_indexed_op_args_value(&op_args, 0..)?;
-            }
-            ParamOperator::ArrayConst => return Err(ParserError::InvalidIndexedOp(op.to_string())),
+        if op == ParamOperator::BvConst {
+            assert_num_args(&op_args, 2)?;
+            assert_num_args(&args, 0)?;
+            let value = op_args[0].as_integer().unwrap();
+            let width = op_args[1].as_integer().unwrap();
+            assert_indexed_op_args_value(&[op_args[0].clone()], 0..)?;
+            assert_indexed_op_args_value(&[op_args[1].clone()], 1..)?;
+            return Ok(self.pool.add(Term::Const(Constant::BitVec(value, width))));
         }
+        check_indexed_op_args(self.pool, op, &op_args, &args)?;
         let op_args = op_args
             .into_iter()
             .map(|c| self.pool.add(Term::Const(c)))
@@ -1531,29 +2412,121 @@ impl<'a, R: BufRead> Parser<'a, R> {
         op_sort: Rc<Term>,
         args: Vec<Rc<Term>>,
     ) -> Result<Rc<Term>, ParserError> {
-        let sorts: Vec<_> = args.iter().map(|t| self.pool.sort(t)).collect();
-        let sorts: Vec<_> = sorts.iter().map(|s| s.as_sort().unwrap()).collect();
-        match &op {
-            ParamOperator::ArrayConst => {
-                assert_num_args(&args, 1)?;
-                SortError::assert_array_sort(
-                    self.pool,
-                    None,
-                    Some(sorts[0]),
-                    op_sort.as_sort().unwrap(),
-                )?;
-            }
-            _ => return Err(ParserError::InvalidQualifiedOp(op.to_string())),
-        }
+        check_qualified_op_args(self.pool, op, &op_sort, &args)?;
         let op_args = vec![op_sort];
         Ok(self.pool.add(Term::ParamOp { op, op_args, args }))
     }
 
     /// Parses any term that starts with `(`, that is, any term that is not a constant or a
     /// variable. This method assumes that the `(` token was already consumed.
-    fn parse_application(&mut self) -> CarcaraResult<Rc<Term>> {
+    /// Parses an application, or any other term that starts with `(`. This method assumes that
+    /// the `(` token, which is at `open_pos`, was already consumed.
+    ///
+    /// To avoid stack overflows on deeply nested terms, nested applications are parsed
+    /// iteratively, using a stack of the applications whose arguments are still being parsed.
+    /// Other terms that start with `(`, like binder and `let` terms, are still parsed
+    /// recursively, and `Config::max_term_depth` limits how deeply any of these can be nested.
+    fn parse_application(&mut self, open_pos: Position) -> CarcaraResult<Rc<Term>> {
+        let base_depth = self.term_depth;
+        let mut stack: Vec<PendingApplication> = Vec::new();
+        let mut start = self.parse_application_head(open_pos, base_depth)?;
+        loop {
+            let mut term = match start {
+                ApplicationStart::Term(term) => term,
+                ApplicationStart::Pending(app) => {
+                    stack.push(app);
+                    start = self.parse_application_arg(&stack, base_depth)?;
+                    continue;
+                }
+            };
+
+            // Once a term is parsed, it is added as an argument of the innermost pending
+            // application. If that was its last argument, the application is built, and added as an
+            // argument of the next one, and so on
+            loop {
+                let Some(app) = stack.last_mut() else {
+                    return Ok(term);
+                };
+                app.args.push(term);
+                if self.current_token != Token::CloseParen {
+                    break;
+                }
+                if app.has_no_args() {
+                    return Err(Error::Parser(
+                        ParserError::EmptySequence,
+                        self.current_position,
+                    ));
+                }
+                self.next_token()?; // Consume `)` token
+                let app = stack.pop().unwrap();
+                term = self.make_application(app)?;
+            }
+            start = self.parse_application_arg(&stack, base_depth)?;
+        }
+    }
+
+    /// Parses the next argument of the innermost application in `stack`. If the argument is an
+    /// application, only its head is parsed.
+    fn parse_application_arg(
+        &mut self,
+        stack: &[PendingApplication],
+        base_depth: usize,
+    ) -> CarcaraResult<ApplicationStart> {
+        match self.current_token {
+            Token::OpenParen => {
+                let (_, pos) = self.next_token()?;
+                self.parse_application_head(pos, base_depth + stack.len())
+            }
+            Token::CloseParen if matches!(stack.last().unwrap().head, ApplicationHead::Term) => {
+                let (token, pos) = self.next_token()?;
+                Err(Error::Parser(ParserError::UnexpectedToken(token), pos))
+            }
+            Token::CloseParen => Err(Error::Parser(
+                ParserError::EmptySequence,
+                self.current_position,
+            )),
+            _ => self.parse_term().map(ApplicationStart::Term),
+        }
+    }
+
+    /// Parses the head of an application whose `(` token, at `open_pos`, was already consumed,
+    /// and which is enclosed by `depth` other applications.
+    fn parse_application_head(
+        &mut self,
+        open_pos: Position,
+        depth: usize,
+    ) -> CarcaraResult<ApplicationStart> {
+        if let Some(max) = self.config.max_term_depth {
+            if depth >= max {
+                return Err(Error::Parser(ParserError::TermTooDeep(max), open_pos));
+            }
+        }
+        let outer_depth = std::mem::replace(&mut self.term_depth, depth + 1);
+        let result = self.parse_application_head_inner();
+        self.term_depth = outer_depth;
+        result
+    }
+
+    fn parse_application_head_inner(&mut self) -> CarcaraResult<ApplicationStart> {
         let head_pos = self.current_position;
-        match &self.current_token {
+        let pending = |head| {
+            Ok(ApplicationStart::Pending(PendingApplication {
+                head,
+                args: Vec::new(),
+                position: head_pos,
+            }))
+        };
+
+        // If the head of the application is an indexed or qualified identifier, like in
+        // `((_ zero_extend 2) x)`, the operator is applied to the arguments directly. Any other
+        // head that starts with `(` is a term, and is parsed as such in the default case
+        let is_indexed_or_qualified = self.current_token == Token::OpenParen
+            && matches!(
+                self.peek_token()?,
+                Token::ReservedWord(Reserved::Underscore | Reserved::As)
+            );
+
+        let term = match &self.current_token {
             &Token::ReservedWord(reserved) => {
                 self.next_token()?;
                 match reserved {
@@ -1581,58 +2554,81 @@ impl<'a, R: BufRead> Parser<'a, R> {
             }
             // Here, I would like to use an `if let` guard, like:
             //
-            //     Token::Symbol(s) if let Ok(operator) = Operator::from_str(s) => { ... }
+            //     Token::Symbol(s) if let Some(operator) = Operator::from_symbol(s) => { ... }
             //
             // However, `if let` guards are still nightly only. For more info, see:
             // https://github.com/rust-lang/rust/issues/51114
-            Token::Symbol(s) if Operator::from_str(s).is_ok() => {
-                let operator = Operator::from_str(s).unwrap();
+            Token::Symbol(s) if Operator::from_symbol(s).is_some() => {
+                let operator = Operator::from_symbol(s).unwrap();
                 self.next_token()?;
-                let args = self.parse_sequence(Self::parse_term, true)?;
-                self.make_op(operator, args)
-                    .map_err(|err| Error::Parser(err, head_pos))
+                return pending(ApplicationHead::Op(operator));
+            }
+            Token::Symbol(s) if self.state.overloads.contains_key(s) => {
+                return pending(ApplicationHead::Overloaded(self.expect_symbol()?));
+            }
+            Token::Symbol(s) if self.state.parametric_functions.contains_key(s) => {
+                return pending(ApplicationHead::Parametric(self.expect_symbol()?));
             }
             Token::Symbol(s) if self.state.function_defs.get(s).is_some() => {
-                let head_pos = self.current_position;
-                let func_name = self.expect_symbol()?;
-                let args = self.parse_sequence(Self::parse_term, true)?;
-                let func = self.state.function_defs.get(&func_name).unwrap();
+                return pending(ApplicationHead::FunctionDef(self.expect_symbol()?));
+            }
+            // When an undeclared function is applied, its parameters are given the sorts of the
+            // arguments. Its return sort may be inferred later, from the enclosing application
+            Token::Symbol(s) if self.config.allow_unknown_symbols && self.is_unknown_symbol(s) => {
+                return pending(ApplicationHead::UnknownSymbol(self.expect_symbol()?));
+            }
+            Token::OpenParen if is_indexed_or_qualified => {
+                self.next_token()?;
+                let (head, _) = self.next_token()?;
+                return if head == Token::ReservedWord(Reserved::Underscore) {
+                    let (op, op_args) = self.parse_indexed_operator()?;
+                    pending(ApplicationHead::IndexedOp(op, op_args))
+                } else {
+                    let (op, op_sort) = self.parse_qualified_operator()?;
+                    pending(ApplicationHead::QualifiedOp(op, op_sort))
+                };
+            }
+            _ => return pending(ApplicationHead::Term),
+        };
+        term.map(ApplicationStart::Term)
+    }
 
+    /// Builds the term for an application, once all of its arguments were parsed.
+    fn make_application(&mut self, app: PendingApplication) -> CarcaraResult<Rc<Term>> {
+        let PendingApplication { head, mut args, position } = app;
+        if self.config.allow_unknown_symbols && !self.state.unknown_symbols.is_empty() {
+            self.infer_unknown_symbol_sorts(&head, &mut args);
+        }
+        let result = match head {
+            ApplicationHead::Op(operator) => self.make_op(operator, args),
+            ApplicationHead::Overloaded(name) => self
+                .resolve_overload(name, &args)
+                .and_then(|func| self.make_app(func, args)),
+            ApplicationHead::Parametric(name) => self
+                .instantiate_function(name, &args)
+                .and_then(|func| self.make_app(func, args)),
+            ApplicationHead::FunctionDef(name) => {
+                let func = self.state.function_defs.get(&name).unwrap();
                 func.apply(self.pool, args)
-                    .map_err(|err| Error::Parser(err, head_pos))
             }
-            Token::OpenParen => {
-                self.next_token()?;
-                match self.current_token {
-                    Token::ReservedWord(Reserved::Underscore) => {
-                        self.next_token()?;
-                        let (op, op_args) = self.parse_indexed_operator()?;
-                        let args = self.parse_sequence(Self::parse_term, true)?;
-                        self.make_indexed_op(op, op_args, args)
-                            .map_err(|err| Error::Parser(err, head_pos))
-                    }
-                    Token::ReservedWord(Reserved::As) => {
-                        self.next_token()?;
-                        let (op, op_sort) = self.parse_qualified_operator()?;
-                        let args = self.parse_sequence(Self::parse_term, true)?;
-                        self.make_qualified_op(op, op_sort, args)
-                            .map_err(|err| Error::Parser(err, head_pos))
-                    }
-                    _ => {
-                        let func = self.parse_application()?;
-                        let args = self.parse_sequence(Self::parse_term, true)?;
-                        self.make_app(func, args)
-                            .map_err(|err| Error::Parser(err, head_pos))
-                    }
-                }
+            ApplicationHead::UnknownSymbol(name) => {
+                let sort = {
+                    let mut sorts: Vec<_> = args.iter().map(|a| self.pool.sort(a)).collect();
+                    sorts.push(self.pool.intern_sort(Sort::Bool));
+                    let sort = self.pool.intern_sort(Sort::Function(sorts));
+                    self.use_unknown_symbol(&name, sort)
+                };
+                let func = self.pool.add(Term::Var(name, sort));
+                self.make_app(func, args)
             }
-            _ => {
-                let func = self.parse_term()?;
-                let args = self.parse_sequence(Self::parse_term, true)?;
+            ApplicationHead::IndexedOp(op, op_args) => self.make_indexed_op(op, op_args, args),
+            ApplicationHead::QualifiedOp(op, op_sort) => self.make_qualified_op(op, op_sort, args),
+            ApplicationHead::Term => {
+                let func = args.remove(0);
                 self.make_app(func, args)
-                    .map_err(|err| Error::Parser(err, head_pos))
             }
-        }
+        };
+        result.map_err(|err| Error::Parser(err, position))
     }
 
     fn make_sort(&mut self, name: String, args: Vec<Rc<Term>>) -> Result<Rc<Term>, ParserError> {
@@ -1663,7 +2659,7 @@ impl<'a, R: BufRead> Parser<'a, R> {
                         .params
                         .iter()
                         .cloned()
-                        .map(|name| self.pool.add(Term::Sort(Sort::Atom(name, Vec::new()))))
+                        .map(|name| self.pool.intern_sort(Sort::Atom(name, Vec::new())))
                         .zip(args)
                         .collect();
 
@@ -1679,7 +2675,7 @@ impl<'a, R: BufRead> Parser<'a, R> {
                 None => Err(ParserError::UndefinedSort(name)),
             },
         }?;
-        Ok(self.pool.add(Term::Sort(sort)))
+        Ok(self.pool.intern_sort(sort))
     }
 
     fn make_indexed_sort(
@@ -1693,7 +2689,7 @@ impl<'a, R: BufRead> Parser<'a, R> {
                     return Err(ParserError::WrongNumberOfArgs(1.into(), args.len()));
                 }
                 if let Some(width) = args[0].as_integer() {
-                    Ok(self.pool.add(Term::Sort(Sort::BitVec(width))))
+                    Ok(self.pool.intern_sort(Sort::BitVec(width)))
                 } else {
                     Err(ParserError::ExpectedIntegerConstant(args[0].clone()))
                 }
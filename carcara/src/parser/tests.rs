@@ -13,6 +13,10 @@ const TEST_CONFIG: Config = Config {
     expand_lets: false,
     allow_int_real_subtyping: false,
     allow_unary_logical_ops: true,
+    allow_unknown_symbols: false,
+    rename_shadowed: false,
+    max_term_depth: None,
+    normalize_clause_order: false,
 };
 
 pub fn parse_terms<const N: usize>(
@@ -50,7 +54,12 @@ pub fn parse_proof(pool: &mut PrimitivePool, input: &str) -> Proof {
         .expect(ERROR_MESSAGE)
         .parse_proof()
         .expect(ERROR_MESSAGE);
-    Proof { premises: IndexSet::new(), commands }
+    Proof {
+        premises: IndexSet::new(),
+        commands,
+        named_terms: IndexMap::new(),
+        original_clauses: IndexMap::new(),
+    }
 }
 
 fn run_parser_tests(pool: &mut PrimitivePool, cases: &[(&str, Term)]) {
@@ -96,6 +105,16 @@ fn test_hash_consing() {
     .into_iter()
     .collect::<IndexSet<&str>>();
 
+    let stats = pool.stats();
+    assert_eq!(7, stats.num_terms);
+    assert_eq!(1, stats.num_sorts);
+    assert_eq!(4, stats.max_depth);
+
+    // Both `2` and `(+ 1 2)` are used three times, and `2` is the first one to be added
+    let (most_shared, count) = stats.most_shared.unwrap();
+    assert_eq!("2", format!("{most_shared}"));
+    assert_eq!(Rc::strong_count(&most_shared) - 1, count);
+
     let pool_terms = pool.storage.into_vec();
     assert_eq!(pool_terms.len(), expected.len());
     for got in pool_terms {
@@ -143,6 +162,31 @@ fn test_arithmetic_ops() {
     ));
 }
 
+#[test]
+fn test_negative_numerals() {
+    let mut p = PrimitivePool::new();
+    let [five, minus_five, minus_two] = [5, -5, -2].map(|n| p.add(Term::new_int(n)));
+    let cases = [
+        ("-5", Term::new_int(-5)),
+        ("-5.5", Term::new_real((-11, 2))),
+        ("(- 5)", Term::Op(Operator::Sub, vec![five.clone()])),
+        ("(- -5)", Term::Op(Operator::Sub, vec![minus_five.clone()])),
+        ("(- 5 -2)", Term::Op(Operator::Sub, vec![five, minus_two])),
+    ];
+    run_parser_tests(&mut p, &cases);
+
+    // Negative numerals are also accepted as step arguments
+    let proof = parse_proof(&mut p, "(step t1 (cl) :rule rule-name :args (-5 (- 5)))");
+    let ProofCommand::Step(step) = &proof.commands[0] else {
+        unreachable!()
+    };
+    assert_eq!(ProofArg::Term(minus_five), step.args[0]);
+    assert!(matches!(
+        step.args[1].as_term().unwrap().as_ref(),
+        Term::Op(Operator::Sub, _)
+    ));
+}
+
 #[test]
 fn test_logic_ops() {
     let mut p = PrimitivePool::new();
@@ -214,6 +258,14 @@ fn test_logic_ops() {
         parse_term_err("(=> true 0)"),
         Error::Parser(ParserError::SortError(_), _),
     ));
+    assert!(matches!(
+        parse_term_err("(not true false)"),
+        Error::Parser(ParserError::WrongNumberOfArgs(_, 2), _),
+    ));
+    assert!(matches!(
+        parse_term_err("(and true false 0)"),
+        Error::Parser(ParserError::SortError(_), _),
+    ));
 }
 
 #[test]
@@ -405,6 +457,38 @@ fn test_annotated_terms() {
     ));
 }
 
+#[test]
+fn test_quantifier_patterns() {
+    let mut pool = PrimitivePool::new();
+    let input = "
+        (declare-fun f (Int) Int)
+        (declare-fun g (Int Int) Bool)
+        (assert (forall ((x Int) (y Int))
+            (! (g x y) :pattern ((f x) (f y)) :named foo :pattern ((g x y)))))
+        (assert (exists ((x Int)) (! (= (f x) 0) :pattern ((f x)))))
+        (assert (forall ((x Int)) (and (! (= (f x) 1) :pattern ((f x))) true)))
+    ";
+    let mut parser = Parser::new(&mut pool, TEST_CONFIG, input.as_bytes()).expect(ERROR_MESSAGE);
+    let premises = parser.parse_problem().expect(ERROR_MESSAGE).1;
+    let patterns = parser.patterns().clone();
+    let premises: Vec<_> = premises.into_iter().collect();
+
+    // The third quantifier has a pattern, but not on its body, so it is ignored
+    assert_eq!(2, patterns.len());
+    let to_strings = |term: &Rc<Term>| -> Vec<Vec<String>> {
+        patterns[term]
+            .iter()
+            .map(|p| p.iter().map(ToString::to_string).collect())
+            .collect()
+    };
+    assert_eq!(
+        vec![vec!["(f x)", "(f y)"], vec!["(g x y)"]],
+        to_strings(&premises[0])
+    );
+    assert_eq!(vec![vec!["(f x)"]], to_strings(&premises[1]));
+    assert!(!patterns.contains_key(&premises[2]));
+}
+
 #[test]
 fn test_declare_fun() {
     let mut p = PrimitivePool::new();
@@ -427,6 +511,54 @@ fn test_declare_fun() {
     assert_eq!(p.add(Term::new_var("x", real_sort)), got);
 }
 
+#[test]
+fn test_reset() {
+    let mut p = PrimitivePool::new();
+    let problem = "
+        (set-logic QF_LIA)
+        (declare-fun x () Int)
+        (declare-sort T 0)
+        (assert (> x 0))
+        (check-sat)
+        (reset)
+        (set-logic QF_UF)
+        (declare-fun x () Bool)
+        (declare-fun T () Bool)
+        (assert (and x T))
+    ";
+    let mut parser = Parser::new(&mut p, TEST_CONFIG, problem.as_bytes()).expect(ERROR_MESSAGE);
+    let (prelude, premises) = parser.parse_problem().expect(ERROR_MESSAGE);
+    assert_eq!(Some("QF_UF"), prelude.logic.as_deref());
+    assert!(prelude.sort_declarations.is_empty());
+    assert_eq!(2, prelude.function_declarations.len());
+    let [expected] = parse_terms(
+        &mut p,
+        "(declare-fun x () Bool) (declare-fun T () Bool)",
+        ["(and x T)"],
+    );
+    assert_eq!(premises.into_iter().collect::<Vec<_>>(), [expected]);
+
+    // `reset-assertions` keeps the logic
+    let problem = "
+        (set-logic QF_LRA)
+        (declare-fun x () Real)
+        (assert (> x 0))
+        (reset-assertions)
+        (declare-fun y () Real)
+        (assert (> y 1))
+    ";
+    let mut parser = Parser::new(&mut p, TEST_CONFIG, problem.as_bytes()).expect(ERROR_MESSAGE);
+    let (prelude, premises) = parser.parse_problem().expect(ERROR_MESSAGE);
+    assert_eq!(Some("QF_LRA"), prelude.logic.as_deref());
+    assert_eq!(1, premises.len());
+    assert!(matches!(
+        parser
+            .reset("x".as_bytes())
+            .and_then(|()| parser.parse_term()),
+        Err(Error::Parser(ParserError::UndefinedIden(_), _)),
+    ));
+}
+
 #[test]
 fn test_declare_sort() {
     let mut p = PrimitivePool::new();
@@ -450,6 +582,23 @@ fn test_declare_sort() {
     );
     let expected_sort = p.add(Term::Sort(Sort::Atom("T".to_owned(), Vec::new())));
     assert_eq!(p.add(Term::new_var("x", expected_sort)), got);
+
+    // Sorts are interned, so separate parses of the same sort give the same term
+    let [xs, bv] = parse_terms(
+        &mut p,
+        "(declare-sort List 1) (declare-fun xs () (List Int)) (declare-fun bv () (_ BitVec 8))",
+        ["xs", "bv"],
+    );
+    let [ys, bv2] = parse_terms(
+        &mut p,
+        "(declare-sort List 1) (declare-fun ys () (List Int)) (declare-fun bv2 () (_ BitVec 8))",
+        ["ys", "bv2"],
+    );
+    assert_eq!(p.sort(&xs), p.sort(&ys));
+    assert_eq!(p.sort(&bv), p.sort(&bv2));
+    let int = p.intern_sort(Sort::Int);
+    let list_int = p.intern_sort(Sort::Atom("List".to_owned(), vec![int]));
+    assert_eq!(list_int, p.sort(&xs));
 }
 
 #[test]
@@ -473,6 +622,21 @@ fn test_define_fun() {
     );
     let expected = parse_term(&mut p, "(* (+ 2 1) (+ 3 1))");
     assert_eq!(expected, got);
+
+    let mut parser = Parser::new(
+        &mut p,
+        TEST_CONFIG,
+        "(declare-fun f (Int) Int)
+         (declare-fun g (Int) Int)
+         (define-fun f ((x Int)) Int (g x))
+         (define-fun g ((x Int)) Int (f x))"
+            .as_bytes(),
+    )
+    .expect(ERROR_MESSAGE);
+    assert!(matches!(
+        parser.parse_problem(),
+        Err(Error::Parser(ParserError::RecursiveDefinition(name), _)) if name == "g",
+    ));
 }
 
 #[test]
@@ -515,6 +679,40 @@ fn test_define_fun_rec() {
     );
 }
 
+#[test]
+fn test_recursive_definitions() {
+    let mut p = PrimitivePool::new();
+    let problem = "
+        (declare-sort List 0)
+        (declare-fun nil () List)
+        (declare-fun tail (List) List)
+        (define-fun-rec length ((l List)) Int (ite (= l nil) 0 (+ 1 (length (tail l)))))
+    ";
+    let mut parser = Parser::new(&mut p, TEST_CONFIG, problem.as_bytes()).expect(ERROR_MESSAGE);
+    parser.parse_problem().expect(ERROR_MESSAGE);
+    parser
+        .reset(
+            "(forall ((l List)) (= (length l) (ite (= l nil) 0 (+ 1 (length (tail l))))))"
+                .as_bytes(),
+        )
+        .expect(ERROR_MESSAGE);
+    let expected = parser.parse_term().expect(ERROR_MESSAGE);
+    assert_eq!(1, parser.recursive_definitions().len());
+    assert_eq!(&expected, &parser.recursive_definitions()["length"]);
+
+    // The body must have the declared return sort
+    let mut parser = Parser::new(
+        &mut p,
+        TEST_CONFIG,
+        "(define-fun-rec bad ((x Int)) Bool (+ x 1))".as_bytes(),
+    )
+    .expect(ERROR_MESSAGE);
+    assert!(matches!(
+        parser.parse_problem(),
+        Err(Error::Parser(ParserError::SortError(_), _))
+    ));
+}
+
 #[test]
 fn test_define_sort() {
     let mut p = PrimitivePool::new();
@@ -596,6 +794,7 @@ fn test_step() {
             premises: Vec::new(),
             args: Vec::new(),
             discharge: Vec::new(),
+            weight: None,
         })
     );
 
@@ -608,6 +807,7 @@ fn test_step() {
             premises: vec![(0, 0)],
             args: Vec::new(),
             discharge: Vec::new(),
+            weight: None,
         })
     );
 
@@ -629,6 +829,7 @@ fn test_step() {
                 .collect()
             },
             discharge: Vec::new(),
+            weight: None,
         })
     );
 
@@ -647,6 +848,7 @@ fn test_step() {
                 ]
             },
             discharge: Vec::new(),
+            weight: None,
         })
     );
 
@@ -659,10 +861,373 @@ fn test_step() {
             premises: vec![(0, 0), (0, 1), (0, 2)],
             args: vec![ProofArg::Term(p.add(Term::new_int(42)))],
             discharge: Vec::new(),
+            weight: None,
         })
     );
 }
 
+#[test]
+fn test_named_terms_round_trip() {
+    let problem = "
+        (declare-fun p () Bool)
+        (declare-fun q () Bool)
+        (assert (! (and p q) :named a0))
+    ";
+    let original = "
+        (assume h1 (! (and p q) :named a0))
+        (step t2 (cl (! (not (and p (! (or p q) :named n1))) :named n2)) :rule hole)
+        (step t3 (cl (= n1 (or q p)) n2) :rule hole :premises (h1 t2))
+        (step t4 (cl) :rule hole :premises (t3))
+    ";
+    let (_, proof, _) =
+        parse_instance(problem.as_bytes(), original.as_bytes(), Config::new()).unwrap();
+    let names: Vec<_> = proof.named_terms.values().map(String::as_str).collect();
+    assert_eq!(["a0", "n1", "n2"], names.as_slice());
+
+    let printed = proof.to_alethe_string();
+    assert_eq!(
+        "(assume h1 (! (and p q) :named a0))
+(step t2 (cl (! (not (and p (! (or p q) :named n1))) :named n2)) :rule hole)
+(step t3 (cl (= n1 (or q p)) n2) :rule hole :premises (h1 t2))
+(step t4 (cl) :rule hole :premises (t3))
+",
+        printed
+    );
+
+    // Since terms are hash consed, we need to parse the problem again in the same pool to compare
+    let mut pool = PrimitivePool::new();
+    let [expected, got] = [original, &printed].map(|proof| {
+        let mut parser = Parser::new(&mut pool, Config::new(), problem.as_bytes()).unwrap();
+        parser.parse_problem().unwrap();
+        parser.reset(proof.as_bytes()).unwrap();
+        parser.parse_proof().unwrap()
+    });
+    assert_eq!(expected, got);
+}
+
+#[test]
+fn test_step_attribute_order() {
+    let mut p = PrimitivePool::new();
+    let input = "
+        (step t1 (cl) :rule rule-name)
+        (step t2 (cl) :rule rule-name :premises (t1) :args (42))
+        (step t3 (cl) :premises (t1) :rule rule-name :args (42))
+        (step t4 (cl) :args (42) :premises (t1) :rule rule-name)
+        (step t5 (cl) :ignore_this :args (42) :and_this (blah 0) :premises (t1) :rule rule-name)
+        (step t6 (cl) :rule hole :args (blah blah))
+        (step t7 (cl) :args (42) :rule hole)
+        (step t8 (cl) :args (blah (blah)) :premises (t1) :rule hole)
+    ";
+    let proof = parse_proof(&mut p, input);
+    let steps: Vec<_> = (proof.commands.iter())
+        .map(|c| match c {
+            ProofCommand::Step(s) => s,
+            _ => unreachable!(),
+        })
+        .collect();
+    let forty_two = ProofArg::Term(p.add(Term::new_int(42)));
+    for step in &steps[1..5] {
+        assert_eq!("rule-name", step.rule);
+        assert_eq!(vec![(0, 0)], step.premises);
+        assert_eq!(vec![forty_two.clone()], step.args);
+    }
+    for step in &steps[5..] {
+        assert_eq!("hole", step.rule);
+        assert!(step.args.is_empty());
+    }
+
+    // Returns the error message, without the command context, and the position of the error
+    let mut parse_error = |input: &str| match Parser::new(&mut p, TEST_CONFIG, input.as_bytes())
+        .expect(ERROR_MESSAGE)
+        .parse_proof()
+    {
+        Err(Error::Parser(e, pos)) => (e.without_context().to_string(), pos),
+        other => panic!("expected parser error, got {:?}", other),
+    };
+    let cases = [
+        (
+            "(step t1 (cl) :args (42))",
+            ParserError::MissingRule("t1".into()),
+            (1, 25),
+        ),
+        (
+            "(step t1 (cl) :args (blah) :rule rule-name)",
+            ParserError::UndefinedIden("blah".into()),
+            (1, 22),
+        ),
+    ];
+    for (input, expected, position) in cases {
+        assert_eq!((expected.to_string(), position), parse_error(input));
+    }
+
+    // If an attribute is repeated, the last value is used
+    let input = "
+        (step t1 (cl) :rule hole)
+        (step t2 (cl) :rule rule-name :args (1) :rule other-rule :args (42))
+    ";
+    let proof = parse_proof(&mut p, input);
+    let ProofCommand::Step(step) = &proof.commands[1] else {
+        unreachable!()
+    };
+    assert_eq!("other-rule", step.rule);
+    assert_eq!(vec![forty_two], step.args);
+}
+
+#[test]
+fn test_slice_input() {
+    // Parsing from a string slice must give the same result as parsing from a `BufRead`, including
+    // for tokens that span multiple lines
+    let problem = "(declare-fun p () Bool)
+        (declare-fun f (Int) Int) ; a comment
+        (declare-fun |a quoted
+symbol| () String)
+        (define-fun s () String \"a string that
+spans two lines\")
+        (assert p)";
+    let proof = "(assume h1 p)
+        (anchor :step t2 :args ((x Int)))
+        (step t2.t1 (cl (= (f x) (f x))) :rule refl)
+        (step t2 (cl (= (forall ((x Int)) (= (f x) (f x))) true)) :rule bind)
+        (step t3 (cl (! (= s |a quoted
+symbol|) :named n)) :rule hole)";
+    let mut pool = PrimitivePool::new();
+
+    let mut parser = Parser::new(&mut pool, TEST_CONFIG, problem.as_bytes()).expect(ERROR_MESSAGE);
+    parser.parse_problem().expect(ERROR_MESSAGE);
+    parser.reset(proof.as_bytes()).expect(ERROR_MESSAGE);
+    let expected = parser.parse_proof().expect(ERROR_MESSAGE);
+    let expected_end = parser.current_position;
+
+    let mut parser =
+        Parser::from_lexer(&mut pool, TEST_CONFIG, Lexer::from_str(problem)).expect(ERROR_MESSAGE);
+    parser.parse_problem().expect(ERROR_MESSAGE);
+    parser
+        .reset_lexer(Lexer::from_str(proof))
+        .expect(ERROR_MESSAGE);
+    assert_eq!(expected, parser.parse_proof().expect(ERROR_MESSAGE));
+    assert_eq!(expected_end, parser.current_position);
+}
+
+#[test]
+fn test_parse_instance_with_lemmas() {
+    let problem = "(declare-fun p () Bool) (declare-fun x () Int)";
+    let proof = "(step t1 (cl (= x x)) :rule refl)";
+    let (_, proof, mut pool, lemmas) = parse_instance_with_lemmas(
+        problem.as_bytes(),
+        proof.as_bytes(),
+        "p (> x 0)",
+        Config::new(),
+    )
+    .expect(ERROR_MESSAGE);
+    let expected = parse_terms(&mut pool, problem, ["p", "(> x 0)"]);
+    assert_eq!(expected.as_slice(), lemmas);
+    assert_eq!(1, proof.commands.len());
+
+    // Lemmas must be of sort `Bool`
+    let result =
+        parse_instance_with_lemmas(problem.as_bytes(), "".as_bytes(), "p x", Config::new());
+    assert!(matches!(
+        result,
+        Err(Error::Parser(ParserError::SortError(_), (1, 3)))
+    ));
+}
+
+#[test]
+fn test_parse_instance_multi() {
+    let problem = "(declare-fun p () Bool) (declare-fun q () Bool) (assert p)";
+    let parts = [
+        "(assume h1",
+        "p)\n(step t1 (cl p q) :rule hole :premises (h1))",
+        "(anchor :step t2)
+        (assume t2.a0 q)
+        (step t2 (cl (not q) q) :rule subproof :discharge (t2.a0))
+        (step t3 (cl p) :rule hole :premises (t1 t2))",
+    ];
+
+    let (_, expected, _) = parse_instance(
+        problem.as_bytes(),
+        parts.join("\n").as_bytes(),
+        Config::new(),
+    )
+    .expect(ERROR_MESSAGE);
+
+    // The first part ends in the middle of a command, right after a symbol, and the next part
+    // starts with another symbol. Since the first part doesn't end in a line break, this checks
+    // that the two symbols aren't joined. The parts also don't need to be the same type of reader
+    let (_, got, _) = parse_instance_multi(
+        problem.as_bytes(),
+        vec![
+            Box::new(parts[0].as_bytes()),
+            Box::new(std::io::Cursor::new(parts[1].to_owned())),
+            Box::new(parts[2].as_bytes()),
+        ],
+        Config::new(),
+    )
+    .expect(ERROR_MESSAGE);
+
+    // The proofs are parsed into different pools, so we can't compare their terms directly
+    let debug = |proof: &Proof| format!("{:?} {:?}", proof.premises, proof.commands);
+    assert_eq!(debug(&expected), debug(&got));
+
+    // Step ids must be unique across all parts
+    let result = parse_instance_multi(
+        problem.as_bytes(),
+        vec![
+            Box::new(parts[0].as_bytes()),
+            Box::new(parts[1].as_bytes()),
+            Box::new("(step t1 (cl) :rule hole)".as_bytes()),
+        ],
+        Config::new(),
+    );
+    assert!(matches!(
+        result,
+        Err(Error::Parser(ParserError::RepeatedStepId(id), (4, 2))) if id == "t1"
+    ));
+}
+
+#[test]
+fn test_error_command_context() {
+    let mut p = PrimitivePool::new();
+    let input = "
+        (assume h1 true)
+        (step t2 (cl) :rule rule-name :premises (h1 42))
+    ";
+    let err = Parser::new(&mut p, TEST_CONFIG, input.as_bytes())
+        .and_then(|mut parser| parser.parse_proof())
+        .expect_err("expected error");
+    let Error::Parser(e, pos) = &err else {
+        panic!("expected parser error");
+    };
+    assert!(matches!(
+        e,
+        ParserError::InCommand { kind: "step", id: Some(id), .. } if id == "t2"
+    ));
+    assert!(matches!(
+        e.without_context(),
+        ParserError::UnexpectedToken(_)
+    ));
+    assert_eq!((3, 53), *pos);
+    assert_eq!("in step 't2': unexpected token: '42'", e.to_string());
+
+    // For `anchor` commands, the id is the end step
+    let err = Parser::new(
+        &mut p,
+        TEST_CONFIG,
+        "(anchor :step t3 :args (x))".as_bytes(),
+    )
+    .and_then(|mut parser| parser.parse_proof())
+    .expect_err("expected error");
+    assert!(matches!(
+        err,
+        Error::Parser(ParserError::InCommand { kind: "anchor", id: Some(id), .. }, _) if id == "t3"
+    ));
+}
+
+#[test]
+fn test_normalize_clause_order() {
+    let mut p = PrimitivePool::new();
+    let config = Config {
+        normalize_clause_order: true,
+        ..TEST_CONFIG
+    };
+    // The id `t1` is reused in the subproof, so the original clauses can't be identified by id
+    let input = "
+        (step t1 (cl p (not q) r) :rule hole)
+        (anchor :step t2)
+        (step t1 (cl r p (not q)) :rule hole)
+        (step t2 (cl (not q) r p) :rule hole)
+    ";
+    let mut parser = Parser::new(
+        &mut p,
+        config,
+        "(declare-fun p () Bool) (declare-fun q () Bool) (declare-fun r () Bool)".as_bytes(),
+    )
+    .expect(ERROR_MESSAGE);
+    parser.parse_problem().expect(ERROR_MESSAGE);
+    parser.reset(input.as_bytes()).expect(ERROR_MESSAGE);
+    let commands = parser.parse_proof().expect(ERROR_MESSAGE);
+    let original_clauses = parser.original_clauses().clone();
+
+    let [ProofCommand::Step(t1), ProofCommand::Subproof(s)] = &commands[..] else {
+        panic!("unexpected proof structure");
+    };
+    let [ProofCommand::Step(inner_t1), ProofCommand::Step(t2)] = &s.commands[..] else {
+        panic!("unexpected subproof structure");
+    };
+    assert_eq!(t1.clause, inner_t1.clause);
+    assert_eq!(t1.clause, t2.clause);
+
+    // The original order is kept for each clause that was reordered
+    let written = [(vec![0], t1), (vec![1, 0], inner_t1), (vec![1, 1], t2)];
+    let inputs = ["(cl p (not q) r)", "(cl r p (not q))", "(cl (not q) r p)"];
+    for ((position, step), input) in written.into_iter().zip(inputs) {
+        let original = original_clauses.get(&position).unwrap_or(&step.clause);
+        let original: Vec<_> = original.iter().map(ToString::to_string).collect();
+        assert_eq!(input, format!("(cl {})", original.join(" ")));
+    }
+
+    // The original order is attached to the proof, and used when printing it
+    let (_, proof, _) = parse_instance(
+        "(declare-fun p () Bool) (declare-fun q () Bool) (declare-fun r () Bool)".as_bytes(),
+        input.as_bytes(),
+        config,
+    )
+    .unwrap();
+    assert_eq!(
+        "(step t1 (cl p (not q) r) :rule hole)
+(anchor :step t2)
+  (step t1 (cl r p (not q)) :rule hole)
+(step t2 (cl (not q) r p) :rule hole)
+",
+        proof.to_alethe_string()
+    );
+}
+
+#[test]
+fn test_step_weight() {
+    let mut p = PrimitivePool::new();
+    let input = "
+        (step t1 (cl) :rule rule-name :weight 3)
+        (step t2 (cl) :weight 4 :rule rule-name :premises (t1))
+        (anchor :step t3)
+        (step t3.t1 (cl) :rule rule-name :weight 5)
+        (step t3 (cl) :rule rule-name)
+    ";
+    let proof = parse_proof(&mut p, input);
+    let ProofCommand::Step(step) = &proof.commands[0] else {
+        unreachable!()
+    };
+    assert_eq!(Some(3), step.weight);
+    assert_eq!(12, proof.total_weight());
+
+    // The total weight saturates instead of overflowing
+    let max = u64::MAX;
+    let input = format!(
+        "(step t1 (cl) :rule rule-name :weight {max})
+        (step t2 (cl) :rule rule-name :weight {max})"
+    );
+    assert_eq!(max, parse_proof(&mut p, &input).total_weight());
+
+    // The weight should be kept when printing the proof
+    let mut printed = Vec::new();
+    crate::ast::write_proof(&mut printed, &proof.commands, Default::default(), false).unwrap();
+    let printed = String::from_utf8(printed).unwrap();
+    assert_eq!(proof.commands, parse_proof(&mut p, &printed).commands);
+
+    let result = Parser::new(
+        &mut p,
+        TEST_CONFIG,
+        "(step t1 (cl) :rule r :weight -1)".as_bytes(),
+    )
+    .expect(ERROR_MESSAGE)
+    .parse_proof();
+    assert!(matches!(
+        result,
+        Err(Error::Parser(e, _))
+            if matches!(e.without_context(), ParserError::InvalidStepWeight(_))
+    ));
+}
+
 #[test]
 fn test_premises_in_subproofs() {
     let mut p = PrimitivePool::new();
@@ -690,6 +1255,7 @@ fn test_premises_in_subproofs() {
             premises: vec![(0, 0), (0, 1)],
             args: Vec::new(),
             discharge: Vec::new(),
+            weight: None,
         })
     );
     assert_eq!(
@@ -701,6 +1267,7 @@ fn test_premises_in_subproofs() {
             premises: vec![(1, 0), (0, 0), (0, 1)],
             args: Vec::new(),
             discharge: Vec::new(),
+            weight: None,
         })
     );
     assert_eq!(
@@ -712,10 +1279,128 @@ fn test_premises_in_subproofs() {
             premises: vec![(0, 0), (1, 0), (0, 1), (1, 1)],
             args: Vec::new(),
             discharge: Vec::new(),
+            weight: None,
         })
     );
 }
 
+#[test]
+fn test_anchor_validation() {
+    let mut p = PrimitivePool::new();
+    let proof = parse_proof(
+        &mut p,
+        "(anchor :step t1 :args ((:= (x Int) 0)))
+        (anchor :step t1.t2)
+        (step t1.t2.t1 (cl (= x 0)) :rule rule-name)
+        (step t1.t2 (cl (= x 0)) :rule rule-name)
+        (step t1 (cl (= x 0)) :rule rule-name)",
+    );
+    assert_eq!(proof.commands.len(), 1);
+
+    let parse = |p: &mut PrimitivePool, input: &str| {
+        Parser::new(p, TEST_CONFIG, input.as_bytes())
+            .expect(ERROR_MESSAGE)
+            .parse_proof()
+    };
+
+    // The inner anchor refers to a step that never appears, so the inner subproof is still open
+    // when the outer one ends
+    let result = parse(
+        &mut p,
+        "(anchor :step t1)
+        (anchor :step t1.t3)
+        (step t1.t2.t1 (cl) :rule rule-name)
+        (step t1.t2 (cl) :rule rule-name)
+        (step t1 (cl) :rule rule-name)",
+    );
+    assert!(matches!(
+        result,
+        Err(Error::Parser(ParserError::MismatchedAnchor(anchor, step), pos))
+            if anchor == "t1.t3" && step == "t1" && pos == (5, 10)
+    ));
+
+    // Step ids are scoped, so a step in an inner subproof may have the same id as the step that
+    // ends an outer one
+    let proof = parse(
+        &mut p,
+        "(anchor :step t1)
+        (anchor :step t2)
+        (step t1 (cl) :rule rule-name)
+        (step t2 (cl) :rule rule-name)
+        (step t1 (cl) :rule rule-name)",
+    )
+    .unwrap();
+    assert!(matches!(&proof[..], [ProofCommand::Subproof(s)] if s.commands.len() == 2));
+
+    // The value assigned in an anchor argument must have the sort of the variable
+    let result = parse(
+        &mut p,
+        "(anchor :step t1 :args ((:= (x Int) true)))
+        (step t1.t1 (cl) :rule rule-name)
+        (step t1 (cl) :rule rule-name)",
+    );
+    assert!(matches!(
+        result,
+        Err(Error::Parser(e, pos))
+            if matches!(e.without_context(), ParserError::SortError(_)) && pos == (1, 37)
+    ));
+}
+
+#[test]
+fn test_repeated_step_ids() {
+    let mut p = PrimitivePool::new();
+    let parse = |p: &mut PrimitivePool, input: &str| {
+        Parser::new(p, TEST_CONFIG, input.as_bytes())
+            .expect(ERROR_MESSAGE)
+            .parse_proof()
+    };
+
+    let result = parse(
+        &mut p,
+        "(assume h1 true)
+        (step t1 (cl) :rule rule-name)
+        (step t1 (cl) :rule rule-name)",
+    );
+    assert!(matches!(
+        result,
+        Err(Error::Parser(ParserError::RepeatedStepId(id), (3, 10))) if id == "t1"
+    ));
+
+    // The step that ends a subproof belongs to the outer scope
+    let result = parse(
+        &mut p,
+        "(step t1 (cl) :rule rule-name)
+        (anchor :step t1)
+        (step t1.t1 (cl) :rule rule-name)
+        (step t1 (cl) :rule rule-name)",
+    );
+    assert!(matches!(
+        result,
+        Err(Error::Parser(ParserError::RepeatedStepId(id), (4, 10))) if id == "t1"
+    ));
+
+    // Ids may be reused in a different subproof scope, in which case premises refer to the
+    // innermost step with that id
+    let proof = parse(
+        &mut p,
+        "(assume h1 true)
+        (anchor :step t2)
+        (assume h1 false)
+        (step t2.t1 (cl) :rule rule-name :premises (h1))
+        (step t2 (cl) :rule rule-name)
+        (step t3 (cl) :rule rule-name :premises (h1))",
+    )
+    .expect(ERROR_MESSAGE);
+    let [_, ProofCommand::Subproof(subproof), ProofCommand::Step(outer)] = &proof[..] else {
+        panic!("unexpected proof structure");
+    };
+    let ProofCommand::Step(inner) = &subproof.commands[1] else {
+        panic!("expected step");
+    };
+    assert_eq!(inner.premises, [(1, 0)]);
+    assert_eq!(outer.premises, [(0, 0)]);
+}
+
 #[test]
 fn test_bitvectors() {
     let mut p = PrimitivePool::new();
@@ -803,3 +1488,336 @@ fn test_qualified_operators() {
         Error::Parser(ParserError::InvalidQualifiedOp(_), _),
     ));
 }
+
+#[test]
+fn test_peek_token() {
+    let mut p = PrimitivePool::new();
+    let input = "((_ zero_extend 2) #b1) ((lambda ((x Int)) x) 1)";
+    let mut parser = Parser::new(&mut p, TEST_CONFIG, input.as_bytes()).unwrap();
+
+    // Peeking doesn't consume any tokens, so it can be done repeatedly
+    assert_eq!(Token::OpenParen, parser.current_token);
+    assert_eq!(&Token::OpenParen, parser.peek_token().unwrap());
+    assert_eq!(&Token::OpenParen, parser.peek_token().unwrap());
+    assert_eq!((Token::OpenParen, (1, 1)), parser.next_token().unwrap());
+    assert_eq!(Token::OpenParen, parser.current_token);
+    assert_eq!((1, 2), parser.current_position);
+    assert_eq!(
+        &Token::ReservedWord(Reserved::Underscore),
+        parser.peek_token().unwrap()
+    );
+
+    // Both applications have a head that starts with `(`, so lookahead is needed to tell the
+    // indexed operator apart from the lambda term
+    let indexed = parser.parse_application((1, 1)).unwrap();
+    assert!(matches!(indexed.as_ref(), Term::ParamOp { .. }));
+    let applied_lambda = parser.parse_term().unwrap();
+    assert!(matches!(applied_lambda.as_ref(), Term::App(..)));
+    assert_eq!(Token::Eof, parser.current_token);
+}
+
+#[test]
+fn test_unknown_symbols() {
+    let input = "
+        (assume h1 (or foo (bar 1 foo)))
+        (step t2 (cl (bar 2 false)) :rule hole :premises (h1))
+    ";
+
+    // By default, using an undeclared symbol is an error, which points to the symbol
+    let mut p = PrimitivePool::new();
+    let err = Parser::new(&mut p, TEST_CONFIG, input.as_bytes())
+        .and_then(|mut parser| parser.parse_proof())
+        .expect_err("expected error");
+    assert!(matches!(
+        err,
+        Error::Parser(e, (2, 24))
+            if matches!(e.without_context(), ParserError::UndefinedIden(s) if s == "foo")
+    ));
+
+    // Otherwise, `foo` is declared as a `Bool` constant, and `bar` as a function from the sorts of
+    // the arguments in its first application to `Bool`
+    let config = Config {
+        allow_unknown_symbols: true,
+        ..TEST_CONFIG
+    };
+    let commands = Parser::new(&mut p, config, input.as_bytes())
+        .and_then(|mut parser| parser.parse_proof())
+        .unwrap();
+    let [bool_sort, int_sort] = [Sort::Bool, Sort::Int].map(|s| p.add(Term::Sort(s)));
+    let foo = p.add(Term::Var("foo".into(), bool_sort.clone()));
+    let bar_sort = p.add(Term::Sort(Sort::Function(vec![
+        int_sort,
+        bool_sort.clone(),
+        bool_sort,
+    ])));
+    let bar = p.add(Term::Var("bar".into(), bar_sort));
+    let one = p.add(Term::new_int(1));
+    let bar_app = p.add(Term::App(bar, vec![one, foo.clone()]));
+    let expected = p.add(Term::Op(Operator::Or, vec![foo, bar_app]));
+    assert_eq!(
+        ProofCommand::Assume { id: "h1".into(), term: expected },
+        commands[0]
+    );
+
+    // Later uses must agree with the sort given by the first use
+    let input = "(assume h1 (or (bar 1 true) (bar true)))";
+    let err = Parser::new(&mut p, config, input.as_bytes())
+        .and_then(|mut parser| parser.parse_proof())
+        .expect_err("expected error");
+    assert!(matches!(
+        err,
+        Error::Parser(e, _) if matches!(e.without_context(), ParserError::WrongNumberOfArgs(..))
+    ));
+
+    // The sorts of symbols and the return sorts of applied symbols are inferred from the sorts
+    // expected by the enclosing application, which may depend on the other arguments
+    let input = "
+        (assume h1 (< x 1))
+        (assume h2 (= (f x) (+ 2.0 y)))
+        (assume h3 (str.in_re s r))
+    ";
+    let commands = Parser::new(&mut p, config, input.as_bytes())
+        .and_then(|mut parser| parser.parse_proof())
+        .unwrap();
+    let expected = parse_terms(
+        &mut p,
+        "(declare-fun x () Int)
+        (declare-fun y () Real)
+        (declare-fun f (Int) Real)
+        (declare-fun s () String)
+        (declare-fun r () RegLan)",
+        ["(< x 1)", "(= (f x) (+ 2.0 y))", "(str.in_re s r)"],
+    );
+    let got: Vec<_> = (commands.iter())
+        .map(|c| match c {
+            ProofCommand::Assume { term, .. } => term.clone(),
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(expected.as_slice(), got.as_slice());
+
+    // Only the first use of a symbol determines its sort, and later uses that disagree with it are
+    // errors. In the second case, `x` is first used as an argument of `or`, so it is a `Bool`
+    let inputs = [
+        "(assume h1 (< x 1)) (assume h2 (and x true))",
+        "(assume h1 (or x (< x 1)))",
+    ];
+    for input in inputs {
+        let err = Parser::new(&mut p, config, input.as_bytes())
+            .and_then(|mut parser| parser.parse_proof())
+            .expect_err("expected error");
+        assert!(matches!(
+            err,
+            Error::Parser(e, _) if matches!(e.without_context(), ParserError::SortError(..))
+        ));
+    }
+}
+
+#[test]
+fn test_parametric_functions() {
+    let mut p = PrimitivePool::new();
+    let [bool_sort, int_sort] = [Sort::Bool, Sort::Int].map(|s| p.add(Term::Sort(s)));
+
+    // Each application of a parametric function instantiates its sort with the argument sorts
+    let [int_app, bool_app] = parse_terms(
+        &mut p,
+        "(declare-fun id (par (A) (A) A))",
+        ["(id 1)", "(id true)"],
+    );
+    for (app, sort) in [(int_app, int_sort.clone()), (bool_app, bool_sort.clone())] {
+        let Term::App(func, _) = app.as_ref() else {
+            panic!("expected application term");
+        };
+        let func_sort = p.add(Term::Sort(Sort::Function(vec![sort.clone(), sort.clone()])));
+        assert_eq!(p.add(Term::new_var("id", func_sort)), *func);
+        assert_eq!(sort, p.sort(&app));
+    }
+
+    let [int_app, bool_app] = parse_terms(
+        &mut p,
+        "(define-fun const (par (A) ((x A)) A x))",
+        ["(const 1)", "(const true)"],
+    );
+    assert_eq!(p.add(Term::new_int(1)), int_app);
+    assert_eq!(p.add(Term::new_bool(true)), bool_app);
+
+    let [got] = parse_terms(
+        &mut p,
+        "(define-fun pick (par (A) ((c Bool) (x A) (y A)) A (ite c x y)))",
+        ["(pick false 1 2)"],
+    );
+    assert_eq!(parse_term(&mut p, "(ite false 1 2)"), got);
+
+    // If the argument sorts don't agree on an instantiation, it is a sort error
+    let mut parser = Parser::new(
+        &mut p,
+        TEST_CONFIG,
+        "(declare-fun f (par (A) (A A) Bool))".as_bytes(),
+    )
+    .expect(ERROR_MESSAGE);
+    parser.parse_problem().expect(ERROR_MESSAGE);
+    parser.reset("(f 1 true)".as_bytes()).expect(ERROR_MESSAGE);
+    assert!(matches!(
+        parser.parse_term(),
+        Err(Error::Parser(ParserError::SortError(_), _))
+    ));
+}
+
+#[test]
+fn test_overloaded_functions() {
+    let mut p = PrimitivePool::new();
+    let [bool_sort, int_sort, real_sort] =
+        [Sort::Bool, Sort::Int, Sort::Real].map(|s| p.add(Term::Sort(s)));
+    let definitions = "
+        (declare-fun f (Int) Int)
+        (declare-fun f (Real) Bool)
+        (declare-fun g (Int) Int)
+        (declare-fun g (Int) Bool)
+    ";
+
+    // The signature is chosen based on the argument sorts
+    let [int_app, real_app] = parse_terms(&mut p, definitions, ["(f 1)", "(f 1.0)"]);
+    for (app, arg_sort, sort) in [
+        (int_app, int_sort.clone(), int_sort.clone()),
+        (real_app, real_sort, bool_sort),
+    ] {
+        let Term::App(func, _) = app.as_ref() else {
+            panic!("expected application term");
+        };
+        let func_sort = p.add(Term::Sort(Sort::Function(vec![arg_sort, sort.clone()])));
+        assert_eq!(p.add(Term::new_var("f", func_sort)), *func);
+        assert_eq!(sort, p.sort(&app));
+    }
+
+    let mut parser = Parser::new(&mut p, TEST_CONFIG, definitions.as_bytes()).expect(ERROR_MESSAGE);
+    parser.parse_problem().expect(ERROR_MESSAGE);
+
+    // Both signatures of `g` match an `Int` argument
+    parser.reset("(g 1)".as_bytes()).expect(ERROR_MESSAGE);
+    assert!(matches!(
+        parser.parse_term(),
+        Err(Error::Parser(ParserError::AmbiguousOverload(name, candidates), (1, 2)))
+            if name == "g" && candidates.len() == 2
+    ));
+
+    // No signature of `f` matches a `Bool` argument
+    parser
+        .reset("(and (f true) true)".as_bytes())
+        .expect(ERROR_MESSAGE);
+    assert!(matches!(
+        parser.parse_term(),
+        Err(Error::Parser(ParserError::NoMatchingOverload(name, candidates), (1, 7)))
+            if name == "f" && candidates.len() == 2
+    ));
+}
+
+#[test]
+fn test_shadowed_binders() {
+    let input = "(forall ((x Int)) (let ((x 1)) (> x 0)))";
+
+    // By default, the shadowing binder is kept as is, but a warning is emitted
+    let mut p = PrimitivePool::new();
+    let mut parser = Parser::new(&mut p, TEST_CONFIG, input.as_bytes()).expect(ERROR_MESSAGE);
+    let got = parser.parse_term().expect(ERROR_MESSAGE);
+    assert!(matches!(
+        parser.warnings(),
+        [ParserWarning::ShadowedBinder { name, position: (1, _), renamed_to: None }] if name == "x"
+    ));
+    assert_eq!(parse_term(&mut p, input), got);
+
+    // Otherwise, the shadowing binder is renamed, as well as its occurrences
+    let config = Config {
+        rename_shadowed: true,
+        ..TEST_CONFIG
+    };
+    let mut parser = Parser::new(&mut p, config, input.as_bytes()).expect(ERROR_MESSAGE);
+    let got = parser.parse_term().expect(ERROR_MESSAGE);
+    assert!(matches!(
+        parser.warnings(),
+        [ParserWarning::ShadowedBinder { name, renamed_to: Some(new_name), .. }]
+            if name == "x" && new_name == "x'"
+    ));
+    let expected = parse_term(&mut p, "(forall ((x Int)) (let ((|x'| 1)) (> |x'| 0)))");
+    assert_eq!(expected, got);
+
+    // Binders that don't shadow anything are never renamed
+    let input = "(and (exists ((x Int)) (> x 0)) (forall ((x Int)) (< x 0)))";
+    let mut parser = Parser::new(&mut p, config, input.as_bytes()).expect(ERROR_MESSAGE);
+    let got = parser.parse_term().expect(ERROR_MESSAGE);
+    assert!(parser.warnings().is_empty());
+    assert_eq!(parse_term(&mut p, input), got);
+}
+
+#[test]
+fn test_max_term_depth() {
+    let nested = |depth: usize| format!("{}p{}", "(not ".repeat(depth), ")".repeat(depth));
+    let config = Config {
+        max_term_depth: Some(20),
+        ..TEST_CONFIG
+    };
+    let mut p = PrimitivePool::new();
+    let mut parser =
+        Parser::new(&mut p, config, "(declare-fun p () Bool)".as_bytes()).expect(ERROR_MESSAGE);
+    parser.parse_problem().expect(ERROR_MESSAGE);
+
+    let shallow = nested(20);
+    parser.reset(shallow.as_bytes()).expect(ERROR_MESSAGE);
+    assert!(parser.parse_term().is_ok());
+
+    let deep = nested(200_000);
+    parser.reset(deep.as_bytes()).expect(ERROR_MESSAGE);
+    assert!(matches!(
+        parser.parse_term(),
+        Err(Error::Parser(ParserError::TermTooDeep(20), (1, 101)))
+    ));
+
+    // The limit is disabled by default
+    assert_eq!(None, Config::new().max_term_depth);
+    let mut parser = Parser::new(&mut p, Config::new(), "(declare-fun p () Bool)".as_bytes())
+        .expect(ERROR_MESSAGE);
+    parser.parse_problem().expect(ERROR_MESSAGE);
+    let over_limit = nested(21);
+    parser.reset(over_limit.as_bytes()).expect(ERROR_MESSAGE);
+    assert!(parser.parse_term().is_ok());
+}
+
+#[test]
+fn test_suggested_max_term_depth() {
+    // This test runs in a thread with the default stack size. Terms as deep as the suggested limit
+    // can be parsed, and substitutions can be applied to them
+    let nested = |depth: usize| format!("{}p{}", "(not ".repeat(depth), ")".repeat(depth));
+    let mut p = PrimitivePool::new();
+    let config = Config {
+        max_term_depth: Some(DEFAULT_MAX_TERM_DEPTH),
+        ..Config::new()
+    };
+    let mut parser =
+        Parser::new(&mut p, config, "(declare-fun p () Bool)".as_bytes()).expect(ERROR_MESSAGE);
+    parser.parse_problem().expect(ERROR_MESSAGE);
+
+    let limit = nested(DEFAULT_MAX_TERM_DEPTH);
+    parser.reset(limit.as_bytes()).expect(ERROR_MESSAGE);
+    let term = parser.parse_term().expect(ERROR_MESSAGE);
+
+    let over_limit = nested(DEFAULT_MAX_TERM_DEPTH + 1);
+    parser.reset(over_limit.as_bytes()).expect(ERROR_MESSAGE);
+    assert!(matches!(
+        parser.parse_term(),
+        Err(Error::Parser(
+            ParserError::TermTooDeep(DEFAULT_MAX_TERM_DEPTH),
+            _
+        ))
+    ));
+
+    let [p_var, q_var] = ["p", "q"].map(|name| {
+        let bool_sort = p.add(Term::Sort(Sort::Bool));
+        p.add(Term::new_var(name, bool_sort))
+    });
+    let mut substitution = Substitution::single(&mut p, p_var, q_var.clone()).unwrap();
+    let result = substitution.apply(&mut p, &term);
+    let mut inner = &result;
+    while let Some(arg) = match_term!((not t) = inner) {
+        inner = arg;
+    }
+    assert_eq!(&q_var, inner);
+}
@@ -1,4 +1,4 @@
-use crate::ast::{Binder, BindingList, Rc, Term};
+use crate::ast::{Arity, Binder, BindingList, Rc, Term};
 use indexmap::{IndexMap, IndexSet};
 use rug::Integer;
 use std::{
@@ -146,6 +146,19 @@ impl<K: Eq + Hash, V> HashMapStack<K, V> {
         self.scopes.iter().rev().find_map(|scope| scope.get(key))
     }
 
+    /// Searches for `key` only in the `n` innermost scopes.
+    pub fn get_in_innermost<Q>(&self, n: usize, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.scopes
+            .iter()
+            .rev()
+            .take(n)
+            .find_map(|scope| scope.get(key))
+    }
+
     pub fn get_with_depth<Q>(&self, key: &Q) -> Option<(usize, &V)>
     where
         K: Borrow<Q>,
@@ -228,6 +241,15 @@ impl From<ops::RangeTo<usize>> for Range {
     }
 }
 
+impl From<Arity> for Range {
+    fn from(arity: Arity) -> Self {
+        match arity {
+            Arity::Fixed(n) => n.into(),
+            Arity::AtLeast(n) => (n..).into(),
+        }
+    }
+}
+
 /// Provides a pretty displayable name for a type. For example, the type name for `Rc<Term>` is
 /// "term".
 pub trait TypeName {
@@ -83,7 +83,9 @@ fn test_file(proof_path: &str) {
             Error::Io(_) => "IO error".to_owned(),
             Error::Parser(_, (line, column)) => format!("parser error at {}:{}", line, column),
             Error::Checker { rule, step, .. } => format!("checker error at '{}' ({})", step, rule),
-            Error::DoesNotReachEmptyClause => format!("{}", e), // This one is already pretty short
+            Error::Structure(_) | Error::DoesNotReachEmptyClause | Error::Timeout(_) => {
+                format!("{}", e)
+            }
         };
         panic!(
             "\"{}\" returned error: {}",
@@ -1,5 +1,7 @@
 use carcara::{
-    benchmarking::{CollectResults, CsvBenchmarkResults, RunMeasurement},
+    benchmarking::{
+        self, CollectResults, CsvBenchmarkResults, JsonBenchmarkResults, RunMeasurement,
+    },
     checker, parser, CarcaraOptions,
 };
 use crossbeam_queue::ArrayQueue;
@@ -11,11 +13,82 @@ use std::{
     time::{Duration, Instant},
 };
 
+#[derive(Debug, Clone, Copy)]
+pub struct BenchmarkSettings {
+    /// The number of measured runs for each instance.
+    pub num_runs: usize,
+
+    /// The number of runs for each instance that are done before the measured runs, and whose
+    /// results are discarded.
+    pub warmup_runs: usize,
+
+    /// The fraction of the measured runs for each instance that is discarded from each end, after
+    /// ordering them by the time they took. That is, if this is `0.1`, the fastest 10% and the
+    /// slowest 10% of the runs are discarded. At least one run is always kept.
+    pub trim_fraction: f64,
+
+    pub num_jobs: usize,
+    pub elaborate: bool,
+
+    /// The number of times opening a file is retried after a transient IO error. Between attempts,
+    /// the worker waits for a short time, which doubles after each attempt.
+    pub io_retries: usize,
+}
+
 #[derive(Debug, Clone, Copy)]
 struct JobDescriptor<'a> {
+    instance_index: usize,
     problem_file: &'a Path,
     proof_file: &'a Path,
+
+    /// The index of this run among the measured runs for this instance. For warm-up runs, this is
+    /// the index among the warm-up runs.
     run_index: usize,
+    is_warmup: bool,
+}
+
+/// The results of a single measured job, kept separately so the job can later be trimmed.
+struct JobResult<T> {
+    instance_index: usize,
+    time: Duration,
+
+    /// The measurements taken during the job. These are discarded if the job is trimmed.
+    measurements: T,
+
+    /// Whether the job was holey, or ended in an error or a timeout. This is never discarded, even
+    /// if the job is trimmed, so that no error goes unreported.
+    outcome: T,
+}
+
+/// The time to wait before the first retry of an operation that failed with a transient IO error.
+const IO_RETRY_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Returns `true` if the error may go away if the operation is retried, for example a timeout in a
+/// network filesystem. Errors like a missing file or a lack of permissions are not transient.
+fn is_transient(error: &io::Error) -> bool {
+    use io::ErrorKind::*;
+    matches!(
+        error.kind(),
+        Interrupted | WouldBlock | TimedOut | ConnectionReset | ConnectionAborted | BrokenPipe
+    )
+}
+
+/// Calls `f` until it succeeds, retrying at most `retries` times if it fails with a transient
+/// error. Returns the first error that is not transient, or the last error if all retries fail.
+fn retry_io<T>(retries: usize, mut f: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut backoff = IO_RETRY_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        match f() {
+            Err(e) if attempt < retries && is_transient(&e) => {
+                log::warn!("transient IO error, retrying: {}", e);
+                thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
 }
 
 fn run_job<T: CollectResults + Default + Send>(
@@ -23,6 +96,7 @@ fn run_job<T: CollectResults + Default + Send>(
     job: JobDescriptor,
     options: &CarcaraOptions,
     elaborate: bool,
+    io_retries: usize,
 ) -> Result<bool, carcara::Error> {
     let proof_file_name = job.proof_file.to_str().unwrap();
     let mut checker_stats = checker::CheckerStatistics {
@@ -42,18 +116,33 @@ fn run_job<T: CollectResults + Default + Send>(
         expand_lets: options.expand_lets,
         allow_int_real_subtyping: options.allow_int_real_subtyping,
         allow_unary_logical_ops: !options.strict,
+        allow_unknown_symbols: options.allow_unknown_symbols,
+        rename_shadowed: options.rename_shadowed,
+        max_term_depth: options.max_term_depth,
+        normalize_clause_order: false,
+    };
+    let open = |path: &Path| retry_io(io_retries, || File::open(path));
+    let problem = BufReader::new(open(job.problem_file)?);
+    let proof = BufReader::new(open(job.proof_file)?);
+    let (prelude, proof, mut pool, trusted_lemmas) = match &options.trusted_lemmas {
+        Some(lemmas) => {
+            let (prelude, proof, pool, lemmas) =
+                parser::parse_instance_with_lemmas(problem, proof, lemmas, config)?;
+            (prelude, proof, pool, Some(lemmas.into_iter().collect()))
+        }
+        None => {
+            let (prelude, proof, pool) = parser::parse_instance(problem, proof, config)?;
+            (prelude, proof, pool, None)
+        }
     };
-    let (prelude, proof, mut pool) = parser::parse_instance(
-        BufReader::new(File::open(job.problem_file)?),
-        BufReader::new(File::open(job.proof_file)?),
-        config,
-    )?;
     let parsing = parsing.elapsed();
 
     let config = checker::Config::new()
         .strict(options.strict)
         .ignore_unknown_rules(options.ignore_unknown_rules)
-        .lia_options(options.lia_options.clone());
+        .lia_options(options.lia_options.clone())
+        .timeout(options.timeout)
+        .trusted_lemmas(trusted_lemmas);
     let mut checker = checker::ProofChecker::new(&mut pool, config, &prelude);
 
     let checking = Instant::now();
@@ -80,53 +169,136 @@ fn run_job<T: CollectResults + Default + Send>(
             polyeq: checker_stats.polyeq_time,
             assume: checker_stats.assume_time,
             assume_core: checker_stats.assume_core_time,
+            resident_memory: benchmarking::resident_memory(),
         },
     );
     *results = checker_stats.results;
     checking_result
 }
 
+/// Runs jobs from the queue until it is empty. If `keep_separate` is `false`, the results of all
+/// measured jobs are accumulated in the first returned value. Otherwise, the results of each
+/// measured job are returned separately in the second value. In either case, the results of
+/// warm-up jobs are discarded.
 fn worker_thread<T: CollectResults + Default + Send>(
     jobs_queue: &ArrayQueue<JobDescriptor>,
     options: &CarcaraOptions,
     elaborate: bool,
-) -> T {
-    let mut results = T::default();
+    io_retries: usize,
+    keep_separate: bool,
+) -> (T, Vec<JobResult<T>>) {
+    let mut accumulated = T::default();
+    let mut separate = Vec::new();
 
     while let Some(job) = jobs_queue.pop() {
-        match run_job(&mut results, job, options, elaborate) {
-            Ok(true) => results.register_holey(),
+        let mut results = if job.is_warmup || keep_separate {
+            T::default()
+        } else {
+            std::mem::take(&mut accumulated)
+        };
+
+        let time = Instant::now();
+        let result = run_job(&mut results, job, options, elaborate, io_retries);
+        let time = time.elapsed();
+
+        // If the job is kept separately, its outcome is registered separately from its
+        // measurements, so that trimming it doesn't discard the outcome
+        let mut outcome = T::default();
+        let outcome_dest = if keep_separate {
+            &mut outcome
+        } else {
+            &mut results
+        };
+        match result {
+            Ok(true) => outcome_dest.register_holey(),
+            Err(carcara::Error::Timeout(_)) => {
+                log::warn!("timed out in file '{}'", job.proof_file.display());
+                outcome_dest.register_timeout();
+            }
             Err(e) => {
                 log::error!("encountered error in file '{}'", job.proof_file.display());
-                results.register_error(&e);
+                outcome_dest.register_error(&e);
             }
             _ => (),
         }
+
+        if job.is_warmup {
+            continue;
+        }
+        if keep_separate {
+            separate.push(JobResult {
+                instance_index: job.instance_index,
+                time,
+                measurements: results,
+                outcome,
+            });
+        } else {
+            accumulated = results;
+        }
     }
 
+    (accumulated, separate)
+}
+
+/// Discards the measurements of the fastest and slowest runs of each instance, according to
+/// `trim_fraction`, and combines the remaining results. The outcomes of all runs are kept,
+/// including the trimmed ones.
+fn trim_results<T: CollectResults + Default>(
+    mut results: Vec<JobResult<T>>,
+    num_runs: usize,
+    trim_fraction: f64,
+) -> T {
+    let num_trimmed = ((num_runs as f64 * trim_fraction) as usize).min((num_runs - 1) / 2);
+    results.sort_unstable_by_key(|r| (r.instance_index, r.time));
+
+    // Since every instance has exactly `num_runs` results, after sorting, the position of each
+    // result among the results for its instance is its index modulo `num_runs`
     results
+        .into_iter()
+        .enumerate()
+        .fold(T::default(), |mut acc, (i, r)| {
+            acc.combine_in_place(r.outcome);
+            if (num_trimmed..num_runs - num_trimmed).contains(&(i % num_runs)) {
+                acc.combine_in_place(r.measurements);
+            }
+            acc
+        })
 }
 
 pub fn run_benchmark<T: CollectResults + Default + Send>(
     instances: &[(PathBuf, PathBuf)],
-    num_runs: usize,
-    num_jobs: usize,
+    settings: BenchmarkSettings,
     options: &CarcaraOptions,
-    elaborate: bool,
 ) -> T {
     const STACK_SIZE: usize = 128 * 1024 * 1024;
 
-    let jobs_queue = ArrayQueue::new(instances.len() * num_runs);
-    for run_index in 0..num_runs {
-        for (problem, proof) in instances {
-            let job = JobDescriptor {
-                problem_file: problem,
-                proof_file: proof,
-                run_index,
-            };
-            jobs_queue.push(job).unwrap();
+    let BenchmarkSettings {
+        num_runs,
+        warmup_runs,
+        trim_fraction,
+        num_jobs,
+        elaborate,
+        io_retries,
+    } = settings;
+
+    // Since the jobs are popped from the queue in order, all warm-up runs are started before any
+    // measured run
+    let jobs_queue = ArrayQueue::new(instances.len() * (warmup_runs + num_runs));
+    for (is_warmup, num) in [(true, warmup_runs), (false, num_runs)] {
+        for run_index in 0..num {
+            for (instance_index, (problem, proof)) in instances.iter().enumerate() {
+                let job = JobDescriptor {
+                    instance_index,
+                    problem_file: problem,
+                    proof_file: proof,
+                    run_index,
+                    is_warmup,
+                };
+                jobs_queue.push(job).unwrap();
+            }
         }
     }
+    let keep_separate = trim_fraction > 0.0 && num_runs > 2;
 
     thread::scope(|s| {
         let jobs_queue = &jobs_queue; // So we don't try to move the queue into the thread closure
@@ -138,34 +310,50 @@ pub fn run_benchmark<T: CollectResults + Default + Send>(
             .map(|_| {
                 thread::Builder::new()
                     .stack_size(STACK_SIZE)
-                    .spawn_scoped(s, move || worker_thread(jobs_queue, options, elaborate))
+                    .spawn_scoped(s, move || {
+                        worker_thread::<T>(
+                            jobs_queue,
+                            options,
+                            elaborate,
+                            io_retries,
+                            keep_separate,
+                        )
+                    })
                     .unwrap()
             })
             .collect();
 
-        workers
+        let (mut combined, separate) = workers
             .into_iter()
             .map(|w| w.join().unwrap())
-            .reduce(T::combine)
-            .unwrap()
+            .reduce(|(mut a, mut a_separate), (b, b_separate)| {
+                a_separate.extend(b_separate);
+                a.combine_in_place(b);
+                (a, a_separate)
+            })
+            .unwrap();
+        if keep_separate {
+            combined.combine_in_place(trim_results(separate, num_runs, trim_fraction));
+        }
+        combined
     })
 }
 
 pub fn run_csv_benchmark(
     instances: &[(PathBuf, PathBuf)],
-    num_runs: usize,
-    num_jobs: usize,
+    settings: BenchmarkSettings,
     options: &CarcaraOptions,
-    elaborate: bool,
     runs_dest: &mut dyn io::Write,
     by_rule_dest: &mut dyn io::Write,
 ) -> io::Result<()> {
-    let result: CsvBenchmarkResults =
-        run_benchmark(instances, num_runs, num_jobs, options, elaborate);
+    let result: CsvBenchmarkResults = run_benchmark(instances, settings, options);
     println!(
         "{} errors encountered during benchmark",
         result.num_errors()
     );
+    if result.num_timeouts() > 0 {
+        println!("{} runs timed out", result.num_timeouts());
+    }
     if result.num_errors() > 0 {
         println!("invalid");
     } else if result.is_holey() {
@@ -175,3 +363,174 @@ pub fn run_csv_benchmark(
     }
     result.write_csv(runs_dest, by_rule_dest)
 }
+
+pub fn run_json_benchmark(
+    instances: &[(PathBuf, PathBuf)],
+    settings: BenchmarkSettings,
+    options: &CarcaraOptions,
+    dest: &mut dyn io::Write,
+) -> io::Result<()> {
+    let result: JsonBenchmarkResults = run_benchmark(instances, settings, options);
+    println!(
+        "{} errors encountered during benchmark",
+        result.num_errors()
+    );
+    if result.num_timeouts() > 0 {
+        println!("{} runs timed out", result.num_timeouts());
+    }
+    if result.num_errors() > 0 {
+        println!("invalid");
+    } else if result.is_holey() {
+        println!("holey");
+    } else {
+        println!("valid");
+    }
+    result.write_json(dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Collects only the ids of the measured runs, and the number of errors.
+    #[derive(Default)]
+    struct RunIds(Vec<(String, usize)>, usize);
+
+    impl CollectResults for RunIds {
+        fn add_step_measurement(&mut self, _: &str, _: &str, _: &str, _: Duration) {}
+        fn add_assume_measurement(&mut self, _: &str, _: &str, _: bool, _: Duration) {}
+        fn add_polyeq_depth(&mut self, _: usize) {}
+
+        fn add_run_measurement(&mut self, id: &(String, usize), _: RunMeasurement) {
+            self.0.push(id.clone());
+        }
+
+        fn register_holey(&mut self) {}
+        fn register_error(&mut self, _: &carcara::Error) {
+            self.1 += 1;
+        }
+        fn register_timeout(&mut self) {}
+
+        fn combine(mut a: Self, b: Self) -> Self {
+            a.0.extend(b.0);
+            a.1 += b.1;
+            a
+        }
+    }
+
+    fn run(name: &str, settings: BenchmarkSettings) -> Vec<(String, usize)> {
+        let dir = std::env::temp_dir().join(format!("carcara-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let problem = dir.join("a.smt2");
+        let proof = dir.join("a.smt2.alethe");
+        std::fs::write(&problem, "(declare-fun p () Bool)\n(assert (not p))\n").unwrap();
+        std::fs::write(&proof, "(step t1 (cl (= p p)) :rule refl)\n").unwrap();
+
+        let instances = [(problem, proof)];
+        let results: RunIds = run_benchmark(&instances, settings, &CarcaraOptions::new());
+        let mut ids = results.0;
+        ids.sort_unstable_by_key(|(_, i)| *i);
+        ids
+    }
+
+    #[test]
+    fn test_warmup_runs_are_discarded() {
+        let settings = BenchmarkSettings {
+            num_runs: 3,
+            warmup_runs: 2,
+            trim_fraction: 0.0,
+            num_jobs: 2,
+            elaborate: false,
+            io_retries: 0,
+        };
+        let ids: Vec<_> = run("warmup", settings)
+            .into_iter()
+            .map(|(_, i)| i)
+            .collect();
+        assert_eq!(vec![0, 1, 2], ids);
+    }
+
+    #[test]
+    fn test_retry_io() {
+        use std::io::{Error, ErrorKind};
+
+        // Fails with the given errors, in order, and then succeeds
+        let fake_open = |errors: &[ErrorKind]| {
+            let mut errors = errors.iter().copied();
+            let mut attempts = 0;
+            let result = retry_io(2, || {
+                attempts += 1;
+                match errors.next() {
+                    Some(kind) => Err(Error::from(kind)),
+                    None => Ok(()),
+                }
+            });
+            (result.map_err(|e| e.kind()), attempts)
+        };
+
+        assert_eq!((Ok(()), 1), fake_open(&[]));
+        assert_eq!((Ok(()), 2), fake_open(&[ErrorKind::TimedOut]));
+        assert_eq!(
+            (Ok(()), 3),
+            fake_open(&[ErrorKind::Interrupted, ErrorKind::TimedOut])
+        );
+
+        // The number of retries is bounded
+        let errors = [ErrorKind::TimedOut; 3];
+        assert_eq!((Err(ErrorKind::TimedOut), 3), fake_open(&errors));
+
+        // Permanent errors are not retried
+        assert_eq!(
+            (Err(ErrorKind::NotFound), 1),
+            fake_open(&[ErrorKind::NotFound])
+        );
+        assert_eq!(
+            (Err(ErrorKind::NotFound), 2),
+            fake_open(&[ErrorKind::TimedOut, ErrorKind::NotFound])
+        );
+    }
+
+    #[test]
+    fn test_trim_fraction() {
+        let settings = BenchmarkSettings {
+            num_runs: 10,
+            warmup_runs: 1,
+            trim_fraction: 0.2,
+            num_jobs: 2,
+            elaborate: false,
+            io_retries: 0,
+        };
+        assert_eq!(6, run("trim", settings).len());
+
+        // At least one run is always kept
+        let settings = BenchmarkSettings {
+            num_runs: 3,
+            trim_fraction: 0.49,
+            ..settings
+        };
+        assert_eq!(1, run("trim", settings).len());
+    }
+
+    #[test]
+    fn test_trimming_keeps_errors() {
+        // The fastest run of the instance failed, so its measurements are trimmed, but the error
+        // must still be reported
+        let results = (0..3)
+            .map(|i| {
+                let mut outcome = RunIds::default();
+                if i == 0 {
+                    outcome.register_error(&carcara::Error::DoesNotReachEmptyClause);
+                }
+                JobResult {
+                    instance_index: 0,
+                    time: Duration::from_millis(i + 1),
+                    measurements: RunIds(vec![("a".to_owned(), i as usize)], 0),
+                    outcome,
+                }
+            })
+            .collect();
+        let trimmed = trim_results(results, 3, 0.34);
+        assert_eq!(vec![("a".to_owned(), 1)], trimmed.0);
+        assert_eq!(1, trimmed.1);
+    }
+}
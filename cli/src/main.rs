@@ -17,6 +17,7 @@ use std::{
     io::{self, BufRead, IsTerminal},
     path::Path,
     sync::atomic,
+    time::Duration,
 };
 
 // `git describe --all` will try to find any ref (including tags) that describes the current commit.
@@ -59,6 +60,19 @@ struct Cli {
     /// Don't use sharing when printing terms.
     #[clap(global = true, short = 'v', long)]
     no_print_with_sharing: bool,
+
+    /// The dialect used when printing proofs.
+    #[clap(arg_enum, global = true, long, default_value_t = Dialect::Cvc5)]
+    dialect: Dialect,
+
+    /// The format used when printing real constants in proofs.
+    #[clap(arg_enum, global = true, long, default_value_t = RealFormat::Exact)]
+    real_format: RealFormat,
+
+    /// The number of fractional digits used when printing real constants with `--real-format
+    /// decimal`.
+    #[clap(global = true, long, default_value_t = 3)]
+    decimal_digits: u32,
 }
 
 #[derive(Subcommand)]
@@ -75,7 +89,8 @@ enum Command {
     /// Checks a series of proof files and records performance statistics.
     Bench(BenchCommandOptions),
 
-    /// Given a step, takes a slice of a proof consisting of all its transitive premises.
+    /// Given a step, takes a slice of a proof consisting of all its transitive premises. If no
+    /// step is given, removes all commands that are not used to reach the end of the proof.
     Slice(SliceCommandOptions),
 
     /// Generates the equivalent SMT instance for every `lia_generic` step in a proof.
@@ -124,6 +139,21 @@ struct ParsingOptions {
     #[clap(long)]
     allow_int_real_subtyping: bool,
 
+    /// Declares undeclared symbols as fresh constants, instead of reporting them as errors. The
+    /// sort of each symbol is inferred from its first use.
+    #[clap(long)]
+    allow_unknown_symbols: bool,
+
+    /// Renames `let` and quantifier bindings that shadow a variable bound by an enclosing binder
+    /// to fresh names.
+    #[clap(long)]
+    rename_shadowed: bool,
+
+    /// Rejects terms nested more deeply than the given depth, instead of possibly overflowing the
+    /// stack.
+    #[clap(long)]
+    max_term_depth: Option<usize>,
+
     /// Enables strict parsing and checking.
     ///
     /// When this flag is enabled: unary `and`, `or` and `xor` terms are not allowed; for the `refl`
@@ -160,6 +190,16 @@ struct CheckingOptions {
     /// Check `lia_generic` steps by calling into cvc5 (deprecated).
     #[clap(long, conflicts_with("lia-solver"))]
     lia_via_cvc5: bool,
+
+    /// Abort checking a proof if it takes longer than the given number of seconds.
+    #[clap(long)]
+    timeout: Option<u64>,
+
+    /// Accept `lemma` and `trust` steps only if their conclusion is one of the lemmas in the given
+    /// file, and consider them as holes. The file should contain a series of SMT-LIB terms, which
+    /// may use any symbol declared in the problem.
+    #[clap(long)]
+    trusted_lemmas: Option<String>,
 }
 
 fn build_carcara_options(
@@ -167,6 +207,9 @@ fn build_carcara_options(
         apply_function_defs,
         expand_let_bindings,
         allow_int_real_subtyping,
+        allow_unknown_symbols,
+        rename_shadowed,
+        max_term_depth,
         strict,
     }: ParsingOptions,
     CheckingOptions {
@@ -175,9 +218,11 @@ fn build_carcara_options(
         lia_solver,
         lia_via_cvc5,
         lia_solver_args,
+        timeout,
+        trusted_lemmas,
     }: CheckingOptions,
     StatsOptions { stats }: StatsOptions,
-) -> CarcaraOptions {
+) -> CliResult<CarcaraOptions> {
     // If no solver is provided by the `--lia-solver` option, *and* the `--lia-via-cvc5` option was
     // passed, we default to cvc5 as a solver
     let solver = lia_solver.or_else(|| lia_via_cvc5.then(|| "cvc5".into()));
@@ -185,15 +230,21 @@ fn build_carcara_options(
         solver: solver.into(),
         arguments: lia_solver_args.split_whitespace().map(Into::into).collect(),
     });
-    CarcaraOptions {
+    let trusted_lemmas = trusted_lemmas.map(std::fs::read_to_string).transpose()?;
+    Ok(CarcaraOptions {
         apply_function_defs,
         expand_lets: expand_let_bindings,
         allow_int_real_subtyping,
+        allow_unknown_symbols,
+        rename_shadowed,
+        max_term_depth,
         lia_options,
         strict,
         ignore_unknown_rules: ignore_unknown_rules || skip_unknown_rules,
+        trusted_lemmas,
         stats,
-    }
+        timeout: timeout.map(Duration::from_secs),
+    })
 }
 
 #[derive(Args)]
@@ -250,6 +301,10 @@ struct ElaborateCommandOptions {
 
     #[clap(flatten)]
     stats: StatsOptions,
+
+    /// Writes the elaborated proof to the given file, instead of printing it.
+    #[clap(long)]
+    output_elaborated: Option<String>,
 }
 
 #[derive(Args)]
@@ -268,10 +323,31 @@ struct BenchCommandOptions {
     #[clap(short, long, default_value_t = 1)]
     num_runs: usize,
 
+    /// Number of warm-up runs to do for each file before the measured runs. The results of these
+    /// runs are discarded.
+    #[clap(long, default_value_t = 0)]
+    warmup_runs: usize,
+
+    /// Fraction of the measured runs for each file to discard from each end, after ordering them
+    /// by time taken. For example, `0.1` discards the fastest and the slowest 10% of the runs.
+    #[clap(long, default_value_t = 0.0, validator = |s: &str| -> Result<(), String> {
+        match s.parse::<f64>() {
+            Ok(x) if (0.0..0.5).contains(&x) => Ok(()),
+            Ok(x) => Err(format!("The trim fraction must be in [0, 0.5), got {x}.")),
+            Err(_) => Err(String::from("Not a number.")),
+        }
+    })]
+    trim_fraction: f64,
+
     /// Number of jobs to run simultaneously when running the benchmark.
     #[clap(short = 'j', long, default_value_t = 1)]
     num_jobs: usize,
 
+    /// Number of times to retry opening a file after a transient IO error, such as a timeout on a
+    /// network filesystem. Errors like a missing file are never retried.
+    #[clap(long, default_value_t = 2)]
+    io_retries: usize,
+
     /// Show benchmark results sorted by total time taken, instead of by average time taken.
     #[clap(short = 't', long)]
     sort_by_total: bool,
@@ -280,6 +356,10 @@ struct BenchCommandOptions {
     #[clap(long = "dump-to-csv")]
     dump_to_csv: bool,
 
+    /// Dump results to a json file instead of printing to screen.
+    #[clap(long = "dump-to-json", conflicts_with = "dump-to-csv")]
+    dump_to_json: bool,
+
     /// The proof files on which the benchmark will be run. If a directory is passed, the checker
     /// will recursively find all proof files in the directory. The problem files will be
     /// inferred from the proof files.
@@ -294,10 +374,14 @@ struct SliceCommandOptions {
     #[clap(flatten)]
     parsing: ParsingOptions,
 
+    /// The id of the step to take the slice from. If this argument is not present, the slice is
+    /// taken from the last step of the proof.
     #[clap(long)]
-    from: String,
+    from: Option<String>,
 
-    #[clap(long, short = 'd')]
+    /// The maximum distance from the source step for a command to be included in the slice. This
+    /// requires `--from`.
+    #[clap(long, short = 'd', requires = "from")]
     max_distance: Option<usize>,
 
     // To make slice more convenient to use, we accept (and ignore!) some options from the `check`
@@ -318,6 +402,28 @@ enum LogLevel {
     Info,
 }
 
+#[derive(ArgEnum, Clone, Copy)]
+enum Dialect {
+    Cvc5,
+    Verit,
+}
+
+impl From<Dialect> for ast::Dialect {
+    fn from(d: Dialect) -> Self {
+        match d {
+            Dialect::Cvc5 => Self::Cvc5,
+            Dialect::Verit => Self::VeriT,
+        }
+    }
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+enum RealFormat {
+    Exact,
+    Smt,
+    Decimal,
+}
+
 impl From<LogLevel> for log::LevelFilter {
     fn from(l: LogLevel) -> Self {
         match l {
@@ -354,10 +460,20 @@ fn main() {
         }
     }
 
-    let print_proof = |commands: Vec<ast::ProofCommand>| -> CliResult<()> {
-        ast::print_proof(&commands, !cli.no_print_with_sharing)?;
-        Ok(())
-    };
+    let write_proof =
+        |dest: &mut dyn io::Write, commands: Vec<ast::ProofCommand>| -> CliResult<()> {
+            let use_sharing = !cli.no_print_with_sharing;
+            let real_format = match cli.real_format {
+                RealFormat::Exact => ast::RealFormat::Exact,
+                RealFormat::Smt => ast::RealFormat::Smt,
+                RealFormat::Decimal => ast::RealFormat::Decimal(cli.decimal_digits),
+            };
+            let dialect = cli.dialect.into();
+            ast::write_proof_with_real_format(dest, &commands, dialect, real_format, use_sharing)?;
+            io::Write::flush(dest)?;
+            Ok(())
+        };
+    let print_proof = |commands: Vec<ast::ProofCommand>| write_proof(&mut io::stdout(), commands);
     let result = match cli.command {
         Command::Parse(options) => parse_command(options).and_then(|p| print_proof(p.commands)),
         Command::Check(options) => {
@@ -373,7 +489,14 @@ fn main() {
             return;
         }
         Command::Elaborate(options) => {
-            elaborate_command(options).and_then(|p| print_proof(p.commands))
+            let output = options.output_elaborated.clone();
+            elaborate_command(options).and_then(|p| match output {
+                Some(path) => {
+                    let mut file = io::BufWriter::new(File::create(path)?);
+                    write_proof(&mut file, p.commands)
+                }
+                None => print_proof(p.commands),
+            })
         }
         Command::Bench(options) => bench_command(options),
         Command::Slice(options) => slice_command(options).and_then(print_proof),
@@ -414,6 +537,10 @@ fn parse_command(options: ParseCommandOptions) -> CliResult<ast::Proof> {
             expand_lets: options.parsing.expand_let_bindings,
             allow_int_real_subtyping: options.parsing.allow_int_real_subtyping,
             allow_unary_logical_ops: !options.parsing.strict,
+            allow_unknown_symbols: options.parsing.allow_unknown_symbols,
+            rename_shadowed: options.parsing.rename_shadowed,
+            max_term_depth: options.parsing.max_term_depth,
+            normalize_clause_order: false,
         },
     )
     .map_err(carcara::Error::from)?;
@@ -422,7 +549,7 @@ fn parse_command(options: ParseCommandOptions) -> CliResult<ast::Proof> {
 
 fn check_command(options: CheckCommandOptions) -> CliResult<bool> {
     let (problem, proof) = get_instance(&options.input)?;
-    let carc_options = build_carcara_options(options.parsing, options.checking, options.stats);
+    let carc_options = build_carcara_options(options.parsing, options.checking, options.stats)?;
     if options.num_threads == 1 {
         check(problem, proof, carc_options)
     } else {
@@ -443,7 +570,7 @@ fn elaborate_command(options: ElaborateCommandOptions) -> CliResult<ast::Proof>
     let (_, elaborated) = check_and_elaborate(
         problem,
         proof,
-        build_carcara_options(options.parsing, options.checking, options.stats),
+        build_carcara_options(options.parsing, options.checking, options.stats)?,
     )?;
     Ok(elaborated)
 }
@@ -465,27 +592,37 @@ fn bench_command(options: BenchCommandOptions) -> CliResult<()> {
         options.parsing,
         options.checking,
         StatsOptions { stats: false },
-    );
+    )?;
+    let settings = benchmarking::BenchmarkSettings {
+        num_runs: options.num_runs,
+        warmup_runs: options.warmup_runs,
+        trim_fraction: options.trim_fraction,
+        num_jobs: options.num_jobs,
+        elaborate: options.elaborate,
+        io_retries: options.io_retries,
+    };
     if options.dump_to_csv {
         benchmarking::run_csv_benchmark(
             &instances,
-            options.num_runs,
-            options.num_jobs,
+            settings,
             &carc_options,
-            options.elaborate,
             &mut File::create("runs.csv")?,
             &mut File::create("by-rule.csv")?,
         )?;
         return Ok(());
     }
+    if options.dump_to_json {
+        benchmarking::run_json_benchmark(
+            &instances,
+            settings,
+            &carc_options,
+            &mut File::create("results.json")?,
+        )?;
+        return Ok(());
+    }
 
-    let results: OnlineBenchmarkResults = benchmarking::run_benchmark(
-        &instances,
-        options.num_runs,
-        options.num_jobs,
-        &carc_options,
-        options.elaborate,
-    );
+    let results: OnlineBenchmarkResults =
+        benchmarking::run_benchmark(&instances, settings, &carc_options);
     if results.is_empty() {
         println!("no benchmark data collected");
         return Ok(());
@@ -498,6 +635,9 @@ fn bench_command(options: BenchCommandOptions) -> CliResult<()> {
     } else {
         println!("valid");
     }
+    if results.num_timeouts > 0 {
+        println!("{} runs timed out", results.num_timeouts);
+    }
     results.print(options.sort_by_total);
     Ok(())
 }
@@ -509,15 +649,32 @@ fn slice_command(options: SliceCommandOptions) -> CliResult<Vec<ast::ProofComman
         expand_lets: options.parsing.expand_let_bindings,
         allow_int_real_subtyping: options.parsing.allow_int_real_subtyping,
         allow_unary_logical_ops: !options.parsing.strict,
+        allow_unknown_symbols: options.parsing.allow_unknown_symbols,
+        rename_shadowed: options.parsing.rename_shadowed,
+        max_term_depth: options.parsing.max_term_depth,
+        normalize_clause_order: false,
     };
     let (_, proof, _) =
         parser::parse_instance(problem, proof, config).map_err(carcara::Error::from)?;
 
+    let Some(from) = options.from else {
+        let pruned = proof.prune_unused()?;
+        let num_commands = |p: &ast::Proof| {
+            let metrics = p.metrics();
+            metrics.num_steps + metrics.num_assumptions
+        };
+        log::info!(
+            "removed {} unused commands",
+            num_commands(&proof) - num_commands(&pruned)
+        );
+        return Ok(pruned.commands);
+    };
+
     let source_index = proof
         .commands
         .iter()
-        .position(|c| c.id() == options.from)
-        .ok_or_else(|| CliError::InvalidSliceId(options.from.to_owned()))?;
+        .position(|c| c.id() == from)
+        .ok_or(CliError::InvalidSliceId(from))?;
 
     let diff =
         carcara::elaborator::slice_proof(&proof.commands, source_index, options.max_distance);
@@ -538,6 +695,10 @@ fn generate_lia_problems_command(options: ParseCommandOptions, use_sharing: bool
             expand_lets: options.parsing.expand_let_bindings,
             allow_int_real_subtyping: options.parsing.allow_int_real_subtyping,
             allow_unary_logical_ops: !options.parsing.strict,
+            allow_unknown_symbols: options.parsing.allow_unknown_symbols,
+            rename_shadowed: options.parsing.rename_shadowed,
+            max_term_depth: options.parsing.max_term_depth,
+            normalize_clause_order: false,
         },
         use_sharing,
     )?;